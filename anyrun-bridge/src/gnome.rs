@@ -0,0 +1,129 @@
+//! The `org.gnome.Shell.SearchProvider2` interface. GNOME Shell asks for result IDs first and
+//! the details of those later, so the matches are kept around until the next search.
+
+use std::{
+    collections::HashMap,
+    process::Command,
+    sync::{Arc, Mutex},
+};
+
+use anyrun_interface::Match;
+use nix::sys::signal::SigSet;
+use tracing::error;
+use zbus::{
+    blocking::ConnectionBuilder,
+    dbus_interface,
+    zvariant::{OwnedValue, Value},
+};
+
+use crate::plugins::Plugins;
+
+const BUS_NAME: &str = "com.kirottu.anyrun.SearchProvider";
+const OBJECT_PATH: &str = "/com/kirottu/anyrun/SearchProvider";
+
+struct SearchProvider {
+    plugins: Arc<Plugins>,
+    /// The matches of the latest search by their result ID
+    results: Mutex<HashMap<String, (usize, Match)>>,
+}
+
+impl SearchProvider {
+    fn search(&self, terms: Vec<String>) -> Vec<String> {
+        let mut results = self.results.lock().unwrap();
+        results.clear();
+
+        self.plugins
+            .query(&terms.join(" "))
+            .into_iter()
+            .enumerate()
+            .map(|(n, (index, _match))| {
+                let id = format!("{}:{}", index, n);
+                results.insert(id.clone(), (index, _match));
+                id
+            })
+            .collect()
+    }
+}
+
+#[dbus_interface(name = "org.gnome.Shell.SearchProvider2")]
+impl SearchProvider {
+    fn get_initial_result_set(&self, terms: Vec<String>) -> Vec<String> {
+        self.search(terms)
+    }
+
+    fn get_subsearch_result_set(
+        &self,
+        _previous_results: Vec<String>,
+        terms: Vec<String>,
+    ) -> Vec<String> {
+        // Plugins don't know how to narrow down earlier results, so just search again
+        self.search(terms)
+    }
+
+    fn get_result_metas(&self, identifiers: Vec<String>) -> Vec<HashMap<String, OwnedValue>> {
+        let results = self.results.lock().unwrap();
+
+        identifiers
+            .into_iter()
+            .filter_map(|id| {
+                let (index, _match) = results.get(&id)?;
+                let mut meta = HashMap::new();
+
+                meta.insert("id".to_string(), Value::from(id.clone()).into());
+                meta.insert(
+                    "name".to_string(),
+                    Value::from(_match.title.to_string()).into(),
+                );
+                let description = _match
+                    .description
+                    .as_ref()
+                    .map(|desc| desc.to_string())
+                    .into_option()
+                    .unwrap_or_else(|| self.plugins.name(*index));
+                meta.insert("description".to_string(), Value::from(description).into());
+                if let Some(icon) = _match.icon.as_ref().into_option() {
+                    meta.insert("gicon".to_string(), Value::from(icon.to_string()).into());
+                }
+
+                Some(meta)
+            })
+            .collect()
+    }
+
+    fn activate_result(&self, identifier: String, _terms: Vec<String>, _timestamp: u32) {
+        let result = self
+            .results
+            .lock()
+            .unwrap()
+            .get(&identifier)
+            .map(|(index, _match)| self.plugins.handle(*index, _match.clone()));
+
+        if let Some(result) = result {
+            crate::handle_result(result);
+        }
+    }
+
+    fn launch_search(&self, _terms: Vec<String>, _timestamp: u32) {
+        if let Err(why) = Command::new("anyrun").spawn() {
+            error!("Failed to launch anyrun: {}", why);
+        }
+    }
+}
+
+/// Serve the search provider on the session bus until one of the shutdown signals arrives
+pub fn serve(plugins: Arc<Plugins>, shutdown: SigSet) -> zbus::Result<()> {
+    let provider = SearchProvider {
+        plugins,
+        results: Mutex::new(HashMap::new()),
+    };
+
+    let _connection = ConnectionBuilder::session()?
+        .name(BUS_NAME)?
+        .serve_at(OBJECT_PATH, provider)?
+        .build()?;
+
+    if let Err(why) = shutdown.wait() {
+        error!("Failed to wait for the shutdown signals: {}", why);
+    }
+    Ok(())
+}
@@ -0,0 +1,120 @@
+//! The `org.kde.krunner1` interface of D-Bus runners. KRunner gets the whole match at once and
+//! only passes the ID back when one is run, so the matches are kept around until the next query.
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use anyrun_interface::Match;
+use nix::sys::signal::SigSet;
+use tracing::error;
+use zbus::{
+    blocking::ConnectionBuilder,
+    dbus_interface,
+    zvariant::{OwnedValue, Value},
+};
+
+use crate::plugins::Plugins;
+
+const BUS_NAME: &str = "com.kirottu.anyrun.KRunner";
+const OBJECT_PATH: &str = "/com/kirottu/anyrun/KRunner";
+
+/// `QueryMatch::PossibleMatch`, the plugins don't tell how good their matches are
+const MATCH_TYPE: i32 = 30;
+
+/// ID, text, icon name, type, relevance and properties
+type RemoteMatch = (
+    String,
+    String,
+    String,
+    i32,
+    f64,
+    HashMap<String, OwnedValue>,
+);
+
+struct Runner {
+    plugins: Arc<Plugins>,
+    /// The matches of the latest query by their ID
+    results: Mutex<HashMap<String, (usize, Match)>>,
+}
+
+#[dbus_interface(name = "org.kde.krunner1")]
+impl Runner {
+    fn actions(&self) -> Vec<(String, String, String)> {
+        Vec::new()
+    }
+
+    #[dbus_interface(name = "Match")]
+    fn get_matches(&self, query: String) -> Vec<RemoteMatch> {
+        let mut results = self.results.lock().unwrap();
+        results.clear();
+
+        let matches = self.plugins.query(&query);
+        let count = matches.len();
+
+        matches
+            .into_iter()
+            .enumerate()
+            .map(|(n, (index, _match))| {
+                let id = format!("{}:{}", index, n);
+                let mut properties = HashMap::new();
+
+                let subtext = _match
+                    .description
+                    .as_ref()
+                    .map(|desc| desc.to_string())
+                    .into_option()
+                    .unwrap_or_else(|| self.plugins.name(index));
+                properties.insert("subtext".to_string(), Value::from(subtext).into());
+
+                // KRunner sorts by relevance, so keep the order anyrun would show them in
+                let remote = (
+                    id.clone(),
+                    _match.title.to_string(),
+                    _match
+                        .icon
+                        .as_ref()
+                        .map(|icon| icon.to_string())
+                        .unwrap_or_default(),
+                    MATCH_TYPE,
+                    1.0 - n as f64 / count as f64,
+                    properties,
+                );
+                results.insert(id, (index, _match));
+                remote
+            })
+            .collect()
+    }
+
+    fn run(&self, match_id: String, _action_id: String) {
+        let result = self
+            .results
+            .lock()
+            .unwrap()
+            .get(&match_id)
+            .map(|(index, _match)| self.plugins.handle(*index, _match.clone()));
+
+        if let Some(result) = result {
+            crate::handle_result(result);
+        }
+    }
+}
+
+/// Serve the runner on the session bus until one of the shutdown signals arrives
+pub fn serve(plugins: Arc<Plugins>, shutdown: SigSet) -> zbus::Result<()> {
+    let runner = Runner {
+        plugins,
+        results: Mutex::new(HashMap::new()),
+    };
+
+    let _connection = ConnectionBuilder::session()?
+        .name(BUS_NAME)?
+        .serve_at(OBJECT_PATH, runner)?
+        .build()?;
+
+    if let Err(why) = shutdown.wait() {
+        error!("Failed to wait for the shutdown signals: {}", why);
+    }
+    Ok(())
+}
@@ -0,0 +1,144 @@
+//! Exposes anyrun plugins to the searches of other desktops, so the same plugins can be used
+//! outside of anyrun.
+
+use std::{env, fs, io, path::PathBuf, sync::Arc};
+
+use anyrun_interface::{
+    paths::{DEFAULT_CONFIG_DIR, SYSTEM_DATA_DIR},
+    HandleResult,
+};
+use clap::{Parser, Subcommand};
+use nix::sys::signal::{SigSet, Signal};
+use serde::Deserialize;
+use tracing::{error, warn};
+use wl_clipboard_rs::copy;
+
+mod gnome;
+mod krunner;
+mod plugins;
+
+#[derive(Parser)]
+struct Args {
+    /// Override the path to the config directory
+    #[arg(short, long)]
+    config_dir: Option<String>,
+    #[command(subcommand)]
+    bridge: Bridge,
+}
+
+#[derive(Subcommand)]
+enum Bridge {
+    /// Serve the plugins as a GNOME Shell search provider
+    Gnome,
+    /// Serve the plugins as a KRunner D-Bus runner
+    Krunner,
+}
+
+/// The part of the anyrun config the bridge cares about
+#[derive(Default)]
+struct Config {
+    plugins: Vec<PathBuf>,
+    plugin_paths: Vec<PathBuf>,
+}
+
+/// A single config file, which only overrides the options it sets
+#[derive(Deserialize)]
+struct ConfigLayer {
+    plugins: Option<Vec<PathBuf>>,
+    plugin_paths: Option<Vec<PathBuf>>,
+}
+
+fn main() {
+    let args = Args::parse();
+    tracing_subscriber::fmt().with_writer(io::stderr).init();
+    let shutdown = shutdown_signals();
+
+    let user_dir = format!(
+        "{}/.config/anyrun",
+        env::var("HOME").expect("Could not determine home directory! Is $HOME set?")
+    );
+    let config_dir = args.config_dir.unwrap_or_else(|| {
+        if PathBuf::from(&user_dir).exists() {
+            user_dir
+        } else {
+            DEFAULT_CONFIG_DIR.to_string()
+        }
+    });
+
+    // Layer the configs the same way as the launcher
+    let mut layers = vec![SYSTEM_DATA_DIR, DEFAULT_CONFIG_DIR];
+    if !layers.contains(&config_dir.as_str()) {
+        layers.push(&config_dir);
+    }
+
+    let mut config = Config::default();
+    for dir in layers {
+        let content = match fs::read_to_string(format!("{}/config.ron", dir)) {
+            Ok(content) => content,
+            Err(why) => {
+                if why.kind() != io::ErrorKind::NotFound {
+                    warn!("Failed to read {}/config.ron: {}", dir, why);
+                }
+                continue;
+            }
+        };
+
+        match ron::Options::default()
+            .with_default_extension(ron::extensions::Extensions::IMPLICIT_SOME)
+            .from_str::<ConfigLayer>(&content)
+        {
+            Ok(layer) => {
+                if let Some(plugins) = layer.plugins {
+                    config.plugins = plugins;
+                }
+                if let Some(plugin_paths) = layer.plugin_paths {
+                    config.plugin_paths = plugin_paths;
+                }
+            }
+            Err(why) => warn!("Failed to parse {}/config.ron: {}", dir, why),
+        }
+    }
+
+    let plugins = Arc::new(plugins::Plugins::load(&config_dir, &config));
+
+    let result = match args.bridge {
+        Bridge::Gnome => gnome::serve(plugins.clone(), shutdown),
+        Bridge::Krunner => krunner::serve(plugins.clone(), shutdown),
+    };
+    plugins.deinit();
+
+    if let Err(why) = result {
+        error!("Failed to serve on D-Bus: {}", why);
+        std::process::exit(1);
+    }
+}
+
+/// SIGINT and SIGTERM, blocked before the plugins start their threads so that only the main thread
+/// waits for them, and the plugins are deinitialized before exiting
+fn shutdown_signals() -> SigSet {
+    let mut signals = SigSet::empty();
+    signals.add(Signal::SIGINT);
+    signals.add(Signal::SIGTERM);
+    if let Err(why) = signals.thread_block() {
+        warn!("Failed to block the shutdown signals: {}", why);
+    }
+    signals
+}
+
+/// Carry out what a plugin returned for a selection. There is no window to keep open, so
+/// everything other than copying is a no-op.
+pub fn handle_result(result: HandleResult) {
+    if let HandleResult::Copy(bytes, mime_type) = result {
+        let mime_type = match mime_type.into_option() {
+            Some(mime_type) => copy::MimeType::Specific(mime_type.into()),
+            None => copy::MimeType::Autodetect,
+        };
+
+        // Serving the clipboard is left to the process wl-clipboard-rs forks off
+        if let Err(why) =
+            copy::Options::new().copy(copy::Source::Bytes(bytes.into_vec().into()), mime_type)
+        {
+            error!("Failed to copy the selection: {}", why);
+        }
+    }
+}
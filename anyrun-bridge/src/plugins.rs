@@ -0,0 +1,117 @@
+use std::{
+    sync::{mpsc, Mutex},
+    thread,
+    time::{Duration, Instant},
+};
+
+use anyrun_interface::{paths, HandleResult, Match, PluginRef, PollResult};
+use tracing::{error, warn};
+
+use crate::Config;
+
+/// How long to wait for the matches of a single query
+const QUERY_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// How long to wait for a plugin to deinitialize, which waits for the queries it is still running
+const DEINIT_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// The loaded plugins, queried synchronously unlike in the launcher
+pub struct Plugins {
+    plugins: Vec<PluginRef>,
+    /// The input of the latest query, which the handlers get with the selection
+    input: Mutex<String>,
+}
+
+impl Plugins {
+    /// Load and initialize the plugins, resolving relative paths like the launcher does
+    pub fn load(config_dir: &str, config: &Config) -> Self {
+        let search_paths = paths::plugin_search_paths(config_dir, &config.plugin_paths);
+
+        let plugins = config
+            .plugins
+            .iter()
+            .filter_map(|plugin_path| {
+                let path = paths::resolve_plugin(plugin_path, &search_paths)?;
+
+                match anyrun_interface::load(&path) {
+                    Ok(plugin) => {
+                        plugin.init()(config_dir.into());
+                        Some(plugin)
+                    }
+                    Err(why) => {
+                        error!("Failed to load plugin {}: {}", path.display(), why);
+                        None
+                    }
+                }
+            })
+            .collect();
+
+        Self {
+            plugins,
+            input: Mutex::new(String::new()),
+        }
+    }
+
+    /// The name of the plugin at `index`
+    pub fn name(&self, index: usize) -> String {
+        self.plugins[index].info()().name.into()
+    }
+
+    /// Get the matches of all the plugins, each paired with the index of the plugin it came from
+    pub fn query(&self, input: &str) -> Vec<(usize, Match)> {
+        *self.input.lock().unwrap() = input.to_string();
+        let ids = self
+            .plugins
+            .iter()
+            .map(|plugin| plugin.get_matches()(input.into()))
+            .collect::<Vec<_>>();
+        let start = Instant::now();
+
+        self.plugins
+            .iter()
+            .zip(ids)
+            .enumerate()
+            .flat_map(|(index, (plugin, id))| {
+                let mut matches = Vec::new();
+                loop {
+                    match plugin.poll_matches()(id) {
+                        PollResult::Ready(rest) => break matches.extend(rest),
+                        PollResult::Partial(streamed) => matches.extend(streamed),
+                        PollResult::Pending if start.elapsed() < QUERY_TIMEOUT => {
+                            thread::sleep(Duration::from_millis(1))
+                        }
+                        _ => break,
+                    }
+                }
+
+                matches.into_iter().map(move |_match| (index, _match))
+            })
+            .collect()
+    }
+
+    /// Deinitialize the plugins before exiting. The plugins that are stuck on a query are left as
+    /// they are, as the process exits anyway.
+    pub fn deinit(&self) {
+        for plugin in &self.plugins {
+            let plugin = *plugin;
+            let (sender, receiver) = mpsc::channel();
+            thread::spawn(move || {
+                plugin.deinit()();
+                let _ = sender.send(());
+            });
+
+            if receiver.recv_timeout(DEINIT_TIMEOUT).is_err() {
+                warn!(
+                    "Plugin {} didn't deinitialize in time",
+                    plugin.info()().name
+                );
+            }
+        }
+    }
+
+    /// Let the plugin at `index` handle the selection
+    pub fn handle(&self, index: usize, selection: Match) -> HandleResult {
+        let input = self.input.lock().unwrap().clone();
+        self.plugins[index].handle_selection()(selection, input.into())
+    }
+}
@@ -1,9 +1,13 @@
+use std::{fmt, path::Path};
+
+pub mod paths;
+
 use abi_stable::{
     declare_root_module_statics,
-    library::RootModule,
+    library::{LibraryError, RootModule},
     package_version_strings,
     sabi_types::VersionStrings,
-    std_types::{ROption, RString, RVec},
+    std_types::{ROption, RString, RVec, Tuple2},
     StableAbi,
 };
 
@@ -16,7 +20,83 @@ pub struct Plugin {
     pub info: extern "C" fn() -> PluginInfo,
     pub get_matches: extern "C" fn(RString) -> u64,
     pub poll_matches: extern "C" fn(u64) -> PollResult,
-    pub handle_selection: extern "C" fn(Match) -> HandleResult,
+    /// Handles the selected match, with the input it was found for
+    pub handle_selection: extern "C" fn(Match, RString) -> HandleResult,
+    pub validate_input: extern "C" fn(RString) -> ValidateResult,
+    pub set_log_sink: extern "C" fn(LogSink),
+    pub set_recency_store: extern "C" fn(RecencyStore),
+    /// Tells the plugin that the matches of the query with the id are no longer needed
+    pub cancel: extern "C" fn(u64),
+    /// The prefix the input needs for the plugin to have matches, if it has one. Asked again for
+    /// every query, as it is only known once `init` has read the config of the plugin.
+    pub prefix: extern "C" fn() -> ROption<RString>,
+    /// Called once before the plugin is unloaded or anyrun exits, no other calls follow it
+    pub deinit: extern "C" fn(),
+    /// The errors the plugin reported since this was last called, for showing them to the user
+    pub errors: extern "C" fn() -> RVec<RString>,
+    /// How many milliseconds after the matches of the query with the id are ready anyrun should
+    /// get them again with the same input, for matches that go out of date
+    pub refresh_interval: extern "C" fn(u64) -> ROption<u64>,
+    /// The [`INTERFACE_VERSION`] the plugin was built against. Missing from the plugins built
+    /// before there was one.
+    #[sabi(missing_field(option))]
+    pub interface_version: extern "C" fn() -> u32,
+    /// More about the match than fits in its row, shown next to the matches while it is selected.
+    /// Called on the main thread whenever the selection moves, so it should be quick.
+    pub get_preview: extern "C" fn(Match) -> ROption<Preview>,
+    /// Tells the plugin where anyrun was opened, for the queries that follow. Called before the
+    /// first query and again whenever more of it becomes known.
+    pub set_context: extern "C" fn(QueryContext),
+}
+
+/// Raised whenever the interface changes in a way that makes the plugins built against the previous
+/// version misbehave, like a field being added to [`Match`] or a call to [`Plugin`]
+pub const INTERFACE_VERSION: u32 = 4;
+
+/// Where plugins send their log messages to
+#[repr(C)]
+#[derive(StableAbi, Clone, Copy)]
+pub struct LogSink {
+    /// Takes the level, the target (module path) and the message
+    pub log: extern "C" fn(LogLevel, RString, RString),
+}
+
+/// Keeps the recently used items of the plugins between runs, so that picker-style plugins can
+/// show them first. Both functions take the name of the plugin first, as every plugin has its own items.
+#[repr(C)]
+#[derive(StableAbi, Clone, Copy)]
+pub struct RecencyStore {
+    /// Mark the item with the key as used just now
+    pub record: extern "C" fn(RString, RString),
+    /// The keys of the recently used items, the most recent first
+    pub recent: extern "C" fn(RString) -> RVec<RString>,
+}
+
+/// The severity of a log message
+#[repr(C)]
+#[derive(StableAbi, Clone, Copy, Debug)]
+pub enum LogLevel {
+    Error,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+/// What anyrun knows about where it was opened, for plugins that act on it, like a translator
+/// defaulting to the text in the clipboard. Any of it can be missing, like when the compositor
+/// doesn't tell.
+#[repr(C)]
+#[derive(StableAbi, Clone, Default, Debug)]
+pub struct QueryContext {
+    /// The name of the output anyrun is shown on, like `DP-1`
+    pub output: ROption<RString>,
+    /// The text in the clipboard
+    pub clipboard: ROption<RString>,
+    /// The app id of the window that was focused before anyrun was opened
+    pub app_id: ROption<RString>,
+    /// The title of the window that was focused before anyrun was opened
+    pub window_title: ROption<RString>,
 }
 
 /// Info of the plugin. Used for the main UI
@@ -30,19 +110,87 @@ pub struct PluginInfo {
 
 /// Represents a match from a plugin
 ///
-/// The `title` and `description` support pango markup when `use_pango` is set to true.
+/// The `title` and `description` support pango markup when `title_pango` and `description_pango`
+/// are set to true. Text that isn't markup is escaped by anyrun, so it is shown as it is.
 /// Refer to [Pango Markup](https://docs.gtk.org/Pango/pango_markup.html) for how to use pango markup.
 #[repr(C)]
 #[derive(StableAbi, Clone)]
 pub struct Match {
     pub title: RString,
     pub description: ROption<RString>,
-    /// Whether the title and description should be interpreted as pango markup.
-    pub use_pango: bool,
+    /// Whether the title should be interpreted as pango markup.
+    pub title_pango: bool,
+    /// Whether the description should be interpreted as pango markup. Descriptions from elsewhere,
+    /// like the web, are best left escaped even when the title is markup.
+    pub description_pango: bool,
     /// The icon name from the icon theme in use
     pub icon: ROption<RString>,
     /// For runners to differentiate between the matches. Not required.
     pub id: ROption<u64>,
+    /// Other things that can be done with the match, shown in a menu opened with Right or Ctrl+Enter.
+    /// The first one is also run straight away with Shift+Enter.
+    pub actions: RVec<Action>,
+    /// The action picked from the menu, set by anyrun when the match is passed to the handler
+    pub action: ROption<u64>,
+    /// A picture shown instead of the icon, for thumbnails and the like that aren't in the icon
+    /// theme. Pictures in files can be shown by setting the icon to an absolute path instead.
+    pub image: ROption<Image>,
+    /// Shown under the description, for what doesn't fit in a line of text
+    pub widgets: RVec<Widget>,
+    /// Matches with a higher priority are shown above the other matches of the plugin, even the
+    /// ones the user pinned or the launcher moved up otherwise. Most matches have 0, matches with
+    /// the same priority are shown in the order the launcher puts them in.
+    pub priority: i32,
+    /// Shown above all the other matches of the plugin, whatever their priority, and never left
+    /// out for `max_entries`. For the few matches that should always be in view, like an exact
+    /// match or a row for creating a new item.
+    pub pinned: bool,
+}
+
+/// The raw pixels of a picture, with 8 bits for each of red, green, blue and alpha
+#[repr(C)]
+#[derive(StableAbi, Clone)]
+pub struct Image {
+    pub width: u32,
+    pub height: u32,
+    /// `width * height * 4` bytes, row by row from the top
+    pub pixels: RVec<u8>,
+}
+
+/// Content of a match besides the title and the description, laid out by anyrun
+#[repr(C)]
+#[derive(StableAbi, Clone)]
+pub enum Widget {
+    /// A bar filled to the fraction between 0 and 1, with the text on it if there is one
+    Progress(f64, ROption<RString>),
+    /// Rows of a label and its value, like the fields of a record
+    Table(RVec<Tuple2<RString, RString>>),
+    /// Text shown as it is in a monospace font, like the output of a command
+    Monospace(RString),
+}
+
+/// The content of the preview pane for a match, like a definition, the start of a file or a man page
+#[repr(C)]
+#[derive(StableAbi, Clone)]
+pub enum Preview {
+    /// Text wrapped to the width of the pane, interpreted as pango markup if the inner value is true
+    Text(RString, bool),
+    /// Text shown as it is in a monospace font, like the contents of a file
+    Monospace(RString),
+    Image(Image),
+    /// A picture in a file, by its absolute path
+    File(RString),
+}
+
+/// A secondary action of a match
+#[repr(C)]
+#[derive(StableAbi, Clone)]
+pub struct Action {
+    pub title: RString,
+    /// The icon name from the icon theme in use
+    pub icon: ROption<RString>,
+    /// Passed to the handler in the `action` of the match
+    pub id: u64,
 }
 
 /// For determining how anyrun should proceed after the plugin has handled a match selection
@@ -55,16 +203,46 @@ pub enum HandleResult {
     /// The inner value can set an exclusive mode for the plugin.
     Refresh(bool),
     /// Copy the content, due to how copying works it must be done like this.
-    Copy(RVec<u8>),
+    /// The MIME type of the content can be set with the second value, otherwise it is detected automatically.
+    Copy(RVec<u8>, ROption<RString>),
     /// Output the content to stdout, printing to stdout has issues in plugins.
     Stdout(RVec<u8>),
+    /// Clear the input and refresh the items in exclusive mode, hiding the typed characters if the
+    /// inner value is true. Useful for asking for a password.
+    Masked(bool),
+    /// Refresh only the items of the plugin that handled the selection, leaving the ones of the
+    /// other plugins as they are. Useful for acting on the items, like killing a process.
+    RefreshPlugin,
+    /// Replace the input with the inner value and refresh the items. Useful for plugins that
+    /// navigate, like descending into a directory.
+    SetInput(RString),
+    /// Type the text into the window that was focused before anyrun, as if it was typed on the
+    /// keyboard. Useful for inserting symbols or snippets at the cursor instead of copying them.
+    Type(RString),
 }
 
+/// For determining whether the input should be dispatched to the plugin
+#[repr(C)]
+#[derive(StableAbi)]
+pub enum ValidateResult {
+    /// Get the matches for the input as is
+    Accept,
+    /// Do not dispatch the input to the plugin at all
+    Reject,
+    /// Get the matches for the inner value instead of the input
+    Transform(RString),
+}
+
+/// The state of the matches of a query, polled until the matches are ready
 #[repr(C)]
 #[derive(StableAbi)]
 pub enum PollResult {
+    /// All the matches, or the rest of them after partial results
     Ready(RVec<Match>),
+    /// The matches found since the previous poll, with more still to come
+    Partial(RVec<Match>),
     Pending,
+    /// The query was replaced by a newer one
     Cancelled,
 }
 
@@ -75,3 +253,53 @@ impl RootModule for PluginRef {
     const NAME: &'static str = "anyrun_plugin";
     const VERSION_STRINGS: VersionStrings = package_version_strings!();
 }
+
+/// Why a plugin couldn't be loaded
+#[derive(Debug)]
+pub enum LoadError {
+    Library(LibraryError),
+    /// The plugin was built against another version of the interface, the inner value if it has
+    /// one. Calling it would crash anyrun when it gets to a part of the interface that changed.
+    Incompatible(Option<u32>),
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Library(why) => write!(f, "{}", why),
+            Self::Incompatible(version) => {
+                match version {
+                    Some(version) => write!(f, "It was built against version {} ", version)?,
+                    None => write!(f, "It was built against another version ")?,
+                }
+                write!(
+                    f,
+                    "of the plugin interface, but this anyrun has version {}. Rebuilding it \
+                    against this version of anyrun makes it load again.",
+                    INTERFACE_VERSION
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for LoadError {}
+
+/// Load the plugin library at the path, refusing it if it was built against another version of
+/// the interface
+pub fn load(path: &Path) -> Result<PluginRef, LoadError> {
+    let plugin = abi_stable::library::lib_header_from_path(path)
+        .and_then(|plugin| plugin.init_root_module::<PluginRef>())
+        .map_err(|why| match why {
+            // The layout of the types changed since the plugin was built
+            LibraryError::AbiInstability(_) | LibraryError::IncompatibleVersionNumber { .. } => {
+                LoadError::Incompatible(None)
+            }
+            why => LoadError::Library(why),
+        })?;
+
+    match plugin.interface_version().map(|version| version()) {
+        Some(INTERFACE_VERSION) => Ok(plugin),
+        version => Err(LoadError::Incompatible(version)),
+    }
+}
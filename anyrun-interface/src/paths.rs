@@ -0,0 +1,66 @@
+//! Where anyrun looks for its configs and plugins, shared with the other programs loading the
+//! plugins like anyrun-bridge, so that they find the same ones.
+
+use std::{
+    env,
+    path::{Path, PathBuf},
+};
+
+/// Default config directory
+pub const DEFAULT_CONFIG_DIR: &str = "/etc/anyrun";
+/// Defaults shipped by the distribution, overridden by the default config directory
+pub const SYSTEM_DATA_DIR: &str = "/usr/share/anyrun";
+
+/// The directory for data like plugins installed by the user
+pub fn data_dir() -> PathBuf {
+    match env::var("XDG_DATA_HOME") {
+        Ok(data_home) => PathBuf::from(data_home),
+        Err(_) => PathBuf::from(format!(
+            "{}/.local/share",
+            env::var("HOME").expect("Could not determine home directory! Is $HOME set?")
+        )),
+    }
+    .join("anyrun")
+}
+
+/// The directories relative plugin paths are looked up from, in order of priority
+pub fn plugin_search_paths(config_dir: &str, plugin_paths: &[PathBuf]) -> Vec<PathBuf> {
+    // Prioritise the ANYRUN_PLUGINS env var over other paths
+    let mut search_paths = match env::var("ANYRUN_PLUGINS") {
+        Ok(string) => string.split(':').map(PathBuf::from).collect::<Vec<_>>(),
+        Err(_) => Vec::new(),
+    };
+
+    search_paths.extend(plugin_paths.iter().cloned());
+    search_paths.append(&mut vec![
+        format!("{}/plugins", config_dir).into(),
+        data_dir().join("plugins"),
+        format!("{}/plugins", DEFAULT_CONFIG_DIR).into(),
+        format!("{}/plugins", SYSTEM_DATA_DIR).into(),
+        "/usr/lib/anyrun".into(),
+    ]);
+
+    search_paths
+}
+
+/// Find the library of a plugin. Bare names like `applications` also match `libapplications.so`.
+pub fn resolve_plugin(plugin_path: &Path, search_paths: &[PathBuf]) -> Option<PathBuf> {
+    if plugin_path.is_absolute() {
+        return Some(plugin_path.to_path_buf());
+    }
+
+    let bare_name = plugin_path.extension().is_none() && plugin_path.components().count() == 1;
+
+    search_paths.iter().find_map(|dir| {
+        let path = dir.join(plugin_path);
+        let lib_path = dir.join(format!("lib{}.so", plugin_path.display()));
+
+        if path.is_file() {
+            Some(path)
+        } else if bare_name && lib_path.is_file() {
+            Some(lib_path)
+        } else {
+            None
+        }
+    })
+}
@@ -2,7 +2,8 @@ use proc_macro::{Span, TokenStream};
 use quote::quote;
 use syn::{parse_macro_input, parse_quote, Ident, ReturnType, Type};
 
-/// The function to handle the selection of an item. Takes a `Match` as its first argument, and the second argument can be one of:
+/// The function to handle the selection of an item. Takes a `Match` as its first argument, optionally
+/// followed by the input the match was found for as an `RString`, and the last argument can be one of:
 /// - &T
 /// - &mut T
 /// - <Nothing>
@@ -14,38 +15,55 @@ pub fn handler(_attr: TokenStream, item: TokenStream) -> TokenStream {
     let function = parse_macro_input!(item as syn::ItemFn);
     let fn_name = &function.sig.ident;
 
-    let data = if function.sig.inputs.len() == 2 {
-        if match function.sig.inputs.last() {
-            ::core::option::Option::Some(syn::FnArg::Typed(pat)) => match &*pat.ty {
-                Type::Reference(reference) => {
-                    reference.mutability.is_some()
-                }
-                _ => return quote! { compile_error!("Last argument must be either a reference to the shared data or should not be present at all.") }.into(),
-            },
-            ::core::option::Option::Some(_) => return quote! { compile_error!("`self` argument, really?") }.into(),
-            ::core::option::Option::None => unreachable!(),
-        } {
-            quote! {
-                ANYRUN_INTERNAL_DATA.write().unwrap().as_mut().unwrap(),
+    let mut data = quote! {};
+    let mut input = quote! {};
+    // The input is dropped unless the function takes it
+    let mut unused = quote! {
+        let _ = input;
+    };
+    for (index, arg) in function.sig.inputs.iter().enumerate().skip(1) {
+        let ty = match arg {
+            syn::FnArg::Typed(pat) => &*pat.ty,
+            syn::FnArg::Receiver(_) => {
+                return quote! { compile_error!("`self` argument, really?") }.into()
             }
-        } else {
-            quote! {
-                ANYRUN_INTERNAL_DATA.read().unwrap().as_ref().unwrap(),
+        };
+
+        match ty {
+            Type::Reference(reference) if index == function.sig.inputs.len() - 1 => {
+                data = if reference.mutability.is_some() {
+                    quote! {
+                        ANYRUN_INTERNAL_DATA.write().unwrap().as_mut().unwrap(),
+                    }
+                } else {
+                    quote! {
+                        ANYRUN_INTERNAL_DATA.read().unwrap().as_ref().unwrap(),
+                    }
+                };
             }
+            // The handlers written before the input was passed on only take the match and the data
+            Type::Path(_) if index == 1 => {
+                input = quote! {
+                    input,
+                };
+                unused = quote! {};
+            }
+            _ => return quote! { compile_error!("The arguments after the match must be the input, a reference to the shared data, or both.") }.into(),
         }
-    } else {
-        quote! {}
-    };
+    }
 
     quote! {
         #[::abi_stable::sabi_extern_fn]
         fn anyrun_internal_handle_selection(
             selection: ::anyrun_plugin::anyrun_interface::Match,
+            input: ::abi_stable::std_types::RString,
         ) -> ::anyrun_plugin::anyrun_interface::HandleResult {
             #function
 
+            #unused
             #fn_name(
                 selection,
+                #input
                 #data
             )
         }
@@ -107,7 +125,10 @@ pub fn get_matches(_attr: TokenStream, item: TokenStream) -> TokenStream {
             ANYRUN_INTERNAL_ID_COUNTER
                 .store(current_id + 1, ::std::sync::atomic::Ordering::Relaxed);
 
+            ::anyrun_plugin::stream::start(current_id);
+            ::anyrun_plugin::refresh::start(current_id);
             let handle = ::std::thread::spawn(move || {
+                ::anyrun_plugin::stream::set_query(current_id);
                 #fn_call
             });
 
@@ -119,6 +140,161 @@ pub fn get_matches(_attr: TokenStream, item: TokenStream) -> TokenStream {
     .into()
 }
 
+/// Function that is run on the main thread before the input is dispatched to `get_matches`, so it should be quick.
+/// Takes the current text input as an `RString` as the first argument, and the second argument can be one of:
+/// - &T
+/// - <Nothing>
+/// where T is the type returned by `init`.
+///
+/// Should return a `ValidateResult` to accept, reject or transform the input. Defining this function is optional.
+#[proc_macro_attribute]
+pub fn validate_input(_attr: TokenStream, item: TokenStream) -> TokenStream {
+    let function = parse_macro_input!(item as syn::ItemFn);
+    let fn_name = &function.sig.ident;
+
+    let fn_call = if function.sig.inputs.len() == 2 {
+        match function.sig.inputs.last() {
+            ::core::option::Option::Some(syn::FnArg::Typed(pat)) => match &*pat.ty {
+                Type::Reference(reference) if reference.mutability.is_none() => (),
+                _ => return quote! { compile_error!("Last argument must be either an immutable reference to the shared data or should not be present at all.") }.into(),
+            },
+            ::core::option::Option::Some(_) => return quote! { compile_error!("`self` argument, really?") }.into(),
+            ::core::option::Option::None => unreachable!(),
+        }
+        // Never block the main thread waiting for the data, accept the input if it is not available
+        quote! {
+            match ANYRUN_INTERNAL_DATA.try_read() {
+                ::core::result::Result::Ok(data) => match data.as_ref() {
+                    ::core::option::Option::Some(data) => #fn_name(input, data),
+                    ::core::option::Option::None => ::anyrun_plugin::anyrun_interface::ValidateResult::Accept,
+                },
+                ::core::result::Result::Err(_) => ::anyrun_plugin::anyrun_interface::ValidateResult::Accept,
+            }
+        }
+    } else {
+        quote! {
+            #fn_name(input)
+        }
+    };
+
+    quote! {
+        #[::abi_stable::sabi_extern_fn]
+        fn anyrun_internal_validate_input(
+            input: ::abi_stable::std_types::RString,
+        ) -> ::anyrun_plugin::anyrun_interface::ValidateResult {
+            #function
+
+            #fn_call
+        }
+    }
+    .into()
+}
+
+/// Function that is run on the main thread whenever a match of the plugin is selected, so it should be quick.
+/// Takes the selected `Match` as the first argument, and the second argument can be one of:
+/// - &T
+/// - <Nothing>
+/// where T is the type returned by `init`.
+///
+/// Should return an `Option<Preview>`, shown in the preview pane while the match is selected. Defining this
+/// function is optional.
+#[proc_macro_attribute]
+pub fn preview(_attr: TokenStream, item: TokenStream) -> TokenStream {
+    let function = parse_macro_input!(item as syn::ItemFn);
+    let fn_name = &function.sig.ident;
+
+    let fn_call = if function.sig.inputs.len() == 2 {
+        match function.sig.inputs.last() {
+            ::core::option::Option::Some(syn::FnArg::Typed(pat)) => match &*pat.ty {
+                Type::Reference(reference) if reference.mutability.is_none() => (),
+                _ => return quote! { compile_error!("Last argument must be either an immutable reference to the shared data or should not be present at all.") }.into(),
+            },
+            ::core::option::Option::Some(_) => return quote! { compile_error!("`self` argument, really?") }.into(),
+            ::core::option::Option::None => unreachable!(),
+        }
+        // Never block the main thread waiting for the data, there is no preview if it is not available
+        quote! {
+            match ANYRUN_INTERNAL_DATA.try_read() {
+                ::core::result::Result::Ok(data) => match data.as_ref() {
+                    ::core::option::Option::Some(data) => #fn_name(selection, data),
+                    ::core::option::Option::None => ::core::option::Option::None,
+                },
+                ::core::result::Result::Err(_) => ::core::option::Option::None,
+            }
+        }
+    } else {
+        quote! {
+            #fn_name(selection)
+        }
+    };
+
+    quote! {
+        #[::abi_stable::sabi_extern_fn]
+        fn anyrun_internal_get_preview(
+            selection: ::anyrun_plugin::anyrun_interface::Match,
+        ) -> ::abi_stable::std_types::ROption<::anyrun_plugin::anyrun_interface::Preview> {
+            #function
+
+            let preview: ::core::option::Option<::anyrun_plugin::anyrun_interface::Preview> = #fn_call;
+            preview.into()
+        }
+    }
+    .into()
+}
+
+/// Function that is run once before the plugin is unloaded or anyrun exits, for saving caches and counters or
+/// closing connections. The only argument can be one of:
+/// - &T
+/// - &mut T
+/// - <Nothing>
+/// where T is the type returned by `init`.
+///
+/// The data is dropped right after, and no other functions are called anymore. Defining this function is optional.
+#[proc_macro_attribute]
+pub fn deinit(_attr: TokenStream, item: TokenStream) -> TokenStream {
+    let function = parse_macro_input!(item as syn::ItemFn);
+    let fn_name = &function.sig.ident;
+
+    let fn_call = if function.sig.inputs.len() == 1 {
+        let data = if match function.sig.inputs.first() {
+            ::core::option::Option::Some(syn::FnArg::Typed(pat)) => match &*pat.ty {
+                Type::Reference(reference) => {
+                    reference.mutability.is_some()
+                }
+                _ => return quote! { compile_error!("The argument must be either a reference to the shared data or should not be present at all.") }.into(),
+            },
+            ::core::option::Option::Some(_) => return quote! { compile_error!("`self` argument, really?") }.into(),
+            ::core::option::Option::None => unreachable!(),
+        } {
+            quote! { data.as_mut() }
+        } else {
+            quote! { data.as_ref() }
+        };
+        quote! {
+            if let ::core::option::Option::Some(data) = #data {
+                #fn_name(data);
+            }
+        }
+    } else {
+        quote! {
+            #fn_name();
+        }
+    };
+
+    quote! {
+        #[::abi_stable::sabi_extern_fn]
+        fn anyrun_internal_deinit() {
+            #function
+
+            // Waits for `init` and the running queries to be done with the data
+            let mut data = ANYRUN_INTERNAL_DATA.write().unwrap();
+            #fn_call
+            *data = ::core::option::Option::None;
+        }
+    }
+    .into()
+}
+
 /// Function that returns the plugin info as a `PluginInfo` object. Takes no arguments.
 #[proc_macro_attribute]
 pub fn info(_attr: TokenStream, item: TokenStream) -> TokenStream {
@@ -161,8 +337,13 @@ pub fn init(_attr: TokenStream, item: TokenStream) -> TokenStream {
         static ANYRUN_INTERNAL_DATA: ::std::sync::RwLock<Option<#data_type>> =
             ::std::sync::RwLock::new(None);
 
-        #[::abi_stable::export_root_module]
-        fn anyrun_internal_init_root_module() -> ::anyrun_plugin::anyrun_interface::PluginRef {
+        // Fall back to the default implementations of the optional functions that are not defined
+        #[allow(unused_imports)]
+        use ::anyrun_plugin::defaults::*;
+
+        // Plugins built into anyrun can't all export the same symbol, anyrun calls this itself
+        #[cfg_attr(not(anyrun_embedded), ::abi_stable::export_root_module)]
+        pub fn anyrun_internal_init_root_module() -> ::anyrun_plugin::anyrun_interface::PluginRef {
             use ::abi_stable::prefix_type::PrefixTypeTrait;
             ::anyrun_plugin::anyrun_interface::Plugin {
                 init: anyrun_internal_init,
@@ -170,6 +351,17 @@ pub fn init(_attr: TokenStream, item: TokenStream) -> TokenStream {
                 get_matches: anyrun_internal_get_matches,
                 poll_matches: anyrun_internal_poll_matches,
                 handle_selection: anyrun_internal_handle_selection,
+                validate_input: anyrun_internal_validate_input,
+                set_log_sink: anyrun_internal_set_log_sink,
+                set_recency_store: anyrun_internal_set_recency_store,
+                cancel: anyrun_internal_cancel,
+                prefix: anyrun_internal_prefix,
+                deinit: anyrun_internal_deinit,
+                errors: anyrun_internal_errors,
+                refresh_interval: anyrun_internal_refresh_interval,
+                interface_version: anyrun_internal_interface_version,
+                get_preview: anyrun_internal_get_preview,
+                set_context: anyrun_internal_set_context,
             }
             .leak_into_prefix()
         }
@@ -181,7 +373,12 @@ pub fn init(_attr: TokenStream, item: TokenStream) -> TokenStream {
                     ::core::option::Option::Some((thread, task_id)) => {
                         if *task_id == id {
                             if !thread.is_finished() {
-                                return ::anyrun_plugin::anyrun_interface::PollResult::Pending;
+                                let streamed = ::anyrun_plugin::stream::take(id);
+                                return if streamed.is_empty() {
+                                    ::anyrun_plugin::anyrun_interface::PollResult::Pending
+                                } else {
+                                    ::anyrun_plugin::anyrun_interface::PollResult::Partial(streamed.into())
+                                };
                             }
                         } else {
                             return ::anyrun_plugin::anyrun_interface::PollResult::Cancelled;
@@ -193,17 +390,71 @@ pub fn init(_attr: TokenStream, item: TokenStream) -> TokenStream {
             }
 
             let (thread, _) = ANYRUN_INTERNAL_THREAD.lock().unwrap().take().unwrap();
-            ::anyrun_plugin::anyrun_interface::PollResult::Ready(thread.join().unwrap())
+            let mut matches = ::anyrun_plugin::stream::take(id);
+            matches.extend(thread.join().unwrap());
+            ::anyrun_plugin::anyrun_interface::PollResult::Ready(matches.into())
+        }
+
+        #[::abi_stable::sabi_extern_fn]
+        fn anyrun_internal_set_log_sink(sink: ::anyrun_plugin::anyrun_interface::LogSink) {
+            ::anyrun_plugin::log::set_sink(sink);
+        }
+
+        #[::abi_stable::sabi_extern_fn]
+        fn anyrun_internal_set_context(context: ::anyrun_plugin::anyrun_interface::QueryContext) {
+            ::anyrun_plugin::context::set(context);
+        }
+
+        #[::abi_stable::sabi_extern_fn]
+        fn anyrun_internal_cancel(id: u64) {
+            ::anyrun_plugin::cancel::cancel(id);
+        }
+
+        #[::abi_stable::sabi_extern_fn]
+        fn anyrun_internal_prefix() -> ::abi_stable::std_types::ROption<::abi_stable::std_types::RString> {
+            ::anyrun_plugin::prefix::get().map(::core::convert::Into::into).into()
+        }
+
+        #[::abi_stable::sabi_extern_fn]
+        fn anyrun_internal_errors() -> ::abi_stable::std_types::RVec<::abi_stable::std_types::RString> {
+            ::anyrun_plugin::report::take()
+                .into_iter()
+                .map(::core::convert::Into::into)
+                .collect()
+        }
+
+        #[::abi_stable::sabi_extern_fn]
+        fn anyrun_internal_refresh_interval(id: u64) -> ::abi_stable::std_types::ROption<u64> {
+            ::anyrun_plugin::refresh::get(id)
+                .map(|interval| interval.as_millis() as u64)
+                .into()
+        }
+
+        #[::abi_stable::sabi_extern_fn]
+        fn anyrun_internal_interface_version() -> u32 {
+            ::anyrun_plugin::anyrun_interface::INTERFACE_VERSION
+        }
+
+        #[::abi_stable::sabi_extern_fn]
+        fn anyrun_internal_set_recency_store(store: ::anyrun_plugin::anyrun_interface::RecencyStore) {
+            ::anyrun_plugin::recent::set_store(store, anyrun_internal_info().name);
         }
 
         #[::abi_stable::sabi_extern_fn]
         fn anyrun_internal_init(config_dir: ::abi_stable::std_types::RString) {
             #function
 
-            ::std::thread::spawn(|| {
+            ::anyrun_plugin::store::set_name(anyrun_internal_info().name);
+
+            // The init of the plugin runs in the background, but only returns once it has the data,
+            // so that the calls right after it wait for the data instead of finding none
+            let (locked, wait) = ::std::sync::mpsc::channel();
+            ::std::thread::spawn(move || {
                 let mut lock = ANYRUN_INTERNAL_DATA.write().unwrap();
+                let _ = locked.send(());
                 *lock = ::core::option::Option::Some(#fn_name(config_dir));
             });
+            let _ = wait.recv();
         }
     }
     .into()
@@ -216,12 +467,16 @@ pub fn config_args(_attr: TokenStream, item: TokenStream) -> TokenStream {
 
     let mut opt_item = item.clone();
 
-    opt_item.attrs = vec![parse_quote!(#[derive(::clap::Args)])];
+    opt_item.attrs = vec![parse_quote!(#[derive(::clap::Args, ::serde::Deserialize)])];
     opt_item.ident = Ident::new(&format!("{}Args", opt_item.ident), Span::call_site().into());
 
     let opt_ident = &opt_item.ident;
 
     let mut operations = quote!();
+    let names = item
+        .fields
+        .iter()
+        .map(|field| field.ident.as_ref().unwrap().to_string());
 
     for field in opt_item.fields.iter_mut() {
         let ty = &field.ty;
@@ -243,6 +498,9 @@ pub fn config_args(_attr: TokenStream, item: TokenStream) -> TokenStream {
         #opt_item
 
         impl #ident {
+            /// The names of the options
+            const FIELDS: &'static [&'static str] = &[#(#names),*];
+
             fn merge_opt(&mut self, opt: #opt_ident) {
                 #operations
             }
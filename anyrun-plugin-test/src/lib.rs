@@ -0,0 +1,265 @@
+//! Testing plugins the way anyrun runs them. The plugin is loaded from its library, or linked in
+//! through the root module the plugin macros generate, given a config directory of its own and
+//! then asked for matches, selections and previews like anyrun asks for them.
+//!
+//! ```ignore
+//! // tests/symbols.rs of the symbols plugin
+//! use anyrun_plugin_test::{titles, HandleResult, Harness};
+//!
+//! #[test]
+//! fn copies_the_symbol() {
+//!     let plugin = Harness::new(symbols::anyrun_internal_init_root_module)
+//!         .config("symbols.ron", r#"Config(prefix: ":sy", symbols: {}, max_entries: 3)"#)
+//!         .init();
+//!
+//!     let matches = plugin.query(":sy greek small letter alpha");
+//!     assert_eq!(titles(&matches)[0], "α");
+//!     assert!(matches!(
+//!         plugin.select(&matches[0], ":sy greek small letter alpha"),
+//!         HandleResult::Copy(..)
+//!     ));
+//! }
+//! ```
+//!
+//! The plugins keep their state in statics, so every test binary should only set up one plugin at
+//! a time. Running the tests with `--test-threads 1`, or keeping one test in each file under
+//! `tests/`, does that.
+
+use std::{
+    collections::HashMap,
+    env, fs,
+    path::{Path, PathBuf},
+    process,
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use abi_stable::std_types::{ROption, RString, RVec};
+pub use anyrun_interface::{
+    HandleResult, LoadError, LogLevel, LogSink, Match, PluginInfo, PluginRef, PollResult, Preview,
+    QueryContext, RecencyStore, ValidateResult,
+};
+
+/// How long the matches of a query are waited for before the test fails
+const QUERY_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// For naming the config directories of the harnesses, which may be set up in parallel
+static DIRS: AtomicU32 = AtomicU32::new(0);
+
+/// The recently used items, kept only for the test instead of in the state directory of anyrun
+static RECENT: Mutex<Option<HashMap<String, Vec<String>>>> = Mutex::new(None);
+
+const STORE: RecencyStore = RecencyStore { record, recent };
+
+extern "C" fn record(plugin: RString, key: RString) {
+    let mut items = RECENT.lock().unwrap();
+    let recent = items
+        .get_or_insert_with(HashMap::new)
+        .entry(plugin.into())
+        .or_default();
+    recent.retain(|item| item.as_str() != key.as_str());
+    recent.insert(0, key.into());
+}
+
+extern "C" fn recent(plugin: RString) -> RVec<RString> {
+    RECENT
+        .lock()
+        .unwrap()
+        .as_ref()
+        .and_then(|items| items.get(plugin.as_str()))
+        .map(|recent| recent.iter().map(|item| item.as_str().into()).collect())
+        .unwrap_or_default()
+}
+
+const SINK: LogSink = LogSink { log: log_sink };
+
+extern "C" fn log_sink(level: LogLevel, target: RString, message: RString) {
+    // Printed with `eprintln` so that the test harness captures it
+    eprintln!("[{:?}] {}: {}", level, target, message);
+}
+
+/// A plugin that is yet to be initialized, for setting up its config directory first
+pub struct Builder {
+    plugin: PluginRef,
+    config_dir: PathBuf,
+    /// Whether the config directory was created for the test, and is removed after it
+    owned: bool,
+}
+
+impl Builder {
+    /// Write a file to the config directory, like the config file of the plugin
+    pub fn config(self, name: &str, content: &str) -> Self {
+        let path = self.config_dir.join(name);
+        if let Err(why) = path
+            .parent()
+            .map_or(Ok(()), fs::create_dir_all)
+            .and_then(|_| fs::write(&path, content))
+        {
+            panic!("Failed to write {}: {}", path.display(), why);
+        }
+        self
+    }
+
+    /// Give the plugin an existing config directory instead of an empty one. The files written
+    /// with [`Builder::config`] after this go to the directory.
+    pub fn config_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        if self.owned {
+            let _ = fs::remove_dir_all(&self.config_dir);
+        }
+        self.config_dir = dir.into();
+        self.owned = false;
+        self
+    }
+
+    /// Initialize the plugin with the config directory, like anyrun does on startup, and wait
+    /// for its init to be done. Panics if it takes longer than 10 seconds.
+    pub fn init(self) -> Harness {
+        self.plugin.set_log_sink()(SINK);
+        self.plugin.set_recency_store()(STORE);
+        self.plugin.init()(self.config_dir.to_string_lossy().as_ref().into());
+
+        let harness = Harness {
+            plugin: self.plugin,
+            config_dir: self.config_dir,
+            owned: self.owned,
+        };
+        // The init runs in the background. A query waits for it, whereas validating the input,
+        // which the queries of the tests start with, accepts everything until it is done.
+        harness.wait(harness.plugin.get_matches()("".into()));
+        harness
+    }
+}
+
+/// An initialized plugin. It is deinitialized when the harness is dropped.
+pub struct Harness {
+    plugin: PluginRef,
+    config_dir: PathBuf,
+    owned: bool,
+}
+
+impl Harness {
+    /// The plugin from the root module the plugin macros generate, like
+    /// `symbols::anyrun_internal_init_root_module`, for testing a plugin from its own crate
+    #[allow(clippy::new_ret_no_self)]
+    pub fn new(root_module: fn() -> PluginRef) -> Builder {
+        Self::builder(root_module())
+    }
+
+    /// The plugin from its library, like `target/debug/libsymbols.so`, refused like anyrun
+    /// refuses it if it was built against another version of the interface
+    pub fn load(path: impl AsRef<Path>) -> Result<Builder, LoadError> {
+        anyrun_interface::load(path.as_ref()).map(Self::builder)
+    }
+
+    fn builder(plugin: PluginRef) -> Builder {
+        let config_dir = env::temp_dir().join(format!(
+            "anyrun-plugin-test-{}-{}",
+            process::id(),
+            DIRS.fetch_add(1, Ordering::Relaxed)
+        ));
+        if let Err(why) = fs::create_dir_all(&config_dir) {
+            panic!("Failed to create {}: {}", config_dir.display(), why);
+        }
+
+        Builder {
+            plugin,
+            config_dir,
+            owned: true,
+        }
+    }
+
+    pub fn plugin(&self) -> PluginRef {
+        self.plugin
+    }
+
+    pub fn config_dir(&self) -> &Path {
+        &self.config_dir
+    }
+
+    pub fn info(&self) -> PluginInfo {
+        self.plugin.info()()
+    }
+
+    /// The prefix the plugin asks for, which anyrun routes the input by
+    pub fn prefix(&self) -> Option<String> {
+        self.plugin.prefix()().into_option().map(String::from)
+    }
+
+    /// Tell the plugin where anyrun was opened, like the text in the clipboard, for the queries
+    /// that follow
+    pub fn set_context(&self, context: QueryContext) {
+        self.plugin.set_context()(context);
+    }
+
+    /// All the matches for the input, after it is validated like anyrun validates it. Panics if
+    /// they aren't ready within 10 seconds.
+    pub fn query(&self, input: &str) -> Vec<Match> {
+        let input = match self.plugin.validate_input()(input.into()) {
+            ValidateResult::Accept => input.into(),
+            ValidateResult::Transform(input) => input,
+            ValidateResult::Reject => return Vec::new(),
+        };
+
+        self.wait(self.plugin.get_matches()(input))
+    }
+
+    /// All the matches of the query, once they are ready
+    fn wait(&self, id: u64) -> Vec<Match> {
+        let start = Instant::now();
+        let mut matches = Vec::new();
+        loop {
+            match self.plugin.poll_matches()(id) {
+                PollResult::Ready(rest) => break matches.extend(rest),
+                PollResult::Partial(streamed) => matches.extend(streamed),
+                PollResult::Pending if start.elapsed() < QUERY_TIMEOUT => {
+                    thread::sleep(Duration::from_millis(1))
+                }
+                PollResult::Pending => panic!("Timed out waiting for the matches"),
+                PollResult::Cancelled => panic!("The query was cancelled"),
+            }
+        }
+        matches
+    }
+
+    /// Select the match that was found for the input
+    pub fn select(&self, selection: &Match, input: &str) -> HandleResult {
+        self.plugin.handle_selection()(selection.clone(), input.into())
+    }
+
+    /// Select the action with the id of the match that was found for the input
+    pub fn select_action(&self, selection: &Match, action: u64, input: &str) -> HandleResult {
+        let mut selection = selection.clone();
+        selection.action = ROption::RSome(action);
+        self.plugin.handle_selection()(selection, input.into())
+    }
+
+    pub fn preview(&self, selection: &Match) -> Option<Preview> {
+        self.plugin.get_preview()(selection.clone()).into_option()
+    }
+
+    /// The errors the plugin reported since the last time
+    pub fn errors(&self) -> Vec<String> {
+        self.plugin.errors()()
+            .into_iter()
+            .map(String::from)
+            .collect()
+    }
+}
+
+impl Drop for Harness {
+    fn drop(&mut self) {
+        self.plugin.deinit()();
+        if self.owned {
+            let _ = fs::remove_dir_all(&self.config_dir);
+        }
+    }
+}
+
+/// The titles of the matches, for comparing them in one assertion
+pub fn titles(matches: &[Match]) -> Vec<&str> {
+    matches.iter().map(|_match| _match.title.as_str()).collect()
+}
@@ -0,0 +1,29 @@
+//! Noticing that anyrun no longer waits for the matches of a query, as the input changed since.
+//! Plugins doing slow work like network requests should check now and then, and give up early
+//! instead of holding up the matches of the newer input.
+//!
+//! ```ignore
+//! for url in urls {
+//!     if cancel::is_cancelled() {
+//!         return RVec::new();
+//!     }
+//!     matches.push(fetch(url));
+//! }
+//! ```
+
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// The queries with ids below this are cancelled, as anyrun only ever waits for the latest one
+static CANCELLED_BEFORE: AtomicU64 = AtomicU64::new(0);
+
+#[doc(hidden)]
+pub fn cancel(query: u64) {
+    CANCELLED_BEFORE.fetch_max(query + 1, Ordering::Relaxed);
+}
+
+/// Whether the query `get_matches` is working on was cancelled. Only works on the thread
+/// `get_matches` is called on.
+pub fn is_cancelled() -> bool {
+    crate::stream::current_query()
+        .is_some_and(|query| query < CANCELLED_BEFORE.load(Ordering::Relaxed))
+}
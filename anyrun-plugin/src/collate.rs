@@ -0,0 +1,60 @@
+//! Comparing names in the order of the locale of the user, so that names with accents and other
+//! non-ASCII letters sort next to the ones without them instead of after `z`.
+//!
+//! ```ignore
+//! entries.sort_by(|a, b| b.score.cmp(&a.score).then_with(|| collate::compare(&a.name, &b.name)));
+//! ```
+
+use std::cmp::Ordering;
+#[cfg(feature = "collate")]
+use std::env;
+
+#[cfg(feature = "collate")]
+use icu_collator::{Collator, CollatorOptions};
+#[cfg(feature = "collate")]
+use icu_locid::Locale;
+
+#[cfg(feature = "collate")]
+thread_local! {
+    // The collator can't be shared between threads, and the matches are found on a thread of their own
+    static COLLATOR: Option<Collator> = collator();
+}
+
+/// The locale used for sorting, from the environment like the C library does
+#[cfg(feature = "collate")]
+fn locale() -> Option<Locale> {
+    let var = ["LC_ALL", "LC_COLLATE", "LANG"]
+        .into_iter()
+        .filter_map(|name| env::var(name).ok())
+        .find(|var| !var.is_empty())?;
+
+    // `de_DE.UTF-8@euro` is `de-DE` to ICU, and the C locale has no language
+    let language = var.split(['.', '@']).next()?.replace('_', "-");
+    match language.as_str() {
+        "C" | "POSIX" => None,
+        language => language.parse().ok(),
+    }
+}
+
+#[cfg(feature = "collate")]
+fn collator() -> Option<Collator> {
+    let locale = locale().unwrap_or(Locale::UND);
+    Collator::try_new(&(&locale).into(), CollatorOptions::new())
+        .map_err(|why| crate::log::warn!("Failed to create a collator for {}: {}", locale, why))
+        .ok()
+}
+
+/// Compare the strings in the order of the locale, falling back to comparing the characters
+#[cfg(feature = "collate")]
+pub fn compare(a: &str, b: &str) -> Ordering {
+    COLLATOR.with(|collator| match collator {
+        Some(collator) => collator.compare(a, b),
+        None => a.cmp(b),
+    })
+}
+
+/// Built without the `collate` feature, the characters are compared as they are
+#[cfg(not(feature = "collate"))]
+pub fn compare(a: &str, b: &str) -> Ordering {
+    a.cmp(b)
+}
@@ -0,0 +1,44 @@
+//! Reading the config file of the plugin from the config directory anyrun passes to `init`.
+
+use std::{fs, io, path::Path};
+
+use serde::de::DeserializeOwned;
+
+/// Read `<name>.ron` or `<name>.toml` from the config directory, in that order. The default config
+/// is used if there is neither, and if the file can't be read or parsed, which is reported to the
+/// user with [`report::error`](crate::report::error).
+///
+/// ```ignore
+/// #[init]
+/// fn init(config_dir: RString) -> Config {
+///     let config: Config = plugin_config(&config_dir, "websearch");
+///     prefix::set(&config.prefix);
+///     config
+/// }
+/// ```
+pub fn plugin_config<T: DeserializeOwned + Default>(config_dir: &str, name: &str) -> T {
+    let dir = Path::new(config_dir);
+
+    for extension in ["ron", "toml"] {
+        let path = dir.join(format!("{}.{}", name, extension));
+        let content = match fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(why) if why.kind() == io::ErrorKind::NotFound => continue,
+            Err(why) => {
+                crate::report::error!("Failed to read {}: {}", path.display(), why);
+                return T::default();
+            }
+        };
+
+        let result = match extension {
+            "ron" => ron::from_str(&content).map_err(|why| why.to_string()),
+            _ => toml::from_str(&content).map_err(|why| why.to_string()),
+        };
+        return result.unwrap_or_else(|why| {
+            crate::report::error!("Failed to parse {}: {}", path.display(), why);
+            T::default()
+        });
+    }
+
+    T::default()
+}
@@ -0,0 +1,27 @@
+//! Where anyrun was opened: the output it is shown on, the text in the clipboard and the window
+//! that was focused before it. Plugins can act on it in `get_matches`, like a translator defaulting
+//! to the text in the clipboard or a window switcher leaving out the window the user came from.
+//!
+//! ```ignore
+//! let text = match input.trim() {
+//!     "" => context::get().clipboard.map(String::from).unwrap_or_default(),
+//!     input => input.to_string(),
+//! };
+//! ```
+
+use std::sync::Mutex;
+
+use anyrun_interface::QueryContext;
+
+static CONTEXT: Mutex<Option<QueryContext>> = Mutex::new(None);
+
+#[doc(hidden)]
+pub fn set(context: QueryContext) {
+    *CONTEXT.lock().unwrap() = Some(context);
+}
+
+/// The context of the current query. The clipboard and the focused window arrive shortly after
+/// anyrun opens, so they may be missing from the first queries.
+pub fn get() -> QueryContext {
+    CONTEXT.lock().unwrap().clone().unwrap_or_default()
+}
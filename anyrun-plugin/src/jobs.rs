@@ -0,0 +1,99 @@
+//! Handing long work off to the background, like installing a package or recording the screen.
+//! anyrun closes right after the handler returns, so the job is watched over by an anyrun process
+//! of its own, the `anyrun` in `$PATH` when the plugin isn't running in anyrun itself, like in
+//! `anyrun-bridge` or a test. It shows the progress of the job in desktop notifications, and the jobs plugin
+//! lists the running jobs for cancelling them.
+//!
+//! ```ignore
+//! if let Err(why) = jobs::start("Updating the system", "pkexec", &["pacman", "-Syu", "--noconfirm"]) {
+//!     report::error!("Failed to start the update: {}", why);
+//! }
+//! ```
+//!
+//! A line the program prints to stdout that contains a percentage, like `Downloading 42%`, moves
+//! the progress bar of its notification.
+
+use std::{
+    env, fs, io,
+    os::unix::process::CommandExt,
+    path::PathBuf,
+    process::{Command, Stdio},
+};
+
+use nix::{
+    sys::signal::{self, Signal},
+    unistd::Pid,
+};
+use serde::{Deserialize, Serialize};
+
+/// A running job, as the process watching over it keeps it
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Job {
+    pub title: String,
+    /// The program and its arguments
+    pub command: Vec<String>,
+    /// When the job started, in seconds since the Unix epoch
+    pub started: u64,
+    /// The latest percentage the program printed
+    pub progress: Option<u8>,
+}
+
+/// Where the running jobs are kept, each in a file named after the process group of the program
+#[doc(hidden)]
+pub fn dir() -> PathBuf {
+    env::var("XDG_RUNTIME_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(|_| env::temp_dir())
+        .join("anyrun-jobs")
+}
+
+/// The anyrun that watches over the jobs
+fn runner() -> PathBuf {
+    env::current_exe()
+        .ok()
+        .filter(|exe| exe.file_name().is_some_and(|name| name == "anyrun"))
+        .unwrap_or_else(|| PathBuf::from("anyrun"))
+}
+
+/// Run the program in the background, with the title in its notifications and in the jobs plugin
+pub fn start(title: &str, program: &str, args: &[&str]) -> io::Result<()> {
+    Command::new(runner())
+        .arg("--run-job")
+        .arg(title)
+        .arg("--")
+        .arg(program)
+        .args(args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        // Closing the terminal anyrun was started from doesn't stop the job
+        .process_group(0)
+        .spawn()
+        .map(|_| ())
+}
+
+/// The running jobs with the process groups of their programs, the oldest first
+pub fn list() -> Vec<(i32, Job)> {
+    let mut jobs = fs::read_dir(dir())
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| {
+            let path = entry.ok()?.path();
+            let group = path.file_stem()?.to_str()?.parse().ok()?;
+            // The watching process may have been killed without cleaning up
+            if signal::killpg(Pid::from_raw(group), None).is_err() {
+                let _ = fs::remove_file(&path);
+                return None;
+            }
+            let job = serde_json::from_str(&fs::read_to_string(&path).ok()?).ok()?;
+            Some((group, job))
+        })
+        .collect::<Vec<(i32, Job)>>();
+
+    jobs.sort_by_key(|(_, job)| job.started);
+    jobs
+}
+
+/// Stop the program of the job, the process watching over it then tells that it was cancelled
+pub fn cancel(group: i32) -> nix::Result<()> {
+    signal::killpg(Pid::from_raw(group), Signal::SIGTERM)
+}
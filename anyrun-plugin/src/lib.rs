@@ -3,10 +3,79 @@ The crate for building plugins for Anyrun.
 
 Each plugin needs 4 functions defined, `init`, `info`, `get_matches` and the `handler`. Documentation
 on what each of these should be is found in their respective attribute macros.
+
+Plugins can optionally define a `validate_input` function as well, anyrun uses a default implementation
+for it otherwise. The same goes for `deinit`, which is for saving what the plugin needs to keep before
+it is unloaded, and for `preview`, which gives the selected match more space than its row, like for a
+definition or the contents of a file.
+
+The config file of the plugin should be read with [`plugin_config`], which handles both RON and TOML
+and reports broken files the same way for every plugin.
+
+Messages should be logged with the macros in the [`log`] module, so that they end up in the log of anyrun
+with the name of the plugin attached. Errors the user should know about, like a failed download, are
+reported with the [`report`] module instead, which shows them in anyrun.
+
+Slow plugins can show the matches they have found so far with the [`stream`] module, and notice that
+the matches are no longer needed with the [`cancel`] module. Matches that go out of date, like a clock,
+are gotten again on an interval with the [`refresh`] module.
+
+Handlers kicking off long work, like installing a package, should hand it off with the [`jobs`] module,
+which runs it in the background with its progress shown in notifications.
+
+Plugins only having matches for input starting with a prefix should tell anyrun about it with the
+[`prefix`] module, so that the other plugins aren't asked once the input is meant for this one.
+
+What anyrun knows about where it was opened, like the text in the clipboard and the window that was
+focused before it, is available to `get_matches` through the [`context`] module.
+
+Plugins picking from a fixed set of items can remember the ones used recently with the [`recent`] module,
+which anyrun keeps between runs.
+
+State that needs to outlive the run, like usage counts or a clipboard history, can be kept in the
+[`store`] of the plugin instead of a file format of its own.
+
+Names shown in alphabetical order should be compared with the [`collate`] module, which sorts them
+the way the language of the user does.
 !*/
 
-pub use anyrun_interface::{self, HandleResult, Match, PluginInfo};
-pub use anyrun_macros::{get_matches, handler, info, init};
+pub use anyrun_interface::{
+    self, Action, HandleResult, Image, Match, PluginInfo, Preview, QueryContext, ValidateResult,
+    Widget,
+};
+pub use anyrun_macros::{deinit, get_matches, handler, info, init, preview, validate_input};
+pub use config::plugin_config;
+
+pub mod cancel;
+pub mod collate;
+mod config;
+pub mod context;
+pub mod jobs;
+pub mod log;
+pub mod prefix;
+pub mod recent;
+pub mod refresh;
+pub mod report;
+pub mod store;
+pub mod stream;
+
+/// Default implementations of the optional plugin functions, a function defined with the
+/// respective attribute macro takes precedence over these.
+#[doc(hidden)]
+pub mod defaults {
+    use abi_stable::std_types::{ROption, RString};
+    use anyrun_interface::{Match, Preview, ValidateResult};
+
+    pub extern "C" fn anyrun_internal_validate_input(_input: RString) -> ValidateResult {
+        ValidateResult::Accept
+    }
+
+    pub extern "C" fn anyrun_internal_deinit() {}
+
+    pub extern "C" fn anyrun_internal_get_preview(_selection: Match) -> ROption<Preview> {
+        ROption::RNone
+    }
+}
 
 /*
 The macro to create a plugin, handles asynchronous execution of getting the matches and the boilerplate
@@ -0,0 +1,78 @@
+//! Logging for plugins. The messages are passed on to anyrun, which logs them with the plugin they came from.
+//!
+//! ```ignore
+//! log::warn!("Failed to read the config file: {}", why);
+//! ```
+
+use std::{fmt, sync::OnceLock};
+
+use anyrun_interface::{LogLevel, LogSink};
+
+static SINK: OnceLock<LogSink> = OnceLock::new();
+
+#[doc(hidden)]
+pub fn set_sink(sink: LogSink) {
+    let _ = SINK.set(sink);
+}
+
+#[doc(hidden)]
+pub fn log(level: LogLevel, target: &str, args: fmt::Arguments) {
+    match SINK.get() {
+        Some(sink) => (sink.log)(level, target.into(), args.to_string().into()),
+        // Not loaded by anyrun, so there is nothing to pass the message on to
+        None => eprintln!("[{:?}] {}: {}", level, target, args),
+    }
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __anyrun_log {
+    ($level:ident, $($arg:tt)+) => {
+        $crate::log::log(
+            $crate::anyrun_interface::LogLevel::$level,
+            module_path!(),
+            format_args!($($arg)+),
+        )
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __anyrun_log_error {
+    ($($arg:tt)+) => { $crate::__anyrun_log!(Error, $($arg)+) };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __anyrun_log_warn {
+    ($($arg:tt)+) => { $crate::__anyrun_log!(Warn, $($arg)+) };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __anyrun_log_info {
+    ($($arg:tt)+) => { $crate::__anyrun_log!(Info, $($arg)+) };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __anyrun_log_debug {
+    ($($arg:tt)+) => { $crate::__anyrun_log!(Debug, $($arg)+) };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __anyrun_log_trace {
+    ($($arg:tt)+) => { $crate::__anyrun_log!(Trace, $($arg)+) };
+}
+
+/// Log a message at the debug level
+pub use crate::__anyrun_log_debug as debug;
+/// Log a message at the error level
+pub use crate::__anyrun_log_error as error;
+/// Log a message at the info level
+pub use crate::__anyrun_log_info as info;
+/// Log a message at the trace level
+pub use crate::__anyrun_log_trace as trace;
+/// Log a message at the warn level
+pub use crate::__anyrun_log_warn as warn;
@@ -0,0 +1,40 @@
+//! Telling anyrun the prefix the input needs to start with for the plugin to have any matches.
+//! Once the typed input can only be meant for one plugin, anyrun stops asking the other ones.
+//!
+//! ```ignore
+//! prefix::set(&config.prefix);
+//! ```
+
+use std::sync::RwLock;
+
+static PREFIX: RwLock<Option<String>> = RwLock::new(None);
+
+/// Set the prefix of the plugin, usually in `init` once the config is read. An empty prefix means
+/// that the plugin has none.
+pub fn set(prefix: &str) {
+    *PREFIX.write().unwrap() = (!prefix.is_empty()).then(|| prefix.to_string());
+}
+
+/// Set the prefix of a plugin passing the input on to scripts or programs with prefixes of their
+/// own, like the `prefix` of each entry in its config. It is only set if there is a single one, as
+/// anyrun would otherwise keep the input meant for the others from the plugin.
+pub fn set_single<'a>(prefixes: impl IntoIterator<Item = Option<&'a str>>) {
+    let mut prefixes = prefixes.into_iter();
+    if let (Some(Some(prefix)), None) = (prefixes.next(), prefixes.next()) {
+        set(prefix);
+    }
+}
+
+/// The input meant for one of the scripts or programs, without the prefix of it and trimmed, or
+/// None if it doesn't start with the prefix
+pub fn strip<'a>(input: &'a str, prefix: Option<&str>) -> Option<&'a str> {
+    match prefix {
+        Some(prefix) => input.strip_prefix(prefix).map(str::trim),
+        None => Some(input),
+    }
+}
+
+#[doc(hidden)]
+pub fn get() -> Option<String> {
+    PREFIX.read().unwrap().clone()
+}
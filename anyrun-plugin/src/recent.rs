@@ -0,0 +1,60 @@
+//! The recently used items of the plugin, kept by anyrun between runs. Useful for showing the
+//! items used the most first when nothing has been typed yet, without the plugin storing them itself.
+//!
+//! ```ignore
+//! recent::record(&selection.title);
+//! recent::sort(&mut symbols, |symbol| &symbol.chr);
+//! ```
+
+use std::sync::OnceLock;
+
+use abi_stable::std_types::RString;
+use anyrun_interface::RecencyStore;
+
+static STORE: OnceLock<(RecencyStore, RString)> = OnceLock::new();
+
+#[doc(hidden)]
+pub fn set_store(store: RecencyStore, plugin: RString) {
+    let _ = STORE.set((store, plugin));
+}
+
+/// Mark the item with the key as used just now
+pub fn record(key: &str) {
+    // Not loaded by anyrun, so there is nowhere to keep the items
+    if let Some((store, plugin)) = STORE.get() {
+        (store.record)(plugin.clone(), key.into());
+    }
+}
+
+/// The keys of the recently used items, the most recent first
+pub fn recent() -> Vec<String> {
+    match STORE.get() {
+        Some((store, plugin)) => (store.recent)(plugin.clone())
+            .into_iter()
+            .map(String::from)
+            .collect(),
+        None => Vec::new(),
+    }
+}
+
+/// The keys of the recently used items of another plugin, by the name in its info
+pub fn recent_of(plugin: &str) -> Vec<String> {
+    match STORE.get() {
+        Some((store, _)) => (store.recent)(plugin.into())
+            .into_iter()
+            .map(String::from)
+            .collect(),
+        None => Vec::new(),
+    }
+}
+
+/// Move the recently used items to the front, the most recent first. The other items keep their order.
+pub fn sort<T>(items: &mut [T], key: impl Fn(&T) -> &str) {
+    let recent = recent();
+    items.sort_by_key(|item| {
+        recent
+            .iter()
+            .position(|recent| recent == key(item))
+            .unwrap_or(usize::MAX)
+    });
+}
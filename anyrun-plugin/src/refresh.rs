@@ -0,0 +1,39 @@
+//! Asking anyrun to get the matches again after a while, for matches that go out of date like a
+//! clock, a countdown or the load of the system. anyrun calls `get_matches` with the same input
+//! again once the interval has passed, for as long as the input stays the same.
+//!
+//! ```ignore
+//! refresh::after(Duration::from_secs(1));
+//! ```
+
+use std::{sync::Mutex, time::Duration};
+
+/// The latest query, and how long after its matches they should be gotten again
+static REFRESH: Mutex<(u64, Option<Duration>)> = Mutex::new((0, None));
+
+/// Get the matches for the query `get_matches` is working on again after the interval. Only works
+/// on the thread `get_matches` is called on, and only for the matches of that one query.
+pub fn after(interval: Duration) {
+    let query = match crate::stream::current_query() {
+        Some(query) => query,
+        None => return,
+    };
+
+    let mut refresh = REFRESH.lock().unwrap();
+    if refresh.0 == query {
+        refresh.1 = Some(interval);
+    }
+}
+
+/// Start a new query, which isn't refreshed unless it asks for it again
+#[doc(hidden)]
+pub fn start(query: u64) {
+    *REFRESH.lock().unwrap() = (query, None);
+}
+
+/// How long after the matches of the query they should be gotten again, if they should be
+#[doc(hidden)]
+pub fn get(query: u64) -> Option<Duration> {
+    let refresh = REFRESH.lock().unwrap();
+    (refresh.0 == query).then_some(refresh.1).flatten()
+}
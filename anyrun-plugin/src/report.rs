@@ -0,0 +1,36 @@
+//! Reporting errors that don't stop the plugin from working to the user, like a failed download, a
+//! missing program or a broken config. anyrun shows them under the matches of the plugin until they
+//! are dismissed, and logs them like [`log::error`](crate::log::error).
+//!
+//! ```ignore
+//! report::error!("Failed to fetch the exchange rates: {}", why);
+//! ```
+
+use std::{fmt, mem, sync::Mutex};
+
+use anyrun_interface::LogLevel;
+
+static ERRORS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+#[doc(hidden)]
+pub fn report(target: &str, args: fmt::Arguments) {
+    let message = args.to_string();
+    crate::log::log(LogLevel::Error, target, format_args!("{}", message));
+    ERRORS.lock().unwrap().push(message);
+}
+
+#[doc(hidden)]
+pub fn take() -> Vec<String> {
+    mem::take(&mut ERRORS.lock().unwrap())
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __anyrun_report_error {
+    ($($arg:tt)+) => {
+        $crate::report::report(module_path!(), format_args!($($arg)+))
+    };
+}
+
+/// Report an error to the user
+pub use crate::__anyrun_report_error as error;
@@ -0,0 +1,198 @@
+//! Keeping state between runs, like usage counts, the history of a clipboard or running timers.
+//! Every plugin has a store of its own in the state directory of anyrun, and writes are committed
+//! to it before they return, so nothing is lost when anyrun is closed right after.
+//!
+//! ```ignore
+//! let mut counts: HashMap<String, u32> = store::get("counts").unwrap_or_default();
+//! *counts.entry(name).or_default() += 1;
+//! if let Err(why) = store::set("counts", &counts) {
+//!     log::warn!("Failed to save the counts: {}", why);
+//! }
+//! ```
+
+#[cfg(feature = "store")]
+use std::{
+    env, fs,
+    path::PathBuf,
+    thread,
+    time::{Duration, Instant},
+};
+use std::{fmt, sync::OnceLock};
+
+#[cfg(feature = "store")]
+use redb::{Database, DatabaseError, ReadableTable, TableDefinition};
+use serde::{de::DeserializeOwned, Serialize};
+
+/// How much a plugin can keep in its store, counting the keys and the serialized values
+pub const QUOTA: usize = 16 * 1024 * 1024;
+
+#[cfg(feature = "store")]
+const TABLE: TableDefinition<&str, &[u8]> = TableDefinition::new("state");
+
+/// How long to wait for another process to be done with the store, like a second anyrun or the
+/// bridge running the same plugin
+#[cfg(feature = "store")]
+const LOCK_TIMEOUT: Duration = Duration::from_secs(1);
+
+static NAME: OnceLock<String> = OnceLock::new();
+#[cfg(feature = "store")]
+static PATH: OnceLock<Option<PathBuf>> = OnceLock::new();
+
+#[derive(Debug)]
+pub enum Error {
+    /// The store couldn't be opened, like when another process held it for too long, or anyrun-plugin
+    /// was built without the `store` feature
+    Unavailable,
+    /// Storing the value would take the plugin over its [`QUOTA`]
+    Quota,
+    /// Reading or writing the store failed
+    Storage(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Unavailable => write!(f, "The store of the plugin is not available"),
+            Self::Quota => write!(f, "The store of the plugin is full"),
+            Self::Storage(why) => write!(f, "{}", why),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+#[cfg(feature = "store")]
+fn storage(why: impl fmt::Display) -> Error {
+    Error::Storage(why.to_string())
+}
+
+/// The name of the plugin, which its store is named after
+#[doc(hidden)]
+pub fn set_name(name: impl Into<String>) {
+    let _ = NAME.set(name.into());
+}
+
+/// The file of the store, in the state directory of anyrun
+#[cfg(feature = "store")]
+fn path() -> Option<&'static PathBuf> {
+    PATH.get_or_init(|| {
+        let name = NAME.get()?.replace('/', "_");
+        let dir = match env::var("XDG_STATE_HOME") {
+            Ok(state_home) => PathBuf::from(state_home),
+            Err(_) => PathBuf::from(format!("{}/.local/state", env::var("HOME").ok()?)),
+        }
+        .join("anyrun/store");
+
+        fs::create_dir_all(&dir)
+            .map_err(|why| crate::log::warn!("Failed to create the store directory: {}", why))
+            .ok()?;
+        Some(dir.join(format!("{}.redb", name)))
+    })
+    .as_ref()
+}
+
+/// Open the store for a single transaction. The file is locked while it is open, so it is closed
+/// right after for the other processes running the plugin to get at it too.
+#[cfg(feature = "store")]
+fn database() -> Result<Database, Error> {
+    let path = path().ok_or(Error::Unavailable)?;
+    let start = Instant::now();
+    loop {
+        match Database::create(path) {
+            Ok(database) => return Ok(database),
+            Err(DatabaseError::DatabaseAlreadyOpen) if start.elapsed() < LOCK_TIMEOUT => {
+                thread::sleep(Duration::from_millis(10))
+            }
+            Err(DatabaseError::DatabaseAlreadyOpen) => return Err(Error::Unavailable),
+            Err(why) => return Err(storage(why)),
+        }
+    }
+}
+
+/// The value stored under the key, if there is one and it is still of the type
+#[cfg(feature = "store")]
+pub fn get<T: DeserializeOwned>(key: &str) -> Option<T> {
+    let database = database().ok()?;
+    let transaction = database.begin_read().ok()?;
+    let table = transaction.open_table(TABLE).ok()?;
+    let value = table.get(key).ok()??;
+    serde_json::from_slice(value.value()).ok()
+}
+
+/// Store the value under the key, replacing the one stored before
+#[cfg(feature = "store")]
+pub fn set<T: Serialize>(key: &str, value: &T) -> Result<(), Error> {
+    let value = serde_json::to_vec(value).map_err(storage)?;
+
+    let database = database()?;
+    let transaction = database.begin_write().map_err(storage)?;
+    {
+        let mut table = transaction.open_table(TABLE).map_err(storage)?;
+        let mut size = key.len() + value.len();
+        for entry in table.iter().map_err(storage)? {
+            let (old_key, old_value) = entry.map_err(storage)?;
+            if old_key.value() != key {
+                size += old_key.value().len() + old_value.value().len();
+            }
+        }
+        if size > QUOTA {
+            return Err(Error::Quota);
+        }
+
+        table.insert(key, value.as_slice()).map_err(storage)?;
+    }
+    transaction.commit().map_err(storage)
+}
+
+/// Remove the value stored under the key
+#[cfg(feature = "store")]
+pub fn remove(key: &str) -> Result<(), Error> {
+    let database = database()?;
+    let transaction = database.begin_write().map_err(storage)?;
+    transaction
+        .open_table(TABLE)
+        .map_err(storage)?
+        .remove(key)
+        .map_err(storage)?;
+    transaction.commit().map_err(storage)
+}
+
+/// The keys of all the stored values
+pub fn keys() -> Vec<String> {
+    stored_keys().unwrap_or_default()
+}
+
+#[cfg(feature = "store")]
+fn stored_keys() -> Option<Vec<String>> {
+    let database = database().ok()?;
+    let transaction = database.begin_read().ok()?;
+    let table = transaction.open_table(TABLE).ok()?;
+    let keys = table
+        .iter()
+        .ok()?
+        .filter_map(|entry| entry.ok().map(|(key, _)| key.value().to_string()))
+        .collect();
+    Some(keys)
+}
+
+// Built without the `store` feature, nothing is kept and the plugins start from scratch every time
+
+#[cfg(not(feature = "store"))]
+pub fn get<T: DeserializeOwned>(_key: &str) -> Option<T> {
+    None
+}
+
+#[cfg(not(feature = "store"))]
+pub fn set<T: Serialize>(_key: &str, _value: &T) -> Result<(), Error> {
+    Err(Error::Unavailable)
+}
+
+#[cfg(not(feature = "store"))]
+pub fn remove(_key: &str) -> Result<(), Error> {
+    Err(Error::Unavailable)
+}
+
+#[cfg(not(feature = "store"))]
+fn stored_keys() -> Option<Vec<String>> {
+    None
+}
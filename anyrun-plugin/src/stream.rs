@@ -0,0 +1,63 @@
+//! Sending matches before `get_matches` returns, for plugins that find them slowly or one by one.
+//! anyrun shows the sent matches right away, followed by the ones `get_matches` returns in the end.
+//!
+//! ```ignore
+//! for word in words {
+//!     stream::send([lookup(word)]);
+//! }
+//! RVec::new()
+//! ```
+
+use std::{cell::Cell, mem, sync::Mutex};
+
+use anyrun_interface::Match;
+
+/// The query whose matches are being streamed, and the matches sent since the last poll
+static STREAMED: Mutex<(u64, Vec<Match>)> = Mutex::new((0, Vec::new()));
+
+thread_local! {
+    /// The query the `get_matches` running on this thread is for
+    static QUERY: Cell<Option<u64>> = const { Cell::new(None) };
+}
+
+/// Send matches for the query `get_matches` is working on. Only works on the thread `get_matches`
+/// is called on, and the matches of a query anyrun no longer waits for are dropped.
+pub fn send(matches: impl IntoIterator<Item = Match>) {
+    let query = match current_query() {
+        Some(query) => query,
+        None => return,
+    };
+
+    let mut streamed = STREAMED.lock().unwrap();
+    if streamed.0 == query {
+        streamed.1.extend(matches);
+    }
+}
+
+/// The query `get_matches` is working on on the current thread
+pub(crate) fn current_query() -> Option<u64> {
+    QUERY.with(Cell::get)
+}
+
+/// Start collecting the matches of a new query, dropping the ones of the previous query
+#[doc(hidden)]
+pub fn start(query: u64) {
+    *STREAMED.lock().unwrap() = (query, Vec::new());
+}
+
+/// Mark the current thread as getting the matches of the query
+#[doc(hidden)]
+pub fn set_query(query: u64) {
+    QUERY.with(|current| current.set(Some(query)));
+}
+
+/// The matches sent for the query since the previous call
+#[doc(hidden)]
+pub fn take(query: u64) -> Vec<Match> {
+    let mut streamed = STREAMED.lock().unwrap();
+    if streamed.0 == query {
+        mem::take(&mut streamed.1)
+    } else {
+        Vec::new()
+    }
+}
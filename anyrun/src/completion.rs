@@ -0,0 +1,91 @@
+//! Word completion for the text input. The rest of the word being typed is suggested from
+//! a bundled list of common words and the words of previously submitted queries.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// The amount of words from submitted queries that are remembered
+const MAX_HARVESTED_WORDS: usize = 500;
+/// Words shorter than this are not remembered
+const MIN_HARVESTED_LENGTH: usize = 3;
+/// The amount of characters that need to be typed before a completion is suggested
+const MIN_PREFIX_LENGTH: usize = 2;
+
+pub struct Completion {
+    /// The bundled words, sorted and in lowercase
+    words: Vec<&'static str>,
+    /// Words from submitted queries, most recent first
+    harvested: Vec<String>,
+    path: PathBuf,
+}
+
+impl Completion {
+    pub fn load(state_dir: &Path) -> Self {
+        let path = state_dir.join("words");
+        let harvested = fs::read_to_string(&path)
+            .map(|content| content.lines().map(String::from).collect())
+            .unwrap_or_default();
+
+        Self {
+            words: include_str!("../res/words.txt").lines().collect(),
+            harvested,
+            path,
+        }
+    }
+
+    /// Get the missing rest of the last word in the input, if a known word starts with it
+    pub fn complete(&self, input: &str) -> Option<&str> {
+        let prefix = input.rsplit(char::is_whitespace).next()?;
+        let prefix_len = prefix.chars().count();
+
+        if prefix_len < MIN_PREFIX_LENGTH {
+            return None;
+        }
+
+        let prefix = prefix.to_lowercase();
+
+        // Prefer words the user has typed before
+        let start = self.words.partition_point(|word| *word < prefix.as_str());
+        let word = self
+            .harvested
+            .iter()
+            .map(String::as_str)
+            .find(|word| {
+                word.chars().count() > prefix_len && word.to_lowercase().starts_with(&prefix)
+            })
+            .or_else(|| {
+                self.words[start..]
+                    .iter()
+                    .copied()
+                    .take_while(|word| word.starts_with(&prefix))
+                    .find(|word| word.len() > prefix.len())
+            })?;
+
+        word.char_indices()
+            .nth(prefix_len)
+            .map(|(index, _)| &word[index..])
+    }
+
+    /// Remember the words of a submitted query
+    pub fn harvest(&mut self, input: &str) {
+        for word in input.split_whitespace().filter(|word| {
+            word.chars().count() >= MIN_HARVESTED_LENGTH && word.chars().all(char::is_alphanumeric)
+        }) {
+            self.harvested.retain(|harvested| harvested != word);
+            self.harvested.insert(0, word.to_string());
+        }
+
+        self.harvested.truncate(MAX_HARVESTED_WORDS);
+
+        if let Err(why) = self
+            .path
+            .parent()
+            .map_or(Ok(()), fs::create_dir_all)
+            .and_then(|_| fs::write(&self.path, self.harvested.join("\n")))
+        {
+            tracing::warn!("Failed to save the completion words: {}", why);
+        }
+    }
+}
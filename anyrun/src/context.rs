@@ -0,0 +1,122 @@
+//! Finding out where anyrun was opened, for the plugins. The focused window and the output are
+//! asked from the compositor before the window of anyrun takes the focus, which works on Hyprland
+//! and Sway. The compositor is asked in the background, as the whole tree of Sway takes a while to
+//! get, so like the clipboard read once the window is shown, they arrive after the first queries.
+
+use std::{
+    env,
+    process::Command,
+    sync::mpsc::{self, Receiver},
+    thread,
+};
+
+use abi_stable::std_types::{ROption, RString};
+use anyrun_interface::QueryContext;
+use serde_json::Value;
+use tracing::warn;
+
+use crate::hyprland;
+
+/// The focused window and the output it is on, as far as the compositor tells
+pub fn focused() -> QueryContext {
+    let context = if env::var_os("HYPRLAND_INSTANCE_SIGNATURE").is_some() {
+        from_hyprland()
+    } else if env::var_os("SWAYSOCK").is_some() {
+        from_sway()
+    } else {
+        return QueryContext::default();
+    };
+
+    context.unwrap_or_else(|| {
+        warn!("Failed to get the focused window from the compositor");
+        QueryContext::default()
+    })
+}
+
+/// [`focused`] on a thread of its own, started right away for the focus to still be on the window
+/// anyrun was opened from
+pub fn focused_in_background() -> Receiver<QueryContext> {
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        let _ = sender.send(focused());
+    });
+    receiver
+}
+
+fn from_hyprland() -> Option<QueryContext> {
+    let window = json(&hyprland::request("j/activewindow").ok()?)?;
+    let monitors = hyprland::request("j/monitors")
+        .ok()
+        .and_then(|monitors| json(&monitors));
+    let output = monitors
+        .as_ref()
+        .and_then(|monitors| monitors.as_array())
+        .and_then(|monitors| {
+            monitors
+                .iter()
+                .find(|monitor| monitor["focused"].as_bool() == Some(true))
+        })
+        .and_then(|monitor| string(&monitor["name"]));
+
+    Some(QueryContext {
+        output: output.into(),
+        clipboard: ROption::RNone,
+        app_id: string(&window["class"]).into(),
+        window_title: string(&window["title"]).into(),
+    })
+}
+
+fn from_sway() -> Option<QueryContext> {
+    let output = Command::new("swaymsg")
+        .args(["-t", "get_tree"])
+        .output()
+        .ok()?;
+    let tree = json(&String::from_utf8_lossy(&output.stdout))?;
+    let (output, node) = find_focused(&tree, None)?;
+    // The focused node is the workspace when no window is focused
+    let window = (node["type"].as_str() != Some("workspace")).then_some(node);
+
+    Some(QueryContext {
+        output: output.map(Into::into).into(),
+        clipboard: ROption::RNone,
+        // The windows running through Xwayland only have a class
+        app_id: window
+            .and_then(|window| {
+                string(&window["app_id"]).or_else(|| string(&window["window_properties"]["class"]))
+            })
+            .into(),
+        window_title: window.and_then(|window| string(&window["name"])).into(),
+    })
+}
+
+/// The focused node in the tree of Sway, with the name of the output it is on
+fn find_focused<'a>(
+    node: &'a Value,
+    output: Option<&'a str>,
+) -> Option<(Option<&'a str>, &'a Value)> {
+    let output = match node["type"].as_str() {
+        Some("output") => node["name"].as_str(),
+        _ => output,
+    };
+    if node["focused"].as_bool() == Some(true) {
+        return Some((output, node));
+    }
+
+    node["nodes"]
+        .as_array()
+        .into_iter()
+        .chain(node["floating_nodes"].as_array())
+        .flatten()
+        .find_map(|child| find_focused(child, output))
+}
+
+fn json(text: &str) -> Option<Value> {
+    serde_json::from_str(text).ok()
+}
+
+fn string(value: &Value) -> Option<RString> {
+    value
+        .as_str()
+        .filter(|text| !text.is_empty())
+        .map(Into::into)
+}
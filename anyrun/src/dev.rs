@@ -0,0 +1,309 @@
+//! `anyrun dev`, a REPL for trying out a plugin while developing it. The plugin is loaded again
+//! whenever the library changes, so a `cargo build` in another terminal is all that's needed.
+
+use std::{
+    env, fs,
+    io::{self, BufRead, Write},
+    path::Path,
+    thread,
+    time::{Duration, Instant, SystemTime},
+};
+
+use abi_stable::std_types::{ROption, Tuple2};
+use anyrun_interface::{
+    HandleResult, Match, PluginRef, PollResult, Preview, ValidateResult, Widget,
+};
+use tracing::error;
+
+/// How long to wait for the matches of a query
+const QUERY_TIMEOUT: Duration = Duration::from_secs(10);
+
+const HELP: &str = "\
+Type a query to see the matches of the plugin for it.
+  :s <n> [a] Select the match with the index n of the latest query, with the action with the id a
+  :p <n>     Show the preview of the match with the index n
+  :r         Load the plugin again
+  :h         Show this help
+  :q         Quit";
+
+struct Loaded {
+    plugin: PluginRef,
+    modified: Option<SystemTime>,
+}
+
+pub fn run(path: &Path, config_dir: &str) -> bool {
+    let mut loaded = match load(path, config_dir, 0) {
+        Some(loaded) => loaded,
+        None => return false,
+    };
+    let mut loads = 1;
+    let mut matches: Vec<Match> = Vec::new();
+    // The input the matches were found for, passed on to the handler
+    let mut input = String::new();
+
+    println!("{}", HELP);
+
+    let stdin = io::stdin();
+    loop {
+        print!("> ");
+        let _ = io::stdout().flush();
+
+        let mut line = String::new();
+        match stdin.lock().read_line(&mut line) {
+            Ok(0) => break,
+            Ok(_) => (),
+            Err(why) => {
+                error!("Failed to read stdin: {}", why);
+                return false;
+            }
+        }
+        let line = line.trim_end_matches('\n');
+
+        // Pick up a rebuilt plugin before running anything
+        let reload = line == ":r" || modified(path) != loaded.modified;
+        if reload {
+            if let Some(new) = load(path, config_dir, loads) {
+                loaded.plugin.deinit()();
+                loaded = new;
+                loads += 1;
+                matches.clear();
+                println!("Loaded {}", path.display());
+            }
+        }
+
+        match line.split_once(' ').unwrap_or((line, "")) {
+            (":q", _) => break,
+            (":h", _) => println!("{}", HELP),
+            (":r", _) => (),
+            (":s", args) => {
+                let (index, action) = args.trim().split_once(' ').unwrap_or((args.trim(), ""));
+                match (index.parse::<usize>(), action.trim()) {
+                    (Ok(index), "") if index < matches.len() => {
+                        let result = loaded.plugin.handle_selection()(
+                            matches[index].clone(),
+                            input.as_str().into(),
+                        );
+                        println!("{}", describe_result(&result));
+                    }
+                    (Ok(index), action) if index < matches.len() => match action.parse::<u64>() {
+                        Ok(action) => {
+                            let mut selection = matches[index].clone();
+                            selection.action = ROption::RSome(action);
+                            let result =
+                                loaded.plugin.handle_selection()(selection, input.as_str().into());
+                            println!("{}", describe_result(&result));
+                        }
+                        Err(_) => println!("No action with the id {:?}", action),
+                    },
+                    _ => println!("No match with the index {:?}", index),
+                }
+            }
+            (":p", index) => match index.trim().parse::<usize>() {
+                Ok(index) if index < matches.len() => {
+                    match loaded.plugin.get_preview()(matches[index].clone()).into_option() {
+                        Some(preview) => println!("{}", describe_preview(&preview)),
+                        None => println!("No preview"),
+                    }
+                }
+                _ => println!("No match with the index {:?}", index.trim()),
+            },
+            _ => {
+                matches = query(loaded.plugin, line);
+                input = line.to_string();
+                for (index, _match) in matches.iter().enumerate() {
+                    println!("{}", describe_match(index, _match));
+                }
+                if matches.is_empty() {
+                    println!("No matches");
+                }
+            }
+        }
+    }
+
+    loaded.plugin.deinit()();
+    true
+}
+
+/// Load the plugin from a copy of the library. The same path is never opened again, as the
+/// already loaded library would be returned instead of the rebuilt one.
+fn load(path: &Path, config_dir: &str, loads: u32) -> Option<Loaded> {
+    let modified = modified(path);
+    let copy = env::temp_dir().join(format!("anyrun-dev-{}-{}.so", std::process::id(), loads));
+
+    if let Err(why) = fs::copy(path, &copy) {
+        error!("Failed to copy {}: {}", path.display(), why);
+        return None;
+    }
+
+    let plugin = anyrun_interface::load(&copy);
+    let _ = fs::remove_file(&copy);
+
+    match plugin {
+        Ok(plugin) => {
+            plugin.set_log_sink()(crate::LOG_SINK);
+            plugin.set_recency_store()(crate::recent::STORE);
+            plugin.init()(config_dir.into());
+
+            let info = plugin.info()();
+            println!("{} ({})", info.name, info.icon);
+
+            Some(Loaded { plugin, modified })
+        }
+        Err(why) => {
+            error!("Failed to load plugin {}: {}", path.display(), why);
+            None
+        }
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+fn query(plugin: PluginRef, input: &str) -> Vec<Match> {
+    let start = Instant::now();
+
+    let input = match plugin.validate_input()(input.into()) {
+        ValidateResult::Accept => input.into(),
+        ValidateResult::Transform(input) => {
+            println!("Input transformed to {:?}", input.as_str());
+            input
+        }
+        ValidateResult::Reject => {
+            println!("Input rejected");
+            return Vec::new();
+        }
+    };
+
+    let id = plugin.get_matches()(input);
+    let mut matches = Vec::new();
+    loop {
+        match plugin.poll_matches()(id) {
+            PollResult::Ready(rest) => break matches.extend(rest),
+            PollResult::Partial(streamed) => {
+                println!(
+                    "{} matches streamed after {:.1}ms",
+                    streamed.len(),
+                    start.elapsed().as_secs_f64() * 1000.0
+                );
+                matches.extend(streamed);
+            }
+            PollResult::Pending if start.elapsed() < QUERY_TIMEOUT => {
+                thread::sleep(Duration::from_millis(1))
+            }
+            PollResult::Pending => {
+                println!("Timed out waiting for the matches");
+                break;
+            }
+            PollResult::Cancelled => {
+                println!("Query cancelled");
+                break;
+            }
+        }
+    }
+
+    println!(
+        "{} matches in {:.1}ms",
+        matches.len(),
+        start.elapsed().as_secs_f64() * 1000.0
+    );
+    if let ROption::RSome(interval) = plugin.refresh_interval()(id) {
+        println!("Refreshed every {}ms", interval);
+    }
+
+    matches
+}
+
+fn describe_preview(preview: &Preview) -> String {
+    match preview {
+        Preview::Text(text, true) => format!("Markup:\n{}", text),
+        Preview::Text(text, false) => text.to_string(),
+        Preview::Monospace(text) => text.to_string(),
+        Preview::Image(image) => format!("Image of {}x{}", image.width, image.height),
+        Preview::File(path) => format!("Picture from {}", path),
+    }
+}
+
+fn describe_match(index: usize, _match: &Match) -> String {
+    let mut line = format!("[{}] {}", index, _match.title);
+
+    if let Some(desc) = _match.description.as_ref().into_option() {
+        line.push_str(&format!("\n    {}", desc));
+    }
+
+    let mut details = Vec::new();
+    if let Some(icon) = _match.icon.as_ref().into_option() {
+        details.push(format!("icon: {}", icon));
+    }
+    if let Some(id) = _match.id.as_ref().into_option() {
+        details.push(format!("id: {}", id));
+    }
+    if let Some(image) = _match.image.as_ref().into_option() {
+        details.push(format!("image: {}x{}", image.width, image.height));
+    }
+    if _match.title_pango {
+        details.push("title pango".to_string());
+    }
+    if _match.description_pango {
+        details.push("description pango".to_string());
+    }
+    if _match.priority != 0 {
+        details.push(format!("priority: {}", _match.priority));
+    }
+    if _match.pinned {
+        details.push("pinned".to_string());
+    }
+    if !details.is_empty() {
+        line.push_str(&format!("\n    ({})", details.join(", ")));
+    }
+
+    for widget in _match.widgets.iter() {
+        match widget {
+            Widget::Progress(fraction, text) => line.push_str(&format!(
+                "\n    progress {:.0}%{}",
+                fraction * 100.0,
+                text.as_ref()
+                    .map_or(String::new(), |text| format!(": {}", text))
+            )),
+            Widget::Table(rows) => {
+                for Tuple2(label, value) in rows.iter() {
+                    line.push_str(&format!("\n    {}: {}", label, value));
+                }
+            }
+            Widget::Monospace(text) => {
+                for text_line in text.lines() {
+                    line.push_str(&format!("\n    | {}", text_line));
+                }
+            }
+        }
+    }
+
+    for action in _match.actions.iter() {
+        line.push_str(&format!("\n    [{}] {}", action.id, action.title));
+    }
+
+    line
+}
+
+fn describe_result(result: &HandleResult) -> String {
+    match result {
+        HandleResult::Close => "Close".to_string(),
+        HandleResult::Refresh(exclusive) => format!("Refresh (exclusive: {})", exclusive),
+        HandleResult::Copy(bytes, mime_type) => format!(
+            "Copy {} bytes as {}: {}",
+            bytes.len(),
+            mime_type
+                .as_ref()
+                .map(|mime_type| mime_type.as_str())
+                .unwrap_or("detected type"),
+            String::from_utf8_lossy(bytes)
+        ),
+        HandleResult::Stdout(bytes) => format!("Stdout: {}", String::from_utf8_lossy(bytes)),
+        HandleResult::Masked(masked) => format!("Masked input (masked: {})", masked),
+        HandleResult::RefreshPlugin => "Refresh the plugin".to_string(),
+        HandleResult::SetInput(input) => format!("Set the input to {:?}", input.as_str()),
+        HandleResult::Type(text) => format!("Type {:?}", text.as_str()),
+    }
+}
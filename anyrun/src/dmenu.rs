@@ -0,0 +1,66 @@
+//! Compatibility with the flags of dmenu, so that anyrun can be dropped into the scripts written
+//! for it. The flags are translated to the options of anyrun before the arguments are parsed.
+
+use std::{env, ffi::OsString, path::Path};
+
+use tracing::debug;
+
+use crate::Config;
+
+/// The flags of dmenu for the looks, which don't map to anything and are ignored with their values
+const IGNORED_WITH_VALUE: &[&str] = &["-fn", "-nb", "-nf", "-sb", "-sf", "-m", "-w"];
+/// The same without values
+const IGNORED: &[&str] = &["-b", "-f"];
+
+/// Translate the dmenu flags, when running as `dmenu` or with `--dmenu`
+pub fn translate_args(args: impl Iterator<Item = OsString>) -> Vec<OsString> {
+    let mut args = args.collect::<Vec<_>>();
+
+    let invoked_as_dmenu = args
+        .first()
+        .and_then(|arg0| Path::new(arg0).file_name())
+        .is_some_and(|name| name == "dmenu");
+    if invoked_as_dmenu {
+        args.insert(1, "--dmenu".into());
+    } else if !args.iter().any(|arg| arg == "--dmenu") {
+        return args;
+    }
+
+    let mut translated = Vec::with_capacity(args.len());
+    let mut args = args.into_iter();
+
+    while let Some(arg) = args.next() {
+        match arg.to_str() {
+            Some("-p") => translated.push("--prompt".into()),
+            Some("-l") => translated.push("--lines".into()),
+            Some("-i") => translated.push("--ignore-case".into()),
+            Some("-password") => translated.extend(["--masked-input".into(), "true".into()]),
+            Some(flag) if IGNORED_WITH_VALUE.contains(&flag) => {
+                debug!("Ignoring the dmenu flag {}", flag);
+                args.next();
+            }
+            Some(flag) if IGNORED.contains(&flag) => debug!("Ignoring the dmenu flag {}", flag),
+            _ => translated.push(arg),
+        }
+    }
+
+    translated
+}
+
+/// Set up the config for picking one of the lines of stdin. The options of the stdin plugin are
+/// passed on to it in the environment.
+pub fn configure(config: &mut Config, plugins_set: bool, lines: Option<usize>, ignore_case: bool) {
+    if !plugins_set {
+        config.plugins = vec!["libstdin.so".into()];
+    }
+    config.hide_plugin_info = true;
+    config.show_results_immediately = true;
+
+    if let Some(lines) = lines {
+        config.max_entries = Some(lines);
+        env::set_var("ANYRUN_STDIN_MAX_ENTRIES", lines.to_string());
+    }
+    if ignore_case {
+        env::set_var("ANYRUN_STDIN_IGNORE_CASE", "1");
+    }
+}
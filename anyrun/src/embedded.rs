@@ -0,0 +1,96 @@
+//! The plugins built into anyrun with the `embed-*` features, for static builds that can't load
+//! libraries. They are referred to by the same names as their libraries, and only used when there
+//! is no library for them. The plugins share the statics of `anyrun-plugin`, like the prefix and
+//! the recent matches, so each of them always runs in a helper process of its own.
+
+use std::path::{Path, PathBuf};
+
+use anyrun_interface::PluginRef;
+
+type RootModule = fn() -> PluginRef;
+
+/// The names of the embedded plugins, like the crates and the `lib<name>.so` of their libraries
+const PLUGINS: &[(&str, RootModule)] = &[
+    #[cfg(feature = "embed-applications")]
+    (
+        "applications",
+        applications::anyrun_internal_init_root_module,
+    ),
+    #[cfg(feature = "embed-dictionary")]
+    ("dictionary", dictionary::anyrun_internal_init_root_module),
+    #[cfg(feature = "embed-external")]
+    ("external", external::anyrun_internal_init_root_module),
+    #[cfg(feature = "embed-jobs")]
+    ("jobs", jobs::anyrun_internal_init_root_module),
+    #[cfg(feature = "embed-kidex")]
+    ("kidex", kidex::anyrun_internal_init_root_module),
+    #[cfg(feature = "embed-lua_runner")]
+    ("lua_runner", lua_runner::anyrun_internal_init_root_module),
+    #[cfg(feature = "embed-mounts")]
+    ("mounts", mounts::anyrun_internal_init_root_module),
+    #[cfg(feature = "embed-power")]
+    ("power", power::anyrun_internal_init_root_module),
+    #[cfg(feature = "embed-printers")]
+    ("printers", printers::anyrun_internal_init_root_module),
+    #[cfg(feature = "embed-randr")]
+    ("randr", randr::anyrun_internal_init_root_module),
+    #[cfg(feature = "embed-removable")]
+    ("removable", removable::anyrun_internal_init_root_module),
+    #[cfg(feature = "embed-resume")]
+    ("resume", resume::anyrun_internal_init_root_module),
+    #[cfg(feature = "embed-rink")]
+    ("rink", rink::anyrun_internal_init_root_module),
+    #[cfg(feature = "embed-rofi")]
+    ("rofi", rofi::anyrun_internal_init_root_module),
+    #[cfg(feature = "embed-screencast")]
+    ("screencast", screencast::anyrun_internal_init_root_module),
+    #[cfg(feature = "embed-shell")]
+    ("shell", shell::anyrun_internal_init_root_module),
+    #[cfg(feature = "embed-symbols")]
+    ("symbols", symbols::anyrun_internal_init_root_module),
+    #[cfg(feature = "embed-translate")]
+    ("translate", translate::anyrun_internal_init_root_module),
+    #[cfg(feature = "embed-websearch")]
+    ("websearch", websearch::anyrun_internal_init_root_module),
+    #[cfg(feature = "embed-windows")]
+    ("windows", windows::anyrun_internal_init_root_module),
+];
+
+/// The name of the embedded plugin `path` refers to, if there is no library at it. Only the file
+/// name counts, as the path is joined to the plugin directories or the working directory on the
+/// way.
+pub fn name(path: &Path) -> Option<&'static str> {
+    if path.is_file() {
+        return None;
+    }
+
+    let file_name = path.file_name()?.to_str()?;
+    let name = file_name
+        .strip_prefix("lib")
+        .and_then(|name| name.strip_suffix(".so"))
+        .unwrap_or(file_name);
+
+    PLUGINS
+        .iter()
+        .map(|(plugin, _)| *plugin)
+        .find(|plugin| *plugin == name)
+}
+
+/// The root module of the embedded plugin `path` refers to
+pub fn find(path: &Path) -> Option<RootModule> {
+    let name = name(path)?;
+    PLUGINS
+        .iter()
+        .find(|(plugin, _)| *plugin == name)
+        .map(|(_, root_module)| *root_module)
+}
+
+/// The path standing in for the library of an embedded plugin, like the one of a real library
+pub fn path(name: &str) -> PathBuf {
+    PathBuf::from(format!("lib{}.so", name))
+}
+
+/// The names and root modules of all the embedded plugins
+pub fn all() -> impl Iterator<Item = (&'static str, RootModule)> {
+    PLUGINS.iter().copied()
+}
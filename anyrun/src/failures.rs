@@ -0,0 +1,63 @@
+//! Counting the failures of the plugins in a row, which are the helper of an isolated plugin
+//! stopping and queries taking longer than `plugin_timeout`. A plugin failing `failure_budget`
+//! times is disabled until `anyrun plugin reset` is run for it. anyrun is started again for every
+//! use, so the counts are kept in the state directory, by the file names of the plugins.
+
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use tracing::warn;
+
+pub struct Failures {
+    counts: HashMap<String, u32>,
+    path: PathBuf,
+}
+
+impl Failures {
+    pub fn load(state_dir: &Path) -> Self {
+        let path = state_dir.join("failures.json");
+        Self {
+            counts: fs::read_to_string(&path)
+                .ok()
+                .and_then(|content| serde_json::from_str(&content).ok())
+                .unwrap_or_default(),
+            path,
+        }
+    }
+
+    /// The failures of the plugin in a row
+    pub fn count(&self, plugin: &str) -> u32 {
+        self.counts.get(plugin).copied().unwrap_or(0)
+    }
+
+    /// Count a failure of the plugin, returning the failures in a row so far
+    pub fn record(&mut self, plugin: &str) -> u32 {
+        let count = self.counts.entry(plugin.to_string()).or_insert(0);
+        *count += 1;
+        let count = *count;
+        self.save();
+        count
+    }
+
+    /// Start counting over, after the plugin worked or when it is reset
+    pub fn clear(&mut self, plugin: &str) {
+        if self.counts.remove(plugin).is_some() {
+            self.save();
+        }
+    }
+
+    fn save(&self) {
+        let content = serde_json::to_string(&self.counts).unwrap_or_default();
+        if let Err(why) = self
+            .path
+            .parent()
+            .map_or(Ok(()), fs::create_dir_all)
+            .and_then(|_| fs::write(&self.path, content))
+        {
+            warn!("Failed to save the failures of the plugins: {}", why);
+        }
+    }
+}
@@ -0,0 +1,180 @@
+//! The log of selected matches, searched with Ctrl+R. The matches are saved with the name of the
+//! plugin they came from and the input they were found for, so that plugin can be asked for them
+//! again and handle them.
+
+use std::{
+    cmp::Reverse,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use abi_stable::std_types::{ROption, RVec};
+use anyrun_interface::Match;
+use fuzzy_matcher::FuzzyMatcher;
+use serde::{Deserialize, Serialize};
+
+/// The amount of selections that are remembered
+const MAX_ENTRIES: usize = 200;
+
+/// A match saved with the plugin it came from, also used for the pinned matches
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Entry {
+    pub plugin: String,
+    title: String,
+    description: Option<String>,
+    #[serde(default)]
+    title_pango: bool,
+    #[serde(default)]
+    description_pango: bool,
+    icon: Option<String>,
+    id: Option<u64>,
+    /// None for the entries saved before the input was kept, which can't be asked for again
+    #[serde(default)]
+    pub input: Option<String>,
+    /// Whether the plugin didn't have the match anymore when it was selected, until anyrun closes
+    #[serde(skip)]
+    pub stale: bool,
+}
+
+impl Entry {
+    pub fn new(plugin: &str, selection: &Match, input: Option<&str>) -> Self {
+        Self {
+            plugin: plugin.to_string(),
+            title: selection.title.to_string(),
+            description: selection
+                .description
+                .as_ref()
+                .map(|desc| desc.to_string())
+                .into(),
+            title_pango: selection.title_pango,
+            description_pango: selection.description_pango,
+            icon: selection.icon.as_ref().map(|icon| icon.to_string()).into(),
+            id: selection.id.into(),
+            input: input.map(str::to_string),
+            stale: false,
+        }
+    }
+
+    /// Whether the entries are for the same match. The IDs aren't compared, as plugins may number
+    /// their matches differently between runs.
+    pub fn same(&self, other: &Entry) -> bool {
+        self.plugin == other.plugin
+            && self.title == other.title
+            && self.description == other.description
+    }
+
+    /// Whether the match the plugin has now is the one the entry is a copy of. The plugin may
+    /// give the ID to another match once the input has changed, so it has to agree with the title.
+    pub fn is(&self, _match: &Match) -> bool {
+        self.title == _match.title.as_str() && self.id == _match.id.into_option()
+    }
+
+    /// A copy of the match for showing it, only the match the plugin has now is handled
+    pub fn to_match(&self) -> Match {
+        Match {
+            title: self.title.clone().into(),
+            description: self.description.clone().map(Into::into).into(),
+            title_pango: self.title_pango,
+            description_pango: self.description_pango,
+            icon: self.icon.clone().map(Into::into).into(),
+            id: self.id.into(),
+            actions: RVec::new(),
+            action: ROption::RNone,
+            image: ROption::RNone,
+            widgets: RVec::new(),
+            priority: 0,
+            pinned: false,
+        }
+    }
+}
+
+pub struct History {
+    /// Most recent first
+    entries: Vec<Entry>,
+    path: PathBuf,
+}
+
+impl History {
+    pub fn load(state_dir: &Path) -> Self {
+        let path = state_dir.join("history");
+        let entries = fs::read_to_string(&path)
+            .map(|content| {
+                content
+                    .lines()
+                    .filter_map(|line| serde_json::from_str(line).ok())
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        Self { entries, path }
+    }
+
+    /// Remember a selected match and the input it was found for, moving it to the top if it was
+    /// already selected before
+    pub fn record(&mut self, plugin: &str, selection: &Match, input: &str) {
+        let entry = Entry::new(plugin, selection, Some(input));
+
+        self.entries.retain(|old| !old.same(&entry));
+        self.entries.insert(0, entry);
+        self.entries.truncate(MAX_ENTRIES);
+
+        let content = self
+            .entries
+            .iter()
+            .filter_map(|entry| serde_json::to_string(entry).ok())
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        if let Err(why) = self
+            .path
+            .parent()
+            .map_or(Ok(()), fs::create_dir_all)
+            .and_then(|_| fs::write(&self.path, content))
+        {
+            tracing::warn!("Failed to save the selection history: {}", why);
+        }
+    }
+
+    /// The past selections from the plugin matching the input, the best matches first
+    pub fn search(&self, plugin: &str, input: &str) -> RVec<Match> {
+        let matcher = fuzzy_matcher::skim::SkimMatcherV2::default().ignore_case();
+
+        let mut entries = self
+            .entries
+            .iter()
+            .filter(|entry| entry.plugin == plugin)
+            .filter_map(|entry| {
+                let text = match &entry.description {
+                    Some(desc) => format!("{} {}", entry.title, desc),
+                    None => entry.title.clone(),
+                };
+                matcher
+                    .fuzzy_match(&text, input)
+                    .map(|score| (entry, score))
+            })
+            .collect::<Vec<_>>();
+
+        // The sort is stable, so equally good matches stay in the order they were selected in
+        if !input.is_empty() {
+            entries.sort_by_key(|(_, score)| Reverse(*score));
+        }
+
+        entries
+            .into_iter()
+            .map(|(entry, _)| entry.to_match())
+            .collect()
+    }
+
+    /// The entry a match found by `search` is a copy of
+    pub fn find(&self, plugin: &str, _match: &Match) -> Option<&Entry> {
+        let entry = Entry::new(plugin, _match, None);
+        self.entries.iter().find(|old| old.same(&entry))
+    }
+
+    /// Mark the entry as no longer had by its plugin
+    pub fn mark_stale(&mut self, entry: &Entry) {
+        for old in self.entries.iter_mut().filter(|old| old.same(entry)) {
+            old.stale = true;
+        }
+    }
+}
@@ -0,0 +1,85 @@
+//! Opening the window in a special workspace of Hyprland, the scratchpad of Hyprland. The window
+//! that was focused before is focused again on close, so the focus doesn't end up on whatever
+//! Hyprland picks once the special workspace goes away.
+
+use std::{
+    env,
+    io::{self, Read, Write},
+    os::unix::net::UnixStream,
+    path::PathBuf,
+    process,
+};
+
+use tracing::warn;
+
+pub struct Scratchpad {
+    workspace: String,
+    /// The address of the window focused before anyrun was opened
+    previous: Option<String>,
+}
+
+impl Scratchpad {
+    /// Remember the focused window, if running on Hyprland
+    pub fn new(workspace: &str) -> Option<Self> {
+        if env::var_os("HYPRLAND_INSTANCE_SIGNATURE").is_none() {
+            warn!("The special workspace is only supported on Hyprland");
+            return None;
+        }
+
+        let previous = request("j/activewindow")
+            .map_err(|why| warn!("Failed to get the focused window from Hyprland: {}", why))
+            .ok()
+            .and_then(|response| serde_json::from_str::<serde_json::Value>(&response).ok())
+            .and_then(|window| window.get("address")?.as_str().map(String::from));
+
+        Some(Self {
+            workspace: workspace.to_string(),
+            previous,
+        })
+    }
+
+    /// Move the window of anyrun to the special workspace and show it
+    pub fn show(&self) {
+        dispatch(&format!(
+            "movetoworkspace special:{},pid:{}",
+            self.workspace,
+            process::id()
+        ));
+    }
+
+    /// Focus the window that was focused before anyrun again
+    pub fn restore(&self) {
+        if let Some(previous) = &self.previous {
+            dispatch(&format!("focuswindow address:{}", previous));
+        }
+    }
+}
+
+fn dispatch(command: &str) {
+    match request(&format!("dispatch {}", command)) {
+        Ok(response) if response.trim() == "ok" => (),
+        Ok(response) => warn!("Hyprland refused `{}`: {}", command, response.trim()),
+        Err(why) => warn!("Failed to send `{}` to Hyprland: {}", command, why),
+    }
+}
+
+/// Send a command to the socket of Hyprland, the same way `hyprctl` does
+pub fn request(command: &str) -> io::Result<String> {
+    let signature = env::var("HYPRLAND_INSTANCE_SIGNATURE")
+        .map_err(|_| io::Error::new(io::ErrorKind::NotFound, "Hyprland is not running"))?;
+
+    // Newer versions of Hyprland keep the socket in the runtime directory instead of /tmp
+    let path = env::var_os("XDG_RUNTIME_DIR")
+        .map(|runtime_dir| PathBuf::from(runtime_dir).join("hypr"))
+        .filter(|dir| dir.join(&signature).exists())
+        .unwrap_or_else(|| PathBuf::from("/tmp/hypr"))
+        .join(&signature)
+        .join(".socket.sock");
+
+    let mut stream = UnixStream::connect(path)?;
+    stream.write_all(command.as_bytes())?;
+
+    let mut response = String::new();
+    stream.read_to_string(&mut response)?;
+    Ok(response)
+}
@@ -0,0 +1,140 @@
+//! The log of submitted queries, recalled with Up and Down like the history of a shell. Every
+//! config directory has its own log, so the profiles don't mix their queries.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize)]
+struct Entry {
+    /// The config directory the query was submitted with
+    profile: String,
+    input: String,
+}
+
+pub struct InputHistory {
+    /// The queries of this profile, most recent first
+    queries: Vec<String>,
+    /// The queries of the other profiles, saved back as they were
+    others: Vec<Entry>,
+    profile: String,
+    size: usize,
+    path: PathBuf,
+    /// The index of the query in the entry and the text that was typed before recalling it
+    recall: Option<(usize, String)>,
+}
+
+impl InputHistory {
+    pub fn load(state_dir: &Path, profile: &str, size: usize) -> Self {
+        let path = state_dir.join("queries");
+        let (queries, others): (Vec<Entry>, Vec<Entry>) = fs::read_to_string(&path)
+            .map(|content| {
+                content
+                    .lines()
+                    .filter_map(|line| serde_json::from_str::<Entry>(line).ok())
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default()
+            .into_iter()
+            .partition(|entry| entry.profile == profile);
+
+        Self {
+            queries: queries.into_iter().map(|entry| entry.input).collect(),
+            others,
+            profile: profile.to_string(),
+            size,
+            path,
+            recall: None,
+        }
+    }
+
+    /// Remember a submitted query, moving it to the top if it was submitted before
+    pub fn record(&mut self, input: &str) {
+        self.recall = None;
+
+        if input.trim().is_empty() {
+            return;
+        }
+
+        self.queries.retain(|query| query != input);
+        self.queries.insert(0, input.to_string());
+        self.queries.truncate(self.size);
+
+        let content = self
+            .others
+            .iter()
+            .filter_map(|entry| serde_json::to_string(entry).ok())
+            .chain(self.queries.iter().filter_map(|query| {
+                serde_json::to_string(&Entry {
+                    profile: self.profile.clone(),
+                    input: query.clone(),
+                })
+                .ok()
+            }))
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        if let Err(why) = self
+            .path
+            .parent()
+            .map_or(Ok(()), fs::create_dir_all)
+            .and_then(|_| fs::write(&self.path, content))
+        {
+            tracing::warn!("Failed to save the input history: {}", why);
+        }
+    }
+
+    /// Whether the input is the query that was recalled last, and not edited since
+    pub fn is_recalled(&self, input: &str) -> bool {
+        self.recall
+            .as_ref()
+            .is_some_and(|(index, _)| self.queries[*index] == input)
+    }
+
+    /// The next older query containing the text typed before recalling, or the input if nothing
+    /// is being recalled
+    pub fn older(&mut self, input: &str) -> Option<&str> {
+        let (start, term) = match self.recall.take() {
+            Some((index, term)) if self.queries[index] == input => (index + 1, term),
+            _ => (0, input.to_string()),
+        };
+
+        let found = (start..self.queries.len())
+            .find(|index| self.queries[*index] != input && self.queries[*index].contains(&term));
+
+        // Stay at the oldest query when there is nothing older
+        match found {
+            Some(index) => {
+                self.recall = Some((index, term));
+                Some(&self.queries[index])
+            }
+            None => {
+                self.recall = start.checked_sub(1).map(|index| (index, term));
+                None
+            }
+        }
+    }
+
+    /// The next newer query containing the text typed before recalling, or that text itself when
+    /// there are no newer ones
+    pub fn newer(&mut self, input: &str) -> Option<String> {
+        let (end, term) = match self.recall.take() {
+            Some((index, term)) if self.queries[index] == input => (index, term),
+            _ => return None,
+        };
+
+        match (0..end)
+            .rev()
+            .find(|index| self.queries[*index].contains(&term))
+        {
+            Some(index) => {
+                self.recall = Some((index, term));
+                Some(self.queries[index].clone())
+            }
+            None => Some(term),
+        }
+    }
+}
@@ -0,0 +1,839 @@
+//! Running plugins in helper processes, enabled with `isolate_plugins`. The helper is anyrun
+//! itself started with `--plugin-host`, and it talks to the launcher with JSON lines over its
+//! stdin and stdout. A plugin that panics only takes its helper down, and a hanging one can be
+//! killed without freezing the launcher.
+
+use std::{
+    cell::{Cell, RefCell},
+    collections::{HashMap, HashSet},
+    env,
+    fs::File,
+    io::{self, BufRead, BufReader, Write},
+    mem,
+    os::fd::FromRawFd,
+    path::Path,
+    process::{Child, ChildStdin, Command, Stdio},
+    sync::{
+        mpsc::{self, Receiver, RecvTimeoutError, TryRecvError},
+        Arc, Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use abi_stable::std_types::{ROption, RVec, Tuple2};
+use anyrun_interface::{
+    Action, HandleResult, Image, Match, PluginInfo, PluginRef, PollResult, Preview, QueryContext,
+    ValidateResult, Widget,
+};
+use nix::unistd;
+use serde::{Deserialize, Serialize};
+use tracing::error;
+
+use crate::{plugin::LoadOptions, PluginEnvironment};
+
+/// How long the helper has to load and initialize the plugin
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(5);
+/// Input is validated on every keystroke, so a helper this slow to respond is considered hung
+const VALIDATE_TIMEOUT: Duration = Duration::from_millis(500);
+/// The preview is gotten whenever the selection moves, so it can't hold up moving it for long
+const PREVIEW_TIMEOUT: Duration = Duration::from_millis(500);
+/// How long the plugin has to handle a selection
+const HANDLE_TIMEOUT: Duration = Duration::from_secs(5);
+/// How long the plugin has to save its state before the helper is stopped
+const DEINIT_TIMEOUT: Duration = Duration::from_secs(2);
+
+#[derive(Serialize, Deserialize)]
+enum Request {
+    Query { id: u64, input: String },
+    Cancel(u64),
+    Validate(String),
+    Handle(RemoteMatch, String),
+    Preview(RemoteMatch),
+    Context(RemoteContext),
+    Deinit,
+}
+
+#[derive(Serialize, Deserialize)]
+enum Response {
+    Info { name: String, icon: String },
+    Failed(String),
+    Matches { id: u64, matches: Vec<RemoteMatch> },
+    Partial { id: u64, matches: Vec<RemoteMatch> },
+    Validated(RemoteValidateResult),
+    Handled(RemoteHandleResult),
+    Preview(Option<RemotePreview>),
+    Prefix(Option<String>),
+    Refresh(u64, u64),
+    Errors(Vec<String>),
+    Deinitialized,
+}
+
+#[derive(Serialize, Deserialize)]
+struct RemoteMatch {
+    title: String,
+    description: Option<String>,
+    title_pango: bool,
+    description_pango: bool,
+    icon: Option<String>,
+    id: Option<u64>,
+    actions: Vec<RemoteAction>,
+    action: Option<u64>,
+    image: Option<RemoteImage>,
+    widgets: Vec<RemoteWidget>,
+    priority: i32,
+    pinned: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+struct RemoteAction {
+    title: String,
+    icon: Option<String>,
+    id: u64,
+}
+
+#[derive(Serialize, Deserialize)]
+struct RemoteImage {
+    width: u32,
+    height: u32,
+    pixels: Vec<u8>,
+}
+
+#[derive(Serialize, Deserialize)]
+enum RemoteWidget {
+    Progress(f64, Option<String>),
+    Table(Vec<(String, String)>),
+    Monospace(String),
+}
+
+#[derive(Serialize, Deserialize)]
+enum RemotePreview {
+    Text(String, bool),
+    Monospace(String),
+    Image(RemoteImage),
+    File(String),
+}
+
+#[derive(Serialize, Deserialize)]
+struct RemoteContext {
+    output: Option<String>,
+    clipboard: Option<String>,
+    app_id: Option<String>,
+    window_title: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+enum RemoteValidateResult {
+    Accept,
+    Reject,
+    Transform(String),
+}
+
+#[derive(Serialize, Deserialize)]
+enum RemoteHandleResult {
+    Close,
+    Refresh(bool),
+    Copy(Vec<u8>, Option<String>),
+    Stdout(Vec<u8>),
+    Masked(bool),
+    RefreshPlugin,
+    SetInput(String),
+    Type(String),
+}
+
+impl From<Match> for RemoteMatch {
+    fn from(_match: Match) -> Self {
+        Self {
+            title: _match.title.into(),
+            description: _match.description.map(String::from).into(),
+            title_pango: _match.title_pango,
+            description_pango: _match.description_pango,
+            icon: _match.icon.map(String::from).into(),
+            id: _match.id.into(),
+            actions: _match
+                .actions
+                .into_iter()
+                .map(|action| RemoteAction {
+                    title: action.title.into(),
+                    icon: action.icon.map(String::from).into(),
+                    id: action.id,
+                })
+                .collect(),
+            action: _match.action.into(),
+            image: _match
+                .image
+                .map(|image| RemoteImage {
+                    width: image.width,
+                    height: image.height,
+                    pixels: image.pixels.into(),
+                })
+                .into(),
+            widgets: _match.widgets.into_iter().map(RemoteWidget::from).collect(),
+            priority: _match.priority,
+            pinned: _match.pinned,
+        }
+    }
+}
+
+impl From<RemoteMatch> for Match {
+    fn from(_match: RemoteMatch) -> Self {
+        Self {
+            title: _match.title.into(),
+            description: _match.description.map(Into::into).into(),
+            title_pango: _match.title_pango,
+            description_pango: _match.description_pango,
+            icon: _match.icon.map(Into::into).into(),
+            id: _match.id.into(),
+            actions: _match
+                .actions
+                .into_iter()
+                .map(|action| Action {
+                    title: action.title.into(),
+                    icon: action.icon.map(Into::into).into(),
+                    id: action.id,
+                })
+                .collect(),
+            action: _match.action.into(),
+            image: _match
+                .image
+                .map(|image| Image {
+                    width: image.width,
+                    height: image.height,
+                    pixels: image.pixels.into(),
+                })
+                .into(),
+            widgets: _match.widgets.into_iter().map(Widget::from).collect(),
+            priority: _match.priority,
+            pinned: _match.pinned,
+        }
+    }
+}
+
+impl From<Widget> for RemoteWidget {
+    fn from(widget: Widget) -> Self {
+        match widget {
+            Widget::Progress(fraction, text) => {
+                Self::Progress(fraction, text.map(String::from).into())
+            }
+            Widget::Table(rows) => Self::Table(
+                rows.into_iter()
+                    .map(|Tuple2(label, value)| (label.into(), value.into()))
+                    .collect(),
+            ),
+            Widget::Monospace(text) => Self::Monospace(text.into()),
+        }
+    }
+}
+
+impl From<RemoteWidget> for Widget {
+    fn from(widget: RemoteWidget) -> Self {
+        match widget {
+            RemoteWidget::Progress(fraction, text) => {
+                Self::Progress(fraction, text.map(Into::into).into())
+            }
+            RemoteWidget::Table(rows) => Self::Table(
+                rows.into_iter()
+                    .map(|(label, value)| Tuple2(label.into(), value.into()))
+                    .collect(),
+            ),
+            RemoteWidget::Monospace(text) => Self::Monospace(text.into()),
+        }
+    }
+}
+
+impl From<Preview> for RemotePreview {
+    fn from(preview: Preview) -> Self {
+        match preview {
+            Preview::Text(text, pango) => Self::Text(text.into(), pango),
+            Preview::Monospace(text) => Self::Monospace(text.into()),
+            Preview::Image(image) => Self::Image(RemoteImage {
+                width: image.width,
+                height: image.height,
+                pixels: image.pixels.into(),
+            }),
+            Preview::File(path) => Self::File(path.into()),
+        }
+    }
+}
+
+impl From<RemotePreview> for Preview {
+    fn from(preview: RemotePreview) -> Self {
+        match preview {
+            RemotePreview::Text(text, pango) => Self::Text(text.into(), pango),
+            RemotePreview::Monospace(text) => Self::Monospace(text.into()),
+            RemotePreview::Image(image) => Self::Image(Image {
+                width: image.width,
+                height: image.height,
+                pixels: image.pixels.into(),
+            }),
+            RemotePreview::File(path) => Self::File(path.into()),
+        }
+    }
+}
+
+impl From<QueryContext> for RemoteContext {
+    fn from(context: QueryContext) -> Self {
+        Self {
+            output: context.output.map(String::from).into(),
+            clipboard: context.clipboard.map(String::from).into(),
+            app_id: context.app_id.map(String::from).into(),
+            window_title: context.window_title.map(String::from).into(),
+        }
+    }
+}
+
+impl From<RemoteContext> for QueryContext {
+    fn from(context: RemoteContext) -> Self {
+        Self {
+            output: context.output.map(Into::into).into(),
+            clipboard: context.clipboard.map(Into::into).into(),
+            app_id: context.app_id.map(Into::into).into(),
+            window_title: context.window_title.map(Into::into).into(),
+        }
+    }
+}
+
+impl From<ValidateResult> for RemoteValidateResult {
+    fn from(result: ValidateResult) -> Self {
+        match result {
+            ValidateResult::Accept => Self::Accept,
+            ValidateResult::Reject => Self::Reject,
+            ValidateResult::Transform(input) => Self::Transform(input.into()),
+        }
+    }
+}
+
+impl From<RemoteValidateResult> for ValidateResult {
+    fn from(result: RemoteValidateResult) -> Self {
+        match result {
+            RemoteValidateResult::Accept => Self::Accept,
+            RemoteValidateResult::Reject => Self::Reject,
+            RemoteValidateResult::Transform(input) => Self::Transform(input.into()),
+        }
+    }
+}
+
+impl From<HandleResult> for RemoteHandleResult {
+    fn from(result: HandleResult) -> Self {
+        match result {
+            HandleResult::Close => Self::Close,
+            HandleResult::Refresh(exclusive) => Self::Refresh(exclusive),
+            HandleResult::Copy(bytes, mime_type) => {
+                Self::Copy(bytes.into(), mime_type.map(String::from).into())
+            }
+            HandleResult::Stdout(bytes) => Self::Stdout(bytes.into()),
+            HandleResult::Masked(masked) => Self::Masked(masked),
+            HandleResult::RefreshPlugin => Self::RefreshPlugin,
+            HandleResult::SetInput(input) => Self::SetInput(input.into()),
+            HandleResult::Type(text) => Self::Type(text.into()),
+        }
+    }
+}
+
+impl From<RemoteHandleResult> for HandleResult {
+    fn from(result: RemoteHandleResult) -> Self {
+        match result {
+            RemoteHandleResult::Close => Self::Close,
+            RemoteHandleResult::Refresh(exclusive) => Self::Refresh(exclusive),
+            RemoteHandleResult::Copy(bytes, mime_type) => {
+                Self::Copy(bytes.into(), mime_type.map(Into::into).into())
+            }
+            RemoteHandleResult::Stdout(bytes) => Self::Stdout(bytes.into()),
+            RemoteHandleResult::Masked(masked) => Self::Masked(masked),
+            RemoteHandleResult::RefreshPlugin => Self::RefreshPlugin,
+            RemoteHandleResult::SetInput(input) => Self::SetInput(input.into()),
+            RemoteHandleResult::Type(text) => Self::Type(text.into()),
+        }
+    }
+}
+
+/// The launcher's end of a helper process
+pub struct Helper {
+    child: RefCell<Child>,
+    stdin: RefCell<ChildStdin>,
+    responses: Receiver<Response>,
+    name: String,
+    icon: String,
+    next_id: Cell<u64>,
+    /// Matches that arrived while waiting for another response
+    ready: RefCell<HashMap<u64, Vec<RemoteMatch>>>,
+    /// The intervals the plugin asked for refreshing the matches of the queries at
+    refresh: RefCell<HashMap<u64, u64>>,
+    /// Streamed matches that haven't been polled yet
+    partial: RefCell<HashMap<u64, Vec<RemoteMatch>>>,
+    /// Queries whose matches are dropped when they arrive
+    cancelled: RefCell<HashSet<u64>>,
+    /// The prefix of the plugin, as of the latest matches
+    prefix: RefCell<Option<String>>,
+    /// The errors the plugin reported that haven't been shown yet
+    errors: RefCell<Vec<String>>,
+    /// Why the helper stopped working, once it has
+    error: RefCell<Option<String>>,
+}
+
+impl Helper {
+    /// Start a helper and wait for it to load the plugin at `path`
+    pub fn spawn(
+        path: &Path,
+        options: &LoadOptions,
+        environment: Option<&PluginEnvironment>,
+    ) -> io::Result<Self> {
+        let mut command = Command::new(env::current_exe()?);
+        command
+            // The working directory of the helper may be changed
+            .arg("--plugin-host")
+            .arg(env::current_dir()?.join(path))
+            .arg("--config-dir")
+            .arg(options.config_dir)
+            .arg("--log-level")
+            .arg(options.log_level.as_str())
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped());
+        if options.journald {
+            command.arg("--journald");
+        }
+        if let Some(environment) = environment {
+            command.envs(&environment.env);
+            if let Some(cwd) = &environment.cwd {
+                command.current_dir(cwd);
+            }
+        }
+        let mut child = command.spawn()?;
+
+        let stdin = child.stdin.take().unwrap();
+        let stdout = child.stdout.take().unwrap();
+
+        // Read the responses in the background, the channel disconnects when the helper exits
+        let (sender, responses) = mpsc::channel();
+        thread::spawn(move || {
+            for line in BufReader::new(stdout).lines() {
+                let response = match line.map(|line| serde_json::from_str(&line)) {
+                    Ok(Ok(response)) => response,
+                    Ok(Err(why)) => {
+                        error!("Invalid response from a plugin helper: {}", why);
+                        continue;
+                    }
+                    Err(_) => break,
+                };
+                if sender.send(response).is_err() {
+                    break;
+                }
+            }
+        });
+
+        let (name, icon) = match responses.recv_timeout(STARTUP_TIMEOUT) {
+            Ok(Response::Info { name, icon }) => (name, icon),
+            Ok(Response::Failed(why)) => {
+                let _ = child.wait();
+                return Err(io::Error::other(why));
+            }
+            _ => {
+                let _ = child.kill();
+                return Err(io::Error::other("The plugin helper did not start"));
+            }
+        };
+
+        Ok(Self {
+            child: RefCell::new(child),
+            stdin: RefCell::new(stdin),
+            responses,
+            name,
+            icon,
+            next_id: Cell::new(0),
+            ready: RefCell::new(HashMap::new()),
+            refresh: RefCell::new(HashMap::new()),
+            partial: RefCell::new(HashMap::new()),
+            cancelled: RefCell::new(HashSet::new()),
+            prefix: RefCell::new(None),
+            errors: RefCell::new(Vec::new()),
+            error: RefCell::new(None),
+        })
+    }
+
+    pub fn info(&self) -> PluginInfo {
+        PluginInfo {
+            name: self.name.clone().into(),
+            icon: self.icon.clone().into(),
+        }
+    }
+
+    pub fn get_matches(&self, input: &str) -> u64 {
+        let id = self.next_id.get();
+        self.next_id.set(id + 1);
+
+        self.send(&Request::Query {
+            id,
+            input: input.to_string(),
+        });
+
+        id
+    }
+
+    pub fn poll_matches(&self, id: u64) -> PollResult {
+        self.poll_responses();
+
+        if self.error.borrow().is_some() {
+            return PollResult::Ready(vec![self.error_match()].into());
+        }
+
+        if self.cancelled.borrow().contains(&id) {
+            return PollResult::Cancelled;
+        }
+
+        // The streamed matches always come before the rest
+        let partial = self.partial.borrow_mut().remove(&id);
+        match (partial, self.ready.borrow_mut().remove(&id)) {
+            (partial, Some(matches)) => PollResult::Ready(
+                partial
+                    .into_iter()
+                    .flatten()
+                    .chain(matches)
+                    .map(Match::from)
+                    .collect(),
+            ),
+            (Some(partial), None) => {
+                PollResult::Partial(partial.into_iter().map(Match::from).collect())
+            }
+            (None, None) => PollResult::Pending,
+        }
+    }
+
+    /// How long after their matches arrived the query should be made again, if the plugin asked
+    pub fn refresh_interval(&self, id: u64) -> Option<Duration> {
+        self.refresh
+            .borrow_mut()
+            .remove(&id)
+            .map(Duration::from_millis)
+    }
+
+    pub fn prefix(&self) -> Option<String> {
+        self.prefix.borrow().clone()
+    }
+
+    pub fn errors(&self) -> Vec<String> {
+        // The errors reported during `init` arrive after the info
+        self.poll_responses();
+        mem::take(&mut self.errors.borrow_mut())
+    }
+
+    pub fn cancel(&self, id: u64) {
+        self.send(&Request::Cancel(id));
+        self.partial.borrow_mut().remove(&id);
+        self.refresh.borrow_mut().remove(&id);
+        // The matches may have arrived already, otherwise they are dropped when they do
+        if self.ready.borrow_mut().remove(&id).is_none() {
+            self.cancelled.borrow_mut().insert(id);
+        }
+    }
+
+    pub fn handle_selection(&self, selection: Match, input: &str) -> HandleResult {
+        let request = Request::Handle(selection.into(), input.to_string());
+        match self.request(&request, HANDLE_TIMEOUT) {
+            Some(Response::Handled(result)) => result.into(),
+            // The error row of a stopped helper is selected, or the helper stopped while handling
+            _ => HandleResult::Refresh(false),
+        }
+    }
+
+    pub fn preview(&self, selection: Match) -> Option<Preview> {
+        match self.request(&Request::Preview(selection.into()), PREVIEW_TIMEOUT) {
+            Some(Response::Preview(preview)) => preview.map(Preview::from),
+            _ => None,
+        }
+    }
+
+    pub fn set_context(&self, context: &QueryContext) {
+        self.send(&Request::Context(context.clone().into()));
+    }
+
+    pub fn validate_input(&self, input: &str) -> ValidateResult {
+        match self.request(&Request::Validate(input.to_string()), VALIDATE_TIMEOUT) {
+            Some(Response::Validated(result)) => result.into(),
+            // Let the query through so the error row gets shown
+            _ => ValidateResult::Accept,
+        }
+    }
+
+    /// Whether the helper stopped working
+    pub fn failed(&self) -> bool {
+        self.error.borrow().is_some()
+    }
+
+    pub fn deinit(&self) {
+        // The helper is stopped either way, a plugin taking too long only loses what it didn't save
+        self.request(&Request::Deinit, DEINIT_TIMEOUT);
+    }
+
+    /// Kill the helper process
+    pub fn stop(&self) {
+        let _ = self.child.borrow_mut().kill();
+    }
+
+    /// Take in the responses that arrived so far, without waiting for more
+    fn poll_responses(&self) {
+        loop {
+            match self.responses.try_recv() {
+                Ok(Response::Matches { id, .. }) if self.cancelled.borrow_mut().remove(&id) => (),
+                Ok(Response::Matches { id, matches }) => {
+                    self.ready.borrow_mut().insert(id, matches);
+                }
+                Ok(Response::Partial { id, .. }) if self.cancelled.borrow().contains(&id) => (),
+                Ok(Response::Partial { id, matches }) => {
+                    self.partial
+                        .borrow_mut()
+                        .entry(id)
+                        .or_default()
+                        .extend(matches);
+                }
+                Ok(Response::Prefix(prefix)) => *self.prefix.borrow_mut() = prefix,
+                Ok(Response::Refresh(id, interval)) => {
+                    self.refresh.borrow_mut().insert(id, interval);
+                }
+                Ok(Response::Errors(errors)) => self.errors.borrow_mut().extend(errors),
+                // Late responses to requests that were already given up on
+                Ok(_) => (),
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    self.fail(None);
+                    break;
+                }
+            }
+        }
+    }
+
+    fn send(&self, request: &Request) -> bool {
+        if self.error.borrow().is_some() {
+            return false;
+        }
+
+        let line = serde_json::to_string(request).unwrap();
+        if writeln!(self.stdin.borrow_mut(), "{}", line).is_err() {
+            self.fail(None);
+            return false;
+        }
+
+        true
+    }
+
+    /// Send a request and wait for the response to it
+    fn request(&self, request: &Request, timeout: Duration) -> Option<Response> {
+        if !self.send(request) {
+            return None;
+        }
+
+        let deadline = Instant::now() + timeout;
+        loop {
+            match self
+                .responses
+                .recv_timeout(deadline.saturating_duration_since(Instant::now()))
+            {
+                Ok(Response::Matches { id, matches }) => {
+                    self.ready.borrow_mut().insert(id, matches);
+                }
+                Ok(Response::Prefix(prefix)) => *self.prefix.borrow_mut() = prefix,
+                Ok(Response::Refresh(id, interval)) => {
+                    self.refresh.borrow_mut().insert(id, interval);
+                }
+                Ok(Response::Errors(errors)) => self.errors.borrow_mut().extend(errors),
+                Ok(response) => return Some(response),
+                Err(RecvTimeoutError::Timeout) => {
+                    self.fail(Some("The plugin stopped responding".to_string()));
+                    return None;
+                }
+                Err(RecvTimeoutError::Disconnected) => {
+                    self.fail(None);
+                    return None;
+                }
+            }
+        }
+    }
+
+    /// Mark the helper as stopped, killing it if it is still running
+    fn fail(&self, reason: Option<String>) {
+        if self.error.borrow().is_some() {
+            return;
+        }
+
+        let mut child = self.child.borrow_mut();
+        let _ = child.kill();
+        let reason = reason.unwrap_or_else(|| match child.wait() {
+            Ok(status) => format!("The plugin exited unexpectedly ({})", status),
+            Err(why) => format!("The plugin exited unexpectedly: {}", why),
+        });
+
+        error!("Plugin {} stopped working: {}", self.name, reason);
+        *self.error.borrow_mut() = Some(reason);
+    }
+
+    fn error_match(&self) -> Match {
+        Match {
+            title: "The plugin has stopped working".into(),
+            description: self.error.borrow().clone().map(Into::into).into(),
+            title_pango: false,
+            description_pango: false,
+            icon: ROption::RSome("dialog-error".into()),
+            id: ROption::RNone,
+            actions: RVec::new(),
+            action: ROption::RNone,
+            image: ROption::RNone,
+            widgets: RVec::new(),
+            priority: 0,
+            pinned: false,
+        }
+    }
+}
+
+/// The helper's end, serving the requests of the launcher until stdin is closed
+pub fn host(path: &Path, config_dir: &str) {
+    // Keep the plugins that print to stdout from corrupting the responses
+    let stdout = unistd::dup(1).expect("Failed to duplicate stdout");
+    unistd::dup2(2, 1).expect("Failed to redirect stdout");
+    let stdout = Arc::new(Mutex::new(unsafe { File::from_raw_fd(stdout) }));
+
+    let plugin = match crate::embedded::find(path) {
+        Some(root_module) => root_module(),
+        None => match anyrun_interface::load(path) {
+            Ok(plugin) => plugin,
+            Err(why) => {
+                respond(&stdout, &Response::Failed(why.to_string()));
+                return;
+            }
+        },
+    };
+
+    plugin.set_log_sink()(crate::LOG_SINK);
+    plugin.set_recency_store()(crate::recent::STORE);
+    plugin.init()(config_dir.into());
+
+    let info = plugin.info()();
+    respond(
+        &stdout,
+        &Response::Info {
+            name: info.name.into(),
+            icon: info.icon.into(),
+        },
+    );
+    forward_errors(&stdout, &plugin);
+
+    // The ids the plugin gave the queries still running, for cancelling them. None while the plugin
+    // is still being asked.
+    let queries = Arc::new(Mutex::new(HashMap::new()));
+    // The prefix the launcher knows of, it is only sent again when it changes
+    let prefix = Arc::new(Mutex::new(None));
+
+    for line in io::stdin().lines() {
+        let line = match line {
+            Ok(line) => line,
+            Err(_) => break,
+        };
+        let request = match serde_json::from_str(&line) {
+            Ok(request) => request,
+            Err(why) => {
+                error!("Invalid request from the launcher: {}", why);
+                continue;
+            }
+        };
+
+        match request {
+            // Wait for the matches in the background, so the other requests aren't held up
+            Request::Query { id, input } => {
+                let stdout = stdout.clone();
+                let queries = queries.clone();
+                let prefix = prefix.clone();
+                // Known before the plugin is asked, so a cancel arriving meanwhile isn't lost
+                queries.lock().unwrap().insert(id, None);
+                thread::spawn(move || {
+                    let plugin_id = plugin.get_matches()(input.into());
+                    let cancelled = match queries.lock().unwrap().get_mut(&id) {
+                        Some(query) => {
+                            *query = Some(plugin_id);
+                            false
+                        }
+                        None => true,
+                    };
+                    if cancelled {
+                        plugin.cancel()(plugin_id);
+                    }
+                    let matches = loop {
+                        match plugin.poll_matches()(plugin_id) {
+                            PollResult::Ready(matches) => break matches,
+                            PollResult::Partial(matches) => respond(
+                                &stdout,
+                                &Response::Partial {
+                                    id,
+                                    matches: matches.into_iter().map(RemoteMatch::from).collect(),
+                                },
+                            ),
+                            PollResult::Pending => thread::sleep(Duration::from_millis(1)),
+                            PollResult::Cancelled => break RVec::new(),
+                        }
+                    };
+
+                    queries.lock().unwrap().remove(&id);
+                    // A changed prefix is sent before the matches it changed with
+                    let current = plugin.prefix()().into_option().map(String::from);
+                    let mut prefix = prefix.lock().unwrap();
+                    if *prefix != current {
+                        respond(&stdout, &Response::Prefix(current.clone()));
+                        *prefix = current;
+                    }
+                    if let ROption::RSome(interval) = plugin.refresh_interval()(plugin_id) {
+                        respond(&stdout, &Response::Refresh(id, interval));
+                    }
+                    forward_errors(&stdout, &plugin);
+                    respond(
+                        &stdout,
+                        &Response::Matches {
+                            id,
+                            matches: matches.into_iter().map(RemoteMatch::from).collect(),
+                        },
+                    );
+                });
+            }
+            Request::Cancel(id) => {
+                if let Some(Some(plugin_id)) = queries.lock().unwrap().remove(&id) {
+                    plugin.cancel()(plugin_id);
+                }
+            }
+            Request::Validate(input) => respond(
+                &stdout,
+                &Response::Validated(plugin.validate_input()(input.into()).into()),
+            ),
+            Request::Handle(selection, input) => {
+                let result = plugin.handle_selection()(selection.into(), input.into());
+                forward_errors(&stdout, &plugin);
+                respond(&stdout, &Response::Handled(result.into()));
+            }
+            Request::Preview(selection) => {
+                let preview = plugin.get_preview()(selection.into());
+                respond(
+                    &stdout,
+                    &Response::Preview(preview.into_option().map(RemotePreview::from)),
+                );
+            }
+            Request::Context(context) => plugin.set_context()(context.into()),
+            Request::Deinit => {
+                plugin.deinit()();
+                respond(&stdout, &Response::Deinitialized);
+            }
+        }
+    }
+}
+
+/// Pass on the errors the plugin reported, ahead of the response they came up in
+fn forward_errors(stdout: &Mutex<File>, plugin: &PluginRef) {
+    let errors = plugin.errors()();
+    if !errors.is_empty() {
+        respond(
+            stdout,
+            &Response::Errors(errors.into_iter().map(String::from).collect()),
+        );
+    }
+}
+
+fn respond(stdout: &Mutex<File>, response: &Response) {
+    let line = serde_json::to_string(response).unwrap();
+    if let Err(why) = writeln!(stdout.lock().unwrap(), "{}", line) {
+        error!("Failed to respond to the launcher: {}", why);
+    }
+}
@@ -0,0 +1,140 @@
+//! Watching over a job a plugin handed off with `anyrun_plugin::jobs`, as `anyrun --run-job
+//! <title> -- <command>`. The job is kept in the runtime directory for the jobs plugin while it
+//! runs, and the notifications about how it is going are sent with `notify-send`.
+
+use std::{
+    fs,
+    io::{BufRead, BufReader},
+    os::unix::process::{CommandExt, ExitStatusExt},
+    path::Path,
+    process::{Command, Stdio},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use anyrun_plugin::jobs::{self, Job};
+use nix::sys::signal::Signal;
+use tracing::warn;
+
+/// Programs print their progress far more often than is worth showing
+const PROGRESS_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Run the job to the end, returning whether it succeeded
+pub fn run(title: &str, command: &[String]) -> bool {
+    let (program, args) = match command.split_first() {
+        Some(command) => command,
+        None => {
+            warn!("No command given for the job {}", title);
+            return false;
+        }
+    };
+
+    let mut child = match Command::new(program)
+        .args(args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        // Cancelling the job stops what the program started as well
+        .process_group(0)
+        .spawn()
+    {
+        Ok(child) => child,
+        Err(why) => {
+            notify(None, title, &format!("Failed to start: {}", why), None);
+            return false;
+        }
+    };
+
+    let path = jobs::dir().join(format!("{}.json", child.id()));
+    let mut job = Job {
+        title: title.to_string(),
+        command: command.to_vec(),
+        started: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |time| time.as_secs()),
+        progress: None,
+    };
+    save(&path, &job);
+    let mut notification = notify(None, title, "Started", None);
+
+    let mut updated = Instant::now();
+    let stdout = BufReader::new(child.stdout.take().unwrap());
+    for line in stdout.lines().map_while(Result::ok) {
+        let progress = match percentage(&line) {
+            Some(progress) if job.progress != Some(progress) => progress,
+            _ => continue,
+        };
+
+        job.progress = Some(progress);
+        save(&path, &job);
+        if updated.elapsed() >= PROGRESS_INTERVAL {
+            notification = notify(notification, title, line.trim(), Some(progress));
+            updated = Instant::now();
+        }
+    }
+
+    let status = child.wait();
+    let _ = fs::remove_file(&path);
+
+    let (body, success) = match status {
+        Ok(status) if status.success() => ("Done".to_string(), true),
+        Ok(status) if status.signal() == Some(Signal::SIGTERM as i32) => {
+            ("Cancelled".to_string(), false)
+        }
+        Ok(status) => (format!("Failed ({})", status), false),
+        Err(why) => (format!("Failed: {}", why), false),
+    };
+    notify(notification, title, &body, None);
+
+    success
+}
+
+fn save(path: &Path, job: &Job) {
+    if let Err(why) = path
+        .parent()
+        .map_or(Ok(()), fs::create_dir_all)
+        .and_then(|_| fs::write(path, serde_json::to_string(job).unwrap_or_default()))
+    {
+        warn!("Failed to save the job: {}", why);
+    }
+}
+
+/// The last percentage in the line, like the 42 of `Downloading 42.5%`
+fn percentage(line: &str) -> Option<u8> {
+    let (before, _) = line.rsplit_once('%')?;
+    let before = before.trim_end();
+    let start = before
+        .char_indices()
+        .rev()
+        .take_while(|(_, chr)| chr.is_ascii_digit() || *chr == '.')
+        .last()
+        .map(|(index, _)| index)?;
+
+    before[start..]
+        .parse::<f64>()
+        .ok()
+        .map(|percentage| percentage.clamp(0.0, 100.0) as u8)
+}
+
+/// Show a notification, replacing the one with the id if there is one. Returns the id of the
+/// notification for replacing it later.
+fn notify(id: Option<u32>, summary: &str, body: &str, progress: Option<u8>) -> Option<u32> {
+    let mut command = Command::new("notify-send");
+    command.arg("--app-name=anyrun").arg("--print-id");
+    if let Some(id) = id {
+        command.arg(format!("--replace-id={}", id));
+    }
+    if let Some(progress) = progress {
+        command.arg(format!("--hint=int:value:{}", progress));
+    }
+
+    match command.arg(summary).arg(body).output() {
+        Ok(output) => String::from_utf8_lossy(&output.stdout)
+            .trim()
+            .parse()
+            .ok()
+            .or(id),
+        Err(why) => {
+            warn!("Failed to run notify-send: {}", why);
+            id
+        }
+    }
+}
@@ -0,0 +1,255 @@
+//! Parsing the options of a config file one by one, so that a misspelled or invalid option only
+//! loses that option instead of the whole file. The file is split at the commas between the
+//! options, and each of them is parsed on its own with the rest of the file left out.
+
+use ron::error::SpannedError;
+use serde::de::DeserializeOwned;
+
+/// Parse the options of the struct in `content` into a `T` each, with the problems found in them.
+/// All the fields of `T` are expected to be optional, `fields` are the names of them. Only fails
+/// if the file can't be split into its options.
+pub fn parse<T: DeserializeOwned>(
+    content: &str,
+    fields: &[&str],
+    options: &ron::Options,
+    source: &str,
+) -> Result<(Vec<T>, Vec<String>), SpannedError> {
+    let (header, pieces) = match split(content) {
+        Some(split) => split,
+        // Let the parser explain what is wrong with it
+        None => {
+            return options
+                .from_str::<T>(content)
+                .map(|parsed| (vec![parsed], Vec::new()))
+        }
+    };
+
+    let mut parsed = Vec::new();
+    let mut warnings = Vec::new();
+
+    for piece in pieces {
+        let name = match field_name(piece) {
+            Some(name) => name,
+            None => continue,
+        };
+        // The name is a part of `content`, which tells where it is
+        let offset = name.as_ptr() as usize - content.as_ptr() as usize;
+        let line = content[..offset].matches('\n').count() + 1;
+
+        if !fields.contains(&name) {
+            warnings.push(match closest(name, fields) {
+                Some(field) => format!(
+                    "{}:{}: Unknown option `{}`, did you mean `{}`?",
+                    source, line, name, field
+                ),
+                None => format!("{}:{}: Unknown option `{}`", source, line, name),
+            });
+            continue;
+        }
+
+        match options.from_str::<T>(&format!("{}({})", header, piece)) {
+            Ok(option) => parsed.push(option),
+            Err(why) => warnings.push(format!(
+                "{}:{}: Invalid option `{}`, using the default: {}",
+                source, line, name, why.code
+            )),
+        }
+    }
+
+    Ok((parsed, warnings))
+}
+
+/// Split the struct into what comes before it, like the extensions and the name of the struct, and
+/// the options
+fn split(content: &str) -> Option<(&str, Vec<&str>)> {
+    let mut depth = 0_usize;
+    let mut open = None;
+    let mut pieces = Vec::new();
+    let mut piece_start = 0;
+    let mut chars = content.char_indices().peekable();
+
+    while let Some((index, chr)) = chars.next() {
+        match chr {
+            // The brackets in strings and chars don't count
+            '"' | '\'' => {
+                while let Some((_, next)) = chars.next() {
+                    match next {
+                        '\\' => {
+                            chars.next();
+                        }
+                        next if next == chr => break,
+                        _ => (),
+                    }
+                }
+            }
+            '/' if chars.peek().map(|(_, next)| *next) == Some('/') => {
+                for (_, next) in chars.by_ref() {
+                    if next == '\n' {
+                        break;
+                    }
+                }
+            }
+            '/' if chars.peek().map(|(_, next)| *next) == Some('*') => {
+                chars.next();
+                let mut star = false;
+                for (_, next) in chars.by_ref() {
+                    if star && next == '/' {
+                        break;
+                    }
+                    star = next == '*';
+                }
+            }
+            // The extensions like `#![enable(implicit_some)]` are in brackets, so the first
+            // parenthesis at the top is the one of the struct
+            '(' | '[' | '{' => {
+                if depth == 0 && open.is_none() && chr == '(' {
+                    open = Some(index);
+                    piece_start = index + 1;
+                }
+                depth += 1;
+            }
+            ')' | ']' | '}' => {
+                depth = depth.checked_sub(1)?;
+                if let (0, Some(open)) = (depth, open) {
+                    pieces.push(&content[piece_start..index]);
+                    return Some((&content[..open], pieces));
+                }
+            }
+            ',' if depth == 1 && open.is_some() => {
+                pieces.push(&content[piece_start..index]);
+                piece_start = index + 1;
+            }
+            _ => (),
+        }
+    }
+
+    None
+}
+
+/// The name of the option, after the comments in front of it
+fn field_name(mut piece: &str) -> Option<&str> {
+    loop {
+        piece = piece.trim_start();
+        if let Some(rest) = piece.strip_prefix("//") {
+            piece = rest.split_once('\n').map_or("", |(_, rest)| rest);
+        } else if let Some(rest) = piece.strip_prefix("/*") {
+            piece = rest.split_once("*/").map_or("", |(_, rest)| rest);
+        } else {
+            break;
+        }
+    }
+
+    let end = piece
+        .find(|chr: char| !chr.is_alphanumeric() && chr != '_')
+        .unwrap_or(piece.len());
+    (end > 0).then(|| &piece[..end])
+}
+
+/// The known field closest to the misspelled name, if any is close enough to be what was meant
+fn closest<'a>(name: &str, fields: &[&'a str]) -> Option<&'a str> {
+    fields
+        .iter()
+        .map(|field| (distance(name, field), *field))
+        .filter(|(distance, _)| *distance <= 2.max(name.len() / 4))
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, field)| field)
+}
+
+/// The Levenshtein distance between the strings
+fn distance(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<_>>();
+    let mut previous = (0..=b.len()).collect::<Vec<_>>();
+
+    for (i, a) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, b) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a != *b);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+
+    previous[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Deserialize;
+
+    use super::*;
+
+    #[derive(Deserialize)]
+    struct Config {
+        width: Option<u32>,
+        name: Option<String>,
+    }
+
+    const FIELDS: &[&str] = &["width", "name"];
+
+    /// Like the config files are parsed, with the fields of `ConfigArgs` all being options
+    fn parse_config(content: &str) -> Result<(Vec<Config>, Vec<String>), SpannedError> {
+        let options = ron::Options::default()
+            .with_default_extension(ron::extensions::Extensions::IMPLICIT_SOME);
+        parse(content, FIELDS, &options, "config.ron")
+    }
+
+    #[test]
+    fn parses_the_options_one_by_one() {
+        let (parsed, warnings) = parse_config("Config(width: 5, name: \"a\")").unwrap();
+        assert_eq!(parsed.len(), 2);
+        assert_eq!(parsed[0].width, Some(5));
+        assert_eq!(parsed[1].name.as_deref(), Some("a"));
+        assert!(warnings.is_empty());
+    }
+
+    #[test]
+    fn keeps_the_other_options_of_an_invalid_one() {
+        let (parsed, warnings) =
+            parse_config("Config(\n  width: \"wide\",\n  name: \"a\",\n)").unwrap();
+        assert_eq!(parsed.len(), 1);
+        assert_eq!(parsed[0].name.as_deref(), Some("a"));
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].starts_with("config.ron:2: Invalid option `width`"));
+    }
+
+    #[test]
+    fn suggests_the_option_meant() {
+        let (parsed, warnings) = parse_config("Config(widht: 5, colour: 1)").unwrap();
+        assert!(parsed.is_empty());
+        assert_eq!(
+            warnings,
+            [
+                "config.ron:1: Unknown option `widht`, did you mean `width`?",
+                "config.ron:1: Unknown option `colour`",
+            ]
+        );
+    }
+
+    #[test]
+    fn splits_only_at_the_commas_between_options() {
+        let content = r#"#![enable(unwrap_newtypes)]
+Config(
+  // a, (comment
+  name: "a, (b",
+  /* c, */ width: 1,
+)"#;
+        let (parsed, warnings) = parse_config(content).unwrap();
+        assert_eq!(parsed.len(), 2);
+        assert_eq!(parsed[0].name.as_deref(), Some("a, (b"));
+        assert_eq!(parsed[1].width, Some(1));
+        assert!(warnings.is_empty());
+    }
+
+    #[test]
+    fn fails_on_what_cant_be_split() {
+        assert!(parse_config("Config(width: 5").is_err());
+    }
+
+    #[test]
+    fn measures_the_distance_of_the_names() {
+        assert_eq!(distance("kitten", "sitting"), 3);
+        assert_eq!(distance("", "abc"), 3);
+        assert_eq!(closest("hieght", &["height", "width"]), Some("height"));
+        assert_eq!(closest("colour", &["height", "width"]), None);
+    }
+}
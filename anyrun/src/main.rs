@@ -1,22 +1,73 @@
 use std::{
-    cell::RefCell,
+    cell::{Cell, RefCell},
+    cmp::Reverse,
+    collections::HashMap,
     env, fs,
     io::{self, Write},
     mem,
-    path::PathBuf,
+    os::unix::process::CommandExt,
+    path::{Path, PathBuf},
+    process,
     rc::Rc,
-    sync::Once,
-    time::Duration,
+    str::FromStr,
+    sync::{mpsc::TryRecvError, Once},
+    time::{Duration, Instant},
 };
 
-use abi_stable::std_types::{ROption, RVec};
-use anyrun_interface::{HandleResult, Match, PluginInfo, PluginRef, PollResult};
-use clap::{Parser, ValueEnum};
+use abi_stable::std_types::{ROption, RString, RVec, Tuple2};
+use anyrun_interface::{
+    paths::{self, plugin_search_paths, DEFAULT_CONFIG_DIR, SYSTEM_DATA_DIR},
+    HandleResult, LogLevel, LogSink, Match, PluginInfo, PollResult, QueryContext, ValidateResult,
+    Widget,
+};
+use clap::{Parser, Subcommand, ValueEnum};
+#[cfg(feature = "x11-clipboard")]
 use clipboard_ext::prelude::*;
+#[cfg(feature = "x11-clipboard")]
 use clipboard_ext::x11_fork::ClipboardContext;
+use completion::Completion;
+use failures::Failures;
 use gtk::{gdk, gdk_pixbuf, gio, glib, prelude::*};
+use history::{Entry, History};
+use hyprland::Scratchpad;
+use input_history::InputHistory;
+use manage::PluginCommand;
 use nix::unistd;
-use serde::Deserialize;
+use pins::Pins;
+use plugin::{LoadOptions, Plugin};
+use preprocess::{Preprocessor, Preprocessors};
+use preview::PreviewPane;
+use profile::PluginProfile;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use tracing::{error, info, warn, Level};
+use tracing_subscriber::{filter::LevelFilter, prelude::*};
+use wl_clipboard_rs::copy;
+
+mod completion;
+mod context;
+mod dev;
+mod dmenu;
+mod embedded;
+mod failures;
+mod history;
+mod hyprland;
+mod input_history;
+mod isolation;
+mod job;
+mod lenient;
+mod manage;
+mod pins;
+mod plugin;
+mod preprocess;
+mod preview;
+mod profile;
+mod query;
+mod recent;
+mod route;
+mod setup;
+mod trace;
+mod virtual_keyboard;
 
 #[anyrun_macros::config_args]
 #[derive(Deserialize)]
@@ -35,9 +86,17 @@ struct Config {
 
     #[serde(default = "Config::default_plugins")]
     plugins: Vec<PathBuf>,
+    #[serde(default)]
+    plugin_paths: Vec<PathBuf>,
 
     #[serde(default)]
     hide_icons: bool,
+    #[serde(default = "Config::default_icon_size")]
+    icon_size: u32,
+    #[serde(default)]
+    monitor_icon_sizes: Vec<MonitorIconSize>,
+    #[serde(default)]
+    compact: bool,
     #[serde(default)]
     hide_plugin_info: bool,
     #[serde(default)]
@@ -45,11 +104,57 @@ struct Config {
     #[serde(default)]
     close_on_click: bool,
     #[serde(default)]
+    close_on_focus_lost: bool,
+    #[serde(default)]
     show_results_immediately: bool,
     #[serde(default)]
     max_entries: Option<usize>,
     #[serde(default = "Config::default_layer")]
     layer: Layer,
+    #[serde(default)]
+    layer_shell: bool,
+    #[serde(default = "Config::default_keyboard_mode")]
+    keyboard_mode: KeyboardMode,
+    #[serde(default)]
+    sticky_results: Vec<StickyResult>,
+    #[serde(default)]
+    preprocessors: Vec<Preprocessor>,
+    #[serde(default)]
+    pending_indicator: bool,
+    #[serde(default)]
+    plugin_timeout: Option<u64>,
+    #[serde(default)]
+    failure_budget: Option<u32>,
+    #[serde(default)]
+    max_input_length: Option<usize>,
+    #[serde(default)]
+    word_completion: bool,
+    #[serde(default)]
+    selection_history: bool,
+    #[serde(default)]
+    masked_input: bool,
+    #[serde(default)]
+    placeholder_text: Option<String>,
+    #[serde(default)]
+    prompt: Option<String>,
+    #[serde(default = "Config::default_animation")]
+    animation: Animation,
+    #[serde(default = "Config::default_animation_duration")]
+    animation_duration: u32,
+    #[serde(default)]
+    input_history: bool,
+    #[serde(default = "Config::default_input_history_size")]
+    input_history_size: usize,
+    #[serde(default)]
+    input_history_exclude: Vec<String>,
+    #[serde(default)]
+    hyprland_special_workspace: Option<String>,
+    #[serde(default)]
+    isolate_plugins: bool,
+    #[serde(default)]
+    plugin_environments: Vec<PluginEnvironment>,
+    #[serde(default = "Config::default_preview_position")]
+    preview_position: PreviewPosition,
 }
 
 impl Config {
@@ -81,6 +186,37 @@ impl Config {
     fn default_layer() -> Layer {
         Layer::Overlay
     }
+
+    fn default_keyboard_mode() -> KeyboardMode {
+        KeyboardMode::Exclusive
+    }
+
+    fn default_animation() -> Animation {
+        Animation::None
+    }
+
+    fn default_animation_duration() -> u32 {
+        150
+    }
+
+    fn default_icon_size() -> u32 {
+        32
+    }
+
+    fn default_input_history_size() -> usize {
+        100
+    }
+
+    fn default_preview_position() -> PreviewPosition {
+        PreviewPosition::Bottom
+    }
+
+    /// The environment configured for the plugin, by its path in `plugins`
+    fn plugin_environment(&self, plugin_path: &Path) -> Option<&PluginEnvironment> {
+        self.plugin_environments
+            .iter()
+            .find(|environment| environment.plugin == plugin_path)
+    }
 }
 
 impl Default for Config {
@@ -91,13 +227,40 @@ impl Default for Config {
             width: Self::default_width(),
             height: Self::default_height(),
             plugins: Self::default_plugins(),
+            plugin_paths: Vec::new(),
             hide_icons: false,
+            icon_size: Self::default_icon_size(),
+            monitor_icon_sizes: Vec::new(),
+            compact: false,
             hide_plugin_info: false,
             ignore_exclusive_zones: false,
             close_on_click: false,
+            close_on_focus_lost: false,
             show_results_immediately: false,
             max_entries: None,
             layer: Self::default_layer(),
+            layer_shell: false,
+            keyboard_mode: Self::default_keyboard_mode(),
+            sticky_results: Vec::new(),
+            preprocessors: Vec::new(),
+            pending_indicator: false,
+            plugin_timeout: None,
+            failure_budget: None,
+            max_input_length: None,
+            word_completion: false,
+            selection_history: false,
+            masked_input: false,
+            placeholder_text: None,
+            prompt: None,
+            animation: Self::default_animation(),
+            animation_duration: Self::default_animation_duration(),
+            input_history: false,
+            input_history_size: Self::default_input_history_size(),
+            input_history_exclude: Vec::new(),
+            hyprland_special_workspace: None,
+            isolate_plugins: false,
+            plugin_environments: Vec::new(),
+            preview_position: Self::default_preview_position(),
         }
     }
 }
@@ -110,6 +273,15 @@ enum Layer {
     Overlay,
 }
 
+/// How the layer shell surface takes the keyboard focus
+#[derive(Deserialize, Clone, ValueEnum)]
+enum KeyboardMode {
+    /// Take all keyboard input while open
+    Exclusive,
+    /// Take the focus like a normal window, so other windows can be focused by clicking them
+    OnDemand,
+}
+
 // Could have a better name
 #[derive(Deserialize, Clone)]
 enum RelativeNum {
@@ -138,12 +310,106 @@ impl From<&str> for RelativeNum {
     }
 }
 
+/// A match that is always shown first when the input matches `query`
+#[derive(Deserialize, Clone)]
+struct StickyResult {
+    /// Regex that the whole input has to match
+    query: String,
+    /// The title of the match to pin
+    title: String,
+    /// Only pin the match if it comes from the plugin with this name
+    #[serde(default)]
+    plugin: Option<String>,
+}
+
+impl FromStr for StickyResult {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        // Clap reports the error along with the value
+        let (query, title) = value.split_once('=').ok_or("expected <query>=<title>")?;
+
+        Ok(Self {
+            query: query.to_string(),
+            title: title.to_string(),
+            plugin: None,
+        })
+    }
+}
+
+/// The size of the match icons on a single monitor, for setups mixing monitors of different DPIs
+#[derive(Deserialize, Clone)]
+struct MonitorIconSize {
+    /// The model of the monitor as GTK reports it, or its index
+    monitor: String,
+    size: u32,
+}
+
+impl From<&str> for MonitorIconSize {
+    fn from(value: &str) -> Self {
+        let (monitor, size) = value
+            .split_once('=')
+            .expect("Invalid MonitorIconSize value");
+
+        Self {
+            monitor: monitor.to_string(),
+            size: size.parse().expect("Invalid MonitorIconSize value"),
+        }
+    }
+}
+
+/// Environment variables and a working directory only used by a single plugin
+#[derive(Deserialize, Clone)]
+struct PluginEnvironment {
+    /// The plugin as it is written in `plugins`
+    plugin: PathBuf,
+    #[serde(default)]
+    env: HashMap<String, String>,
+    #[serde(default)]
+    cwd: Option<PathBuf>,
+}
+
+impl From<&str> for PluginEnvironment {
+    fn from(value: &str) -> Self {
+        let (plugin, var) = value
+            .split_once(':')
+            .expect("Invalid PluginEnvironment value");
+        let (key, value) = var
+            .split_once('=')
+            .expect("Invalid PluginEnvironment value");
+
+        Self {
+            plugin: plugin.into(),
+            env: HashMap::from([(key.to_string(), value.to_string())]),
+            cwd: None,
+        }
+    }
+}
+
 /// A "view" of plugin's info and matches
 #[derive(Clone)]
 struct PluginView {
-    plugin: PluginRef,
+    plugin: Plugin,
     row: gtk::ListBoxRow,
     list: gtk::ListBox,
+    /// Shown while the plugin is still working on the matches
+    spinner: gtk::Spinner,
+    /// The errors the plugin reported, until they are dismissed
+    errors: gtk::Box,
+    /// Timings of the plugin, if profiling is enabled
+    profile: Option<Rc<RefCell<PluginProfile>>>,
+    /// The query the plugin is still working on, cancelled when a newer one is dispatched
+    query: Rc<Cell<Option<u64>>>,
+    /// The query the shown matches are from, once all of them are in
+    shown: Rc<Cell<Option<u64>>>,
+    /// Whether the query is a refresh the plugin asked for, which keeps the selection in place
+    refreshing: Rc<Cell<bool>>,
+    /// The file name of the plugin, which its failures are counted by
+    file: String,
+    /// Whether the helper stopping was counted already, it stays stopped for the rest of the run
+    failed: Rc<Cell<bool>>,
+    /// Whether the plugin has the sticky match, which moves it above the other plugins
+    sticky: Rc<Cell<bool>>,
 }
 
 #[derive(Parser)]
@@ -151,8 +417,96 @@ struct Args {
     /// Override the path to the config directory
     #[arg(short, long)]
     config_dir: Option<String>,
+    /// The most verbose level of messages to log
+    #[arg(long, default_value = "info")]
+    log_level: Level,
+    /// Log to the systemd journal instead of stderr
+    #[arg(long)]
+    journald: bool,
+    /// Measure how long the plugins take to load and to get matches, reported on exit
+    #[arg(long)]
+    profile_plugins: bool,
+    /// Record the calls into the plugins and write them to the file on exit, in the trace event
+    /// format of Chrome
+    #[arg(long)]
+    trace_plugins: Option<PathBuf>,
+    /// Serve the plugin at the path to the launcher, used by `isolate_plugins`
+    #[arg(long, hide = true)]
+    plugin_host: Option<PathBuf>,
+    /// Watch over the job with the title that a plugin handed off, used by `anyrun_plugin::jobs`
+    #[arg(long, hide = true)]
+    run_job: Option<String>,
+    /// The command of the job for `--run-job`, after `--`
+    #[arg(last = true, hide = true, requires = "run_job")]
+    job_command: Vec<String>,
+    /// Pick one of the lines of stdin like dmenu, also accepting the flags of dmenu
+    #[arg(long)]
+    dmenu: bool,
+    /// The amount of lines shown with `--dmenu`
+    #[arg(long, requires = "dmenu")]
+    lines: Option<usize>,
+    /// Match the lines case-insensitively with `--dmenu`
+    #[arg(long, requires = "dmenu")]
+    ignore_case: bool,
+    /// Print the selected match instead of letting the plugin handle it
+    #[arg(long, value_enum)]
+    print_selection: Option<SelectionFormat>,
     #[command(flatten)]
     config: ConfigArgs,
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Print the matches for the input without opening the window
+    Query {
+        input: String,
+        /// Run the query on another machine over SSH, with the plugins installed there
+        #[arg(long)]
+        remote: Option<String>,
+        /// How to print the matches
+        #[arg(long, value_enum, default_value = "plain")]
+        format: query::Format,
+    },
+    /// Manage the installed plugins
+    Plugin {
+        #[command(subcommand)]
+        command: PluginCommand,
+    },
+    /// Try out a plugin in a REPL, loading it again when it is rebuilt
+    Dev { plugin: PathBuf },
+    /// Work with the config files
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommand,
+    },
+}
+
+#[derive(Subcommand)]
+enum ConfigCommand {
+    /// Report the unknown and invalid options in the config files, exiting with an error if
+    /// there are any
+    Check,
+}
+
+/// How the window and the results appear
+#[derive(Deserialize, Clone, ValueEnum)]
+enum Animation {
+    None,
+    Fade,
+    Slide,
+}
+
+/// Where the preview of the selected match is shown
+#[derive(Deserialize, Clone, PartialEq, ValueEnum)]
+enum PreviewPosition {
+    /// Under the matches
+    Bottom,
+    /// Next to the matches
+    Side,
+    /// Not at all, without asking the plugins for previews
+    Hidden,
 }
 
 #[derive(Deserialize, Clone, ValueEnum)]
@@ -161,9 +515,28 @@ enum Position {
     Center,
 }
 
+#[derive(Clone, Copy, ValueEnum)]
+enum SelectionFormat {
+    /// An object with the title, description, id and the name of the plugin
+    Json,
+}
+
+/// The selected match printed with `--print-selection=json`
+#[derive(Serialize)]
+struct Selection {
+    plugin: String,
+    title: String,
+    description: Option<String>,
+    id: Option<u64>,
+    action: Option<u64>,
+}
+
 /// Actions to run after GTK has finished
 enum PostRunAction {
-    Copy(Vec<u8>),
+    Copy(Vec<u8>, Option<String>),
+    Type(String),
+    /// Start anyrun again, with the config written by the setup
+    Restart,
     None,
 }
 
@@ -177,6 +550,37 @@ struct RuntimeData {
     /// Used for displaying errors later on
     error_label: String,
     config_dir: String,
+    /// Where the setup writes the config to on the first run, shown instead of the launcher then
+    setup_dir: Option<String>,
+    /// The current text input, used to check for sticky results
+    input: String,
+    /// Compiled query patterns of the sticky results in the config
+    sticky_results: Vec<(Regex, StickyResult)>,
+    /// The rewrites of the input before it is dispatched
+    preprocessors: Preprocessors,
+    /// Suggests completions for the input if enabled
+    completion: Option<Completion>,
+    /// The log of selected matches if enabled
+    history: Option<History>,
+    /// Whether the past selections are shown instead of the matches of the plugins
+    history_mode: bool,
+    /// The submitted queries recalled with Up and Down if enabled
+    input_history: Option<InputHistory>,
+    /// The matches pinned with Ctrl+P
+    pins: Pins,
+    /// Whether the typed characters are hidden. The input is then not remembered anywhere.
+    masked: bool,
+    /// The special workspace of Hyprland the window is opened in if enabled
+    scratchpad: Option<Scratchpad>,
+    /// The failures of the plugins in a row, if `failure_budget` is set
+    failures: Option<Failures>,
+    /// Where anyrun was opened, passed on to the plugins as more of it becomes known
+    context: QueryContext,
+    profile_plugins: bool,
+    print_selection: Option<SelectionFormat>,
+    /// Passed on to the plugin helpers
+    log_level: Level,
+    journald: bool,
 }
 
 /// The naming scheme for CSS styling
@@ -186,6 +590,8 @@ struct RuntimeData {
 mod style_names {
     /// The text entry box
     pub const ENTRY: &str = "entry";
+    /// The prompt next to the entry box and the box containing both
+    pub const PROMPT: &str = "prompt";
     /// "Main" widgets (main GtkListBox, main GtkBox)
     pub const MAIN: &str = "main";
     /// The window
@@ -197,61 +603,228 @@ mod style_names {
 
     pub const MATCH_TITLE: &str = "match-title";
     pub const MATCH_DESC: &str = "match-desc";
+    /// The progress bars, tables and monospace blocks of a match
+    pub const MATCH_WIDGET: &str = "match-widget";
+    /// The icon marking a pinned match
+    pub const MATCH_PINNED: &str = "match-pinned";
+    /// The icon marking a pinned or past match the plugin didn't have anymore when it was selected
+    pub const MATCH_STALE: &str = "match-stale";
+    /// The menu with the other actions of a match and the rows of it
+    pub const ACTION: &str = "action";
+    /// The errors a plugin reported, under its matches
+    pub const PLUGIN_ERROR: &str = "plugin-error";
+    /// The pane with the preview of the selected match and the widgets in it
+    pub const PREVIEW: &str = "preview";
 }
 
-/// Default config directory
-pub const DEFAULT_CONFIG_DIR: &str = "/etc/anyrun";
+/// How long the matches of a plugin need to be pending before the spinner is shown
+const PENDING_INDICATOR_DELAY: Duration = Duration::from_millis(200);
+
+/// The directory for state that persists between runs
+fn state_dir() -> PathBuf {
+    match env::var("XDG_STATE_HOME") {
+        Ok(state_home) => PathBuf::from(state_home),
+        Err(_) => PathBuf::from(format!(
+            "{}/.local/state",
+            env::var("HOME").expect("Could not determine home directory! Is $HOME set?")
+        )),
+    }
+    .join("anyrun")
+}
 
-fn main() {
-    let app = gtk::Application::new(Some("com.kirottu.anyrun"), Default::default());
+/// Build the config from the layers in `SYSTEM_DATA_DIR`, `DEFAULT_CONFIG_DIR` and `config_dir`,
+/// with the options set in a later layer overriding the earlier ones. Returns the errors to display,
+/// and whether any of the layers had a config.
+fn load_config(config_dir: &str) -> (Config, String, bool) {
+    let mut layers = vec![SYSTEM_DATA_DIR, DEFAULT_CONFIG_DIR];
+    if !layers.contains(&config_dir) {
+        layers.push(config_dir);
+    }
 
-    // Register here so we know if the instance is the primary or a remote
-    app.register(None::<&gio::Cancellable>).unwrap();
+    let mut config = Config::default();
+    let mut errors = Vec::new();
+    let mut found = false;
 
-    // If another instance is running, quit
-    if app.is_remote() {
+    for dir in layers {
+        let content = match fs::read_to_string(format!("{}/config.ron", dir)) {
+            Ok(content) => content,
+            Err(why) if why.kind() == io::ErrorKind::NotFound => continue,
+            Err(why) => {
+                // There is a config, even if it can't be read, so the setup mustn't replace it
+                found = true;
+                errors.push(format!("Failed to read {}/config.ron: {}", dir, why));
+                continue;
+            }
+        };
+        found = true;
+
+        // The options are optional in the layers, but written without `Some`. A wrong option only
+        // loses that option, with a warning.
+        match lenient::parse::<ConfigArgs>(
+            &content,
+            Config::FIELDS,
+            &ron::Options::default()
+                .with_default_extension(ron::extensions::Extensions::IMPLICIT_SOME),
+            &format!("{}/config.ron", dir),
+        ) {
+            Ok((options, warnings)) => {
+                for option in options {
+                    config.merge_opt(option);
+                }
+                errors.extend(warnings);
+            }
+            Err(why) => errors.push(format!(
+                "Failed to parse {}/config.ron, ignoring it: {}",
+                dir, why
+            )),
+        }
+    }
+
+    if !found && errors.is_empty() {
+        errors.push("No Anyrun config file found, using default config".to_string());
+    }
+
+    (config, errors.join("\n"), found)
+}
+
+/// Find the library of a plugin. Without a library, the plugins built into anyrun stand in for
+/// them.
+fn resolve_plugin(plugin_path: &Path, search_paths: &[PathBuf]) -> Option<PathBuf> {
+    paths::resolve_plugin(plugin_path, search_paths)
+        .or_else(|| embedded::name(plugin_path).map(embedded::path))
+}
+
+fn main() {
+    let args = Args::parse_from(dmenu::translate_args(env::args_os()));
+
+    // Running as the helper process of an isolated plugin
+    if let Some(path) = &args.plugin_host {
+        init_logging(args.log_level, args.journald);
+        isolation::host(
+            path,
+            args.config_dir.as_deref().unwrap_or(DEFAULT_CONFIG_DIR),
+        );
         return;
     }
 
-    let args = Args::parse();
+    // Running a job a plugin handed off to the background
+    if let Some(title) = &args.run_job {
+        init_logging(args.log_level, args.journald);
+        let success = job::run(title, &args.job_command);
+        process::exit(if success { 0 } else { 1 });
+    }
+
+    init_logging(args.log_level, args.journald);
 
     // Figure out the config dir
     let user_dir = format!(
         "{}/.config/anyrun",
         env::var("HOME").expect("Could not determine home directory! Is $HOME set?")
     );
-    let config_dir = args.config_dir.unwrap_or_else(|| {
+    let config_dir = args.config_dir.clone().unwrap_or_else(|| {
         if PathBuf::from(&user_dir).exists() {
-            user_dir
+            user_dir.clone()
         } else {
             DEFAULT_CONFIG_DIR.to_string()
         }
     });
 
-    // Load config, if unable to then read default config. If an error occurs the message will be displayed.
-    let (mut config, error_label) = match fs::read_to_string(format!("{}/config.ron", config_dir)) {
-        Ok(content) => ron::from_str(&content)
-            .map(|config| (config, String::new()))
-            .unwrap_or_else(|why| {
-                (
-                    Config::default(),
-                    format!(
-                        "Failed to parse Anyrun config file, using default config: {}",
-                        why
-                    ),
-                )
-            }),
-        Err(why) => (
-            Config::default(),
-            format!(
-                "Failed to read Anyrun config file, using default config: {}",
-                why
-            ),
-        ),
-    };
+    // Layer the system and user configs, if none can be read use the default config.
+    // If an error occurs the message will be displayed.
+    let (mut config, error_label, found) = load_config(&config_dir);
 
+    let plugins_set = args.config.plugins.is_some();
     config.merge_opt(args.config);
 
+    // Set anyrun up on the first run, unless it was started for something that has its own plugins
+    let setup_dir =
+        (!found && !plugins_set && !args.dmenu).then(|| args.config_dir.unwrap_or(user_dir));
+
+    if args.dmenu {
+        dmenu::configure(&mut config, plugins_set, args.lines, args.ignore_case);
+    }
+
+    if let Some(command) = args.command {
+        if !error_label.is_empty() {
+            warn!("{}", error_label);
+        }
+
+        let success = match command {
+            Command::Query {
+                input,
+                remote: Some(host),
+                format,
+            } => query::remote(&host, &input, format),
+            Command::Query { input, format, .. } => query::run(
+                &input,
+                format,
+                &config,
+                &LoadOptions {
+                    config_dir: &config_dir,
+                    isolate: config.isolate_plugins,
+                    log_level: args.log_level,
+                    journald: args.journald,
+                },
+            ),
+            Command::Plugin { command } => manage::run(command, &config, &config_dir),
+            Command::Dev { plugin } => dev::run(&plugin, &config_dir),
+            // The problems were already reported above
+            Command::Config {
+                command: ConfigCommand::Check,
+            } => {
+                if error_label.is_empty() {
+                    println!("No problems found in the config");
+                }
+                error_label.is_empty()
+            }
+        };
+        process::exit(if success { 0 } else { 1 });
+    }
+
+    let app = gtk::Application::new(Some("com.kirottu.anyrun"), Default::default());
+
+    // Register here so we know if the instance is the primary or a remote
+    app.register(None::<&gio::Cancellable>).unwrap();
+
+    // If another instance is running, quit
+    if app.is_remote() {
+        return;
+    }
+
+    let sticky_results = config
+        .sticky_results
+        .iter()
+        .filter_map(|sticky| {
+            Regex::new(&format!("^(?:{})$", sticky.query))
+                .map_err(|why| warn!("Invalid sticky result query: {}", why))
+                .ok()
+                .map(|regex| (regex, sticky.clone()))
+        })
+        .collect();
+    let preprocessors = Preprocessors::new(&config.preprocessors);
+
+    let completion = config
+        .word_completion
+        .then(|| Completion::load(&state_dir()));
+    let history = config
+        .selection_history
+        .then(|| History::load(&state_dir()));
+    let input_history = config
+        .input_history
+        .then(|| InputHistory::load(&state_dir(), &config_dir, config.input_history_size));
+    let failures = config.failure_budget.map(|_| Failures::load(&state_dir()));
+    // Remember the focused window before the window of anyrun takes the focus
+    let scratchpad = config
+        .hyprland_special_workspace
+        .as_deref()
+        .and_then(Scratchpad::new);
+    let focused = context::focused_in_background();
+    let masked = config.masked_input;
+    let trace_plugins = args.trace_plugins.clone();
+    if trace_plugins.is_some() {
+        trace::start();
+    }
+
     let runtime_data: Rc<RefCell<RuntimeData>> = Rc::new(RefCell::new(RuntimeData {
         exclusive: None,
         plugins: Vec::new(),
@@ -259,8 +832,46 @@ fn main() {
         config,
         error_label,
         config_dir,
+        setup_dir,
+        input: String::new(),
+        sticky_results,
+        preprocessors,
+        completion,
+        history,
+        history_mode: false,
+        input_history,
+        pins: Pins::load(&state_dir()),
+        masked,
+        scratchpad,
+        failures,
+        context: QueryContext::default(),
+        profile_plugins: args.profile_plugins,
+        print_selection: args.print_selection,
+        log_level: args.log_level,
+        journald: args.journald,
     }));
 
+    // The clipboard may have been read already, the rest is from the compositor
+    let runtime_data_clone = runtime_data.clone();
+    glib::timeout_add_local(Duration::from_millis(10), move || {
+        match focused.try_recv() {
+            Ok(context) => {
+                let mut runtime_data = runtime_data_clone.borrow_mut();
+                let clipboard = runtime_data.context.clipboard.take();
+                runtime_data.context = QueryContext {
+                    clipboard,
+                    ..context
+                };
+                for plugin_view in &runtime_data.plugins {
+                    plugin_view.plugin.set_context(&runtime_data.context);
+                }
+                glib::Continue(false)
+            }
+            Err(TryRecvError::Empty) => glib::Continue(true),
+            Err(TryRecvError::Disconnected) => glib::Continue(false),
+        }
+    });
+
     let runtime_data_clone = runtime_data.clone();
     app.connect_activate(move |app| activate(app, runtime_data_clone.clone()));
 
@@ -269,28 +880,158 @@ fn main() {
 
     let runtime_data = runtime_data.borrow_mut();
 
+    if runtime_data.profile_plugins {
+        profile::report(
+            &runtime_data
+                .plugins
+                .iter()
+                .filter_map(|view| view.profile.as_ref().map(|profile| profile.borrow()))
+                .collect::<Vec<_>>(),
+        );
+    }
+
+    if let Some(path) = &trace_plugins {
+        if let Err(why) = trace::write(path) {
+            error!("Failed to write the trace of the plugins: {}", why);
+        }
+    }
+
+    // Stop the plugin helpers, so that they don't outlive anyrun while the copy is being served
+    for plugin_view in &runtime_data.plugins {
+        plugin_view.plugin.deinit();
+        plugin_view.plugin.stop();
+    }
+
+    if let Some(scratchpad) = &runtime_data.scratchpad {
+        scratchpad.restore();
+    }
+
     // Perform a post run action if one is set
     match &runtime_data.post_run_action {
-        PostRunAction::Copy(bytes) => match unsafe { unistd::fork() } {
+        PostRunAction::Copy(bytes, mime_type) => match unsafe { unistd::fork() } {
             // The parent process just exits and prints that out
             Ok(unistd::ForkResult::Parent { .. }) => {
-                println!("Child spawned to serve copy requests.");
+                info!("Child spawned to serve copy requests.");
             }
             // Child process starts serving copy requests
             Ok(unistd::ForkResult::Child) => {
-                let mut ctx = ClipboardContext::new().unwrap();
+                if env::var("WAYLAND_DISPLAY").is_ok() {
+                    if let Err(why) = wayland_copy(bytes, mime_type.as_deref()) {
+                        error!("Failed to serve copy requests: {}", why);
+                    }
+                } else if mime_type
+                    .as_ref()
+                    .is_some_and(|mime_type| !mime_type.starts_with("text/"))
+                {
+                    error!("Only text can be copied outside of Wayland");
+                } else {
+                    #[cfg(feature = "x11-clipboard")]
+                    {
+                        let mut ctx = ClipboardContext::new().unwrap();
 
-                let content = std::str::from_utf8(bytes).unwrap();
-                ctx.set_contents(content.to_string()).unwrap();
+                        let content = std::str::from_utf8(bytes).unwrap();
+                        ctx.set_contents(content.to_string()).unwrap();
+                    }
+                    #[cfg(not(feature = "x11-clipboard"))]
+                    error!("Anyrun was built without copying outside of Wayland");
+                }
             }
             Err(why) => {
-                eprintln!("Failed to fork for copy sharing: {}", why);
+                error!("Failed to fork for copy sharing: {}", why);
             }
         },
+        PostRunAction::Type(text) => virtual_keyboard::type_text(text),
+        PostRunAction::Restart => {
+            let why = match env::current_exe() {
+                Ok(exe) => process::Command::new(exe)
+                    .args(env::args_os().skip(1))
+                    .exec(),
+                Err(why) => why,
+            };
+            error!("Failed to start anyrun again: {}", why);
+        }
         PostRunAction::None => (),
     }
 }
 
+/// Set up the subscriber for the logs of anyrun and the plugins
+fn init_logging(level: Level, journald: bool) {
+    #[cfg(feature = "journald")]
+    let journald_layer = if journald {
+        tracing_journald::layer()
+            .map_err(|why| eprintln!("Failed to connect to journald: {}", why))
+            .ok()
+    } else {
+        None
+    };
+    #[cfg(not(feature = "journald"))]
+    let journald_layer: Option<tracing_subscriber::layer::Identity> = {
+        if journald {
+            eprintln!("Anyrun was built without journald support, logging to stderr");
+        }
+        None
+    };
+    let stderr_layer = journald_layer
+        .is_none()
+        .then(|| tracing_subscriber::fmt::layer().with_writer(io::stderr));
+
+    tracing_subscriber::registry()
+        .with(LevelFilter::from_level(level))
+        .with(journald_layer)
+        .with(stderr_layer)
+        .init();
+}
+
+/// Logs the messages of the plugins with the plugin they came from
+const LOG_SINK: LogSink = LogSink {
+    log: plugin_log_sink,
+};
+
+/// The target of the messages is the module path in the plugin, starting with its crate name
+extern "C" fn plugin_log_sink(level: LogLevel, target: RString, message: RString) {
+    let plugin = target.split("::").next().unwrap_or_default();
+
+    match level {
+        LogLevel::Error => tracing::error!(target: "plugin", plugin, "{}", message),
+        LogLevel::Warn => tracing::warn!(target: "plugin", plugin, "{}", message),
+        LogLevel::Info => tracing::info!(target: "plugin", plugin, "{}", message),
+        LogLevel::Debug => tracing::debug!(target: "plugin", plugin, "{}", message),
+        LogLevel::Trace => tracing::trace!(target: "plugin", plugin, "{}", message),
+    }
+}
+
+/// Serve the copy requests with the data-control protocol, in the foreground
+/// as this process is already detached from the launcher
+fn wayland_copy(bytes: &[u8], mime_type: Option<&str>) -> Result<(), copy::Error> {
+    let mut opts = copy::Options::new();
+    opts.foreground(true);
+
+    let mime_type = match mime_type {
+        Some(mime_type) => mime_type,
+        None => {
+            return opts.copy(
+                copy::Source::Bytes(bytes.into()),
+                copy::MimeType::Autodetect,
+            )
+        }
+    };
+
+    let mut offers = vec![copy::MimeSource {
+        source: copy::Source::Bytes(bytes.into()),
+        mime_type: copy::MimeType::Specific(mime_type.to_string()),
+    }];
+
+    // Also offer rich text as plain text for clients that only accept that
+    if mime_type.starts_with("text/") && !mime_type.starts_with("text/plain") {
+        offers.push(copy::MimeSource {
+            source: copy::Source::Bytes(bytes.into()),
+            mime_type: copy::MimeType::Text,
+        });
+    }
+
+    opts.copy_multi(offers)
+}
+
 fn find_selected_match_and_view(
     runtime_data: &Rc<RefCell<RuntimeData>>,
 ) -> Option<(gtk::ListBoxRow, PluginView)> {
@@ -307,12 +1048,89 @@ fn handle_selected_match_action(
     runtime_data: &Rc<RefCell<RuntimeData>>,
     entry: &gtk::Entry,
     window: &gtk::ApplicationWindow,
+    action: Option<u64>,
 ) -> Inhibit {
+    // The pins and the past selections are only copies of the matches, and the plugins keep what
+    // the IDs stand for per query. The plugin is asked for the match again instead.
+    let stored = unsafe {
+        selected_match
+            .data::<Entry>("stored")
+            .map(|stored| stored.as_ref().clone())
+    };
+    if let Some(stored) = stored {
+        requery(
+            stored,
+            selected_match,
+            plugin_view,
+            runtime_data,
+            entry,
+            window,
+            action,
+        );
+        return Inhibit(true);
+    }
+
     let mut runtime_data_clone = runtime_data.borrow_mut();
 
-    match plugin_view.plugin.handle_selection()(unsafe {
-        (*selected_match.data::<Match>("match").unwrap().as_ptr()).clone()
-    }) {
+    // A match asked for again is handled with the input it was found for
+    let input = unsafe {
+        selected_match
+            .data::<String>("input")
+            .map(|input| input.as_ref().clone())
+    }
+    .unwrap_or_else(|| runtime_data_clone.input.clone());
+    let mut selection =
+        unsafe { (*selected_match.data::<Match>("match").unwrap().as_ptr()).clone() };
+    selection.action = action.into();
+
+    // Remember the words of the submitted input for completions and the selection for the history.
+    // Matches for hidden input may contain it, so nothing is remembered then.
+    if !runtime_data_clone.masked {
+        if let Some(completion) = runtime_data_clone.completion.as_mut() {
+            completion.harvest(&input);
+        }
+        if let Some(history) = runtime_data_clone.history.as_mut() {
+            history.record(&plugin_view.plugin.info().name, &selection, &input);
+        }
+    }
+
+    // The queries for the excluded plugins are not remembered either, as those may be just as sensitive
+    let plugin_name = plugin_view.plugin.info().name.to_string();
+    let excluded = runtime_data_clone.masked
+        || runtime_data_clone
+            .config
+            .input_history_exclude
+            .contains(&plugin_name);
+    if let Some(input_history) = runtime_data_clone.input_history.as_mut() {
+        if !excluded {
+            input_history.record(&input);
+        }
+    }
+
+    if let Some(SelectionFormat::Json) = runtime_data_clone.print_selection {
+        let selection = Selection {
+            plugin: plugin_name,
+            title: selection.title.into(),
+            description: selection.description.map(String::from).into(),
+            id: selection.id.into(),
+            action,
+        };
+
+        if let Err(why) = serde_json::to_writer(io::stdout().lock(), &selection)
+            .map_err(io::Error::from)
+            .and_then(|_| writeln!(io::stdout().lock()))
+        {
+            error!("Error outputting the selection to stdout: {}", why);
+        }
+        window.close();
+        return Inhibit(true);
+    }
+
+    // The plugin gets the input the way its matches were made for
+    let input = runtime_data_clone
+        .preprocessors
+        .apply(plugin_view.plugin.info().name.as_str(), &input);
+    match plugin_view.plugin.handle_selection(selection, input.into()) {
         HandleResult::Close => {
             window.close();
             Inhibit(true)
@@ -324,21 +1142,331 @@ fn handle_selected_match_action(
                 runtime_data_clone.exclusive = None;
             }
             mem::drop(runtime_data_clone); // Drop the mutable borrow
+
+            // Show what the plugin refreshed instead of the history
+            set_history_mode(false, entry, runtime_data);
             refresh_matches(entry.text().into(), runtime_data.clone());
             Inhibit(false)
         }
-        HandleResult::Copy(bytes) => {
-            runtime_data_clone.post_run_action = PostRunAction::Copy(bytes.into());
+        HandleResult::Copy(bytes, mime_type) => {
+            runtime_data_clone.post_run_action =
+                PostRunAction::Copy(bytes.into(), mime_type.map(String::from).into());
+            window.close();
+            Inhibit(true)
+        }
+        HandleResult::Type(text) => {
+            runtime_data_clone.post_run_action = PostRunAction::Type(text.into());
             window.close();
             Inhibit(true)
         }
         HandleResult::Stdout(bytes) => {
             if let Err(why) = io::stdout().lock().write_all(&bytes) {
-                eprintln!("Error outputting content to stdout: {}", why);
+                error!("Error outputting content to stdout: {}", why);
             }
             window.close();
             Inhibit(true)
         }
+        HandleResult::Masked(masked) => {
+            runtime_data_clone.exclusive = Some(plugin_view.clone());
+            runtime_data_clone.masked = masked;
+            mem::drop(runtime_data_clone); // Drop the mutable borrow
+
+            set_history_mode(false, entry, runtime_data);
+            entry.set_visibility(!masked);
+            // Clearing the text refreshes the matches, unless there was nothing to clear
+            if entry.text().is_empty() {
+                refresh_matches(String::new(), runtime_data.clone());
+            } else {
+                entry.set_text("");
+            }
+            Inhibit(false)
+        }
+        HandleResult::RefreshPlugin => {
+            mem::drop(runtime_data_clone); // Drop the mutable borrow
+
+            // The history has to make way for the matches of all the plugins
+            if runtime_data.borrow().history_mode {
+                set_history_mode(false, entry, runtime_data);
+                refresh_matches(entry.text().into(), runtime_data.clone());
+            } else {
+                let input = runtime_data.borrow().preprocessors.global(&entry.text());
+                query_plugin(plugin_view, input, runtime_data);
+            }
+            Inhibit(false)
+        }
+        HandleResult::SetInput(input) => {
+            mem::drop(runtime_data_clone); // Drop the mutable borrow
+
+            set_history_mode(false, entry, runtime_data);
+            // Changing the text refreshes the matches, unless it stays the same
+            if entry.text() == input.as_str() {
+                refresh_matches(input.into(), runtime_data.clone());
+            } else {
+                entry.set_text(&input);
+            }
+            entry.set_position(-1);
+            Inhibit(false)
+        }
+    }
+}
+
+/// Ask the plugin for the stored match again with the input it was found for, and handle the match
+/// it has now with the same title and ID. The stored match is marked as stale if there is none.
+fn requery(
+    stored: Entry,
+    selected_match: &gtk::ListBoxRow,
+    plugin_view: &PluginView,
+    runtime_data: &Rc<RefCell<RuntimeData>>,
+    entry: &gtk::Entry,
+    window: &gtk::ApplicationWindow,
+    action: Option<u64>,
+) {
+    let query = stored.input.as_ref().and_then(|input| {
+        let query = runtime_data
+            .borrow()
+            .preprocessors
+            .apply(plugin_view.plugin.info().name.as_str(), input);
+        match plugin_view.plugin.validate_input(query.as_str().into()) {
+            ValidateResult::Accept => Some(query),
+            ValidateResult::Transform(query) => Some(query.into()),
+            ValidateResult::Reject => None,
+        }
+    });
+    let query = match query {
+        Some(query) => query,
+        None => return mark_stale(&stored, entry, runtime_data),
+    };
+
+    // The plugin works on one query at a time
+    if let Some(id) = plugin_view.query.take() {
+        plugin_view.plugin.cancel(id);
+    }
+    let id = plugin_view.plugin.get_matches(query.into());
+    let start = Instant::now();
+    let mut streamed = Vec::new();
+
+    let selected_match = selected_match.clone();
+    let plugin_view = plugin_view.clone();
+    let runtime_data = runtime_data.clone();
+    let entry = entry.clone();
+    let window = window.clone();
+    glib::timeout_add_local(Duration::from_micros(1000), move || {
+        match plugin_view.plugin.poll_matches(id) {
+            PollResult::Ready(matches) => streamed.extend(matches),
+            PollResult::Partial(matches) => {
+                streamed.extend(matches);
+                return glib::Continue(true);
+            }
+            PollResult::Pending => {
+                let timeout = runtime_data.borrow().config.plugin_timeout;
+                if timeout.is_none_or(|timeout| start.elapsed() < Duration::from_millis(timeout)) {
+                    return glib::Continue(true);
+                }
+                plugin_view.plugin.cancel(id);
+            }
+            // A newer query was dispatched meanwhile, the selection is left for it
+            PollResult::Cancelled => return glib::Continue(false),
+        }
+
+        match streamed.drain(..).find(|_match| stored.is(_match)) {
+            Some(_match) => {
+                unsafe {
+                    selected_match.set_data("match", _match);
+                    selected_match.steal_data::<Entry>("stored");
+                    selected_match.set_data("input", stored.input.clone().unwrap_or_default());
+                }
+                handle_selected_match_action(
+                    &selected_match,
+                    &plugin_view,
+                    &runtime_data,
+                    &entry,
+                    &window,
+                    action,
+                );
+            }
+            None => mark_stale(&stored, &entry, &runtime_data),
+        }
+        glib::Continue(false)
+    });
+}
+
+/// Show that the plugin no longer has the pinned or past match
+fn mark_stale(stored: &Entry, entry: &gtk::Entry, runtime_data: &Rc<RefCell<RuntimeData>>) {
+    {
+        let mut runtime_data = runtime_data.borrow_mut();
+        if runtime_data.history_mode {
+            if let Some(history) = runtime_data.history.as_mut() {
+                history.mark_stale(stored);
+            }
+        } else {
+            runtime_data.pins.mark_stale(stored);
+        }
+    }
+    refresh_matches(entry.text().into(), runtime_data.clone());
+}
+
+/// Show the other actions of the selected match in a menu next to it, the chosen one is passed to
+/// the handler with the match
+fn show_actions(
+    selected_match: &gtk::ListBoxRow,
+    plugin_view: &PluginView,
+    runtime_data: &Rc<RefCell<RuntimeData>>,
+    entry: &gtk::Entry,
+    window: &gtk::ApplicationWindow,
+) -> Inhibit {
+    let actions = unsafe {
+        (*selected_match.data::<Match>("match").unwrap().as_ptr())
+            .actions
+            .clone()
+    };
+    if actions.is_empty() {
+        return Inhibit(false);
+    }
+
+    let list = gtk::ListBox::builder().name(style_names::ACTION).build();
+    for action in actions.iter() {
+        let hbox = gtk::Box::builder()
+            .orientation(gtk::Orientation::Horizontal)
+            .spacing(10)
+            .name(style_names::ACTION)
+            .build();
+        if let ROption::RSome(icon) = &action.icon {
+            if !runtime_data.borrow().config.hide_icons {
+                hbox.add(
+                    &gtk::Image::builder()
+                        .name(style_names::ACTION)
+                        .icon_name(icon)
+                        .pixel_size(16)
+                        .build(),
+                );
+            }
+        }
+        hbox.add(
+            &gtk::Label::builder()
+                .name(style_names::ACTION)
+                .halign(gtk::Align::Start)
+                .label(&action.title)
+                .build(),
+        );
+        list.add(
+            &gtk::ListBoxRow::builder()
+                .name(style_names::ACTION)
+                .child(&hbox)
+                .build(),
+        );
+    }
+
+    let popover = gtk::Popover::builder()
+        .relative_to(selected_match)
+        .position(gtk::PositionType::Right)
+        .name(style_names::ACTION)
+        .child(&list)
+        .build();
+
+    list.connect_row_activated({
+        let popover = popover.clone();
+        let selected_match = selected_match.clone();
+        let plugin_view = plugin_view.clone();
+        let runtime_data = runtime_data.clone();
+        let entry = entry.clone();
+        let window = window.clone();
+        move |_, row| {
+            popover.popdown();
+            let action = actions[row.index() as usize].id;
+            handle_selected_match_action(
+                &selected_match,
+                &plugin_view,
+                &runtime_data,
+                &entry,
+                &window,
+                Some(action),
+            );
+        }
+    });
+    // Typing goes on in the entry once the menu is gone
+    let entry = entry.clone();
+    popover.connect_closed(move |popover| {
+        entry.grab_focus_without_selecting();
+        unsafe { popover.destroy() };
+    });
+
+    popover.show_all();
+    popover.popup();
+    if let Some(row) = list.row_at_index(0) {
+        list.select_row(Some(&row));
+        row.grab_focus();
+    }
+
+    Inhibit(true)
+}
+
+/// Anyrun was built without layer shell support, so the window covers the screen instead
+#[cfg(not(feature = "layer-shell"))]
+fn init_layer_shell(window: &gtk::ApplicationWindow, _config: &Config) {
+    warn!("Anyrun was built without layer shell support, using a fullscreen window");
+    window.fullscreen();
+}
+
+/// Turn the window into a layer shell surface anchored to every edge of the screen
+#[cfg(feature = "layer-shell")]
+fn init_layer_shell(window: &gtk::ApplicationWindow, config: &Config) {
+    gtk_layer_shell::init_for_window(window);
+
+    gtk_layer_shell::set_layer(
+        window,
+        match config.layer {
+            Layer::Background => gtk_layer_shell::Layer::Background,
+            Layer::Bottom => gtk_layer_shell::Layer::Bottom,
+            Layer::Top => gtk_layer_shell::Layer::Top,
+            Layer::Overlay => gtk_layer_shell::Layer::Overlay,
+        },
+    );
+    gtk_layer_shell::set_keyboard_mode(
+        window,
+        match config.keyboard_mode {
+            KeyboardMode::Exclusive => gtk_layer_shell::KeyboardMode::Exclusive,
+            KeyboardMode::OnDemand => gtk_layer_shell::KeyboardMode::OnDemand,
+        },
+    );
+
+    for edge in [
+        gtk_layer_shell::Edge::Top,
+        gtk_layer_shell::Edge::Bottom,
+        gtk_layer_shell::Edge::Left,
+        gtk_layer_shell::Edge::Right,
+    ] {
+        gtk_layer_shell::set_anchor(window, edge, true);
+    }
+
+    // Cover the exclusive zones of bars and such too, instead of fitting between them
+    if config.ignore_exclusive_zones {
+        gtk_layer_shell::set_exclusive_zone(window, -1);
+    }
+}
+
+/// Replace the input with an older or newer past query
+fn recall_input(newer: bool, entry: &gtk::Entry, runtime_data: &Rc<RefCell<RuntimeData>>) {
+    let text = entry.text();
+    let recalled = match runtime_data.borrow_mut().input_history.as_mut() {
+        Some(input_history) if newer => input_history.newer(&text),
+        Some(input_history) => input_history.older(&text).map(String::from),
+        None => None,
+    };
+
+    if let Some(recalled) = recalled {
+        entry.set_text(&recalled);
+        entry.set_position(-1);
+    }
+}
+
+/// Switch between showing the past selections and the matches of the plugins
+fn set_history_mode(enabled: bool, entry: &gtk::Entry, runtime_data: &Rc<RefCell<RuntimeData>>) {
+    runtime_data.borrow_mut().history_mode = enabled;
+
+    if enabled {
+        entry.set_placeholder_text(Some("Search history"));
+    } else {
+        entry.set_placeholder_text(runtime_data.borrow().config.placeholder_text.as_deref());
     }
 }
 
@@ -355,8 +1483,15 @@ fn activate(app: &gtk::Application, runtime_data: Rc<RefCell<RuntimeData>>) {
     // Center the window
     window.set_position(gtk::WindowPosition::CenterAlways);
 
-    // Set the window to be fullscreen
-    window.fullscreen();
+    // Cover the screen with a layer shell surface if enabled and supported, with a fullscreen window otherwise
+    let layer_shell = runtime_data.borrow().config.layer_shell;
+    #[cfg(feature = "layer-shell")]
+    let layer_shell = layer_shell && gtk_layer_shell::is_supported();
+    if layer_shell {
+        init_layer_shell(&window, &runtime_data.borrow().config);
+    } else {
+        window.fullscreen();
+    }
 
     // Get the screen's size and set it as the default size for the window
     let display = gdk::Display::default().unwrap();
@@ -389,12 +1524,19 @@ fn activate(app: &gtk::Application, runtime_data: Rc<RefCell<RuntimeData>>) {
         Inhibit(false)
     });
 
-    // Try to load custom CSS, if it fails load the default CSS
+    // Try to load custom CSS from the user or the system, if it fails load the default CSS
     let provider = gtk::CssProvider::new();
-    if let Err(why) =
-        provider.load_from_path(&format!("{}/style.css", runtime_data.borrow().config_dir))
-    {
-        eprintln!("Failed to load custom CSS: {}", why);
+    let css_path = [
+        runtime_data.borrow().config_dir.as_str(),
+        DEFAULT_CONFIG_DIR,
+        SYSTEM_DATA_DIR,
+    ]
+    .iter()
+    .map(|dir| format!("{}/style.css", dir))
+    .find(|path| PathBuf::from(path).exists())
+    .unwrap_or_else(|| format!("{}/style.css", runtime_data.borrow().config_dir));
+    if let Err(why) = provider.load_from_path(&css_path) {
+        warn!("Failed to load custom CSS: {}", why);
         provider
             .load_from_data(include_bytes!("../res/style.css"))
             .unwrap();
@@ -405,22 +1547,28 @@ fn activate(app: &gtk::Application, runtime_data: Rc<RefCell<RuntimeData>>) {
         gtk::STYLE_PROVIDER_PRIORITY_APPLICATION,
     );
 
+    // The plugins are only loaded once anyrun is set up
+    let setup_dir = runtime_data.borrow().setup_dir.clone();
+    if let Some(setup_dir) = setup_dir {
+        setup::show(&window, &provider, runtime_data, &setup_dir);
+        return;
+    }
+
     // Create the main list of plugin views
     let main_list = gtk::ListBox::builder()
         .selection_mode(gtk::SelectionMode::None)
         .name(style_names::MAIN)
         .build();
+    let preview = PreviewPane::new(height / 2);
 
-    // Prioritise the ANYRUN_PLUGINS env var over other paths
-    let mut plugin_paths = match env::var("ANYRUN_PLUGINS") {
-        Ok(string) => string.split(':').map(PathBuf::from).collect::<Vec<_>>(),
-        Err(_) => Vec::new(),
-    };
+    let search_paths = plugin_search_paths(
+        &runtime_data.borrow().config_dir,
+        &runtime_data.borrow().config.plugin_paths,
+    );
 
-    plugin_paths.append(&mut vec![
-        format!("{}/plugins", runtime_data.borrow().config_dir).into(),
-        format!("{}/plugins", DEFAULT_CONFIG_DIR).into(),
-    ]);
+    // The plugins left out for failing too often or not loading, the user is told about them with
+    // the errors
+    let mut disabled = Vec::new();
 
     // Load plugins from the paths specified in the config file
     let plugins = runtime_data
@@ -428,36 +1576,56 @@ fn activate(app: &gtk::Application, runtime_data: Rc<RefCell<RuntimeData>>) {
         .config
         .plugins
         .iter()
-        .map(|plugin_path| {
-            // Load the plugin's dynamic library.
-            let mut user_path =
-                PathBuf::from(&format!("{}/plugins", runtime_data.borrow().config_dir));
-            let mut global_path = PathBuf::from("/etc/anyrun/plugins");
-            user_path.extend(plugin_path.iter());
-            global_path.extend(plugin_path.iter());
-
-            // Load the plugin's dynamic library.
-
-            let plugin = if plugin_path.is_absolute() {
-                abi_stable::library::lib_header_from_path(plugin_path)
-            } else {
-                let path = plugin_paths
-                    .clone()
-                    .into_iter()
-                    .map(|mut path| {
-                        path.push(plugin_path);
-                        path
-                    })
-                    .find(|path| path.exists())
-                    .expect("Invalid plugin path");
-
-                abi_stable::library::lib_header_from_path(&path)
+        .filter_map(|plugin_path| {
+            let load_start = Instant::now();
+
+            let path = resolve_plugin(plugin_path, &search_paths).expect("Invalid plugin path");
+            let file = path
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_default();
+
+            if let (Some(budget), Some(failures)) = (
+                runtime_data.borrow().config.failure_budget,
+                &runtime_data.borrow().failures,
+            ) {
+                if failures.count(&file) >= budget {
+                    disabled.push(format!(
+                        "{} is disabled for failing {} times in a row, `anyrun plugin reset {}` enables it again",
+                        file, budget, file
+                    ));
+                    return None;
+                }
             }
-            .and_then(|plugin| plugin.init_root_module::<PluginRef>())
-            .expect("Failed to load plugin");
 
-            // Run the plugin's init code to init static resources etc.
-            plugin.init()(runtime_data.borrow().config_dir.clone().into());
+            let plugin = {
+                let runtime_data = runtime_data.borrow();
+                Plugin::load(
+                    &path,
+                    &LoadOptions {
+                        config_dir: &runtime_data.config_dir,
+                        isolate: runtime_data.config.isolate_plugins,
+                        log_level: runtime_data.log_level,
+                        journald: runtime_data.journald,
+                    },
+                    runtime_data.config.plugin_environment(plugin_path),
+                )
+            };
+            let plugin = match plugin {
+                Ok(plugin) => plugin,
+                Err(why) => {
+                    disabled.push(format!("Failed to load {}: {}", file, why));
+                    return None;
+                }
+            };
+            plugin.set_context(&runtime_data.borrow().context);
+
+            let profile = runtime_data.borrow().profile_plugins.then(|| {
+                Rc::new(RefCell::new(PluginProfile::new(
+                    plugin.info().name.into(),
+                    load_start.elapsed(),
+                )))
+            });
 
             let plugin_box = gtk::Box::builder()
                 .orientation(gtk::Orientation::Horizontal)
@@ -466,7 +1634,7 @@ fn activate(app: &gtk::Application, runtime_data: Rc<RefCell<RuntimeData>>) {
                 .build();
             if !runtime_data.borrow().config.hide_plugin_info {
                 plugin_box.add(&create_info_box(
-                    &plugin.info()(),
+                    &plugin.info(),
                     runtime_data.borrow().config.hide_icons,
                 ));
                 plugin_box.add(
@@ -480,21 +1648,78 @@ fn activate(app: &gtk::Application, runtime_data: Rc<RefCell<RuntimeData>>) {
                 .name(style_names::PLUGIN)
                 .hexpand(true)
                 .build();
+            if runtime_data.borrow().config.preview_position != PreviewPosition::Hidden {
+                preview.follow(&list, plugin.clone());
+            }
+            let spinner = gtk::Spinner::builder()
+                .name(style_names::PLUGIN)
+                .halign(gtk::Align::Start)
+                .no_show_all(true)
+                .build();
+            let errors = gtk::Box::builder()
+                .orientation(gtk::Orientation::Vertical)
+                .name(style_names::PLUGIN_ERROR)
+                .build();
 
-            plugin_box.add(&list);
+            let list_box = gtk::Box::builder()
+                .orientation(gtk::Orientation::Vertical)
+                .name(style_names::PLUGIN)
+                .hexpand(true)
+                .build();
+            list_box.add(&list);
+            list_box.add(&spinner);
+            list_box.add(&errors);
+
+            plugin_box.add(&list_box);
 
             let row = gtk::ListBoxRow::builder().name(style_names::PLUGIN).build();
-            row.add(&plugin_box);
+            row.add(&animate(&plugin_box, &runtime_data.borrow().config));
 
             main_list.add(&row);
 
-            PluginView { plugin, row, list }
+            Some(PluginView {
+                plugin,
+                row,
+                list,
+                spinner,
+                errors,
+                profile,
+                query: Rc::new(Cell::new(None)),
+                shown: Rc::new(Cell::new(None)),
+                refreshing: Rc::new(Cell::new(false)),
+                file,
+                failed: Rc::new(Cell::new(false)),
+                sticky: Rc::new(Cell::new(false)),
+            })
         })
         .collect::<Vec<PluginView>>();
 
+    // The plugin with the sticky match goes first, the others stay in the order of the config
+    let order = plugins
+        .iter()
+        .map(|view| (view.row.clone(), view.sticky.clone()))
+        .collect::<Vec<_>>();
+    main_list.set_sort_func(Some(Box::new(move |a, b| {
+        let key = |row: &gtk::ListBoxRow| {
+            let position = order.iter().position(|(other, _)| other == row);
+            let sticky = position.is_some_and(|position| order[position].1.get());
+            (!sticky, position)
+        };
+        key(a).cmp(&key(b)) as i32
+    })));
+
     // Assign the plugins here to avoid multiple mutable/immutable borrows
     runtime_data.borrow_mut().plugins = plugins;
 
+    for notice in disabled {
+        warn!("{}", notice);
+        let mut runtime_data = runtime_data.borrow_mut();
+        if !runtime_data.error_label.is_empty() {
+            runtime_data.error_label.push('\n');
+        }
+        runtime_data.error_label.push_str(&notice);
+    }
+
     // Connect selection events to avoid completely messing up selection logic
     for plugin_view in runtime_data.borrow().plugins.iter() {
         let plugins_clone = runtime_data.borrow().plugins.clone();
@@ -526,12 +1751,50 @@ fn activate(app: &gtk::Application, runtime_data: Rc<RefCell<RuntimeData>>) {
     let entry = gtk::Entry::builder()
         .hexpand(true)
         .name(style_names::ENTRY)
+        .max_length(runtime_data.borrow().config.max_input_length.unwrap_or(0) as i32)
+        .visibility(!runtime_data.borrow().masked)
         .build();
+    entry.set_placeholder_text(runtime_data.borrow().config.placeholder_text.as_deref());
 
     // Refresh the matches when text input changes
     let runtime_data_clone = runtime_data.clone();
+    let inserting_completion = Cell::new(false);
     entry.connect_changed(move |entry| {
-        refresh_matches(entry.text().to_string(), runtime_data_clone.clone())
+        // The suggested completion is not a part of the input until it is accepted
+        if inserting_completion.get() {
+            return;
+        }
+
+        let text = entry.text().to_string();
+        // Only suggest completions while typing, not while deleting or recalling a past query, and
+        // never for hidden input
+        let typed = text.chars().count() > runtime_data_clone.borrow().input.chars().count()
+            && !runtime_data_clone.borrow().masked
+            && !runtime_data_clone
+                .borrow()
+                .input_history
+                .as_ref()
+                .is_some_and(|input_history| input_history.is_recalled(&text));
+
+        refresh_matches(text.clone(), runtime_data_clone.clone());
+
+        let suggestion = runtime_data_clone
+            .borrow()
+            .completion
+            .as_ref()
+            .and_then(|completion| completion.complete(&text))
+            .map(String::from);
+
+        // Insert the rest of the word selected, so that typing replaces it
+        if let Some(suggestion) = suggestion.filter(|_| typed) {
+            let start = text.chars().count() as i32;
+            let mut end = start;
+
+            inserting_completion.set(true);
+            entry.insert_text(&suggestion, &mut end);
+            entry.select_region(start, end);
+            inserting_completion.set(false);
+        }
     });
 
     // Handle other key presses for selection control and all other things that may be needed
@@ -540,19 +1803,109 @@ fn activate(app: &gtk::Application, runtime_data: Rc<RefCell<RuntimeData>>) {
 
     window.connect_key_press_event(move |window, event| {
         use gdk::keys::constants;
+        // The menu of actions handles its keys itself
+        if window
+            .focused_widget()
+            .is_some_and(|focus| focus.ancestor(gtk::Popover::static_type()).is_some())
+        {
+            return Inhibit(false);
+        }
+
         match event.keyval() {
+            // Leave the history before closing
+            constants::Escape if runtime_data_clone.borrow().history_mode => {
+                set_history_mode(false, &entry_clone, &runtime_data_clone);
+                refresh_matches(entry_clone.text().to_string(), runtime_data_clone.clone());
+                Inhibit(true)
+            }
             // Close window on escape
             constants::Escape => {
                 window.close();
                 Inhibit(true)
             }
+            // Toggle searching the past selections
+            constants::r
+                if event.state().contains(gdk::ModifierType::CONTROL_MASK)
+                    && runtime_data_clone.borrow().history.is_some() =>
+            {
+                let history_mode = !runtime_data_clone.borrow().history_mode;
+                set_history_mode(history_mode, &entry_clone, &runtime_data_clone);
+                refresh_matches(entry_clone.text().to_string(), runtime_data_clone.clone());
+                Inhibit(true)
+            }
+            // Pin the selected match, or unpin it if it is pinned already
+            constants::p if event.state().contains(gdk::ModifierType::CONTROL_MASK) => {
+                if let Some((selected_match, plugin_view)) =
+                    find_selected_match_and_view(&runtime_data_clone)
+                {
+                    let selection = unsafe {
+                        (*selected_match.data::<Match>("match").unwrap().as_ptr()).clone()
+                    };
+                    {
+                        let mut runtime_data = runtime_data_clone.borrow_mut();
+                        // A copy is pinned with the input it was found for
+                        let stored = unsafe {
+                            selected_match
+                                .data::<Entry>("stored")
+                                .map(|stored| stored.as_ref().input.clone())
+                        };
+                        let input = stored.unwrap_or_else(|| Some(runtime_data.input.clone()));
+                        runtime_data.pins.toggle(
+                            plugin_view.plugin.info().name.as_str(),
+                            &selection,
+                            input.as_deref(),
+                        );
+                    }
+                    refresh_matches(entry_clone.text().to_string(), runtime_data_clone.clone());
+                }
+                Inhibit(true)
+            }
+            // Search the past queries for the input, going further back on every press. Ctrl+R is
+            // taken by the past selections.
+            constants::s
+                if event.state().contains(gdk::ModifierType::CONTROL_MASK)
+                    && runtime_data_clone.borrow().input_history.is_some() =>
+            {
+                recall_input(false, &entry_clone, &runtime_data_clone);
+                Inhibit(true)
+            }
+            // Recall the past queries while no match is selected, starting from an empty entry
+            constants::Up | constants::Down
+                if !runtime_data_clone.borrow().history_mode
+                    && find_selected_match_and_view(&runtime_data_clone).is_none()
+                    && runtime_data_clone
+                        .borrow()
+                        .input_history
+                        .as_ref()
+                        .is_some_and(|input_history| {
+                            let text = entry_clone.text();
+                            input_history.is_recalled(&text)
+                                || (text.is_empty() && event.keyval() == constants::Up)
+                        }) =>
+            {
+                recall_input(
+                    event.keyval() == constants::Down,
+                    &entry_clone,
+                    &runtime_data_clone,
+                );
+                Inhibit(true)
+            }
             // Handle selections
+            // Accept a suggested completion
+            constants::Tab
+                if entry_clone
+                    .selection_bounds()
+                    .is_some_and(|(_, end)| end == entry_clone.text_length() as i32)
+                    && runtime_data_clone.borrow().completion.is_some() =>
+            {
+                entry_clone.set_position(-1);
+                refresh_matches(entry_clone.text().to_string(), runtime_data_clone.clone());
+                Inhibit(true)
+            }
             constants::Down | constants::Tab | constants::Up => {
                 // Combine all of the matches into a `Vec` to allow for easier handling of the selection
-                let combined_matches = runtime_data_clone
-                    .borrow()
-                    .plugins
-                    .iter()
+                let combined_matches = shown_order(&runtime_data_clone.borrow().plugins)
+                    .into_iter()
                     .flat_map(|view| {
                         view.list.children().into_iter().map(|child| {
                             (
@@ -627,10 +1980,73 @@ fn activate(app: &gtk::Application, runtime_data: Rc<RefCell<RuntimeData>>) {
 
                 Inhibit(true)
             }
+            // Open the menu with the other actions of the selected match, with Right only at the
+            // end of the input so that the cursor can still be moved, and Ctrl+Right still moves
+            // it by words
+            constants::Return | constants::Right
+                if (event.keyval() == constants::Right
+                    && entry_clone.position() == entry_clone.text_length() as i32
+                    && !event.state().contains(gdk::ModifierType::CONTROL_MASK))
+                    || (event.keyval() == constants::Return
+                        && event.state().contains(gdk::ModifierType::CONTROL_MASK)) =>
+            {
+                match find_selected_match_and_view(&runtime_data_clone) {
+                    Some((selected_match, plugin_view)) => {
+                        let no_actions = unsafe {
+                            (*selected_match.data::<Match>("match").unwrap().as_ptr())
+                                .actions
+                                .is_empty()
+                        };
+                        // Ctrl+Enter on a match without a menu runs the match, like Enter
+                        if no_actions && event.keyval() == constants::Return {
+                            handle_selected_match_action(
+                                &selected_match,
+                                &plugin_view,
+                                &runtime_data_clone,
+                                &entry_clone,
+                                window,
+                                None,
+                            )
+                        } else {
+                            show_actions(
+                                &selected_match,
+                                &plugin_view,
+                                &runtime_data_clone,
+                                &entry_clone,
+                                window,
+                            )
+                        }
+                    }
+                    None => Inhibit(false),
+                }
+            }
+            // Run the first of the other actions of the selected match straight away, or the match
+            // itself if it has none
+            constants::Return if event.state().contains(gdk::ModifierType::SHIFT_MASK) => {
+                match find_selected_match_and_view(&runtime_data_clone) {
+                    Some((selected_match, plugin_view)) => {
+                        let action = unsafe {
+                            (*selected_match.data::<Match>("match").unwrap().as_ptr())
+                                .actions
+                                .first()
+                                .map(|action| action.id)
+                        };
+                        handle_selected_match_action(
+                            &selected_match,
+                            &plugin_view,
+                            &runtime_data_clone,
+                            &entry_clone,
+                            window,
+                            action,
+                        )
+                    }
+                    None => Inhibit(false),
+                }
+            }
             // Handle when the selected match is "activated"
             constants::Return => {
                 if let Some((selected_match, plugin_view)) = find_selected_match_and_view(&runtime_data_clone) {
-                    handle_selected_match_action(&selected_match, &plugin_view, &runtime_data_clone, &entry_clone, window)
+                    handle_selected_match_action(&selected_match, &plugin_view, &runtime_data_clone, &entry_clone, window, None)
                 } else {
                     Inhibit(false)
                 }
@@ -639,6 +2055,15 @@ fn activate(app: &gtk::Application, runtime_data: Rc<RefCell<RuntimeData>>) {
         }
     });
 
+    // If the option is enabled, close the window when another window is focused
+    let runtime_data_clone = runtime_data.clone();
+    window.connect_focus_out_event(move |window, _| {
+        if runtime_data_clone.borrow().config.close_on_focus_lost {
+            window.close();
+        }
+        Inhibit(false)
+    });
+
     window.connect_button_press_event({
         let runtime_data_clone = runtime_data.clone();
         let entry_clone = entry.clone();
@@ -656,13 +2081,31 @@ fn activate(app: &gtk::Application, runtime_data: Rc<RefCell<RuntimeData>>) {
                         return Inhibit(false);
                     }
 
-                    return handle_selected_match_action(&selected_match, &plugin_view, &runtime_data_clone, &entry_clone, window)
+                    return handle_selected_match_action(&selected_match, &plugin_view, &runtime_data_clone, &entry_clone, window, None)
                 }
             }
             Inhibit(false)
         }
     });
 
+    // Move the window to the special workspace once the compositor knows about it
+    let runtime_data_clone = runtime_data.clone();
+    window.connect_map(move |_| {
+        if let Some(scratchpad) = &runtime_data_clone.borrow().scratchpad {
+            scratchpad.show();
+        }
+
+        // The clipboard can only be read once the window is there
+        let runtime_data = runtime_data_clone.clone();
+        gtk::Clipboard::get(&gdk::SELECTION_CLIPBOARD).request_text(move |_, text| {
+            let mut runtime_data = runtime_data.borrow_mut();
+            runtime_data.context.clipboard = text.map(Into::into).into();
+            for plugin_view in &runtime_data.plugins {
+                plugin_view.plugin.set_context(&runtime_data.context);
+            }
+        });
+    });
+
     // Only create the widgets once to avoid issues
     let configure_once = Once::new();
 
@@ -671,6 +2114,7 @@ fn activate(app: &gtk::Application, runtime_data: Rc<RefCell<RuntimeData>>) {
         let runtime_data = runtime_data.clone();
         let entry = entry.clone();
         let main_list = main_list.clone();
+        let preview = preview.clone();
 
         configure_once.call_once(move || {
             {
@@ -691,7 +2135,26 @@ fn activate(app: &gtk::Application, runtime_data: Rc<RefCell<RuntimeData>>) {
                     .height_request(height)
                     .name(style_names::MAIN)
                     .build();
-                main_vbox.add(&entry);
+
+                // Show the prompt in front of the entry if one is set
+                match &runtime_data.config.prompt {
+                    Some(prompt) => {
+                        let entry_box = gtk::Box::builder()
+                            .orientation(gtk::Orientation::Horizontal)
+                            .spacing(10)
+                            .name(style_names::PROMPT)
+                            .build();
+                        entry_box.add(
+                            &gtk::Label::builder()
+                                .label(prompt)
+                                .name(style_names::PROMPT)
+                                .build(),
+                        );
+                        entry_box.add(&entry);
+                        main_vbox.add(&entry_box);
+                    }
+                    None => main_vbox.add(&entry),
+                }
 
                 // Display the error message
                 if !runtime_data.error_label.is_empty() {
@@ -699,26 +2162,52 @@ fn activate(app: &gtk::Application, runtime_data: Rc<RefCell<RuntimeData>>) {
                         &gtk::Label::builder()
                             .label(&format!(
                                 r#"<span foreground="red">{}</span>"#,
-                                runtime_data.error_label
+                                glib::markup_escape_text(&runtime_data.error_label)
                             ))
                             .use_markup(true)
                             .build(),
                     );
                 }
 
-                fixed.put(&main_vbox, x, y);
+                fixed.put(&animate(&main_vbox, &runtime_data.config), x, y);
                 window.add(&fixed);
                 window.show_all();
 
                 // Add and show the list later, to avoid showing empty plugin categories on launch
-                main_vbox.add(&main_list);
+                match runtime_data.config.preview_position {
+                    PreviewPosition::Side => {
+                        let results = gtk::Box::builder()
+                            .orientation(gtk::Orientation::Horizontal)
+                            .spacing(10)
+                            .name(style_names::MAIN)
+                            .build();
+                        results.add(&main_list);
+                        results.add(&preview.window);
+                        main_vbox.add(&results);
+                        results.show();
+                    }
+                    _ => {
+                        main_vbox.add(&main_list);
+                        main_vbox.add(&preview.window);
+                    }
+                }
                 main_list.show();
                 entry.grab_focus(); // Grab the focus so typing is immediately accepted by the entry box
             }
 
             if runtime_data.borrow().config.show_results_immediately {
                 // Get initial matches
-                refresh_matches(String::new(), runtime_data);
+                refresh_matches(String::new(), runtime_data.clone());
+            } else {
+                // The pins are shown on an empty query all the same
+                let runtime_data = runtime_data.borrow();
+                for plugin_view in runtime_data.plugins.iter() {
+                    handle_matches(plugin_view.clone(), &runtime_data, RVec::new());
+                }
+            }
+
+            for plugin_view in runtime_data.borrow().plugins.iter() {
+                check_pins(plugin_view.clone(), runtime_data.clone());
             }
         });
 
@@ -730,18 +2219,96 @@ fn activate(app: &gtk::Application, runtime_data: Rc<RefCell<RuntimeData>>) {
 }
 
 fn handle_matches(plugin_view: PluginView, runtime_data: &RuntimeData, matches: RVec<Match>) {
+    // The matches are no longer pending
+    plugin_view.spinner.stop();
+    plugin_view.spinner.hide();
+
+    show_errors(&plugin_view);
+
+    let selected_index = plugin_view.list.selected_row().map(|row| row.index());
+
     // Clear out the old matches from the list
     for widget in plugin_view.list.children() {
         plugin_view.list.remove(&widget);
     }
 
-    // If there are no matches, hide the plugin's results
+    // The past selections and the pins shown on their own are only copies of the matches, they are
+    // kept with the entry they are from to ask the plugin for the match again once selected
+    let plugin_name = plugin_view.plugin.info().name;
+    let mut matches = matches
+        .into_iter()
+        .map(|_match| {
+            let stored = runtime_data
+                .history
+                .as_ref()
+                .filter(|_| runtime_data.history_mode)
+                .and_then(|history| history.find(plugin_name.as_str(), &_match))
+                .cloned();
+            (_match, stored)
+        })
+        .collect::<Vec<_>>();
+
+    // Pinned matches go above the others, and all of them are shown while nothing is typed
+    let excluded = runtime_data
+        .exclusive
+        .as_ref()
+        .is_some_and(|exclusive| exclusive.plugin != plugin_view.plugin);
+    if !runtime_data.history_mode && !excluded {
+        let (pinned, rest): (Vec<_>, Vec<_>) = matches
+            .into_iter()
+            .partition(|(_match, _)| runtime_data.pins.is_pinned(plugin_name.as_str(), _match));
+        matches = if runtime_data.input.is_empty() {
+            runtime_data
+                .pins
+                .entries(plugin_name.as_str())
+                .map(|pin| (pin.to_match(), Some(pin.clone())))
+                .collect()
+        } else {
+            pinned
+        };
+        matches.extend(rest);
+    }
+
+    // If there are no matches, hide the plugin's results unless it has errors to show
     if matches.is_empty() {
-        plugin_view.row.hide();
+        set_sticky(&plugin_view, false);
+        if plugin_view.errors.children().is_empty() {
+            plugin_view.row.hide();
+        }
         return;
     }
 
-    for _match in matches {
+    // Move a match pinned for the current input to the top of the list
+    let sticky_index = runtime_data
+        .sticky_results
+        .iter()
+        .filter(|(query, sticky)| {
+            query.is_match(&runtime_data.input)
+                && sticky
+                    .plugin
+                    .as_ref()
+                    .is_none_or(|name| name == plugin_name.as_str())
+        })
+        .find_map(|(_, sticky)| {
+            matches
+                .iter()
+                .position(|(_match, _)| _match.title.as_str() == sticky.title)
+        });
+
+    if let Some(index) = sticky_index {
+        let _match = matches.remove(index);
+        matches.insert(0, _match);
+    }
+
+    // The plugin can still move its matches above the ones moved up by anyrun, the sort is stable
+    // so the order stays otherwise
+    let sticky_rank = sticky_index.map(|_| rank(&matches[0].0));
+    matches.sort_by_key(|(_match, _)| Reverse(rank(_match)));
+    let sticky = sticky_rank.is_some_and(|sticky_rank| rank(&matches[0].0) == sticky_rank);
+    set_sticky(&plugin_view, sticky);
+    let icon_size = icon_size(&plugin_view.list, &runtime_data.config);
+
+    for (i, (_match, stored)) in matches.into_iter().enumerate() {
         let hbox = gtk::Box::builder()
             .orientation(gtk::Orientation::Horizontal)
             .spacing(10)
@@ -749,43 +2316,71 @@ fn handle_matches(plugin_view: PluginView, runtime_data: &RuntimeData, matches:
             .hexpand(true)
             .build();
         if !runtime_data.config.hide_icons {
-            if let ROption::RSome(icon) = &_match.icon {
-                let mut builder = gtk::Image::builder()
+            if let ROption::RSome(pixels) = &_match.image {
+                let image = gtk::Image::builder()
                     .name(style_names::MATCH)
-                    .pixel_size(32);
+                    .pixel_size(icon_size)
+                    .build();
 
-                let path = PathBuf::from(icon.as_str());
+                let pixels = pixels.clone();
+                load_icon_pixels(&image, &pixels, icon_size);
+                image.connect_scale_factor_notify(move |image| {
+                    load_icon_pixels(image, &pixels, icon_size)
+                });
 
-                // If the icon path is absolute, load that file
-                if path.is_absolute() {
-                    match gdk_pixbuf::Pixbuf::from_file_at_size(icon.as_str(), 32, 32) {
-                        Ok(pixbuf) => builder = builder.pixbuf(&pixbuf),
-                        Err(why) => {
-                            println!("Failed to load icon file: {}", why);
-                            builder = builder.icon_name("image-missing"); // Set "broken" icon
-                        }
-                    }
+                hbox.add(&image);
+            } else if let ROption::RSome(icon) = &_match.icon {
+                let image = gtk::Image::builder()
+                    .name(style_names::MATCH)
+                    .pixel_size(icon_size)
+                    .build();
+
+                // If the icon path is absolute, load that file. Icon names are scaled by the icon theme.
+                if Path::new(icon.as_str()).is_absolute() {
+                    let path = icon.to_string();
+                    load_icon_file(&image, &path, icon_size);
+                    image.connect_scale_factor_notify(move |image| {
+                        load_icon_file(image, &path, icon_size)
+                    });
                 } else {
-                    builder = builder.icon_name(icon);
+                    image.set_icon_name(Some(icon));
                 }
 
-                hbox.add(&builder.build());
+                hbox.add(&image);
+            }
+        }
+
+        // Only the icon is shown in compact mode, with the rest in the tooltip
+        let compact = runtime_data.config.compact
+            && !runtime_data.config.hide_icons
+            && (_match.icon.is_some() || _match.image.is_some());
+        if compact {
+            let mut tooltip = markup(&_match.title, _match.title_pango);
+            if let ROption::RSome(desc) = &_match.description {
+                tooltip.push('\n');
+                tooltip.push_str(&markup(desc, _match.description_pango));
             }
+            hbox.set_tooltip_markup(Some(&tooltip));
         }
+
         let title = gtk::Label::builder()
             .name(style_names::MATCH_TITLE)
             .wrap(true)
             .xalign(0.0)
-            .use_markup(_match.use_pango)
+            .use_markup(true)
             .halign(gtk::Align::Start)
             .valign(gtk::Align::Center)
             .vexpand(true)
-            .label(&_match.title)
+            .label(&markup(&_match.title, _match.title_pango))
             .build();
 
-        // If a description is present, make a box with it and the title
+        // If a description or widgets are present, make a box with them and the title
         match &_match.description {
-            ROption::RSome(desc) => {
+            _ if compact => (),
+            ROption::RNone if _match.widgets.is_empty() => {
+                hbox.add(&title);
+            }
+            description => {
                 let title_desc_box = gtk::Box::builder()
                     .orientation(gtk::Orientation::Vertical)
                     .name(style_names::MATCH)
@@ -793,32 +2388,59 @@ fn handle_matches(plugin_view: PluginView, runtime_data: &RuntimeData, matches:
                     .vexpand(true)
                     .build();
                 title_desc_box.add(&title);
-                title_desc_box.add(
-                    &gtk::Label::builder()
-                        .name(style_names::MATCH_DESC)
-                        .wrap(true)
-                        .xalign(0.0)
-                        .use_markup(_match.use_pango)
-                        .halign(gtk::Align::Start)
-                        .valign(gtk::Align::Center)
-                        .label(desc)
-                        .build(),
-                );
+                if let ROption::RSome(desc) = description {
+                    title_desc_box.add(
+                        &gtk::Label::builder()
+                            .name(style_names::MATCH_DESC)
+                            .wrap(true)
+                            .xalign(0.0)
+                            .use_markup(true)
+                            .halign(gtk::Align::Start)
+                            .valign(gtk::Align::Center)
+                            .label(&markup(desc, _match.description_pango))
+                            .build(),
+                    );
+                }
+                for widget in _match.widgets.iter() {
+                    title_desc_box.add(&create_widget(widget));
+                }
                 hbox.add(&title_desc_box);
             }
-            ROption::RNone => {
-                hbox.add(&title);
-            }
+        }
+        if !compact && runtime_data.pins.is_pinned(plugin_name.as_str(), &_match) {
+            hbox.add(
+                &gtk::Image::builder()
+                    .name(style_names::MATCH_PINNED)
+                    .icon_name("view-pin-symbolic")
+                    .valign(gtk::Align::Center)
+                    .build(),
+            );
+        }
+        if !compact && stored.as_ref().is_some_and(|stored| stored.stale) {
+            hbox.add(
+                &gtk::Image::builder()
+                    .name(style_names::MATCH_STALE)
+                    .icon_name("dialog-warning-symbolic")
+                    .tooltip_text("The plugin no longer has this match")
+                    .valign(gtk::Align::Center)
+                    .build(),
+            );
         }
         let row = gtk::ListBoxRow::builder()
             .name(style_names::MATCH)
-            .height_request(32)
+            .height_request(icon_size)
             .build();
-        row.add(&hbox);
+        row.add(&animate(&hbox, &runtime_data.config));
         // GTK data setting is not type checked, so it is unsafe.
         // Only `Match` objects are stored though.
         unsafe {
             row.set_data("match", _match);
+            if let Some(stored) = stored {
+                row.set_data("stored", stored);
+            }
+            if sticky && i == 0 {
+                row.set_data("sticky", true);
+            }
         }
         plugin_view.list.add(&row);
     }
@@ -826,9 +2448,8 @@ fn handle_matches(plugin_view: PluginView, runtime_data: &RuntimeData, matches:
     // Refresh the items in the view
     plugin_view.row.show_all();
 
-    let combined_matches = runtime_data
-        .plugins
-        .iter()
+    let combined_matches = shown_order(&runtime_data.plugins)
+        .into_iter()
         .flat_map(|view| {
             view.list
                 .children()
@@ -837,25 +2458,310 @@ fn handle_matches(plugin_view: PluginView, runtime_data: &RuntimeData, matches:
         })
         .collect::<Vec<(gtk::ListBoxRow, &PluginView)>>();
 
-    // If `max_entries` is set, truncate the amount of entries
+    // If `max_entries` is set, truncate the amount of entries. The pinned ones of the plugins stay.
     if let Some(max_matches) = runtime_data.config.max_entries {
         for (row, view) in combined_matches.iter().skip(max_matches) {
-            view.list.remove(row);
+            if !unsafe { (*row.data::<Match>("match").unwrap().as_ptr()).pinned } {
+                view.list.remove(row);
+            }
         }
     }
 
     // Hide the plugins that no longer have any entries
     for (_, view) in &combined_matches {
-        if view.list.children().is_empty() {
+        if view.list.children().is_empty() && view.errors.children().is_empty() {
             view.row.hide();
         }
     }
 
-    if let Some((row, view)) = combined_matches.get(0) {
+    // A refresh keeps the same match selected, and leaves a selection in another plugin alone
+    if plugin_view.refreshing.get() {
+        if let Some(row) = selected_index.and_then(|index| plugin_view.list.row_at_index(index)) {
+            plugin_view.list.select_row(Some(&row));
+            return;
+        }
+        if runtime_data
+            .plugins
+            .iter()
+            .any(|view| view.list.selected_row().is_some())
+        {
+            return;
+        }
+    }
+
+    // Prefer selecting a sticky match over the first one
+    let selected = combined_matches
+        .iter()
+        .find(|(row, _)| row.parent().is_some() && unsafe { row.data::<bool>("sticky").is_some() })
+        .or_else(|| combined_matches.first());
+
+    if let Some((row, view)) = selected {
         view.list.select_row(Some(row));
     }
 }
 
+/// Move the plugin above the others while it has the sticky match, and back once it doesn't
+fn set_sticky(plugin_view: &PluginView, sticky: bool) {
+    if plugin_view.sticky.replace(sticky) == sticky {
+        return;
+    }
+    if let Some(main_list) = plugin_view
+        .row
+        .parent()
+        .and_then(|parent| parent.downcast::<gtk::ListBox>().ok())
+    {
+        main_list.invalidate_sort();
+    }
+}
+
+/// The plugins in the order they are shown in, with the one with the sticky match first
+fn shown_order(plugins: &[PluginView]) -> Vec<&PluginView> {
+    let mut views = plugins.iter().collect::<Vec<_>>();
+    views.sort_by_key(|view| !view.sticky.get());
+    views
+}
+
+/// Lay out a widget of a match
+fn create_widget(widget: &Widget) -> gtk::Widget {
+    match widget {
+        Widget::Progress(fraction, text) => gtk::ProgressBar::builder()
+            .name(style_names::MATCH_WIDGET)
+            .fraction(fraction.clamp(0.0, 1.0))
+            .show_text(text.is_some())
+            .text(text.as_ref().map_or("", |text| text.as_str()))
+            .valign(gtk::Align::Center)
+            .build()
+            .upcast(),
+        Widget::Table(rows) => {
+            let grid = gtk::Grid::builder()
+                .name(style_names::MATCH_WIDGET)
+                .column_spacing(10)
+                .build();
+            for (i, Tuple2(label, value)) in rows.iter().enumerate() {
+                grid.attach(
+                    &gtk::Label::builder()
+                        .name(style_names::MATCH_WIDGET)
+                        .xalign(0.0)
+                        .valign(gtk::Align::Start)
+                        .label(label.as_str())
+                        .build(),
+                    0,
+                    i as i32,
+                    1,
+                    1,
+                );
+                grid.attach(
+                    &gtk::Label::builder()
+                        .name(style_names::MATCH_WIDGET)
+                        .wrap(true)
+                        .xalign(0.0)
+                        .hexpand(true)
+                        .label(value.as_str())
+                        .build(),
+                    1,
+                    i as i32,
+                    1,
+                    1,
+                );
+            }
+            grid.upcast()
+        }
+        Widget::Monospace(text) => gtk::Label::builder()
+            .name(style_names::MATCH_WIDGET)
+            .xalign(0.0)
+            .halign(gtk::Align::Start)
+            .use_markup(true)
+            .label(&format!("<tt>{}</tt>", glib::markup_escape_text(text)))
+            .build()
+            .upcast(),
+    }
+}
+
+/// Add the errors the plugin reported since the last time to its section
+fn show_errors(plugin_view: &PluginView) {
+    for error in plugin_view.plugin.errors() {
+        // A plugin running into the same problem for every query only shows it once
+        let shown = plugin_view
+            .errors
+            .children()
+            .iter()
+            .any(|row| unsafe { *row.data::<String>("error").unwrap().as_ptr() == error });
+        if !shown {
+            add_error(plugin_view, error);
+        }
+    }
+}
+
+/// Show an error under the matches of the plugin, with a button dismissing it
+fn add_error(plugin_view: &PluginView, error: String) {
+    let row = gtk::Box::builder()
+        .orientation(gtk::Orientation::Horizontal)
+        .spacing(10)
+        .name(style_names::PLUGIN_ERROR)
+        .build();
+    row.add(
+        &gtk::Image::builder()
+            .name(style_names::PLUGIN_ERROR)
+            .icon_name("dialog-error")
+            .build(),
+    );
+    row.add(
+        &gtk::Label::builder()
+            .name(style_names::PLUGIN_ERROR)
+            .wrap(true)
+            .xalign(0.0)
+            .hexpand(true)
+            .label(&error)
+            .build(),
+    );
+    let dismiss = gtk::Button::builder()
+        .name(style_names::PLUGIN_ERROR)
+        .image(&gtk::Image::from_icon_name(
+            Some("window-close-symbolic"),
+            gtk::IconSize::Button,
+        ))
+        .relief(gtk::ReliefStyle::None)
+        .valign(gtk::Align::Center)
+        .build();
+    let plugin_view_clone = plugin_view.clone();
+    dismiss.connect_clicked(move |button| {
+        if let Some(row) = button.parent() {
+            plugin_view_clone.errors.remove(&row);
+        }
+        if plugin_view_clone.list.children().is_empty()
+            && plugin_view_clone.errors.children().is_empty()
+        {
+            plugin_view_clone.row.hide();
+        }
+    });
+    row.add(&dismiss);
+
+    // GTK data setting is not type checked, so it is unsafe.
+    // Only the reported errors are stored though.
+    unsafe {
+        row.set_data("error", error);
+    }
+    plugin_view.errors.add(&row);
+    row.show_all();
+    plugin_view.row.show();
+}
+
+/// Wrap the widget in a revealer playing the configured animation whenever the widget is shown
+fn animate(widget: &impl IsA<gtk::Widget>, config: &Config) -> gtk::Widget {
+    let transition = match config.animation {
+        Animation::None => return widget.clone().upcast(),
+        Animation::Fade => gtk::RevealerTransitionType::Crossfade,
+        Animation::Slide => gtk::RevealerTransitionType::SlideDown,
+    };
+
+    let revealer = gtk::Revealer::builder()
+        .transition_type(transition)
+        .transition_duration(config.animation_duration)
+        .build();
+    revealer.add(widget);
+
+    // Revealing only animates once the revealer is mapped, and hiding it resets it for the next time
+    revealer.connect_map(|revealer| revealer.set_reveal_child(true));
+    revealer.connect_unmap(|revealer| revealer.set_reveal_child(false));
+
+    revealer.upcast()
+}
+
+/// Where the plugin wants the match, the ones with a greater rank are shown first
+fn rank(_match: &Match) -> (bool, i32) {
+    (_match.pinned, _match.priority)
+}
+
+/// The size of the match icons on the monitor the widget is shown on
+fn icon_size(widget: &impl IsA<gtk::Widget>, config: &Config) -> i32 {
+    let monitor = widget.window().and_then(|window| {
+        let display = window.display();
+        let monitor = display.monitor_at_window(&window)?;
+        let index =
+            (0..display.n_monitors()).find(|i| display.monitor(*i).as_ref() == Some(&monitor));
+        Some((monitor.model(), index))
+    });
+
+    config
+        .monitor_icon_sizes
+        .iter()
+        .find(|icon_size| {
+            monitor.as_ref().is_some_and(|(model, index)| {
+                model.as_deref() == Some(icon_size.monitor.as_str())
+                    || index.map(|index| index.to_string()) == Some(icon_size.monitor.clone())
+            })
+        })
+        .map_or(config.icon_size, |icon_size| icon_size.size) as i32
+}
+
+/// Load the icon file at the scale of the monitor, so that it stays sharp on HiDPI screens. With
+/// fractional scaling the next integer scale is used and the compositor scales it down from there.
+fn load_icon_file(image: &gtk::Image, path: &str, size: i32) {
+    let scale = image.scale_factor();
+
+    match gdk_pixbuf::Pixbuf::from_file_at_size(path, size * scale, size * scale) {
+        Ok(pixbuf) => {
+            let surface = pixbuf.create_surface(scale, image.window().as_ref());
+            image.set_from_surface(surface.as_ref());
+        }
+        Err(why) => {
+            warn!("Failed to load icon file: {}", why);
+            image.set_icon_name(Some("image-missing")); // Set "broken" icon
+        }
+    }
+}
+
+/// The text of a match as pango markup, escaped unless the plugin has made it markup already
+fn markup(text: &str, pango: bool) -> String {
+    if pango {
+        text.to_string()
+    } else {
+        glib::markup_escape_text(text).to_string()
+    }
+}
+
+/// Show the pixels of a picture from a plugin, scaled to the icon size and keeping its aspect ratio
+fn load_icon_pixels(image: &gtk::Image, pixels: &anyrun_interface::Image, size: i32) {
+    let scale = image.scale_factor();
+    let (width, height) = (pixels.width as usize, pixels.height as usize);
+
+    if width == 0 || height == 0 || pixels.pixels.len() < width * height * 4 {
+        warn!(
+            "Invalid image of {}x{} with {} bytes",
+            width,
+            height,
+            pixels.pixels.len()
+        );
+        image.set_icon_name(Some("image-missing")); // Set "broken" icon
+        return;
+    }
+
+    let pixbuf = gdk_pixbuf::Pixbuf::from_bytes(
+        &glib::Bytes::from(pixels.pixels.as_slice()),
+        gdk_pixbuf::Colorspace::Rgb,
+        true,
+        8,
+        width as i32,
+        height as i32,
+        width as i32 * 4,
+    );
+    let target = (size * scale) as usize;
+    let (scaled_width, scaled_height) = if width >= height {
+        (target, (height * target / width).max(1))
+    } else {
+        ((width * target / height).max(1), target)
+    };
+
+    let surface = pixbuf
+        .scale_simple(
+            scaled_width as i32,
+            scaled_height as i32,
+            gdk_pixbuf::InterpType::Bilinear,
+        )
+        .and_then(|pixbuf| pixbuf.create_surface(scale, image.window().as_ref()));
+    image.set_from_surface(surface.as_ref());
+}
+
 /// Create the info box for the plugin
 fn create_info_box(info: &PluginInfo, hide_icons: bool) -> gtk::Box {
     let info_box = gtk::Box::builder()
@@ -903,25 +2809,200 @@ fn create_info_box(info: &PluginInfo, hide_icons: bool) -> gtk::Box {
 
 /// Refresh the matches from the plugins
 fn refresh_matches(input: String, runtime_data: Rc<RefCell<RuntimeData>>) {
+    runtime_data.borrow_mut().input = input.clone();
+
+    // Show the past selections from each plugin instead of asking the plugins
+    if runtime_data.borrow().history_mode {
+        let runtime_data = runtime_data.borrow();
+        for plugin_view in runtime_data.plugins.iter() {
+            let matches = match &runtime_data.history {
+                Some(history) => history.search(&plugin_view.plugin.info().name, &input),
+                None => RVec::new(),
+            };
+            handle_matches(plugin_view.clone(), &runtime_data, matches);
+        }
+        return;
+    }
+
+    // Only ask the plugin the input is meant for, once its prefix tells it apart from the others.
+    // The rewrites for all plugins may have made the prefix of one out of a shorthand.
+    let input = runtime_data.borrow().preprocessors.global(&input);
+    let routed = if runtime_data.borrow().exclusive.is_some() {
+        None
+    } else {
+        route(&input, &runtime_data.borrow().plugins)
+    };
+
     for plugin_view in runtime_data.borrow().plugins.iter() {
-        let id = plugin_view.plugin.get_matches()(input.clone().into());
-        let plugin_view = plugin_view.clone();
-        let runtime_data_clone = runtime_data.clone();
-        // If a plugin has requested exclusivity, respect it
-        if let Some(exclusive) = &runtime_data.borrow().exclusive {
-            if plugin_view.plugin.info() == exclusive.plugin.info() {
-                glib::timeout_add_local(Duration::from_micros(1000), move || {
-                    async_match(plugin_view.clone(), runtime_data_clone.clone(), id)
-                });
-            } else {
+        match &routed {
+            Some(routed) if plugin_view.plugin != routed.plugin => {
+                if let Some(id) = plugin_view.query.take() {
+                    plugin_view.plugin.cancel(id);
+                }
                 handle_matches(plugin_view.clone(), &runtime_data.borrow(), RVec::new());
             }
-        } else {
+            _ => query_plugin(plugin_view, input.clone(), &runtime_data),
+        }
+    }
+
+    // Show where the input goes right away, without waiting for the matches
+    if let Some(routed) = routed {
+        routed.row.show();
+    }
+}
+
+/// The plugin the input can only be meant for, see [`route::route`]
+fn route(input: &str, plugins: &[PluginView]) -> Option<PluginView> {
+    let prefixes = plugins
+        .iter()
+        .map(|plugin_view| plugin_view.plugin.prefix())
+        .collect::<Vec<_>>();
+    route::route(input, &prefixes).map(|index| plugins[index].clone())
+}
+
+/// Dispatch the input to the plugin and show its matches once they arrive
+fn query_plugin(plugin_view: &PluginView, input: String, runtime_data: &Rc<RefCell<RuntimeData>>) {
+    // Don't let the plugin keep working on matches for the previous input
+    if let Some(id) = plugin_view.query.take() {
+        plugin_view.plugin.cancel(id);
+    }
+    plugin_view.shown.set(None);
+    plugin_view.refreshing.set(false);
+
+    let input = runtime_data
+        .borrow()
+        .preprocessors
+        .plugin(plugin_view.plugin.info().name.as_str(), &input);
+
+    // Let the plugin reject or rewrite the input before it is dispatched to it
+    let input = match plugin_view.plugin.validate_input(input.as_str().into()) {
+        ValidateResult::Accept => input,
+        ValidateResult::Transform(input) => input.into(),
+        ValidateResult::Reject => {
+            handle_matches(plugin_view.clone(), &runtime_data.borrow(), RVec::new());
+            return;
+        }
+    };
+    let id = plugin_view.plugin.get_matches(input.into());
+    plugin_view.query.set(Some(id));
+    let start = Instant::now();
+    let plugin_view = plugin_view.clone();
+    let runtime_data_clone = runtime_data.clone();
+    // The partial results of this query, the ones of older queries are never mixed in
+    let mut streamed = Vec::new();
+    // If a plugin has requested exclusivity, respect it
+    if let Some(exclusive) = &runtime_data.borrow().exclusive {
+        if plugin_view.plugin == exclusive.plugin {
             glib::timeout_add_local(Duration::from_micros(1000), move || {
-                async_match(plugin_view.clone(), runtime_data_clone.clone(), id)
+                async_match(
+                    plugin_view.clone(),
+                    runtime_data_clone.clone(),
+                    id,
+                    start,
+                    &mut streamed,
+                )
             });
+        } else {
+            plugin_view.query.set(None);
+            plugin_view.plugin.cancel(id);
+            handle_matches(plugin_view.clone(), &runtime_data.borrow(), RVec::new());
         }
+    } else {
+        glib::timeout_add_local(Duration::from_micros(1000), move || {
+            async_match(
+                plugin_view.clone(),
+                runtime_data_clone.clone(),
+                id,
+                start,
+                &mut streamed,
+            )
+        });
+    }
+}
+
+/// Ask the plugin again for the inputs its matches were pinned for, unpinning the ones it no longer
+/// has. The queries of the user go first, and since the plugin works on one query at a time, the
+/// first one dispatched meanwhile stops the check with the rest of the pins kept as they are.
+fn check_pins(plugin_view: PluginView, runtime_data: Rc<RefCell<RuntimeData>>) {
+    let name = plugin_view.plugin.info().name.to_string();
+    let mut inputs = runtime_data.borrow().pins.inputs(&name);
+    if inputs.is_empty() {
+        return;
     }
+
+    let mut current: Option<(u64, String, Instant)> = None;
+    let mut streamed = Vec::new();
+    glib::timeout_add_local(Duration::from_millis(10), move || {
+        let (id, input, start) = match &current {
+            Some(current) => current.clone(),
+            None => {
+                // Wait for the plugin to be done with the query of the user
+                if plugin_view.query.get().is_some() {
+                    return glib::Continue(true);
+                }
+                let input = match inputs.pop() {
+                    Some(input) => input,
+                    None => {
+                        // Show the pins as checked if nothing was typed yet
+                        let runtime_data_ref = runtime_data.borrow();
+                        if runtime_data_ref.input.is_empty() && !runtime_data_ref.history_mode {
+                            if runtime_data_ref.config.show_results_immediately {
+                                let input = runtime_data_ref.preprocessors.global("");
+                                drop(runtime_data_ref);
+                                query_plugin(&plugin_view, input, &runtime_data);
+                            } else {
+                                handle_matches(plugin_view.clone(), &runtime_data_ref, RVec::new());
+                            }
+                        }
+                        return glib::Continue(false);
+                    }
+                };
+
+                let runtime_data = runtime_data.borrow();
+                let query = runtime_data.preprocessors.global(&input);
+                let query = runtime_data.preprocessors.plugin(&name, &query);
+                let query = match plugin_view.plugin.validate_input(query.clone().into()) {
+                    ValidateResult::Accept => query,
+                    ValidateResult::Transform(query) => query.into(),
+                    // Nothing to check the pins against
+                    ValidateResult::Reject => return glib::Continue(true),
+                };
+                let id = plugin_view.plugin.get_matches(query.into());
+                let start = Instant::now();
+                current = Some((id, input.clone(), start));
+                (id, input, start)
+            }
+        };
+
+        match plugin_view.plugin.poll_matches(id) {
+            PollResult::Ready(matches) => {
+                streamed.extend(matches);
+                runtime_data
+                    .borrow_mut()
+                    .pins
+                    .confirm(&name, &input, &mem::take(&mut streamed));
+                current = None;
+                glib::Continue(true)
+            }
+            PollResult::Partial(matches) => {
+                streamed.extend(matches);
+                glib::Continue(true)
+            }
+            PollResult::Pending => {
+                // Keep the pins the plugin takes too long for
+                let timeout = runtime_data.borrow().config.plugin_timeout;
+                if let Some(timeout) = timeout {
+                    if start.elapsed() >= Duration::from_millis(timeout) {
+                        plugin_view.plugin.cancel(id);
+                        streamed.clear();
+                        current = None;
+                    }
+                }
+                glib::Continue(true)
+            }
+            PollResult::Cancelled => glib::Continue(false),
+        }
+    });
 }
 
 /// Handle the asynchronously running match task
@@ -929,13 +3010,139 @@ fn async_match(
     plugin_view: PluginView,
     runtime_data: Rc<RefCell<RuntimeData>>,
     id: u64,
+    start: Instant,
+    streamed: &mut Vec<Match>,
 ) -> glib::Continue {
-    match plugin_view.plugin.poll_matches()(id) {
+    // A newer query was dispatched since
+    if plugin_view.query.get() != Some(id) {
+        return glib::Continue(false);
+    }
+
+    match plugin_view.plugin.poll_matches(id) {
         PollResult::Ready(matches) => {
-            handle_matches(plugin_view, &runtime_data.borrow(), matches);
+            plugin_view.query.set(None);
+            if let Some(profile) = &plugin_view.profile {
+                profile.borrow_mut().queries.push(start.elapsed());
+            }
+            if trace::enabled() {
+                trace::record(
+                    &plugin_view.plugin.info().name,
+                    "query",
+                    start,
+                    start.elapsed(),
+                );
+            }
+            streamed.extend(matches);
+            handle_matches(
+                plugin_view.clone(),
+                &runtime_data.borrow(),
+                mem::take(streamed).into(),
+            );
+            plugin_view.shown.set(Some(id));
+            if let Some(interval) = plugin_view.plugin.refresh_interval(id) {
+                schedule_refresh(&plugin_view, &runtime_data, id, interval);
+            }
+
+            // A stopped helper answers every query with its error row, only stopping counts
+            if !plugin_view.plugin.failed() {
+                if let Some(failures) = runtime_data.borrow_mut().failures.as_mut() {
+                    failures.clear(&plugin_view.file);
+                }
+            } else if !plugin_view.failed.replace(true) {
+                record_failure(&plugin_view, &runtime_data);
+            }
+            glib::Continue(false)
+        }
+        // Show the matches found so far, and keep polling for the rest
+        PollResult::Partial(matches) => {
+            streamed.extend(matches);
+            handle_matches(plugin_view, &runtime_data.borrow(), streamed.clone().into());
+            glib::Continue(true)
+        }
+        PollResult::Pending => {
+            // Give up on the plugin if it is taking too long, keeping only what it streamed in time
+            let timeout = runtime_data.borrow().config.plugin_timeout;
+            if let Some(timeout) = timeout {
+                if start.elapsed() >= Duration::from_millis(timeout) {
+                    plugin_view.query.set(None);
+                    plugin_view.plugin.cancel(id);
+                    handle_matches(
+                        plugin_view.clone(),
+                        &runtime_data.borrow(),
+                        mem::take(streamed).into(),
+                    );
+                    record_failure(&plugin_view, &runtime_data);
+                    return glib::Continue(false);
+                }
+            }
+
+            let runtime_data = runtime_data.borrow();
+
+            // Let the user know that a slow plugin is still working
+            if runtime_data.config.pending_indicator
+                && !plugin_view.spinner.is_visible()
+                && start.elapsed() >= PENDING_INDICATOR_DELAY
+            {
+                plugin_view.row.show_all();
+                plugin_view.spinner.show();
+                plugin_view.spinner.start();
+            }
+
+            glib::Continue(true)
+        }
+        PollResult::Cancelled => {
+            plugin_view.query.set(None);
             glib::Continue(false)
         }
-        PollResult::Pending => glib::Continue(true),
-        PollResult::Cancelled => glib::Continue(false),
+    }
+}
+
+/// Query the plugin again after the interval it asked for, if its matches for the same input are
+/// still shown by then
+fn schedule_refresh(
+    plugin_view: &PluginView,
+    runtime_data: &Rc<RefCell<RuntimeData>>,
+    id: u64,
+    interval: Duration,
+) {
+    let plugin_view = plugin_view.clone();
+    let runtime_data = runtime_data.clone();
+    let input = runtime_data.borrow().input.clone();
+    glib::timeout_add_local_once(interval, move || {
+        if plugin_view.shown.get() != Some(id)
+            || plugin_view.query.get().is_some()
+            || runtime_data.borrow().input != input
+            || runtime_data.borrow().history_mode
+        {
+            return;
+        }
+
+        let input = runtime_data.borrow().preprocessors.global(&input);
+        query_plugin(&plugin_view, input, &runtime_data);
+        plugin_view.refreshing.set(true);
+    });
+}
+
+/// Count a failure of the plugin, telling the user once it has used up `failure_budget`
+fn record_failure(plugin_view: &PluginView, runtime_data: &Rc<RefCell<RuntimeData>>) {
+    let mut runtime_data = runtime_data.borrow_mut();
+    let budget = match runtime_data.config.failure_budget {
+        Some(budget) => budget,
+        None => return,
+    };
+    let count = match runtime_data.failures.as_mut() {
+        Some(failures) => failures.record(&plugin_view.file),
+        None => return,
+    };
+
+    if count == budget {
+        let notice = format!(
+            "{} failed {} times in a row and is disabled from the next start on, `anyrun plugin reset {}` enables it again",
+            plugin_view.plugin.info().name,
+            count,
+            plugin_view.file
+        );
+        warn!("{}", notice);
+        add_error(plugin_view, notice);
     }
 }
@@ -0,0 +1,295 @@
+//! The `anyrun plugin` subcommands, for finding the installed plugins and toggling them in the
+//! config without editing the paths by hand.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyrun_interface::PluginInfo;
+use clap::Subcommand;
+use tracing::error;
+
+use crate::{failures::Failures, Config};
+
+#[derive(Subcommand)]
+pub enum PluginCommand {
+    /// List the plugins found in the plugin directories
+    List,
+    /// Show the details of a plugin
+    Info { name: String },
+    /// Add a plugin to the plugins of the user config
+    Enable { name: String },
+    /// Remove a plugin from the plugins of the user config
+    Disable { name: String },
+    /// Enable a plugin again that used up its `failure_budget`
+    Reset { name: String },
+}
+
+/// A plugin library found in one of the plugin directories
+pub struct Installed {
+    pub path: PathBuf,
+    pub info: PluginInfo,
+}
+
+impl Installed {
+    /// The file name of the plugin, which its failures are counted by
+    fn file(&self) -> String {
+        self.path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default()
+    }
+
+    /// Whether `name` refers to this plugin, by the name in its info or by its file name
+    fn is(&self, name: &str) -> bool {
+        let stem = self
+            .path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().to_string())
+            .unwrap_or_default();
+
+        self.info.name.eq_ignore_ascii_case(name)
+            || stem == name
+            || stem.strip_prefix("lib") == Some(name)
+    }
+
+    /// How the plugin is referred to in the config. The plugins in the plugin directories are
+    /// referred to by their file name, like in the default config.
+    pub fn entry(&self, search_paths: &[PathBuf]) -> PathBuf {
+        search_paths
+            .iter()
+            .find_map(|dir| self.path.strip_prefix(dir).ok())
+            .unwrap_or(&self.path)
+            .to_path_buf()
+    }
+}
+
+pub fn run(command: PluginCommand, config: &Config, config_dir: &str) -> bool {
+    let search_paths = crate::plugin_search_paths(config_dir, &config.plugin_paths);
+    let installed = find_installed(&search_paths);
+    let enabled = config
+        .plugins
+        .iter()
+        .filter_map(|plugin_path| crate::resolve_plugin(plugin_path, &search_paths))
+        .collect::<Vec<_>>();
+
+    let find = |name: &str| {
+        let plugin = installed.iter().find(|plugin| plugin.is(name));
+        if plugin.is_none() {
+            error!("No plugin named {} was found", name);
+        }
+        plugin
+    };
+
+    match command {
+        PluginCommand::List => {
+            for plugin in &installed {
+                println!(
+                    "{:<20} {:<8} {}",
+                    plugin.info.name,
+                    if enabled.contains(&plugin.path) {
+                        "enabled"
+                    } else {
+                        ""
+                    },
+                    plugin.path.display()
+                );
+            }
+            true
+        }
+        PluginCommand::Info { name } => match find(&name) {
+            Some(plugin) => {
+                println!("Name:    {}", plugin.info.name);
+                println!("Icon:    {}", plugin.info.icon);
+                println!("Path:    {}", plugin.path.display());
+                println!("Enabled: {}", enabled.contains(&plugin.path));
+                println!(
+                    "Failed:  {} times in a row",
+                    Failures::load(&crate::state_dir()).count(&plugin.file())
+                );
+                true
+            }
+            None => false,
+        },
+        PluginCommand::Enable { name } => match find(&name) {
+            Some(plugin) if enabled.contains(&plugin.path) => {
+                println!("{} is already enabled", plugin.info.name);
+                true
+            }
+            Some(plugin) => {
+                let mut plugins = config.plugins.clone();
+                plugins.push(plugin.entry(&search_paths));
+                write_plugins(config_dir, &plugins)
+            }
+            None => false,
+        },
+        PluginCommand::Disable { name } => match find(&name) {
+            Some(plugin) => {
+                let plugins = config
+                    .plugins
+                    .iter()
+                    .filter(|plugin_path| {
+                        crate::resolve_plugin(plugin_path, &search_paths).as_ref()
+                            != Some(&plugin.path)
+                    })
+                    .cloned()
+                    .collect::<Vec<_>>();
+
+                if plugins.len() == config.plugins.len() {
+                    println!("{} is not enabled", plugin.info.name);
+                    true
+                } else {
+                    write_plugins(config_dir, &plugins)
+                }
+            }
+            None => false,
+        },
+        PluginCommand::Reset { name } => match find(&name) {
+            Some(plugin) => {
+                Failures::load(&crate::state_dir()).clear(&plugin.file());
+                println!("Reset the failures of {}", plugin.info.name);
+                true
+            }
+            None => false,
+        },
+    }
+}
+
+/// Load the info of every plugin library in the plugin directories, without initializing them
+pub fn find_installed(search_paths: &[PathBuf]) -> Vec<Installed> {
+    let mut installed: Vec<Installed> = Vec::new();
+
+    for dir in search_paths {
+        let mut paths = match fs::read_dir(dir) {
+            Ok(entries) => entries
+                .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                .filter(|path| path.extension().is_some_and(|ext| ext == "so"))
+                .collect::<Vec<_>>(),
+            Err(_) => continue,
+        };
+        paths.sort();
+
+        for path in paths {
+            // The same plugin in a directory of lower priority is shadowed
+            if installed
+                .iter()
+                .any(|plugin| plugin.path.file_name() == path.file_name())
+            {
+                continue;
+            }
+
+            match anyrun_interface::load(&path) {
+                Ok(plugin) => installed.push(Installed {
+                    info: plugin.info()(),
+                    path,
+                }),
+                Err(why) => error!("Failed to load plugin {}: {}", path.display(), why),
+            }
+        }
+    }
+
+    // The plugins built into anyrun, unless a library shadows them
+    for (name, root_module) in crate::embedded::all() {
+        let path = crate::embedded::path(name);
+        if !installed
+            .iter()
+            .any(|plugin| plugin.path.file_name() == path.file_name())
+        {
+            installed.push(Installed {
+                info: root_module().info()(),
+                path,
+            });
+        }
+    }
+
+    installed
+}
+
+/// Replace the plugins in the config file of `config_dir`, keeping the rest of the file intact
+fn write_plugins(config_dir: &str, plugins: &[PathBuf]) -> bool {
+    let path = Path::new(config_dir).join("config.ron");
+    let content = fs::read_to_string(&path).unwrap_or_else(|_| "(\n)\n".to_string());
+
+    let list = plugins
+        .iter()
+        .map(|plugin| format!("    {:?},\n", plugin.display().to_string()))
+        .collect::<String>();
+
+    let content = match find_plugins_list(&content) {
+        Some((start, end)) => format!("{}[\n{}  ]{}", &content[..start], list, &content[end..]),
+        None => match find_config_start(&content) {
+            Some(index) => format!(
+                "{}\n  plugins: [\n{}  ],{}",
+                &content[..index],
+                list,
+                &content[index..]
+            ),
+            None => {
+                error!("Failed to find the config in {}", path.display());
+                return false;
+            }
+        },
+    };
+
+    if let Err(why) = fs::create_dir_all(config_dir).and_then(|_| fs::write(&path, content)) {
+        error!("Failed to write {}: {}", path.display(), why);
+        return false;
+    }
+
+    println!("Updated the plugins in {}", path.display());
+    true
+}
+
+/// The byte range of the list of the `plugins` option, brackets included
+fn find_plugins_list(content: &str) -> Option<(usize, usize)> {
+    let mut offset = 0;
+
+    for line in content.split_inclusive('\n') {
+        let trimmed = line.trim_start();
+
+        if let Some(rest) = trimmed.strip_prefix("plugins") {
+            if rest.trim_start().starts_with(':') {
+                let start = offset + line.find('[')?;
+                let mut in_string = false;
+                let mut escaped = false;
+
+                for (index, c) in content[start..].char_indices() {
+                    match c {
+                        _ if escaped => escaped = false,
+                        '\\' if in_string => escaped = true,
+                        '"' => in_string = !in_string,
+                        ']' if !in_string => return Some((start, start + index + 1)),
+                        _ => (),
+                    }
+                }
+
+                return None;
+            }
+        }
+
+        offset += line.len();
+    }
+
+    None
+}
+
+/// The index right after the opening parenthesis of the config struct
+fn find_config_start(content: &str) -> Option<usize> {
+    let mut offset = 0;
+
+    for line in content.split_inclusive('\n') {
+        let trimmed = line.trim_start();
+
+        // Skip the comments and extension attributes before the struct
+        if !trimmed.starts_with("//") && !trimmed.starts_with("#!") {
+            if let Some(index) = line.find('(') {
+                return Some(offset + index + 1);
+            }
+        }
+
+        offset += line.len();
+    }
+
+    None
+}
@@ -0,0 +1,122 @@
+//! The matches pinned with Ctrl+P. They are shown on an empty query, and put above the other
+//! matches of their plugin whenever the plugin has them for the input. The input a match was
+//! pinned for is kept with it, to ask the plugin again on startup whether it still has the match,
+//! and for the match itself once it is selected.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyrun_interface::Match;
+
+use crate::history::Entry;
+
+pub struct Pins {
+    /// In the order they were pinned in. The input is None for the pins made before it was kept,
+    /// which are never checked.
+    entries: Vec<Entry>,
+    path: PathBuf,
+}
+
+impl Pins {
+    pub fn load(state_dir: &Path) -> Self {
+        let path = state_dir.join("pins");
+        let entries = fs::read_to_string(&path)
+            .map(|content| {
+                content
+                    .lines()
+                    .filter_map(|line| serde_json::from_str(line).ok())
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        Self { entries, path }
+    }
+
+    fn save(&self) {
+        let content = self
+            .entries
+            .iter()
+            .filter_map(|pin| serde_json::to_string(pin).ok())
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        if let Err(why) = self
+            .path
+            .parent()
+            .map_or(Ok(()), fs::create_dir_all)
+            .and_then(|_| fs::write(&self.path, content))
+        {
+            tracing::warn!("Failed to save the pinned matches: {}", why);
+        }
+    }
+
+    /// Pin the match, or unpin it if it was pinned already
+    pub fn toggle(&mut self, plugin: &str, selection: &Match, input: Option<&str>) {
+        let entry = Entry::new(plugin, selection, input);
+        let count = self.entries.len();
+        self.entries.retain(|old| !old.same(&entry));
+        if self.entries.len() == count {
+            self.entries.push(entry);
+        }
+        self.save();
+    }
+
+    pub fn is_pinned(&self, plugin: &str, _match: &Match) -> bool {
+        let entry = Entry::new(plugin, _match, None);
+        self.entries.iter().any(|pinned| pinned.same(&entry))
+    }
+
+    /// The pins of the plugin
+    pub fn entries<'a>(&'a self, plugin: &'a str) -> impl Iterator<Item = &'a Entry> {
+        self.entries.iter().filter(move |pin| pin.plugin == plugin)
+    }
+
+    /// Mark the pin as no longer had by its plugin
+    pub fn mark_stale(&mut self, entry: &Entry) {
+        for pin in self.entries.iter_mut().filter(|pin| pin.same(entry)) {
+            pin.stale = true;
+        }
+    }
+
+    /// The inputs the matches of the plugin were pinned for, each of them once
+    pub fn inputs(&self, plugin: &str) -> Vec<String> {
+        let mut inputs = Vec::new();
+        for pin in self.entries(plugin) {
+            if let Some(input) = &pin.input {
+                if !inputs.contains(input) {
+                    inputs.push(input.clone());
+                }
+            }
+        }
+        inputs
+    }
+
+    /// Check the pins of the plugin for the input against what it has for the input now. The pins
+    /// it still has are updated with its icon and id, the others are unpinned.
+    pub fn confirm(&mut self, plugin: &str, input: &str, matches: &[Match]) {
+        let mut changed = false;
+        self.entries.retain_mut(|pin| {
+            if pin.plugin != plugin || pin.input.as_deref() != Some(input) {
+                return true;
+            }
+            let fresh = matches
+                .iter()
+                .map(|_match| Entry::new(plugin, _match, Some(input)))
+                .find(|entry| entry.same(pin));
+            changed = true;
+            match fresh {
+                Some(entry) => {
+                    *pin = entry;
+                    true
+                }
+                None => false,
+            }
+        });
+
+        if changed {
+            self.save();
+        }
+    }
+}
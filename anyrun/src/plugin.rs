@@ -0,0 +1,202 @@
+//! A plugin loaded into the launcher or running in a helper process, behind the same calls.
+
+use std::{
+    path::Path,
+    rc::Rc,
+    time::{Duration, Instant},
+};
+
+use abi_stable::std_types::RString;
+use anyrun_interface::{
+    HandleResult, Match, PluginInfo, PluginRef, PollResult, Preview, QueryContext, ValidateResult,
+};
+use tracing::Level;
+
+use crate::{embedded, isolation::Helper, trace, PluginEnvironment};
+
+/// The options for loading the plugins, the same for all of them
+pub struct LoadOptions<'a> {
+    pub config_dir: &'a str,
+    /// Run the plugins in helper processes
+    pub isolate: bool,
+    /// Passed on to the helpers
+    pub log_level: Level,
+    pub journald: bool,
+}
+
+#[derive(Clone)]
+pub enum Plugin {
+    Library(PluginRef),
+    Isolated(Rc<Helper>),
+}
+
+impl Plugin {
+    /// Load and initialize the plugin at `path`, with the environment configured for it. Fails
+    /// with why it couldn't be loaded, for showing it to the user. A plugin with an environment
+    /// always runs in a helper, as the environment and the working directory of anyrun are shared
+    /// by all of its threads.
+    pub fn load(
+        path: &Path,
+        options: &LoadOptions,
+        environment: Option<&PluginEnvironment>,
+    ) -> Result<Self, String> {
+        let start = Instant::now();
+
+        // The embedded plugins share the statics of anyrun-plugin, so they can't be loaded together
+        if options.isolate || environment.is_some() || embedded::find(path).is_some() {
+            let plugin = Self::Isolated(Rc::new(
+                Helper::spawn(path, options, environment).map_err(|why| why.to_string())?,
+            ));
+            // Starting the helper includes the init of the plugin
+            if trace::enabled() {
+                trace::record(&plugin.info().name, "init", start, start.elapsed());
+            }
+            return Ok(plugin);
+        }
+
+        let plugin = anyrun_interface::load(path).map_err(|why| why.to_string())?;
+
+        plugin.set_log_sink()(crate::LOG_SINK);
+        plugin.set_recency_store()(crate::recent::STORE);
+
+        // Run the plugin's init code to init static resources etc.
+        plugin.init()(options.config_dir.into());
+
+        if trace::enabled() {
+            trace::record(&plugin.info()().name, "init", start, start.elapsed());
+        }
+
+        Ok(Self::Library(plugin))
+    }
+
+    /// Make a call into the plugin, recording it in the trace if `--trace-plugins` is set
+    fn traced<T>(&self, call: &str, f: impl FnOnce() -> T) -> T {
+        if !trace::enabled() {
+            return f();
+        }
+
+        let start = Instant::now();
+        let result = f();
+        trace::record(&self.info().name, call, start, start.elapsed());
+        result
+    }
+
+    pub fn info(&self) -> PluginInfo {
+        match self {
+            Self::Library(plugin) => plugin.info()(),
+            Self::Isolated(helper) => helper.info(),
+        }
+    }
+
+    pub fn get_matches(&self, input: RString) -> u64 {
+        self.traced("get_matches", || match self {
+            Self::Library(plugin) => plugin.get_matches()(input),
+            Self::Isolated(helper) => helper.get_matches(&input),
+        })
+    }
+
+    /// Tell the plugin where anyrun was opened, for the queries that follow
+    pub fn set_context(&self, context: &QueryContext) {
+        match self {
+            Self::Library(plugin) => plugin.set_context()(context.clone()),
+            Self::Isolated(helper) => helper.set_context(context),
+        }
+    }
+
+    pub fn poll_matches(&self, id: u64) -> PollResult {
+        match self {
+            Self::Library(plugin) => plugin.poll_matches()(id),
+            Self::Isolated(helper) => helper.poll_matches(id),
+        }
+    }
+
+    /// How long after the matches of the query are ready the plugin wants them to be gotten again
+    pub fn refresh_interval(&self, id: u64) -> Option<Duration> {
+        match self {
+            Self::Library(plugin) => plugin.refresh_interval()(id)
+                .into_option()
+                .map(Duration::from_millis),
+            Self::Isolated(helper) => helper.refresh_interval(id),
+        }
+    }
+
+    /// The prefix the input needs for the plugin to have matches, if it has one
+    pub fn prefix(&self) -> Option<String> {
+        match self {
+            Self::Library(plugin) => plugin.prefix()().into_option().map(String::from),
+            Self::Isolated(helper) => helper.prefix(),
+        }
+    }
+
+    /// The errors the plugin reported since the last time, for showing them to the user
+    pub fn errors(&self) -> Vec<String> {
+        match self {
+            Self::Library(plugin) => plugin.errors()().into_iter().map(String::from).collect(),
+            Self::Isolated(helper) => helper.errors(),
+        }
+    }
+
+    /// Whether the helper of an isolated plugin stopped working. A plugin loaded into anyrun takes
+    /// anyrun down with it instead.
+    pub fn failed(&self) -> bool {
+        match self {
+            Self::Library(_) => false,
+            Self::Isolated(helper) => helper.failed(),
+        }
+    }
+
+    /// Tell the plugin that the matches of the query are no longer needed
+    pub fn cancel(&self, id: u64) {
+        self.traced("cancel", || match self {
+            Self::Library(plugin) => plugin.cancel()(id),
+            Self::Isolated(helper) => helper.cancel(id),
+        })
+    }
+
+    pub fn handle_selection(&self, selection: Match, input: RString) -> HandleResult {
+        self.traced("handler", || match self {
+            Self::Library(plugin) => plugin.handle_selection()(selection, input),
+            Self::Isolated(helper) => helper.handle_selection(selection, &input),
+        })
+    }
+
+    /// The preview of the selected match, if the plugin has one for it
+    pub fn preview(&self, selection: Match) -> Option<Preview> {
+        self.traced("preview", || match self {
+            Self::Library(plugin) => plugin.get_preview()(selection).into_option(),
+            Self::Isolated(helper) => helper.preview(selection),
+        })
+    }
+
+    pub fn validate_input(&self, input: RString) -> ValidateResult {
+        self.traced("validate_input", || match self {
+            Self::Library(plugin) => plugin.validate_input()(input),
+            Self::Isolated(helper) => helper.validate_input(&input),
+        })
+    }
+
+    /// Let the plugin save what it needs to before it is unloaded, no calls may follow
+    pub fn deinit(&self) {
+        self.traced("deinit", || match self {
+            Self::Library(plugin) => plugin.deinit()(),
+            Self::Isolated(helper) => helper.deinit(),
+        })
+    }
+
+    /// Stop the helper process of an isolated plugin
+    pub fn stop(&self) {
+        if let Self::Isolated(helper) = self {
+            helper.stop();
+        }
+    }
+}
+
+impl PartialEq for Plugin {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::Library(a), Self::Library(b)) => std::ptr::fn_addr_eq(a.info(), b.info()),
+            (Self::Isolated(a), Self::Isolated(b)) => Rc::ptr_eq(a, b),
+            _ => false,
+        }
+    }
+}
@@ -0,0 +1,206 @@
+//! Rewriting the input before it is dispatched to the plugins, configured with `preprocessors`.
+//! The rewrites for all plugins are done before the input is routed by the prefixes, so they can
+//! expand a shorthand into the prefix of a plugin. The ones for a single plugin are done after.
+
+use regex::Regex;
+use serde::Deserialize;
+use tracing::warn;
+
+#[derive(Deserialize, Clone)]
+pub struct Preprocessor {
+    pub rewrite: Rewrite,
+    /// Only rewrite the input for the plugin with this name, instead of for all of them
+    #[serde(default)]
+    pub plugin: Option<String>,
+}
+
+/// For all plugins, `lowercase`, `strip-emoji`, `plain-punctuation` or `<pattern>=<with>`
+impl From<&str> for Preprocessor {
+    fn from(value: &str) -> Self {
+        let rewrite = match value {
+            "lowercase" => Rewrite::Lowercase,
+            "strip-emoji" => Rewrite::StripEmoji,
+            "plain-punctuation" => Rewrite::PlainPunctuation,
+            _ => {
+                let (pattern, with) = value.split_once('=').expect("Invalid Preprocessor value");
+                Rewrite::Replace {
+                    pattern: pattern.to_string(),
+                    with: with.to_string(),
+                }
+            }
+        };
+
+        Self {
+            rewrite,
+            plugin: None,
+        }
+    }
+}
+
+#[derive(Deserialize, Clone)]
+pub enum Rewrite {
+    /// Replace every match of the regex, `$1` and `$name` in `with` refer to its groups
+    Replace {
+        pattern: String,
+        with: String,
+    },
+    Lowercase,
+    StripEmoji,
+    /// Turn typographic quotes and dashes, like the ones pasted from documents, into plain ones
+    PlainPunctuation,
+}
+
+enum Step {
+    Replace(Regex, String),
+    Lowercase,
+    StripEmoji,
+    PlainPunctuation,
+}
+
+impl Step {
+    fn apply(&self, input: &str) -> String {
+        match self {
+            Self::Replace(regex, with) => regex.replace_all(input, with.as_str()).into_owned(),
+            Self::Lowercase => input.to_lowercase(),
+            Self::StripEmoji => input.chars().filter(|chr| !is_emoji(*chr)).collect(),
+            Self::PlainPunctuation => input
+                .chars()
+                .map(|chr| match chr {
+                    '‘' | '’' | '‚' | '‛' | '′' => '\'',
+                    '“' | '”' | '„' | '‟' | '″' | '«' | '»' => '"',
+                    '‐' | '‑' | '‒' | '–' | '—' | '−' => '-',
+                    '\u{a0}' | '\u{2007}' | '\u{202f}' => ' ',
+                    chr => chr,
+                })
+                .collect(),
+        }
+    }
+}
+
+/// The configured rewrites, in the order they are done in
+pub struct Preprocessors {
+    steps: Vec<(Option<String>, Step)>,
+}
+
+impl Preprocessors {
+    pub fn new(preprocessors: &[Preprocessor]) -> Self {
+        Self {
+            steps: preprocessors
+                .iter()
+                .filter_map(|preprocessor| {
+                    let step = match &preprocessor.rewrite {
+                        Rewrite::Replace { pattern, with } => Regex::new(pattern)
+                            .map_err(|why| warn!("Invalid preprocessor pattern: {}", why))
+                            .ok()
+                            .map(|regex| Step::Replace(regex, with.clone()))?,
+                        Rewrite::Lowercase => Step::Lowercase,
+                        Rewrite::StripEmoji => Step::StripEmoji,
+                        Rewrite::PlainPunctuation => Step::PlainPunctuation,
+                    };
+                    Some((preprocessor.plugin.clone(), step))
+                })
+                .collect(),
+        }
+    }
+
+    /// Do the rewrites for all plugins
+    pub fn global(&self, input: &str) -> String {
+        self.steps
+            .iter()
+            .filter(|(plugin, _)| plugin.is_none())
+            .fold(input.to_string(), |input, (_, step)| step.apply(&input))
+    }
+
+    /// Do the rewrites for the plugin with the name, on input that went through `global` already
+    pub fn plugin(&self, name: &str, input: &str) -> String {
+        self.steps
+            .iter()
+            .filter(|(plugin, _)| plugin.as_deref() == Some(name))
+            .fold(input.to_string(), |input, (_, step)| step.apply(&input))
+    }
+
+    /// Do all the rewrites of the input for the plugin with the name
+    pub fn apply(&self, name: &str, input: &str) -> String {
+        self.plugin(name, &self.global(input))
+    }
+}
+
+/// Whether the character is a part of an emoji, including the joiners and modifiers of them
+fn is_emoji(chr: char) -> bool {
+    matches!(
+        chr as u32,
+        0x1f000..=0x1faff // Pictographs, emoticons, flags and skin tones
+            | 0x2600..=0x27bf // Miscellaneous symbols and dingbats
+            | 0x2b00..=0x2bff // Arrows, stars and squares
+            | 0xfe0e..=0xfe0f // Variation selectors
+            | 0x200d // Zero width joiner
+            | 0x20e3 // Combining keycap
+            | 0xe0020..=0xe007f // Tags of subdivision flags
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn preprocessors(preprocessors: &[(&str, Option<&str>)]) -> Preprocessors {
+        Preprocessors::new(
+            &preprocessors
+                .iter()
+                .map(|(value, plugin)| Preprocessor {
+                    plugin: plugin.map(str::to_string),
+                    ..Preprocessor::from(*value)
+                })
+                .collect::<Vec<_>>(),
+        )
+    }
+
+    #[test]
+    fn parses_the_rewrites() {
+        assert!(matches!(
+            Preprocessor::from("lowercase").rewrite,
+            Rewrite::Lowercase
+        ));
+        assert!(matches!(
+            Preprocessor::from("^ff =:web ").rewrite,
+            Rewrite::Replace { pattern, with } if pattern == "^ff " && with == ":web "
+        ));
+    }
+
+    #[test]
+    fn rewrites_in_order() {
+        let preprocessors = preprocessors(&[("lowercase", None), ("^ff =:web ", None)]);
+        assert_eq!(preprocessors.global("FF rust"), ":web rust");
+    }
+
+    #[test]
+    fn keeps_the_rewrites_of_a_plugin_to_it() {
+        let preprocessors =
+            preprocessors(&[("lowercase", None), ("(\\d+)k=${1}000", Some("Rink"))]);
+        assert_eq!(preprocessors.global("5K"), "5k");
+        assert_eq!(preprocessors.plugin("Rink", "5k"), "5000");
+        assert_eq!(preprocessors.plugin("Shell", "5k"), "5k");
+        assert_eq!(preprocessors.apply("Rink", "5K"), "5000");
+    }
+
+    #[test]
+    fn leaves_out_invalid_patterns() {
+        let preprocessors = preprocessors(&[("(=x", None), ("lowercase", None)]);
+        assert_eq!(preprocessors.global("(A"), "(a");
+    }
+
+    #[test]
+    fn strips_emoji() {
+        let preprocessors = preprocessors(&[("strip-emoji", None)]);
+        assert_eq!(preprocessors.global("hi 👋🏽! ❤️ 1️⃣"), "hi !  1");
+    }
+
+    #[test]
+    fn plains_the_punctuation() {
+        let preprocessors = preprocessors(&[("plain-punctuation", None)]);
+        assert_eq!(
+            preprocessors.global("“quoted” – it’s\u{a0}«here»"),
+            "\"quoted\" - it's \"here\""
+        );
+    }
+}
@@ -0,0 +1,120 @@
+//! The pane showing more about the selected match than fits in its row, for the plugins that have
+//! a preview for it.
+
+use std::{cell::RefCell, rc::Rc};
+
+use anyrun_interface::{Match, Preview};
+use gtk::{glib, prelude::*};
+
+use crate::{plugin::Plugin, style_names};
+
+/// How large the pictures are shown
+const IMAGE_SIZE: i32 = 256;
+
+#[derive(Clone)]
+pub struct PreviewPane {
+    /// Scrolls the previews that are longer than the pane can get, like man pages
+    pub window: gtk::ScrolledWindow,
+    content: gtk::Box,
+    /// The list the selected match of the preview is in
+    owner: Rc<RefCell<Option<gtk::ListBox>>>,
+}
+
+impl PreviewPane {
+    pub fn new(max_height: i32) -> Self {
+        let content = gtk::Box::builder()
+            .orientation(gtk::Orientation::Vertical)
+            .name(style_names::PREVIEW)
+            .build();
+        let window = gtk::ScrolledWindow::builder()
+            .hscrollbar_policy(gtk::PolicyType::Never)
+            .propagate_natural_height(true)
+            .max_content_height(max_height)
+            .hexpand(true)
+            .no_show_all(true)
+            .name(style_names::PREVIEW)
+            .build();
+        window.add(&content);
+
+        Self {
+            window,
+            content,
+            owner: Rc::new(RefCell::new(None)),
+        }
+    }
+
+    /// Show the preview of the match selected in the list of the plugin, and hide the pane once
+    /// the list has no selection anymore
+    pub fn follow(&self, list: &gtk::ListBox, plugin: Plugin) {
+        let pane = self.clone();
+        list.connect_row_selected(move |list, row| {
+            let selection = row.and_then(|row| unsafe {
+                row.data::<Match>("match")
+                    .map(|selection| (*selection.as_ptr()).clone())
+            });
+
+            match selection {
+                Some(selection) => {
+                    *pane.owner.borrow_mut() = Some(list.clone());
+                    pane.show(plugin.preview(selection));
+                }
+                // The selection may have moved to another list already
+                None if pane.owner.borrow().as_ref() == Some(list) => pane.show(None),
+                None => (),
+            }
+        });
+    }
+
+    /// Show the preview, or hide the pane without one
+    fn show(&self, preview: Option<Preview>) {
+        for child in self.content.children() {
+            self.content.remove(&child);
+        }
+
+        let preview = match preview {
+            Some(preview) => preview,
+            None => {
+                self.window.hide();
+                return;
+            }
+        };
+
+        let widget: gtk::Widget = match &preview {
+            Preview::Text(text, pango) => gtk::Label::builder()
+                .name(style_names::PREVIEW)
+                .wrap(true)
+                .xalign(0.0)
+                .use_markup(true)
+                .label(&crate::markup(text, *pango))
+                .build()
+                .upcast(),
+            Preview::Monospace(text) => gtk::Label::builder()
+                .name(style_names::PREVIEW)
+                .xalign(0.0)
+                .use_markup(true)
+                .label(&format!("<tt>{}</tt>", glib::markup_escape_text(text)))
+                .build()
+                .upcast(),
+            Preview::Image(pixels) => {
+                let image = gtk::Image::builder()
+                    .name(style_names::PREVIEW)
+                    .pixel_size(IMAGE_SIZE)
+                    .build();
+                crate::load_icon_pixels(&image, pixels, IMAGE_SIZE);
+                image.upcast()
+            }
+            Preview::File(path) => {
+                let image = gtk::Image::builder()
+                    .name(style_names::PREVIEW)
+                    .pixel_size(IMAGE_SIZE)
+                    .build();
+                crate::load_icon_file(&image, path, IMAGE_SIZE);
+                image.upcast()
+            }
+        };
+
+        self.content.add(&widget);
+        self.content.show_all();
+        self.window.show();
+    }
+}
@@ -0,0 +1,51 @@
+//! Latency measurements of the plugins, enabled with `--profile-plugins`.
+
+use std::{ops::Deref, time::Duration};
+
+/// The timings of a single plugin
+pub struct PluginProfile {
+    pub name: String,
+    /// Loading the library and running the `init` function of the plugin. The plugin does most of
+    /// its initialization in the background, so that mostly shows up in the first query instead.
+    pub load: Duration,
+    /// How long it took for the matches of each query to be ready
+    pub queries: Vec<Duration>,
+}
+
+impl PluginProfile {
+    pub fn new(name: String, load: Duration) -> Self {
+        Self {
+            name,
+            load,
+            queries: Vec::new(),
+        }
+    }
+}
+
+/// Print a table of the timings to stderr
+pub fn report<P: Deref<Target = PluginProfile>>(profiles: &[P]) {
+    eprintln!(
+        "{:<20} {:>10} {:>8} {:>10} {:>10} {:>10}",
+        "Plugin", "Load", "Queries", "First", "Average", "Max"
+    );
+
+    for profile in profiles {
+        let format = |duration: Option<Duration>| {
+            duration
+                .map(|duration| format!("{:.1}ms", duration.as_secs_f64() * 1000.0))
+                .unwrap_or_else(|| "-".to_string())
+        };
+        let average = (!profile.queries.is_empty())
+            .then(|| profile.queries.iter().sum::<Duration>() / profile.queries.len() as u32);
+
+        eprintln!(
+            "{:<20} {:>10} {:>8} {:>10} {:>10} {:>10}",
+            profile.name,
+            format(Some(profile.load)),
+            profile.queries.len(),
+            format(profile.queries.first().copied()),
+            format(average),
+            format(profile.queries.iter().max().copied()),
+        );
+    }
+}
@@ -0,0 +1,250 @@
+//! Headless queries with `anyrun query`, printing the matches instead of showing them.
+
+use std::{
+    io::{self, Write},
+    path::Path,
+    process::Command,
+    thread,
+    time::{Duration, Instant},
+};
+
+use anyrun_interface::{PollResult, ValidateResult};
+use clap::ValueEnum;
+use gtk::prelude::*;
+use serde::Serialize;
+use tracing::error;
+
+use crate::{
+    plugin::{LoadOptions, Plugin},
+    preprocess::Preprocessors,
+    route, Config,
+};
+
+/// How long to wait for the matches when `plugin_timeout` isn't set
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum Format {
+    /// The titles of the matches, one per line
+    Plain,
+    /// An array of objects with the plugin, title, description, icon and the path of the icon
+    Json,
+    /// The plugin, title, description and the path of the icon separated by tabs, one match per line
+    Tsv,
+    /// The titles of the matches, each followed by a null character
+    Null,
+}
+
+#[derive(Serialize)]
+struct Output {
+    plugin: String,
+    title: String,
+    description: Option<String>,
+    icon: Option<String>,
+}
+
+#[derive(Serialize)]
+struct JsonOutput<'a> {
+    #[serde(flatten)]
+    output: &'a Output,
+    icon_path: Option<String>,
+}
+
+/// Get the matches of all the plugins for the input and print them in the format
+pub fn run(input: &str, format: Format, config: &Config, options: &LoadOptions) -> bool {
+    let search_paths = crate::plugin_search_paths(options.config_dir, &config.plugin_paths);
+    let timeout = config
+        .plugin_timeout
+        .map_or(DEFAULT_TIMEOUT, Duration::from_millis);
+
+    // A plugin that can't be found fails the query, but the others are still asked
+    let mut found = true;
+    let plugins = config
+        .plugins
+        .iter()
+        .filter_map(|plugin_path| {
+            let path = match crate::resolve_plugin(plugin_path, &search_paths) {
+                Some(path) => path,
+                None => {
+                    error!("Invalid plugin path: {}", plugin_path.display());
+                    found = false;
+                    return None;
+                }
+            };
+            Plugin::load(&path, options, config.plugin_environment(plugin_path))
+                .map_err(|why| error!("Failed to load plugin {}: {}", path.display(), why))
+                .ok()
+        })
+        .collect::<Vec<_>>();
+
+    // Only ask the plugin the input is meant for, like the window does
+    let preprocessors = Preprocessors::new(&config.preprocessors);
+    let input = preprocessors.global(input);
+    let prefixes = plugins
+        .iter()
+        .map(|plugin| plugin.prefix())
+        .collect::<Vec<_>>();
+    let routed = route::route(&input, &prefixes);
+    let ids = plugins
+        .iter()
+        .enumerate()
+        .map(|(index, plugin)| {
+            if routed.is_some_and(|routed| routed != index) {
+                return None;
+            }
+            let input = preprocessors.plugin(plugin.info().name.as_str(), &input);
+            match plugin.validate_input(input.as_str().into()) {
+                ValidateResult::Accept => Some(plugin.get_matches(input.into())),
+                ValidateResult::Transform(input) => Some(plugin.get_matches(input)),
+                ValidateResult::Reject => None,
+            }
+        })
+        .collect::<Vec<_>>();
+    let start = Instant::now();
+
+    let mut results = Vec::new();
+    for (plugin, id) in plugins.iter().zip(ids) {
+        let id = match id {
+            Some(id) => id,
+            None => continue,
+        };
+
+        // Keep the streamed matches even if the rest doesn't arrive in time
+        let mut matches = Vec::new();
+        loop {
+            match plugin.poll_matches(id) {
+                PollResult::Ready(rest) => break matches.extend(rest),
+                PollResult::Partial(streamed) => matches.extend(streamed),
+                PollResult::Pending if start.elapsed() < timeout => {
+                    thread::sleep(Duration::from_millis(1))
+                }
+                _ => break,
+            }
+        }
+
+        let name = plugin.info().name.to_string();
+        results.extend(matches.into_iter().map(|_match| Output {
+            plugin: name.clone(),
+            title: _match.title.into(),
+            description: _match.description.map(String::from).into(),
+            icon: _match.icon.map(String::from).into(),
+        }));
+    }
+
+    for plugin in &plugins {
+        plugin.deinit();
+        plugin.stop();
+    }
+
+    if let Err(why) = print(&results, format) {
+        error!("Error outputting matches to stdout: {}", why);
+        return false;
+    }
+
+    found
+}
+
+/// Write the matches to stdout in the format
+fn print(results: &[Output], format: Format) -> io::Result<()> {
+    let mut stdout = io::stdout().lock();
+
+    match format {
+        Format::Plain => {
+            for output in results {
+                writeln!(stdout, "{}", output.title)?;
+            }
+        }
+        Format::Json => {
+            let icon_paths = IconPaths::new();
+            let results = results
+                .iter()
+                .map(|output| JsonOutput {
+                    output,
+                    icon_path: output
+                        .icon
+                        .as_deref()
+                        .and_then(|icon| icon_paths.find(icon)),
+                })
+                .collect::<Vec<_>>();
+
+            serde_json::to_writer(&mut stdout, &results)?;
+            writeln!(stdout)?;
+        }
+        Format::Tsv => {
+            let icon_paths = IconPaths::new();
+            // Tabs and newlines in the fields would break the columns
+            let clean = |field: &str| field.replace(['\t', '\n'], " ");
+
+            for output in results {
+                writeln!(
+                    stdout,
+                    "{}\t{}\t{}\t{}",
+                    clean(&output.plugin),
+                    clean(&output.title),
+                    clean(output.description.as_deref().unwrap_or_default()),
+                    output
+                        .icon
+                        .as_deref()
+                        .and_then(|icon| icon_paths.find(icon))
+                        .unwrap_or_default(),
+                )?;
+            }
+        }
+        Format::Null => {
+            for output in results {
+                write!(stdout, "{}\0", output.title)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Looks up the files of icon names in the icon theme, if GTK can be initialized without a window
+struct IconPaths {
+    theme: Option<gtk::IconTheme>,
+}
+
+impl IconPaths {
+    fn new() -> Self {
+        Self {
+            theme: gtk::init().ok().and_then(|_| gtk::IconTheme::default()),
+        }
+    }
+
+    fn find(&self, icon: &str) -> Option<String> {
+        if Path::new(icon).is_absolute() {
+            return Some(icon.to_string());
+        }
+
+        self.theme
+            .as_ref()?
+            .lookup_icon(icon, 32, gtk::IconLookupFlags::empty())?
+            .filename()
+            .map(|path| path.display().to_string())
+    }
+}
+
+/// Run the query with the anyrun installed on `host`, printing its output as is
+pub fn remote(host: &str, input: &str, format: Format) -> bool {
+    // SSH passes the command to the remote shell as a single string
+    let command = format!(
+        "anyrun query --format {} -- '{}'",
+        format.to_possible_value().unwrap().get_name(),
+        input.replace('\'', "'\\''")
+    );
+
+    // A host starting with `-` would otherwise be taken as an option of ssh
+    match Command::new("ssh")
+        .arg("--")
+        .arg(host)
+        .arg(command)
+        .status()
+    {
+        Ok(status) => status.success(),
+        Err(why) => {
+            error!("Failed to run ssh: {}", why);
+            false
+        }
+    }
+}
@@ -0,0 +1,50 @@
+//! The store behind the recently used items of the plugins. The file is read again for every
+//! call, as the plugin helpers of `isolate_plugins` write to it from their own processes.
+
+use std::{collections::HashMap, fs, path::PathBuf};
+
+use abi_stable::std_types::{RString, RVec};
+use anyrun_interface::RecencyStore;
+use tracing::warn;
+
+/// The amount of items remembered for every plugin
+const MAX_ITEMS: usize = 50;
+
+pub const STORE: RecencyStore = RecencyStore { record, recent };
+
+fn path() -> PathBuf {
+    crate::state_dir().join("recent.json")
+}
+
+fn load() -> HashMap<String, Vec<String>> {
+    fs::read_to_string(path())
+        .ok()
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+extern "C" fn record(plugin: RString, key: RString) {
+    let mut items = load();
+    let recent = items.entry(plugin.into()).or_default();
+    recent.retain(|item| item.as_str() != key.as_str());
+    recent.insert(0, key.into());
+    recent.truncate(MAX_ITEMS);
+
+    let path = path();
+    if let Err(why) = path
+        .parent()
+        .map_or(Ok(()), fs::create_dir_all)
+        .and_then(|_| fs::write(&path, serde_json::to_string(&items).unwrap_or_default()))
+    {
+        warn!("Failed to save the recently used items: {}", why);
+    }
+}
+
+extern "C" fn recent(plugin: RString) -> RVec<RString> {
+    load()
+        .remove(plugin.as_str())
+        .unwrap_or_default()
+        .into_iter()
+        .map(RString::from)
+        .collect()
+}
@@ -0,0 +1,93 @@
+//! Telling which plugin the input is meant for by the prefixes, the same for the window and for
+//! `anyrun query`.
+
+/// The index of the plugin the input can only be meant for, out of the prefixes of the plugins.
+/// That is the plugin with the longest of the prefixes the input starts with, so that `:sh` goes to
+/// the plugin with that prefix and not to the one with `:`. While the input typed so far begins
+/// with a symbol like `:` and a longer prefix still starts with it, it goes to that plugin if it is
+/// the only one it could be meant for. The input goes to every plugin if more than one of them
+/// could be meant.
+pub fn route(input: &str, prefixes: &[Option<String>]) -> Option<usize> {
+    let prefixed = prefixes
+        .iter()
+        .enumerate()
+        .filter_map(|(index, prefix)| Some((index, prefix.as_deref()?)))
+        .collect::<Vec<_>>();
+
+    let typing = prefixed
+        .iter()
+        .filter(|(_, prefix)| {
+            prefix.len() > input.len()
+                && prefix.starts_with(input)
+                && input.starts_with(|chr: char| !chr.is_alphanumeric())
+        })
+        .collect::<Vec<_>>();
+    let typed = prefixed
+        .iter()
+        .filter(|(_, prefix)| input.starts_with(prefix))
+        .collect::<Vec<_>>();
+
+    if !typing.is_empty() {
+        return match (typing.as_slice(), typed.is_empty()) {
+            ([(index, _)], true) => Some(*index),
+            _ => None,
+        };
+    }
+
+    let longest = typed.iter().map(|(_, prefix)| prefix.len()).max()?;
+    let mut candidates = typed.iter().filter(|(_, prefix)| prefix.len() == longest);
+    let (routed, _) = candidates.next()?;
+    candidates.next().is_none().then_some(*routed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn prefixes(prefixes: &[&str]) -> Vec<Option<String>> {
+        prefixes
+            .iter()
+            .map(|prefix| (!prefix.is_empty()).then(|| prefix.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn routes_to_the_longest_prefix() {
+        let prefixes = prefixes(&[":", "", ":sh"]);
+        assert_eq!(route(":sh ls", &prefixes), Some(2));
+        assert_eq!(route(":ls", &prefixes), Some(0));
+        assert_eq!(route("ls", &prefixes), None);
+    }
+
+    #[test]
+    fn routes_while_the_prefix_is_typed() {
+        let prefixes = prefixes(&[":sh", ":web", "=", ""]);
+        assert_eq!(route(":s", &prefixes), Some(0));
+        assert_eq!(route(":w", &prefixes), Some(1));
+        // Either of them may be meant
+        assert_eq!(route(":", &prefixes), None);
+    }
+
+    #[test]
+    fn waits_for_a_longer_prefix() {
+        let prefixes = prefixes(&[":", ":sh"]);
+        assert_eq!(route(":", &prefixes), None);
+        assert_eq!(route(":s", &prefixes), None);
+        assert_eq!(route(":sh", &prefixes), Some(1));
+        assert_eq!(route(":x", &prefixes), Some(0));
+    }
+
+    #[test]
+    fn only_predicts_prefixes_starting_with_a_symbol() {
+        let prefixes = prefixes(&["calc", "def"]);
+        assert_eq!(route("ca", &prefixes), None);
+        assert_eq!(route("calc 1", &prefixes), Some(0));
+    }
+
+    #[test]
+    fn leaves_plugins_with_the_same_prefix_to_all_of_them() {
+        let prefixes = prefixes(&[":w", ":w"]);
+        assert_eq!(route(":w rust", &prefixes), None);
+        assert_eq!(route(":", &prefixes), None);
+    }
+}
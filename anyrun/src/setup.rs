@@ -0,0 +1,239 @@
+//! The setup shown in the window on the first run, while there is no config anywhere. The plugins,
+//! the width, the position and the theme picked in it are written to the config directory, and
+//! anyrun starts again with them.
+
+use std::{
+    cell::RefCell,
+    fs::{self, OpenOptions},
+    io::{self, Write},
+    path::Path,
+    rc::Rc,
+};
+
+use gtk::{gdk, prelude::*};
+
+use crate::{manage, style_names, PostRunAction, RuntimeData};
+
+/// The themes to pick from, with their style sheets
+const THEMES: &[(&str, &str)] = &[
+    ("System", include_str!("../res/style.css")),
+    ("Dark", include_str!("../res/themes/dark.css")),
+    ("Light", include_str!("../res/themes/light.css")),
+];
+
+/// Where the window can be put, with the fraction of the height of the screen above it
+const POSITIONS: &[(&str, f64)] = &[("Top", 0.0), ("Center", 0.3)];
+
+/// What was picked in the setup
+struct Choices {
+    plugins: Vec<(gtk::CheckButton, String)>,
+    width: gtk::Scale,
+    position: gtk::ComboBoxText,
+    theme: gtk::ComboBoxText,
+}
+
+/// Show the setup in the window instead of the launcher, with the config written to `config_dir`
+pub fn show(
+    window: &gtk::ApplicationWindow,
+    provider: &gtk::CssProvider,
+    runtime_data: Rc<RefCell<RuntimeData>>,
+    config_dir: &str,
+) {
+    let (screen_width, screen_height) = window.default_size();
+
+    let setup_box = gtk::Box::builder()
+        .orientation(gtk::Orientation::Vertical)
+        .spacing(10)
+        .halign(gtk::Align::Center)
+        .valign(gtk::Align::Start)
+        .width_request(screen_width / 2)
+        .name(style_names::MAIN)
+        .build();
+    setup_box.add(
+        &gtk::Label::builder()
+            .use_markup(true)
+            .label("<big>Welcome to anyrun</big>\nPick what anyrun should look like and what it should search. All of it can be changed later in the config.")
+            .wrap(true)
+            .name(style_names::MAIN)
+            .build(),
+    );
+
+    // The plugins of the default config are picked to begin with
+    let search_paths = {
+        let runtime_data = runtime_data.borrow();
+        crate::plugin_search_paths(&runtime_data.config_dir, &runtime_data.config.plugin_paths)
+    };
+    let defaults = crate::Config::default_plugins();
+    let plugin_list = gtk::Box::builder()
+        .orientation(gtk::Orientation::Vertical)
+        .name(style_names::PLUGIN)
+        .build();
+    let plugins = manage::find_installed(&search_paths)
+        .into_iter()
+        .map(|plugin| {
+            let entry = plugin.entry(&search_paths);
+            let button = gtk::CheckButton::builder()
+                .label(plugin.info.name.as_str())
+                .active(defaults.contains(&entry))
+                .name(style_names::PLUGIN)
+                .build();
+            plugin_list.add(&button);
+            (button, entry.display().to_string())
+        })
+        .collect();
+    setup_box.add(&section("Plugins", &plugin_list));
+
+    let width = gtk::Scale::with_range(gtk::Orientation::Horizontal, 20.0, 100.0, 5.0);
+    width.set_value(50.0);
+    width.set_digits(0);
+    width.set_hexpand(true);
+    setup_box.add(&section("Width in percent of the screen", &width));
+
+    let position = combo_box(POSITIONS.iter().map(|(name, _)| *name));
+    setup_box.add(&section("Position", &position));
+
+    let theme = combo_box(THEMES.iter().map(|(name, _)| *name));
+    setup_box.add(&section("Theme", &theme));
+
+    let error = gtk::Label::builder()
+        .wrap(true)
+        .no_show_all(true)
+        .name(style_names::PLUGIN_ERROR)
+        .build();
+    setup_box.add(&error);
+
+    let save = gtk::Button::builder()
+        .label("Start anyrun")
+        .halign(gtk::Align::End)
+        .name(style_names::MAIN)
+        .build();
+    setup_box.add(&save);
+
+    // Show what was picked right away
+    width.connect_value_changed({
+        let setup_box = setup_box.clone();
+        move |width| {
+            setup_box.set_width_request((width.value() / 100.0 * screen_width as f64) as i32)
+        }
+    });
+    position.connect_changed({
+        let setup_box = setup_box.clone();
+        move |position| {
+            let (_, fraction) = POSITIONS[position.active().unwrap_or(0) as usize];
+            setup_box.set_margin_top((fraction * screen_height as f64) as i32);
+        }
+    });
+    theme.connect_changed({
+        let provider = provider.clone();
+        move |theme| {
+            let (_, css) = THEMES[theme.active().unwrap_or(0) as usize];
+            if let Err(why) = provider.load_from_data(css.as_bytes()) {
+                tracing::warn!("Failed to load the theme: {}", why);
+            }
+        }
+    });
+
+    let choices = Choices {
+        plugins,
+        width,
+        position,
+        theme,
+    };
+    let config_dir = config_dir.to_string();
+    save.connect_clicked({
+        let window = window.clone();
+        move |_| match write(&config_dir, &choices) {
+            Ok(()) => {
+                runtime_data.borrow_mut().post_run_action = PostRunAction::Restart;
+                window.close();
+            }
+            Err(why) => {
+                error.set_label(&format!(
+                    "Failed to write the config to {}: {}",
+                    config_dir, why
+                ));
+                error.show();
+            }
+        }
+    });
+
+    // Leaving the setup writes nothing, so it is shown again the next time
+    window.connect_key_press_event(|window, event| {
+        if event.keyval() == gdk::keys::constants::Escape {
+            window.close();
+            return Inhibit(true);
+        }
+        Inhibit(false)
+    });
+
+    window.add(&setup_box);
+    window.show_all();
+    save.grab_focus();
+}
+
+/// A label with the widget under it
+fn section(label: &str, widget: &impl IsA<gtk::Widget>) -> gtk::Box {
+    let section = gtk::Box::builder()
+        .orientation(gtk::Orientation::Vertical)
+        .spacing(5)
+        .name(style_names::PLUGIN)
+        .build();
+    section.add(
+        &gtk::Label::builder()
+            .label(label)
+            .xalign(0.0)
+            .name(style_names::PLUGIN)
+            .build(),
+    );
+    section.add(widget);
+    section
+}
+
+fn combo_box<'a>(options: impl Iterator<Item = &'a str>) -> gtk::ComboBoxText {
+    let combo_box = gtk::ComboBoxText::new();
+    for option in options {
+        combo_box.append_text(option);
+    }
+    combo_box.set_active(Some(0));
+    combo_box
+}
+
+/// Write the file unless there is one already, which is kept as it is
+fn write_new(path: &Path, content: &str) -> io::Result<()> {
+    match OpenOptions::new().write(true).create_new(true).open(path) {
+        Ok(mut file) => file.write_all(content.as_bytes()),
+        Err(why) if why.kind() == io::ErrorKind::AlreadyExists => Ok(()),
+        Err(why) => Err(why),
+    }
+}
+
+/// Write the config and the style sheet for the choices, leaving the files the user has alone
+fn write(config_dir: &str, choices: &Choices) -> io::Result<()> {
+    let plugins = choices
+        .plugins
+        .iter()
+        .filter(|(button, _)| button.is_active())
+        .map(|(_, entry)| format!("    {:?},\n", entry))
+        .collect::<String>();
+    let (_, y) = POSITIONS[choices.position.active().unwrap_or(0) as usize];
+    let (_, css) = THEMES[choices.theme.active().unwrap_or(0) as usize];
+
+    let config = format!(
+        "// Written by the setup of anyrun, examples/config.ron of anyrun describes all the options
+Config(
+  width: Fraction({:.2}),
+  y: Fraction({:.2}),
+  plugins: [
+{}  ],
+)
+",
+        choices.width.value() / 100.0,
+        y,
+        plugins
+    );
+
+    let dir = Path::new(config_dir);
+    fs::create_dir_all(dir)?;
+    write_new(&dir.join("config.ron"), &config)?;
+    write_new(&dir.join("style.css"), css)
+}
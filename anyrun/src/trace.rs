@@ -0,0 +1,112 @@
+//! A trace of the calls into the plugins in the trace event format of Chrome, enabled with
+//! `--trace-plugins`. Opened in `chrome://tracing` or Perfetto, every plugin gets a row of its own
+//! with the calls and the queries it answered. Polling for the matches isn't recorded, as that
+//! happens every millisecond.
+
+use std::{
+    fs::File,
+    io::{self, BufWriter},
+    path::Path,
+    process,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use serde::Serialize;
+
+static TRACE: Mutex<Option<Trace>> = Mutex::new(None);
+
+struct Trace {
+    start: Instant,
+    events: Vec<Event>,
+    /// The plugins by the ids of their rows
+    plugins: Vec<String>,
+}
+
+#[derive(Serialize)]
+struct Event {
+    name: String,
+    cat: &'static str,
+    ph: &'static str,
+    /// Microseconds since the trace was started
+    ts: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    dur: Option<u64>,
+    pid: u32,
+    tid: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    args: Option<serde_json::Value>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct TraceFile<'a> {
+    trace_events: &'a [Event],
+    display_time_unit: &'static str,
+}
+
+pub fn start() {
+    *TRACE.lock().unwrap() = Some(Trace {
+        start: Instant::now(),
+        events: Vec::new(),
+        plugins: Vec::new(),
+    });
+}
+
+pub fn enabled() -> bool {
+    TRACE.lock().unwrap().is_some()
+}
+
+/// Record a call into the plugin that started at `start`
+pub fn record(plugin: &str, call: &str, start: Instant, duration: Duration) {
+    let mut trace = TRACE.lock().unwrap();
+    let trace = match trace.as_mut() {
+        Some(trace) => trace,
+        None => return,
+    };
+
+    let tid = match trace.plugins.iter().position(|name| name == plugin) {
+        Some(tid) => tid,
+        None => {
+            // Name the row after the plugin
+            trace.plugins.push(plugin.to_string());
+            trace.events.push(Event {
+                name: "thread_name".to_string(),
+                cat: "__metadata",
+                ph: "M",
+                ts: 0,
+                dur: None,
+                pid: process::id(),
+                tid: trace.plugins.len() - 1,
+                args: Some(serde_json::json!({ "name": plugin })),
+            });
+            trace.plugins.len() - 1
+        }
+    };
+
+    trace.events.push(Event {
+        name: call.to_string(),
+        cat: "plugin",
+        ph: "X",
+        ts: start.saturating_duration_since(trace.start).as_micros() as u64,
+        dur: Some(duration.as_micros() as u64),
+        pid: process::id(),
+        tid,
+        args: None,
+    });
+}
+
+/// Write the recorded events to the file
+pub fn write(path: &Path) -> io::Result<()> {
+    let trace = TRACE.lock().unwrap();
+    let events = trace.as_ref().map_or(&[][..], |trace| &trace.events);
+
+    serde_json::to_writer(
+        BufWriter::new(File::create(path)?),
+        &TraceFile {
+            trace_events: events,
+            display_time_unit: "ms",
+        },
+    )
+    .map_err(io::Error::from)
+}
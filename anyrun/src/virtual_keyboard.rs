@@ -0,0 +1,151 @@
+//! Typing text into the window focused before anyrun, with the virtual keyboard protocol of
+//! Wayland. The keymap of the virtual keyboard has a key for every character of the text, the
+//! same trick `wtype` uses, so no layout is needed. Compositors without the protocol fall back to
+//! `wtype` itself, as does anyrun built without the `virtual-keyboard` feature.
+
+#[cfg(feature = "virtual-keyboard")]
+use std::{
+    fs::File,
+    io::Write,
+    os::fd::{AsFd, FromRawFd},
+    time::Instant,
+};
+use std::{process::Command, thread, time::Duration};
+
+#[cfg(feature = "virtual-keyboard")]
+use nix::sys::memfd::{memfd_create, MemFdCreateFlag};
+use tracing::error;
+#[cfg(feature = "virtual-keyboard")]
+use tracing::warn;
+#[cfg(feature = "virtual-keyboard")]
+use wayland_client::{
+    delegate_noop,
+    globals::{registry_queue_init, GlobalListContents},
+    protocol::{wl_keyboard::KeymapFormat, wl_registry, wl_seat::WlSeat},
+    Connection, Dispatch, QueueHandle,
+};
+#[cfg(feature = "virtual-keyboard")]
+use wayland_protocols_misc::zwp_virtual_keyboard_v1::client::{
+    zwp_virtual_keyboard_manager_v1::ZwpVirtualKeyboardManagerV1,
+    zwp_virtual_keyboard_v1::ZwpVirtualKeyboardV1,
+};
+
+/// How long the compositor gets to give the focus back to the previous window
+const FOCUS_DELAY: Duration = Duration::from_millis(50);
+
+#[cfg(feature = "virtual-keyboard")]
+struct State;
+
+#[cfg(feature = "virtual-keyboard")]
+impl Dispatch<wl_registry::WlRegistry, GlobalListContents> for State {
+    fn event(
+        _: &mut Self,
+        _: &wl_registry::WlRegistry,
+        _: wl_registry::Event,
+        _: &GlobalListContents,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+#[cfg(feature = "virtual-keyboard")]
+delegate_noop!(State: ignore WlSeat);
+#[cfg(feature = "virtual-keyboard")]
+delegate_noop!(State: ZwpVirtualKeyboardManagerV1);
+#[cfg(feature = "virtual-keyboard")]
+delegate_noop!(State: ZwpVirtualKeyboardV1);
+
+/// Type the text, with `wtype` if the compositor has no virtual keyboards
+pub fn type_text(text: &str) {
+    thread::sleep(FOCUS_DELAY);
+
+    #[cfg(feature = "virtual-keyboard")]
+    match virtual_keyboard(text) {
+        Ok(()) => return,
+        Err(why) => warn!(
+            "Failed to type with a virtual keyboard, trying wtype: {}",
+            why
+        ),
+    }
+
+    if let Err(why) = Command::new("wtype").arg("--").arg(text).status() {
+        error!("Failed to run wtype: {}", why);
+    }
+}
+
+#[cfg(feature = "virtual-keyboard")]
+fn virtual_keyboard(text: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let connection = Connection::connect_to_env()?;
+    let (globals, mut queue) = registry_queue_init::<State>(&connection)?;
+    let qh = queue.handle();
+
+    let seat = globals.bind::<WlSeat, _, _>(&qh, 1..=1, ())?;
+    let manager = globals.bind::<ZwpVirtualKeyboardManagerV1, _, _>(&qh, 1..=1, ())?;
+    let keyboard = manager.create_virtual_keyboard(&seat, &qh, ());
+
+    let mut chars = text.chars().collect::<Vec<_>>();
+    chars.sort_unstable();
+    chars.dedup();
+
+    // The keymap is passed as a file only in memory, which no other program can get at and which is
+    // gone once the compositor has read it and both closed it
+    let name = c"anyrun-keymap";
+    let fd = memfd_create(name, MemFdCreateFlag::MFD_CLOEXEC)?;
+    // The file takes ownership of the descriptor, closing it when dropped
+    let mut file = unsafe { File::from_raw_fd(fd) };
+    let keymap = keymap(&chars);
+    file.write_all(keymap.as_bytes())?;
+    file.write_all(b"\0")?;
+    keyboard.keymap(
+        KeymapFormat::XkbV1 as u32,
+        file.as_fd(),
+        keymap.len() as u32 + 1,
+    );
+    queue.roundtrip(&mut State)?;
+    drop(file);
+
+    let start = Instant::now();
+    for chr in text.chars() {
+        // The keycodes of the keymap start at 9, the protocol uses the evdev ones 8 lower
+        let key = chars.binary_search(&chr).unwrap() as u32 + 1;
+        let time = start.elapsed().as_millis() as u32;
+        keyboard.key(time, key, 1);
+        keyboard.key(time, key, 0);
+        queue.roundtrip(&mut State)?;
+    }
+
+    keyboard.destroy();
+    queue.roundtrip(&mut State)?;
+
+    Ok(())
+}
+
+/// An XKB keymap with a key for each of the characters
+#[cfg(feature = "virtual-keyboard")]
+fn keymap(chars: &[char]) -> String {
+    let mut keycodes = String::new();
+    let mut symbols = String::new();
+
+    for (index, chr) in chars.iter().enumerate() {
+        let keysym = match chr {
+            '\n' => "Return".to_string(),
+            '\t' => "Tab".to_string(),
+            chr => format!("U{:04X}", *chr as u32),
+        };
+        keycodes.push_str(&format!("    <K{}> = {};\n", index, index + 9));
+        symbols.push_str(&format!("    key <K{}> {{ [ {} ] }};\n", index, keysym));
+    }
+
+    format!(
+        "xkb_keymap {{\n\
+         xkb_keycodes \"anyrun\" {{\n    minimum = 8;\n    maximum = {};\n{}}};\n\
+         xkb_types \"anyrun\" {{ include \"complete\" }};\n\
+         xkb_compatibility \"anyrun\" {{ include \"complete\" }};\n\
+         xkb_symbols \"anyrun\" {{\n{}}};\n\
+         }};\n",
+        chars.len() + 9,
+        keycodes,
+        symbols
+    )
+}
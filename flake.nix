@@ -72,14 +72,25 @@
           # plugin every time.
           applications = mkPlugin "applications";
           dictionary = mkPlugin "dictionary";
+          external = mkPlugin "external";
+          jobs = mkPlugin "jobs";
           kidex = mkPlugin "kidex";
+          lua_runner = mkPlugin "lua_runner";
+          mounts = mkPlugin "mounts";
+          power = mkPlugin "power";
+          printers = mkPlugin "printers";
           randr = mkPlugin "randr";
+          removable = mkPlugin "removable";
+          resume = mkPlugin "resume";
           rink = mkPlugin "rink";
+          rofi = mkPlugin "rofi";
+          screencast = mkPlugin "screencast";
           shell = mkPlugin "shell";
           stdin = mkPlugin "stdin";
           symbols = mkPlugin "symbols";
           translate = mkPlugin "translate";
           websearch = mkPlugin "websearch";
+          windows = mkPlugin "windows";
         };
 
         # Set up an overlay from packages exposed by this flake
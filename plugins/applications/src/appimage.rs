@@ -0,0 +1,124 @@
+//! Entries for the AppImages in the configured directories, which come without desktop files
+//! unless a tool like AppImageLauncher integrated them. The integrated ones are left to their
+//! desktop files.
+
+use std::{
+    env, fs,
+    os::unix::fs::PermissionsExt,
+    path::{Path, PathBuf},
+};
+
+use anyrun_plugin::log;
+
+use crate::{scrubber::DesktopEntry, visibility::Visibility, Config};
+
+/// The parts of the file names after the name of the application
+const ARCHITECTURES: &[&str] = &[
+    "x86_64", "amd64", "x64", "i386", "i686", "aarch64", "arm64", "armhf",
+];
+
+/// The directories, with `~` expanded to the home directory
+pub fn dirs(config: &Config) -> Vec<PathBuf> {
+    config
+        .appimage_dirs
+        .iter()
+        .map(|dir| match dir.strip_prefix("~/") {
+            Some(rest) => Path::new(&env::var("HOME").unwrap_or_default()).join(rest),
+            None => PathBuf::from(dir),
+        })
+        .collect()
+}
+
+pub fn entries<'a>(
+    config: &Config,
+    known: impl Iterator<Item = &'a DesktopEntry> + Clone,
+) -> Vec<DesktopEntry> {
+    let mut entries = Vec::new();
+
+    for dir in dirs(config) {
+        let dir_entries = match fs::read_dir(&dir) {
+            Ok(dir_entries) => dir_entries,
+            Err(why) => {
+                log::debug!("Skipping directory {}: {}", dir.display(), why);
+                continue;
+            }
+        };
+
+        for path in dir_entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+        {
+            let is_appimage = path
+                .extension()
+                .is_some_and(|extension| extension.eq_ignore_ascii_case("appimage"));
+            // They can't be run without being made executable first
+            let executable = fs::metadata(&path)
+                .is_ok_and(|metadata| metadata.permissions().mode() & 0o111 != 0);
+            if !is_appimage || !executable {
+                continue;
+            }
+
+            let path_str = path.to_string_lossy().to_string();
+            if known.clone().any(|entry| entry.command.contains(&path_str)) {
+                continue;
+            }
+
+            entries.push(DesktopEntry {
+                id: path
+                    .file_name()
+                    .unwrap_or_default()
+                    .to_string_lossy()
+                    .to_string(),
+                exec: quote(&path_str),
+                command: vec![path_str.clone()],
+                path: None,
+                name: name(&path),
+                generic_name: None,
+                categories: Vec::new(),
+                keywords: Vec::new(),
+                desc: Some("AppImage".to_string()),
+                icon: "application-x-executable".to_string(),
+                term: false,
+                visibility: Visibility::default(),
+                actions: Vec::new(),
+            });
+        }
+    }
+
+    entries
+}
+
+/// The name of the application from the file name, like `Obsidian` from
+/// `Obsidian-1.4.16-x86_64.AppImage`
+fn name(path: &Path) -> String {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let name = stem
+        .split(['-', '_'])
+        .take_while(|part| {
+            // The version, like `1.4.16` or `v1.4.16`
+            let version = part.strip_prefix(['v', 'V']).unwrap_or(part);
+            !version.starts_with(|c: char| c.is_ascii_digit())
+                && !ARCHITECTURES.contains(&part.to_ascii_lowercase().as_str())
+        })
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    if name.is_empty() {
+        stem.to_string()
+    } else {
+        name
+    }
+}
+
+/// Quote the path like in the `Exec` key of a desktop file
+fn quote(path: &str) -> String {
+    let mut quoted = String::from('"');
+    for c in path.chars() {
+        if matches!(c, '"' | '`' | '$' | '\\') {
+            quoted.push('\\');
+        }
+        quoted.push(c);
+    }
+    quoted.push('"');
+    quoted
+}
@@ -0,0 +1,105 @@
+//! The desktop entries parsed on the previous startup, with the modification times of their files.
+//! Only the files that changed since are parsed again, the others are taken from the cache.
+
+use std::{
+    collections::{HashMap, HashSet},
+    env, fs,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use anyrun_plugin::log;
+use serde::{Deserialize, Serialize};
+
+use crate::scrubber::DesktopEntry;
+
+/// Raised whenever what is kept of an entry changes, so the entries cached before are parsed again
+const VERSION: u32 = 8;
+
+#[derive(Serialize, Deserialize, Default)]
+pub struct Cache {
+    version: u32,
+    /// The options the entries were parsed with, the cached entries are of no use with others
+    options: String,
+    files: HashMap<PathBuf, CachedFile>,
+    /// The files asked for since loading, the others are gone and left out when saving
+    #[serde(skip)]
+    used: HashSet<PathBuf>,
+    #[serde(skip)]
+    changed: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+struct CachedFile {
+    modified: SystemTime,
+    entries: Vec<DesktopEntry>,
+}
+
+fn path() -> PathBuf {
+    match env::var("XDG_CACHE_HOME") {
+        Ok(cache_home) => PathBuf::from(cache_home),
+        Err(_) => PathBuf::from(format!("{}/.cache", env::var("HOME").unwrap_or_default())),
+    }
+    .join("anyrun/applications.json")
+}
+
+impl Cache {
+    pub fn load(options: String) -> Self {
+        fs::read_to_string(path())
+            .ok()
+            .and_then(|content| serde_json::from_str::<Self>(&content).ok())
+            .filter(|cache| cache.version == VERSION && cache.options == options)
+            .unwrap_or(Self {
+                version: VERSION,
+                options,
+                ..Default::default()
+            })
+    }
+
+    /// The entries of the file, parsed with `parse` if the file changed since it was cached
+    pub fn entries(
+        &mut self,
+        path: &Path,
+        parse: impl FnOnce(&Path) -> Vec<DesktopEntry>,
+    ) -> Vec<DesktopEntry> {
+        self.used.insert(path.to_path_buf());
+
+        let modified = match fs::metadata(path).and_then(|metadata| metadata.modified()) {
+            Ok(modified) => modified,
+            Err(_) => return parse(path),
+        };
+        match self.files.get(path) {
+            Some(cached) if cached.modified == modified => cached.entries.clone(),
+            _ => {
+                let entries = parse(path);
+                self.files.insert(
+                    path.to_path_buf(),
+                    CachedFile {
+                        modified,
+                        entries: entries.clone(),
+                    },
+                );
+                self.changed = true;
+                entries
+            }
+        }
+    }
+
+    /// Write the cache if any of the files changed, leaving out the ones that are gone
+    pub fn save(mut self) {
+        let count = self.files.len();
+        self.files.retain(|path, _| self.used.contains(path));
+        if !self.changed && self.files.len() == count {
+            return;
+        }
+
+        let path = path();
+        if let Err(why) = path
+            .parent()
+            .map_or(Ok(()), fs::create_dir_all)
+            .and_then(|_| fs::write(&path, serde_json::to_string(&self).unwrap_or_default()))
+        {
+            log::warn!("Failed to save the desktop entry cache: {}", why);
+        }
+    }
+}
@@ -0,0 +1,94 @@
+//! Starting the applications apart from anyrun, so that they keep running when anyrun exits and
+//! aren't its children. The program is forked twice and gets a session of its own, optionally in
+//! a systemd scope of its own too, with its output going nowhere instead of to anyrun.
+
+use std::{
+    io,
+    os::unix::process::CommandExt,
+    path::Path,
+    process::{Command, Stdio},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use nix::{
+    libc,
+    unistd::{self, ForkResult},
+};
+use serde::Deserialize;
+
+use crate::Config;
+
+#[derive(Deserialize, Clone, Copy, Default)]
+pub enum Detach {
+    /// In a session of its own
+    #[default]
+    Setsid,
+    /// Also in a scope of its own with `systemd-run --user --scope`, so it gets a cgroup of its
+    /// own instead of sharing the one of anyrun, and of the compositor
+    SystemdRun,
+}
+
+/// The name of the scope for the application, like `app-anyrun-org.mozilla.firefox-1234.scope` as
+/// the desktop environments name them
+fn scope(id: &str) -> String {
+    let id = id.strip_suffix(".desktop").unwrap_or(id);
+    let mut escaped = String::new();
+    for c in id.chars() {
+        match c {
+            // A dash separates the parts of the name
+            '-' => escaped.push_str(r"\x2d"),
+            c if c.is_ascii_alphanumeric() || matches!(c, ':' | '_' | '.') => escaped.push(c),
+            _ => escaped.push('_'),
+        }
+    }
+    let random = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |now| now.subsec_nanos());
+    format!("app-anyrun-{}-{}.scope", escaped, random)
+}
+
+/// Run the program and its arguments, the desktop file id naming the scope
+pub fn spawn(config: &Config, id: &str, args: &[String], dir: Option<&Path>) -> io::Result<()> {
+    let mut args = args.to_vec();
+    if let Detach::SystemdRun = config.detach {
+        let wrapper = [
+            "systemd-run".to_string(),
+            "--user".to_string(),
+            "--scope".to_string(),
+            "--quiet".to_string(),
+            "--collect".to_string(),
+            format!("--unit={}", scope(id)),
+            "--".to_string(),
+        ];
+        args.splice(0..0, wrapper);
+    }
+
+    let (program, args) = args
+        .split_first()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "The command is empty"))?;
+    let mut command = Command::new(program);
+    command
+        .args(args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null());
+    if let Some(dir) = dir {
+        command.current_dir(dir);
+    }
+
+    // The child forks again and exits right away, so the program ends up as a child of init. The
+    // error of running the program still reaches `spawn`, through the pipe std keeps for that.
+    unsafe {
+        command.pre_exec(|| {
+            match unistd::fork()? {
+                ForkResult::Parent { .. } => libc::_exit(0),
+                ForkResult::Child => (),
+            }
+            unistd::setsid()?;
+            Ok(())
+        });
+    }
+
+    // Only the first child is waited for, which is gone as soon as it forked
+    command.spawn()?.wait().map(|_| ())
+}
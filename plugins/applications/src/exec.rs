@@ -0,0 +1,203 @@
+//! The command line in the `Exec` key of a desktop entry, split into the program and its arguments
+//! by the quoting rules of the spec with the field codes expanded. The entries run their programs
+//! directly, the command line isn't given to a shell.
+
+use std::path::Path;
+
+/// What the field codes expand to
+pub struct Fields<'a> {
+    pub name: &'a str,
+    pub icon: Option<&'a str>,
+    /// The desktop file, for `%k`
+    pub file: &'a Path,
+}
+
+enum Piece {
+    Char(char),
+    Code(char),
+}
+
+/// Undo the escapes every string value in a desktop file may have, which come before the quoting
+/// of the command line
+pub fn unescape(value: &str) -> String {
+    let mut unescaped = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('s') => unescaped.push(' '),
+            Some('n') => unescaped.push('\n'),
+            Some('t') => unescaped.push('\t'),
+            Some('r') => unescaped.push('\r'),
+            Some('\\') => unescaped.push('\\'),
+            // Left for the quoting of the command line, like `\"` in a quoted argument
+            Some(other) => {
+                unescaped.push('\\');
+                unescaped.push(other);
+            }
+            None => unescaped.push('\\'),
+        }
+    }
+    unescaped
+}
+
+/// The program and its arguments, none if the quotes aren't closed or there is no program
+pub fn parse(exec: &str, fields: &Fields) -> Option<Vec<String>> {
+    let mut args: Vec<Vec<Piece>> = Vec::new();
+    let mut arg: Option<Vec<Piece>> = None;
+    let mut quoted = false;
+    let mut chars = exec.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if quoted {
+            match c {
+                '"' => quoted = false,
+                '\\' => match chars.peek() {
+                    Some(&escaped @ ('"' | '`' | '$' | '\\')) => {
+                        chars.next();
+                        arg.get_or_insert_with(Vec::new).push(Piece::Char(escaped));
+                    }
+                    _ => arg.get_or_insert_with(Vec::new).push(Piece::Char(c)),
+                },
+                _ => arg.get_or_insert_with(Vec::new).push(Piece::Char(c)),
+            }
+            continue;
+        }
+
+        match c {
+            ' ' | '\t' | '\n' => args.extend(arg.take()),
+            // Quoted arguments may be empty, unlike the others
+            '"' => {
+                quoted = true;
+                arg.get_or_insert_with(Vec::new);
+            }
+            '%' => match chars.next() {
+                Some('%') => arg.get_or_insert_with(Vec::new).push(Piece::Char('%')),
+                Some(code) => arg.get_or_insert_with(Vec::new).push(Piece::Code(code)),
+                None => arg.get_or_insert_with(Vec::new).push(Piece::Char('%')),
+            },
+            _ => arg.get_or_insert_with(Vec::new).push(Piece::Char(c)),
+        }
+    }
+    if quoted {
+        return None;
+    }
+    args.extend(arg);
+
+    let mut expanded = Vec::new();
+    for arg in args {
+        match arg.as_slice() {
+            // The files and urls, of which there are none when the entry is launched from here
+            [Piece::Code('f' | 'F' | 'u' | 'U')] => (),
+            [Piece::Code('i')] => {
+                if let Some(icon) = fields.icon {
+                    expanded.push("--icon".to_string());
+                    expanded.push(icon.to_string());
+                }
+            }
+            _ => {
+                let mut text = String::new();
+                for piece in arg {
+                    match piece {
+                        Piece::Char(c) => text.push(c),
+                        Piece::Code('c') => text.push_str(fields.name),
+                        Piece::Code('k') => text.push_str(&fields.file.to_string_lossy()),
+                        // Like the deprecated ones, which expand to nothing
+                        Piece::Code(_) => (),
+                    }
+                }
+                expanded.push(text);
+            }
+        }
+    }
+
+    if expanded.is_empty() {
+        None
+    } else {
+        Some(expanded)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(exec: &str) -> Option<Vec<String>> {
+        parse(
+            exec,
+            &Fields {
+                name: "Files",
+                icon: Some("folder"),
+                file: Path::new("/usr/share/applications/files.desktop"),
+            },
+        )
+    }
+
+    #[test]
+    fn unescapes_the_string_values() {
+        assert_eq!(unescape(r"a\sb\tc\\d"), "a b\tc\\d");
+        assert_eq!(unescape(r#"say \"hi\""#), r#"say \"hi\""#);
+        assert_eq!(unescape(r"trailing\"), r"trailing\");
+    }
+
+    #[test]
+    fn splits_on_whitespace() {
+        assert_eq!(
+            args("nautilus  --new-window\t%U"),
+            Some(vec!["nautilus".to_string(), "--new-window".to_string()])
+        );
+    }
+
+    #[test]
+    fn keeps_quoted_arguments_together() {
+        assert_eq!(
+            args(r#"sh -c "echo \"a b\" \$HOME" """#),
+            Some(vec![
+                "sh".to_string(),
+                "-c".to_string(),
+                r#"echo "a b" $HOME"#.to_string(),
+                String::new(),
+            ])
+        );
+    }
+
+    #[test]
+    fn fails_on_unclosed_quotes() {
+        assert_eq!(args(r#"sh -c "echo"#), None);
+        assert_eq!(args(r#"sh -c "echo \""#), None);
+    }
+
+    #[test]
+    fn expands_the_field_codes() {
+        assert_eq!(
+            args("app 100%% %c %k %i"),
+            Some(vec![
+                "app".to_string(),
+                "100%".to_string(),
+                "Files".to_string(),
+                "/usr/share/applications/files.desktop".to_string(),
+                "--icon".to_string(),
+                "folder".to_string(),
+            ])
+        );
+    }
+
+    #[test]
+    fn leaves_out_the_icon_without_one() {
+        let fields = Fields {
+            name: "Files",
+            icon: None,
+            file: Path::new("files.desktop"),
+        };
+        assert_eq!(parse("app %i", &fields), Some(vec!["app".to_string()]));
+    }
+
+    #[test]
+    fn needs_a_program() {
+        assert_eq!(args(""), None);
+        assert_eq!(args("%f %U"), None);
+    }
+}
@@ -0,0 +1,64 @@
+//! The `include` and `exclude` lists of the config, matched against the ids of the desktop files
+//! without `.desktop`, like `org.gnome.Nautilus`, and against the names of the entries.
+
+use anyrun_plugin::report;
+use regex::Regex;
+
+use crate::{scrubber::DesktopEntry, Config};
+
+pub struct Filter {
+    include: Vec<Regex>,
+    exclude: Vec<Regex>,
+}
+
+/// A glob like `org.gnome.*-symbolic`, where `*` is any text and `?` any character, or a regex
+/// between slashes like `/^Uninstall /`
+fn compile(pattern: &str) -> Option<Regex> {
+    let regex = match pattern
+        .strip_prefix('/')
+        .and_then(|pattern| pattern.strip_suffix('/'))
+    {
+        Some(regex) => regex.to_string(),
+        None => {
+            let mut regex = String::from("^");
+            for c in pattern.chars() {
+                match c {
+                    '*' => regex.push_str(".*"),
+                    '?' => regex.push('.'),
+                    _ => regex.push_str(&regex::escape(&c.to_string())),
+                }
+            }
+            regex.push('$');
+            regex
+        }
+    };
+
+    Regex::new(&regex)
+        .map_err(|why| report::error!("Invalid pattern {}: {}", pattern, why))
+        .ok()
+}
+
+impl Filter {
+    pub fn new(config: &Config) -> Self {
+        Self {
+            include: config
+                .include
+                .iter()
+                .filter_map(|pattern| compile(pattern))
+                .collect(),
+            exclude: config
+                .exclude
+                .iter()
+                .filter_map(|pattern| compile(pattern))
+                .collect(),
+        }
+    }
+
+    pub fn allows(&self, entry: &DesktopEntry) -> bool {
+        let id = entry.id.strip_suffix(".desktop").unwrap_or(&entry.id);
+        let matches = |regex: &Regex| regex.is_match(id) || regex.is_match(&entry.name);
+
+        (self.include.is_empty() || self.include.iter().any(matches))
+            && !self.exclude.iter().any(matches)
+    }
+}
@@ -0,0 +1,276 @@
+//! Finding the icons of the entries that the icon theme of GTK wouldn't find, like the ones only in
+//! `/usr/share/pixmaps`, the ones in another theme or the ones named with an extension. Those are
+//! given to anyrun by their paths, the others keep their names so that the icon theme is used.
+//! What was found is kept in the store of the plugin with the modification times of the
+//! directories it was found in, and only looked for again once one of them changed.
+
+use std::{
+    collections::{HashMap, HashSet},
+    env, fs,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use anyrun_plugin::{log, store};
+use serde::{Deserialize, Serialize};
+
+use crate::scrubber;
+
+const KEY: &str = "icons";
+
+/// The icon shown for the entries whose icon file is missing
+const FALLBACK: &str = "application-x-executable";
+
+const EXTENSIONS: &[&str] = &["png", "svg", "xpm"];
+
+/// The size the icons are looked for in, the others are taken when there is none in it
+const SIZE: u32 = 48;
+
+#[derive(Serialize, Deserialize)]
+pub struct Icons {
+    /// The names in the icon theme and the themes it inherits from, which GTK finds itself
+    themed: HashSet<String>,
+    /// The best file for the other names, with how far its size is from [`SIZE`]
+    files: HashMap<String, (u32, PathBuf)>,
+    /// The icon theme of GTK they were found for
+    theme: String,
+    /// The directories they were found in, with their modification times when they were, or None
+    /// for the ones that weren't there
+    dirs: HashMap<PathBuf, Option<SystemTime>>,
+}
+
+/// The directories the icon themes are in, the ones of the user first
+fn base_dirs() -> Vec<PathBuf> {
+    let mut dirs = vec![
+        PathBuf::from(format!("{}/.icons", env::var("HOME").unwrap_or_default())),
+        PathBuf::from(format!("{}/icons", scrubber::data_home())),
+    ];
+    let data_dirs =
+        env::var("XDG_DATA_DIRS").unwrap_or_else(|_| "/usr/local/share:/usr/share".to_string());
+    dirs.extend(data_dirs.split(':').map(|dir| Path::new(dir).join("icons")));
+    dirs
+}
+
+/// The icon theme set for GTK, from its settings file
+fn theme_name() -> Option<String> {
+    let config_home = env::var("XDG_CONFIG_HOME")
+        .unwrap_or_else(|_| format!("{}/.config", env::var("HOME").unwrap_or_default()));
+
+    ["gtk-4.0", "gtk-3.0"].into_iter().find_map(|version| {
+        let settings =
+            fs::read_to_string(format!("{}/{}/settings.ini", config_home, version)).ok()?;
+        settings.lines().find_map(|line| {
+            let (key, value) = line.split_once('=')?;
+            (key.trim() == "gtk-icon-theme-name").then(|| value.trim().to_string())
+        })
+    })
+}
+
+/// The `index.theme` of the theme, from the first base directory that has it
+fn index(bases: &[PathBuf], theme: &str) -> Option<String> {
+    bases
+        .iter()
+        .find_map(|base| fs::read_to_string(base.join(theme).join("index.theme")).ok())
+}
+
+/// The directories of the theme with the icons of applications in them, with how far their sizes
+/// are from [`SIZE`], and the themes it inherits from
+fn app_dirs(index: &str) -> (Vec<(String, u32)>, Vec<String>) {
+    let mut dirs = Vec::new();
+    let mut inherits = Vec::new();
+    let mut section = "";
+    let mut context = "";
+    let mut size: u32 = 0;
+    let mut scalable = false;
+
+    // A sentinel section at the end closes the last one
+    for line in index.lines().chain(["[end]"]) {
+        let line = line.trim();
+        if line.starts_with('[') {
+            if context == "Applications" && section != "Icon Theme" {
+                let distance = if scalable { 0 } else { size.abs_diff(SIZE) };
+                dirs.push((section.to_string(), distance));
+            }
+            section = line.trim_start_matches('[').trim_end_matches(']');
+            context = "";
+            size = 0;
+            scalable = false;
+            continue;
+        }
+
+        let (key, value) = match line.split_once('=') {
+            Some((key, value)) => (key.trim(), value.trim()),
+            None => continue,
+        };
+        match key {
+            "Inherits" if section == "Icon Theme" => {
+                inherits.extend(value.split(',').map(|theme| theme.trim().to_string()))
+            }
+            "Context" => context = value,
+            "Size" => size = value.parse().unwrap_or(0),
+            "Type" => scalable = value == "Scalable",
+            _ => (),
+        }
+    }
+
+    (dirs, inherits)
+}
+
+fn modified(dir: &Path) -> Option<SystemTime> {
+    fs::metadata(dir)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+/// The names of the icons in the directory, with their paths
+fn icons_in(dir: &Path) -> impl Iterator<Item = (String, PathBuf)> {
+    fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let path = entry.path();
+            let extension = path.extension()?.to_str()?;
+            if !EXTENSIONS.contains(&extension) {
+                return None;
+            }
+            Some((path.file_stem()?.to_string_lossy().to_string(), path))
+        })
+}
+
+impl Icons {
+    /// The icons found before, if none of the directories changed since, otherwise the ones found
+    /// now
+    pub fn load() -> Self {
+        let theme = theme_name().unwrap_or_else(|| "Adwaita".to_string());
+        if let Some(cached) = store::get::<Self>(KEY) {
+            let unchanged = cached.dirs.iter().all(|(dir, when)| modified(dir) == *when);
+            if cached.theme == theme && unchanged {
+                return cached;
+            }
+        }
+
+        let icons = Self::find(theme);
+        if let Err(why) = store::set(KEY, &icons) {
+            log::warn!("Failed to save the icons: {}", why);
+        }
+        icons
+    }
+
+    fn find(theme: String) -> Self {
+        let bases = base_dirs();
+        let mut icons = Self {
+            themed: HashSet::new(),
+            files: HashMap::new(),
+            theme: theme.clone(),
+            dirs: HashMap::new(),
+        };
+
+        // The icon theme of GTK and the ones it inherits from, after which GTK looks in hicolor
+        let mut themes = vec![theme];
+        let mut next = 0;
+        while next < themes.len() {
+            let theme = themes[next].clone();
+            next += 1;
+            for inherited in icons.add_themed(&bases, &theme) {
+                if !themes.contains(&inherited) {
+                    themes.push(inherited);
+                }
+            }
+        }
+        if !themes.iter().any(|theme| theme == "hicolor") {
+            icons.add_themed(&bases, "hicolor");
+            themes.push("hicolor".to_string());
+        }
+
+        // The other themes, for the icons only they have
+        for base in &bases {
+            icons.seen(base);
+            let others = fs::read_dir(base)
+                .into_iter()
+                .flatten()
+                .filter_map(|entry| entry.ok())
+                .map(|entry| entry.file_name().to_string_lossy().to_string())
+                .filter(|theme| !themes.contains(theme));
+            for theme in others {
+                icons.seen(&base.join(&theme));
+                let (dirs, _) =
+                    index(&bases, &theme).map_or_else(Default::default, |index| app_dirs(&index));
+                for (dir, distance) in dirs {
+                    let dir = base.join(&theme).join(dir);
+                    icons.seen(&dir);
+                    for (name, path) in icons_in(&dir) {
+                        icons.add(name, distance, path);
+                    }
+                }
+            }
+        }
+
+        // The icons outside of any theme come last
+        let pixmaps = Path::new("/usr/share/pixmaps");
+        icons.seen(pixmaps);
+        for (name, path) in icons_in(pixmaps) {
+            icons.add(name, u32::MAX, path);
+        }
+
+        icons
+    }
+
+    /// Add the names in the theme to the ones GTK finds, returning the themes it inherits from
+    fn add_themed(&mut self, bases: &[PathBuf], theme: &str) -> Vec<String> {
+        // A theme installed later shows up in the base directories
+        for base in bases {
+            self.seen(&base.join(theme));
+        }
+
+        let (dirs, inherits) =
+            index(bases, theme).map_or_else(Default::default, |index| app_dirs(&index));
+        for (dir, _) in dirs {
+            for base in bases {
+                let dir = base.join(theme).join(&dir);
+                self.seen(&dir);
+                self.themed.extend(icons_in(&dir).map(|(name, _)| name));
+            }
+        }
+        inherits
+    }
+
+    /// Keep the modification time of the directory, to tell when the icons have to be found again
+    fn seen(&mut self, dir: &Path) {
+        self.dirs.insert(dir.to_path_buf(), modified(dir));
+    }
+
+    fn add(&mut self, name: String, distance: u32, path: PathBuf) {
+        match self.files.get(&name) {
+            Some((best, _)) if *best <= distance => (),
+            _ => {
+                self.files.insert(name, (distance, path));
+            }
+        }
+    }
+
+    /// The name of the icon if GTK finds it in the icon theme, otherwise the path of its file. The
+    /// names that aren't anywhere are kept too, as other icons than the ones of applications
+    /// aren't looked for.
+    pub fn resolve(&self, icon: &str) -> String {
+        if icon.starts_with('/') {
+            return if Path::new(icon).exists() {
+                icon.to_string()
+            } else {
+                FALLBACK.to_string()
+            };
+        }
+
+        // Icons are meant to be named without their extensions, but some aren't
+        let name = Path::new(icon)
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .filter(|extension| EXTENSIONS.contains(extension))
+            .map_or(icon, |extension| &icon[..icon.len() - extension.len() - 1]);
+
+        match self.files.get(name) {
+            Some((_, path)) if !self.themed.contains(name) => path.to_string_lossy().to_string(),
+            _ => name.to_string(),
+        }
+    }
+}
@@ -0,0 +1,76 @@
+//! How often and how recently the entries were launched, kept in the store of the plugin by the
+//! ids of their desktop files. Every launch counts for less as it gets older, so the entries that
+//! stopped being used fall back in the ranking.
+
+use std::{
+    collections::HashMap,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyrun_plugin::{log, store};
+use serde::{Deserialize, Serialize};
+
+const KEY: &str = "launches";
+
+/// After how long a launch counts for half as much
+const HALF_LIFE: f64 = 30.0 * 24.0 * 60.0 * 60.0;
+
+#[derive(Serialize, Deserialize, Clone, Copy)]
+struct Launches {
+    /// The launches, each weighed by its age at the time of the last one
+    weight: f64,
+    /// The seconds since the epoch
+    last: u64,
+}
+
+#[derive(Default)]
+pub struct Frecency {
+    launches: HashMap<String, Launches>,
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |now| now.as_secs())
+}
+
+impl Launches {
+    fn weight_at(&self, now: u64) -> f64 {
+        let age = now.saturating_sub(self.last) as f64;
+        self.weight * 0.5f64.powf(age / HALF_LIFE)
+    }
+}
+
+impl Frecency {
+    pub fn load() -> Self {
+        Self {
+            launches: store::get(KEY).unwrap_or_default(),
+        }
+    }
+
+    /// How much the launches of the entry weigh now, 1 for a single launch just now
+    pub fn weight(&self, id: &str) -> f64 {
+        self.launches
+            .get(id)
+            .map_or(0.0, |launches| launches.weight_at(now()))
+    }
+
+    pub fn record(&mut self, id: &str) {
+        let now = now();
+        let weight = self
+            .launches
+            .get(id)
+            .map_or(0.0, |launches| launches.weight_at(now));
+        self.launches.insert(
+            id.to_string(),
+            Launches {
+                weight: weight + 1.0,
+                last: now,
+            },
+        );
+
+        if let Err(why) = store::set(KEY, &self.launches) {
+            log::warn!("Failed to save the launches: {}", why);
+        }
+    }
+}
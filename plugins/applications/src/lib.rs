@@ -1,16 +1,79 @@
 use abi_stable::std_types::{ROption, RString, RVec};
 use anyrun_plugin::{anyrun_interface::HandleResult, *};
+use detach::Detach;
 use fuzzy_matcher::FuzzyMatcher;
+use launches::Frecency;
 use scrubber::DesktopEntry;
 use serde::Deserialize;
-use std::{env, fs, process::Command};
+use std::sync::{Arc, Mutex, RwLock};
+use terminal::TerminalCommand;
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Clone)]
 pub struct Config {
     desktop_actions: bool,
     max_entries: usize,
     terminal: Option<String>,
+    #[serde(default)]
+    terminal_command: Option<TerminalCommand>,
+    #[serde(default)]
+    detach: Detach,
     ignore_prefix: String,
+    /// Only show the applications when the input starts with this
+    #[serde(default)]
+    prefix: String,
+    #[serde(default = "Config::default_launch_boost")]
+    launch_boost: f64,
+    #[serde(default = "Config::default_enabled")]
+    flatpak: bool,
+    #[serde(default = "Config::default_enabled")]
+    snap: bool,
+    #[serde(default = "Config::default_enabled")]
+    appimages: bool,
+    #[serde(default = "Config::default_appimage_dirs")]
+    appimage_dirs: Vec<String>,
+    #[serde(default = "Config::default_enabled")]
+    honor_no_display: bool,
+    #[serde(default = "Config::default_enabled")]
+    honor_hidden: bool,
+    #[serde(default = "Config::default_enabled")]
+    honor_show_in: bool,
+    #[serde(default = "Config::default_enabled")]
+    honor_try_exec: bool,
+    /// Only show the entries matching one of these, if there are any
+    #[serde(default)]
+    include: Vec<String>,
+    #[serde(default)]
+    exclude: Vec<String>,
+    #[serde(default = "Config::default_title")]
+    title: String,
+    /// Left out of the match when it comes out empty
+    #[serde(default = "Config::default_description")]
+    description: String,
+    /// Whether the command line of the entries is matched against the input too
+    #[serde(default = "Config::default_enabled")]
+    search_exec: bool,
+}
+
+impl Config {
+    fn default_launch_boost() -> f64 {
+        500.0
+    }
+
+    fn default_enabled() -> bool {
+        true
+    }
+
+    fn default_appimage_dirs() -> Vec<String> {
+        vec!["~/Applications".to_string(), "~/.local/bin".to_string()]
+    }
+
+    fn default_title() -> String {
+        "{name}".to_string()
+    }
+
+    fn default_description() -> String {
+        "{comment}".to_string()
+    }
 }
 
 impl Default for Config {
@@ -19,93 +82,127 @@ impl Default for Config {
             desktop_actions: false,
             max_entries: 5,
             terminal: None,
+            terminal_command: None,
+            detach: Detach::Setsid,
             ignore_prefix: "".to_string(),
+            prefix: "".to_string(),
+            launch_boost: Self::default_launch_boost(),
+            flatpak: true,
+            snap: true,
+            appimages: true,
+            appimage_dirs: Self::default_appimage_dirs(),
+            honor_no_display: true,
+            honor_hidden: true,
+            honor_show_in: true,
+            honor_try_exec: true,
+            include: Vec::new(),
+            exclude: Vec::new(),
+            title: Self::default_title(),
+            description: Self::default_description(),
+            search_exec: true,
         }
     }
 }
 
 pub struct State {
     config: Config,
-    entries: Vec<(DesktopEntry, u64)>,
+    /// Scanned again when the desktop files change
+    entries: Arc<RwLock<Vec<(DesktopEntry, u64)>>>,
+    frecency: Mutex<Frecency>,
 }
 
+mod appimage;
+mod cache;
+mod detach;
+mod exec;
+mod filter;
+mod icons;
+mod launches;
+mod locale;
 mod scrubber;
+mod template;
+mod terminal;
+mod visibility;
+mod watch;
 
-const SENSIBLE_TERMINALS: &[&str] = &["alacritty", "foot", "kitty", "wezterm", "wterm"];
+/// The action running the entry in a terminal, the desktop actions come after it
+const IN_TERMINAL: u64 = 0;
 
 #[handler]
 pub fn handler(selection: Match, state: &State) -> HandleResult {
-    let entry = state
-        .entries
-        .iter()
-        .find_map(|(entry, id)| {
-            if *id == selection.id.unwrap() {
-                Some(entry)
-            } else {
-                None
-            }
-        })
-        .unwrap();
-
-    if entry.term {
-        match &state.config.terminal {
-            Some(term) => {
-                if let Err(why) = Command::new(term).arg("-e").arg(&entry.exec).spawn() {
-                    eprintln!("Error running desktop entry: {}", why);
-                }
-            }
-            None => {
-                for term in SENSIBLE_TERMINALS {
-                    if Command::new(term)
-                        .arg("-e")
-                        .arg(&entry.exec)
-                        .spawn()
-                        .is_ok()
-                    {
-                        break;
-                    }
-                }
-            }
+    let entries = state.entries.read().unwrap();
+    let entry = match entries.iter().find_map(|(entry, id)| {
+        if *id == selection.id.unwrap() {
+            Some(entry)
+        } else {
+            None
         }
-    } else if let Err(why) = {
-        let current_dir = &env::current_dir().unwrap();
-
-        Command::new("sh")
-            .arg("-c")
-            .arg(&entry.exec)
-            .current_dir(if let Some(path) = &entry.path {
-                if path.exists() { path } else { current_dir }
-            } else {
-                current_dir
-            })
-            .spawn()
+    }) {
+        Some(entry) => entry,
+        // The desktop file was removed since the match was found
+        None => return HandleResult::Close,
+    };
+
+    // The launches of the actions count for the application, which they are shown with
+    if state.config.launch_boost != 0.0 {
+        state.frecency.lock().unwrap().record(&entry.id);
     }
-    {
-        eprintln!("Error running desktop entry: {}", why);
+
+    let (entry, in_terminal) = match selection.action {
+        ROption::RSome(IN_TERMINAL) => (entry, true),
+        ROption::RSome(action) => match entry.actions.get(action as usize - 1) {
+            Some(action) => (action, action.term),
+            None => return HandleResult::Close,
+        },
+        ROption::RNone => (entry, entry.term),
+    };
+
+    let args = command(entry);
+
+    if in_terminal {
+        if let Err(why) = terminal::run(&state.config, &entry.id, &args) {
+            log::error!("Error running desktop entry in a terminal: {}", why);
+        }
+    } else if let Err(why) = detach::spawn(
+        &state.config,
+        &entry.id,
+        &args,
+        entry.path.as_deref().filter(|path| path.exists()),
+    ) {
+        log::error!("Error running desktop entry: {}", why);
     }
 
     HandleResult::Close
 }
 
+/// The program and its arguments, the command line is only given to a shell when it couldn't be
+/// parsed like the spec says
+fn command(entry: &DesktopEntry) -> Vec<String> {
+    if entry.command.is_empty() {
+        vec!["sh".to_string(), "-c".to_string(), entry.exec.clone()]
+    } else {
+        entry.command.clone()
+    }
+}
+
 #[init]
 pub fn init(config_dir: RString) -> State {
-    let config: Config = match fs::read_to_string(format!("{}/applications.ron", config_dir)) {
-        Ok(content) => ron::from_str(&content).unwrap_or_else(|why| {
-            eprintln!("Error parsing applications plugin config: {}", why);
-            Config::default()
-        }),
-        Err(why) => {
-            eprintln!("Error reading applications plugin config: {}", why);
-            Config::default()
-        }
-    };
+    let config: Config = plugin_config(&config_dir, "applications");
+    prefix::set(&config.prefix);
 
     let entries = scrubber::scrubber(&config).unwrap_or_else(|why| {
-        eprintln!("Failed to load desktop entries: {}", why);
+        log::error!("Failed to load desktop entries: {}", why);
         Vec::new()
     });
 
-    State { config, entries }
+    let entries = Arc::new(RwLock::new(entries));
+    watch::start(config.clone(), entries.clone());
+
+    State {
+        config,
+        entries,
+        frecency: Mutex::new(Frecency::load()),
+    }
 }
 
 #[get_matches]
@@ -113,46 +210,102 @@ pub fn get_matches(input: RString, state: &State) -> RVec<Match> {
     if !state.config.ignore_prefix.is_empty() && input.starts_with(&state.config.ignore_prefix) {
         return RVec::new();
     }
+    let input = if state.config.prefix.is_empty() {
+        input.as_str()
+    } else if let Some(input) = input.strip_prefix(&state.config.prefix) {
+        input.trim()
+    } else {
+        return RVec::new();
+    };
 
     let matcher = fuzzy_matcher::skim::SkimMatcherV2::default().smart_case();
-    let mut entries = state
-        .entries
+    // Always the entries first and then the launches, like the handler, so that neither waits
+    // for the other while a scan waits to write the entries
+    let entries = state.entries.read().unwrap();
+    let frecency = state.frecency.lock().unwrap();
+    let mut entries = entries
         .iter()
         .filter_map(|(entry, id)| {
-            let name_score = matcher.fuzzy_match(&entry.name, &input).unwrap_or(0);
+            let name_score = matcher.fuzzy_match(&entry.name, input).unwrap_or(0);
             let comment_score = match &entry.desc {
                 None => 0,
-                Some(comment) => matcher.fuzzy_match(&comment, &input).unwrap_or(0),
+                Some(comment) => matcher.fuzzy_match(comment, input).unwrap_or(0),
+            };
+            let generic_name_score = match &entry.generic_name {
+                None => 0,
+                Some(generic_name) => matcher.fuzzy_match(generic_name, input).unwrap_or(0),
+            };
+            let exec_score = if state.config.search_exec {
+                matcher.fuzzy_match(&entry.exec, input).unwrap_or(0)
+            } else {
+                0
             };
-            let exec_score = matcher.fuzzy_match(&entry.exec, &input).unwrap_or(0);
 
             let keyword_score = entry
                 .keywords
                 .iter()
-                .map(|keyword| matcher.fuzzy_match(keyword, &input).unwrap_or(0))
+                .map(|keyword| matcher.fuzzy_match(keyword, input).unwrap_or(0))
                 .sum::<i64>();
 
-            let score = (name_score * 150 + comment_score * 50 + 25 * exec_score + keyword_score) - entry.offset;
+            let score = name_score * 150
+                + comment_score * 50
+                + generic_name_score * 50
+                + 25 * exec_score
+                + keyword_score;
 
             if score > 0 {
-                Some((entry, *id, score))
+                // The entries launched often and lately come first among the ones that match
+                let bonus = frecency.weight(&entry.id) * state.config.launch_boost;
+                Some((entry, *id, score + bonus as i64))
             } else {
                 None
             }
         })
         .collect::<Vec<_>>();
 
-    entries.sort_by(|a, b| b.2.cmp(&a.2));
+    // Equally good matches are in alphabetical order
+    entries.sort_by(|a, b| {
+        b.2.cmp(&a.2)
+            .then_with(|| collate::compare(&a.0.name, &b.0.name))
+    });
 
     entries.truncate(state.config.max_entries);
     entries
         .into_iter()
         .map(|(entry, id, _)| Match {
-            title: entry.name.clone().into(),
-            description: entry.desc.clone().map(|desc| desc.into()).into(),
-            use_pango: false,
+            title: template::render(&state.config.title, entry).into(),
+            description: Some(template::render(&state.config.description, entry))
+                .filter(|description| !description.is_empty())
+                .map(RString::from)
+                .into(),
+            title_pango: false,
+            description_pango: false,
             icon: ROption::RSome(entry.icon.clone().into()),
             id: ROption::RSome(id),
+            // First for Shift+Enter, for the command line tools that come with desktop files
+            actions: [Action {
+                title: "Run in Terminal".into(),
+                icon: ROption::RSome("utilities-terminal".into()),
+                id: IN_TERMINAL,
+            }]
+            .into_iter()
+            .chain(
+                entry
+                    .actions
+                    .iter()
+                    .enumerate()
+                    .map(|(index, action)| Action {
+                        title: action.name.clone().into(),
+                        icon: ROption::RSome(action.icon.clone().into()),
+                        id: index as u64 + 1,
+                    }),
+            )
+            .collect(),
+            action: ROption::RNone,
+            image: ROption::RNone,
+            widgets: RVec::new(),
+            priority: 0,
+            pinned: false,
         })
         .collect()
 }
@@ -0,0 +1,62 @@
+//! The locale of the user, for the localized keys of the desktop files like `Name[de_DE]`. The
+//! keys are looked up from the most specific one for the locale to the unlocalized one, like the
+//! spec says.
+
+use std::{collections::HashMap, env};
+
+/// The locale the messages are shown in, without its encoding, like `de_DE@euro`
+pub fn locale() -> Option<String> {
+    let locale = ["LC_ALL", "LC_MESSAGES", "LANG"]
+        .into_iter()
+        .filter_map(|var| env::var(var).ok())
+        .find(|locale| !locale.is_empty())?;
+    if locale == "C" || locale.starts_with("C.") || locale == "POSIX" {
+        return None;
+    }
+
+    let (rest, modifier) = match locale.split_once('@') {
+        Some((rest, modifier)) => (rest, Some(modifier)),
+        None => (locale.as_str(), None),
+    };
+    let rest = rest.split('.').next().unwrap_or(rest);
+    Some(match modifier {
+        Some(modifier) => format!("{}@{}", rest, modifier),
+        None => rest.to_string(),
+    })
+}
+
+/// The keys to look for in the order they are preferred, like `Name[de_DE@euro]`, `Name[de_DE]`,
+/// `Name[de@euro]`, `Name[de]` and `Name`
+fn keys(key: &str, locale: Option<&str>) -> Vec<String> {
+    let mut keys = Vec::new();
+    if let Some(locale) = locale {
+        let (rest, modifier) = match locale.split_once('@') {
+            Some((rest, modifier)) => (rest, Some(modifier)),
+            None => (locale, None),
+        };
+        let (lang, country) = match rest.split_once('_') {
+            Some((lang, country)) => (lang, Some(country)),
+            None => (rest, None),
+        };
+
+        if let (Some(country), Some(modifier)) = (country, modifier) {
+            keys.push(format!("{}[{}_{}@{}]", key, lang, country, modifier));
+        }
+        if let Some(country) = country {
+            keys.push(format!("{}[{}_{}]", key, lang, country));
+        }
+        if let Some(modifier) = modifier {
+            keys.push(format!("{}[{}@{}]", key, lang, modifier));
+        }
+        keys.push(format!("{}[{}]", key, lang));
+    }
+    keys.push(key.to_string());
+    keys
+}
+
+/// The value of the key for the locale, or the unlocalized one
+pub fn get<'a>(map: &HashMap<&str, &'a str>, key: &str, locale: Option<&str>) -> Option<&'a str> {
+    keys(key, locale)
+        .iter()
+        .find_map(|key| map.get(key.as_str()).copied())
+}
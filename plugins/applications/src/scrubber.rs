@@ -1,17 +1,41 @@
-use std::{collections::HashMap, env, ffi::OsStr, fs, path::PathBuf};
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap},
+    env,
+    ffi::OsStr,
+    fs,
+    hash::{Hash, Hasher},
+    io,
+    path::{Path, PathBuf},
+};
 
-use crate::Config;
+use anyrun_plugin::log;
+use serde::{Deserialize, Serialize};
 
-#[derive(Clone, Debug)]
+use crate::{
+    appimage, cache::Cache, exec, filter::Filter, icons::Icons, locale, visibility::Visibility,
+    Config,
+};
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct DesktopEntry {
+    /// The name of the desktop file, with the name of the action for the actions
+    pub id: String,
     pub exec: String,
+    /// The program and its arguments, empty when the `Exec` key couldn't be parsed and is run
+    /// through `sh -c` instead
+    pub command: Vec<String>,
     pub path: Option<PathBuf>,
     pub name: String,
+    /// What kind of application it is, like `Web Browser`
+    pub generic_name: Option<String>,
+    pub categories: Vec<String>,
     pub keywords: Vec<String>,
     pub desc: Option<String>,
     pub icon: String,
     pub term: bool,
-    pub offset: i64,
+    pub visibility: Visibility,
+    /// The desktop actions, like `New Window`, picked from the menu of the application
+    pub actions: Vec<DesktopEntry>,
 }
 
 const FIELD_CODE_LIST: &[&str] = &[
@@ -19,12 +43,19 @@ const FIELD_CODE_LIST: &[&str] = &[
 ];
 
 impl DesktopEntry {
-    fn from_dir_entry(entry: &fs::DirEntry, config: &Config) -> Vec<Self> {
-        if entry.path().extension() == Some(OsStr::new("desktop")) {
-            let content = match fs::read_to_string(entry.path()) {
+    fn from_path(path: &Path, config: &Config) -> Vec<Self> {
+        if path.extension() == Some(OsStr::new("desktop")) {
+            let content = match fs::read_to_string(path) {
                 Ok(content) => content,
                 Err(_) => return Vec::new(),
             };
+            let id = path
+                .file_name()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .to_string();
+            let locale = locale::locale();
+            let locale = locale.as_deref();
 
             let lines = content.lines().collect::<Vec<_>>();
 
@@ -49,9 +80,7 @@ impl DesktopEntry {
                 line = Some(section.last().unwrap_or(&""));
             }
 
-            let mut ret = Vec::new();
-
-            let entry = match new_sections.iter().find_map(|section| {
+            let mut entry = match new_sections.iter().find_map(|section| {
                 if section[0].starts_with("[Desktop Entry]") {
                     let mut map = HashMap::new();
 
@@ -61,13 +90,9 @@ impl DesktopEntry {
                         }
                     }
 
-                    if map.get("Type")? == &"Application"
-                        && match map.get("NoDisplay") {
-                            Some(no_display) => !no_display.parse::<bool>().unwrap_or(true),
-                            None => true,
-                        }
-                    {
+                    if map.get("Type")? == &"Application" {
                         Some(DesktopEntry {
+                            id: id.clone(),
                             exec: {
                                 let mut exec = map.get("Exec")?.to_string();
 
@@ -76,10 +101,27 @@ impl DesktopEntry {
                                 }
                                 exec
                             },
+                            command: command(
+                                map.get("Exec")?,
+                                locale::get(&map, "Name", locale)?,
+                                map.get("Icon").copied(),
+                                path,
+                            ),
                             path: map.get("Path").map(PathBuf::from),
-                            name: map.get("Name")?.to_string(),
-                            keywords: map
-                                .get("Keywords")
+                            name: locale::get(&map, "Name", locale)?.to_string(),
+                            generic_name: locale::get(&map, "GenericName", locale)
+                                .map(|generic_name| generic_name.to_string()),
+                            categories: map
+                                .get("Categories")
+                                .map(|categories| {
+                                    categories
+                                        .split(';')
+                                        .filter(|category| !category.is_empty())
+                                        .map(|category| category.to_string())
+                                        .collect()
+                                })
+                                .unwrap_or_default(),
+                            keywords: locale::get(&map, "Keywords", locale)
                                 .map(|keywords| {
                                     keywords
                                         .split(';')
@@ -87,7 +129,8 @@ impl DesktopEntry {
                                         .collect::<Vec<_>>()
                                 })
                                 .unwrap_or_default(),
-                            desc: map.get("Comment").map(|comment| comment.to_string()),
+                            desc: locale::get(&map, "Comment", locale)
+                                .map(|comment| comment.to_string()),
                             icon: map
                                 .get("Icon")
                                 .unwrap_or(&"application-x-executable")
@@ -96,7 +139,8 @@ impl DesktopEntry {
                                 .get("Terminal")
                                 .map(|val| val.to_lowercase() == "true")
                                 .unwrap_or(false),
-                            offset: 0,
+                            visibility: Visibility::from_map(&map),
+                            actions: Vec::new(),
                         })
                     } else {
                         None
@@ -106,11 +150,11 @@ impl DesktopEntry {
                 }
             }) {
                 Some(entry) => entry,
-                None => return Vec::new(),
+                None => return tombstone(&id, &new_sections).into_iter().collect(),
             };
 
             if config.desktop_actions {
-                for (i, section) in new_sections.iter().enumerate() {
+                for section in &new_sections {
                     let mut map = HashMap::new();
 
                     for line in section.iter().skip(1) {
@@ -120,7 +164,15 @@ impl DesktopEntry {
                     }
 
                     if section[0].starts_with("[Desktop Action") {
-                        ret.push(DesktopEntry {
+                        let action = DesktopEntry {
+                            id: format!(
+                                "{}:{}",
+                                id,
+                                section[0]
+                                    .trim_start_matches("[Desktop Action")
+                                    .trim_end_matches(']')
+                                    .trim()
+                            ),
                             exec: match map.get("Exec") {
                                 Some(exec) => {
                                     let mut exec = exec.to_string();
@@ -132,13 +184,20 @@ impl DesktopEntry {
                                 }
                                 None => continue,
                             },
+                            command: match map.get("Exec") {
+                                Some(exec) => {
+                                    command(exec, &entry.name, Some(entry.icon.as_str()), path)
+                                }
+                                None => continue,
+                            },
                             path: entry.path.clone(),
-                            name: match map.get("Name") {
+                            name: match locale::get(&map, "Name", locale) {
                                 Some(name) => name.to_string(),
                                 None => continue,
                             },
-                            keywords: map
-                                .get("Keywords")
+                            generic_name: entry.generic_name.clone(),
+                            categories: entry.categories.clone(),
+                            keywords: locale::get(&map, "Keywords", locale)
                                 .map(|keywords| {
                                     keywords
                                         .split(';')
@@ -152,101 +211,195 @@ impl DesktopEntry {
                                 .get("Terminal")
                                 .map(|val| val.to_lowercase() == "true")
                                 .unwrap_or(false),
-                            offset: i as i64,
-                        })
+                            visibility: entry.visibility.clone(),
+                            actions: Vec::new(),
+                        };
+                        entry.actions.push(action);
                     }
                 }
             }
 
-            ret.push(entry);
-            ret
+            vec![entry]
         } else {
             Vec::new()
         }
     }
+
+    /// Whether the entry only hides the one of the same id from a directory read before, with
+    /// nothing to run of its own
+    pub fn is_tombstone(&self) -> bool {
+        self.exec.is_empty() && self.command.is_empty()
+    }
 }
 
-pub fn scrubber(config: &Config) -> Result<Vec<(DesktopEntry, u64)>, Box<dyn std::error::Error>> {
-    // Create iterator over all the files in the XDG_DATA_DIRS
-    // XDG compliancy is cool
-    let user_path = match env::var("XDG_DATA_HOME") {
-        Ok(data_home) => {
-            format!("{}/applications/", data_home)
-        }
+/// A file with `Hidden=true` or `NoDisplay=true` and without what an application needs, like the
+/// copy of the user made only to hide the application of the system
+fn tombstone(id: &str, sections: &[Vec<&str>]) -> Option<DesktopEntry> {
+    let section = sections
+        .iter()
+        .find(|section| section[0].starts_with("[Desktop Entry]"))?;
+    let map = section
+        .iter()
+        .skip(1)
+        .filter_map(|line| line.split_once('='))
+        .collect::<HashMap<_, _>>();
+
+    let visibility = Visibility::from_map(&map);
+    if !visibility.hides() {
+        return None;
+    }
+    Some(DesktopEntry {
+        id: id.to_string(),
+        exec: String::new(),
+        command: Vec::new(),
+        path: None,
+        name: String::new(),
+        generic_name: None,
+        categories: Vec::new(),
+        keywords: Vec::new(),
+        desc: None,
+        icon: String::new(),
+        term: false,
+        visibility,
+        actions: Vec::new(),
+    })
+}
+
+fn command(exec: &str, name: &str, icon: Option<&str>, file: &Path) -> Vec<String> {
+    let fields = exec::Fields { name, icon, file };
+    exec::parse(&exec::unescape(exec), &fields).unwrap_or_else(|| {
+        log::warn!("Failed to parse the Exec key of {}", file.display());
+        Vec::new()
+    })
+}
+
+/// The data directory of the user, where the desktop files of the user and the user installed
+/// Flatpaks are
+pub fn data_home() -> String {
+    match env::var("XDG_DATA_HOME") {
+        Ok(data_home) => data_home,
         Err(_) => {
             format!(
-                "{}/.local/share/applications/",
+                "{}/.local/share",
                 env::var("HOME").expect("Unable to determine home directory!")
             )
         }
+    }
+}
+
+/// The directory of the desktop files of the user, which override the ones of the system
+fn user_dir() -> String {
+    format!("{}/applications/", data_home())
+}
+
+fn flatpak_dirs() -> Vec<String> {
+    vec![
+        "/var/lib/flatpak/exports/share/applications/".to_string(),
+        format!("{}/flatpak/exports/share/applications/", data_home()),
+    ]
+}
+
+fn snap_dirs() -> Vec<String> {
+    vec!["/var/lib/snapd/desktop/applications/".to_string()]
+}
+
+/// All the directories the desktop files are read from, the ones read later overriding the ones
+/// read before
+pub fn dirs(config: &Config) -> Vec<String> {
+    let mut dirs: Vec<String> = match env::var("XDG_DATA_DIRS") {
+        Ok(data_dirs) => data_dirs
+            .split(':')
+            .map(|dir| format!("{}/applications/", dir.trim_end_matches('/')))
+            .collect(),
+        Err(_) => vec!["/usr/share/applications/".to_string()],
     };
 
-    let mut entries: HashMap<String, DesktopEntry> = match env::var("XDG_DATA_DIRS") {
-        Ok(data_dirs) => {
-            // The vec for all the DirEntry objects
-            let mut paths = Vec::new();
-            // Parse the XDG_DATA_DIRS variable and list files of all the paths
-            for dir in data_dirs.split(':') {
-                match fs::read_dir(format!("{}/applications/", dir)) {
-                    Ok(dir) => {
-                        paths.extend(dir);
-                    }
-                    Err(why) => {
-                        eprintln!("Error reading directory {}: {}", dir, why);
-                    }
+    // The exports are usually in `XDG_DATA_DIRS` already, but not when the session was started
+    // without the profile scripts of Flatpak and Snap
+    for (enabled, extra) in [(config.flatpak, flatpak_dirs()), (config.snap, snap_dirs())] {
+        if enabled {
+            for dir in extra {
+                if !dirs.contains(&dir) {
+                    dirs.push(dir);
                 }
             }
-            // Make sure the list of paths isn't empty
-            if paths.is_empty() {
-                return Err("No valid desktop file dirs found!".into());
-            }
-
-            // Return it
-            paths
+        } else {
+            dirs.retain(|dir| !extra.contains(dir));
         }
-        Err(_) => fs::read_dir("/usr/share/applications")?.collect(),
     }
-    .into_iter()
-    .filter_map(|entry| {
-        let entry = match entry {
-            Ok(entry) => entry,
-            Err(_why) => return None,
+
+    dirs.push(user_dir());
+    dirs
+}
+
+pub fn scrubber(config: &Config) -> Result<Vec<(DesktopEntry, u64)>, Box<dyn std::error::Error>> {
+    // Only the files that changed since the last startup are parsed
+    let mut cache = Cache::load(format!(
+        "desktop_actions: {}, locale: {}",
+        config.desktop_actions,
+        locale::locale().unwrap_or_default()
+    ));
+
+    let mut entries: HashMap<String, DesktopEntry> = HashMap::new();
+    let mut found = false;
+
+    for dir in dirs(config) {
+        let dir_entries = match fs::read_dir(&dir) {
+            Ok(dir_entries) => dir_entries,
+            // The directories of the package managers that aren't installed
+            Err(why) if why.kind() == io::ErrorKind::NotFound => {
+                log::debug!("Skipping directory {}: {}", dir, why);
+                continue;
+            }
+            Err(why) => {
+                log::warn!("Error reading directory {}: {}", dir, why);
+                continue;
+            }
         };
-        let entries = DesktopEntry::from_dir_entry(&entry, config);
-        Some(
-            entries
-                .into_iter()
-                .map(|entry| (format!("{}{}", entry.name, entry.icon), entry)),
-        )
-    })
-    .flatten()
-    .collect();
+        found = true;
 
-    // Go through user directory desktop files for overrides
-    match fs::read_dir(&user_path) {
-        Ok(dir_entries) => entries.extend(
+        // Keyed by the id of the desktop file, so a file of the user replaces the one of the
+        // system with the same name, even when it is only there to hide it
+        entries.extend(
             dir_entries
-                .into_iter()
-                .filter_map(|entry| {
-                    let entry = match entry {
-                        Ok(entry) => entry,
-                        Err(_why) => return None,
-                    };
-                    let entries = DesktopEntry::from_dir_entry(&entry, config);
-                    Some(
-                        entries
-                            .into_iter()
-                            .map(|entry| (format!("{}{}", entry.name, entry.icon), entry)),
-                    )
+                .filter_map(|entry| entry.ok())
+                .flat_map(|entry| {
+                    cache.entries(&entry.path(), |path| DesktopEntry::from_path(path, config))
                 })
-                .flatten(),
-        ),
-        Err(why) => eprintln!("Error reading directory {}: {}", user_path, why),
+                .map(|entry| (entry.id.clone(), entry)),
+        );
     }
+    cache.save();
 
+    // Make sure the list of paths isn't empty
+    if !found {
+        return Err("No valid desktop file dirs found!".into());
+    }
+
+    if config.appimages {
+        let appimages = appimage::entries(config, entries.values());
+        entries.extend(appimages.into_iter().map(|entry| (entry.id.clone(), entry)));
+    }
+
+    let filter = Filter::new(config);
+    let icons = Icons::load();
+
+    // The ids stay the same when the entries are scanned again, so a match found before still
+    // refers to the same entry
     Ok(entries
         .into_iter()
-        .enumerate()
-        .map(|(i, (_, entry))| (entry, i as u64))
+        .filter(|(_, entry)| {
+            !entry.is_tombstone() && entry.visibility.shown(config) && filter.allows(entry)
+        })
+        .map(|(key, mut entry)| {
+            entry.icon = icons.resolve(&entry.icon);
+            for action in &mut entry.actions {
+                action.icon = icons.resolve(&action.icon);
+            }
+
+            let mut hasher = DefaultHasher::new();
+            key.hash(&mut hasher);
+            (entry, hasher.finish())
+        })
         .collect())
 }
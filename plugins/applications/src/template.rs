@@ -0,0 +1,128 @@
+//! The `title` and `description` templates of the config, like `{name}[ ({generic_name})]`. The
+//! fields are put in where their names are between braces, and the parts between square brackets
+//! are left out when a field in them is empty.
+
+use std::{iter::Peekable, str::Chars};
+
+use crate::scrubber::DesktopEntry;
+
+fn field(entry: &DesktopEntry, name: &str) -> Option<String> {
+    Some(match name {
+        "name" => entry.name.clone(),
+        "generic_name" => entry.generic_name.clone().unwrap_or_default(),
+        "comment" => entry.desc.clone().unwrap_or_default(),
+        "categories" => entry.categories.join(", "),
+        "keywords" => entry.keywords.join(", "),
+        "exec" => entry.exec.trim().to_string(),
+        "id" => entry.id.clone(),
+        _ => return None,
+    })
+}
+
+pub fn render(template: &str, entry: &DesktopEntry) -> String {
+    render_part(&mut template.chars().peekable(), entry).0
+}
+
+/// The text up to the end of the optional part it is in, with whether a field in it was empty
+fn render_part(chars: &mut Peekable<Chars>, entry: &DesktopEntry) -> (String, bool) {
+    let mut text = String::new();
+    let mut empty = false;
+
+    while let Some(c) = chars.next() {
+        match c {
+            '{' => {
+                let name = chars.by_ref().take_while(|&c| c != '}').collect::<String>();
+                match field(entry, &name) {
+                    Some(value) => {
+                        empty |= value.is_empty();
+                        text.push_str(&value);
+                    }
+                    // Left in so the mistake shows
+                    None => text.push_str(&format!("{{{}}}", name)),
+                }
+            }
+            '[' => {
+                let (part, part_empty) = render_part(chars, entry);
+                if !part_empty {
+                    text.push_str(&part);
+                }
+            }
+            ']' => break,
+            // For the braces and brackets meant literally
+            '\\' => text.extend(chars.next()),
+            _ => text.push(c),
+        }
+    }
+
+    (text, empty)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::visibility::Visibility;
+
+    fn entry(generic_name: Option<&str>) -> DesktopEntry {
+        DesktopEntry {
+            id: "firefox.desktop".to_string(),
+            exec: "firefox %u ".to_string(),
+            command: Vec::new(),
+            path: None,
+            name: "Firefox".to_string(),
+            generic_name: generic_name.map(str::to_string),
+            categories: vec!["Network".to_string(), "WebBrowser".to_string()],
+            keywords: Vec::new(),
+            desc: None,
+            icon: "firefox".to_string(),
+            term: false,
+            visibility: Visibility::from_map(&HashMap::new()),
+            actions: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn puts_in_the_fields() {
+        assert_eq!(
+            render("{name}: {categories} ({exec})", &entry(None)),
+            "Firefox: Network, WebBrowser (firefox %u)"
+        );
+    }
+
+    #[test]
+    fn leaves_out_optional_parts_with_empty_fields() {
+        let template = "{name}[ ({generic_name})]";
+        assert_eq!(render(template, &entry(None)), "Firefox");
+        assert_eq!(
+            render(template, &entry(Some("Web Browser"))),
+            "Firefox (Web Browser)"
+        );
+    }
+
+    #[test]
+    fn nests_optional_parts() {
+        let template = "{name}[ - {generic_name}[, {comment}]]";
+        assert_eq!(
+            render(template, &entry(Some("Web Browser"))),
+            "Firefox - Web Browser"
+        );
+        assert_eq!(render(template, &entry(None)), "Firefox");
+    }
+
+    #[test]
+    fn leaves_unknown_fields_in() {
+        assert_eq!(
+            render("{name} {version}", &entry(None)),
+            "Firefox {version}"
+        );
+    }
+
+    #[test]
+    fn escapes_braces_and_brackets() {
+        assert_eq!(
+            render(r"\{name\} \[{name}\] \\", &entry(None)),
+            r"{name} [Firefox] \"
+        );
+    }
+}
@@ -0,0 +1,91 @@
+//! Running the entries in a terminal. The terminals don't agree on how they take the command to
+//! run, so the command line of the terminal is a template with `{}` where the command goes.
+
+use std::io;
+
+use serde::Deserialize;
+
+use crate::{detach, visibility, Config};
+
+/// The terminals looked for when none is configured, in this order
+const SENSIBLE_TERMINALS: &[&[&str]] = &[
+    &["alacritty", "-e", "{}"],
+    &["foot", "{}"],
+    &["kitty", "{}"],
+    &["wezterm", "start", "--", "{}"],
+    &["gnome-terminal", "--", "{}"],
+    &["konsole", "-e", "{}"],
+    &["wterm", "-e", "{}"],
+    &["xterm", "-e", "{}"],
+];
+
+#[derive(Deserialize, Clone)]
+#[serde(untagged)]
+pub enum TerminalCommand {
+    /// Split at the spaces, like `foot -e {}`
+    Line(String),
+    /// For arguments with spaces in them, like `["wezterm", "start", "--", "{}"]`
+    Args(Vec<String>),
+}
+
+impl TerminalCommand {
+    fn template(&self) -> Vec<&str> {
+        match self {
+            Self::Line(line) => line.split_whitespace().collect(),
+            Self::Args(args) => args.iter().map(String::as_str).collect(),
+        }
+    }
+}
+
+/// The command line of the terminal with the command put in. An argument that is just `{}` is
+/// replaced by the arguments of the command, `{}` in an argument by the command quoted for a shell,
+/// and the command goes at the end when there is no `{}`.
+fn fill(template: &[&str], command: &[String]) -> Vec<String> {
+    let mut args = Vec::new();
+    let mut placed = false;
+
+    for arg in template {
+        if *arg == "{}" {
+            args.extend(command.iter().cloned());
+            placed = true;
+        } else if arg.contains("{}") {
+            let quoted = command
+                .iter()
+                .map(|arg| format!("'{}'", arg.replace('\'', r"'\''")))
+                .collect::<Vec<_>>()
+                .join(" ");
+            args.push(arg.replace("{}", &quoted));
+            placed = true;
+        } else {
+            args.push(arg.to_string());
+        }
+    }
+    if !placed {
+        args.extend(command.iter().cloned());
+    }
+    args
+}
+
+/// Run the command in the configured terminal, or the first of the known ones that is installed
+pub fn run(config: &Config, id: &str, command: &[String]) -> io::Result<()> {
+    let spawn = |template: &[&str]| detach::spawn(config, id, &fill(template, command), None);
+
+    if let Some(terminal) = &config.terminal_command {
+        return spawn(&terminal.template());
+    }
+    // Kept from before the template, for the terminals that take `-e`
+    if let Some(terminal) = &config.terminal {
+        return spawn(&[terminal.as_str(), "-e", "{}"]);
+    }
+
+    match SENSIBLE_TERMINALS
+        .iter()
+        .find(|terminal| visibility::installed(terminal[0]))
+    {
+        Some(terminal) => spawn(terminal),
+        None => Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            "None of the known terminals is installed, set terminal_command in the config",
+        )),
+    }
+}
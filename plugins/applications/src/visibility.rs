@@ -0,0 +1,93 @@
+//! Which of the entries to leave out of the list, by the keys of the desktop files meant for that.
+//! The keys are kept with the entries and checked after the desktop files of the user overrode the
+//! ones of the system, so a copy with `Hidden=true` hides the application.
+
+use std::{collections::HashMap, env, fs, os::unix::fs::PermissionsExt, path::Path};
+
+use serde::{Deserialize, Serialize};
+
+use crate::Config;
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Visibility {
+    no_display: bool,
+    /// The application is meant to be treated as uninstalled
+    hidden: bool,
+    /// The desktops to only show the application on, any if empty
+    only_show_in: Vec<String>,
+    not_show_in: Vec<String>,
+    /// A program that has to be installed for the application to work
+    try_exec: Option<String>,
+}
+
+fn list(value: Option<&&str>) -> Vec<String> {
+    value
+        .map(|value| {
+            value
+                .split(';')
+                .filter(|desktop| !desktop.is_empty())
+                .map(|desktop| desktop.to_string())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Whether the program is an executable file, looked for in `PATH` without a `/` in it
+pub fn installed(program: &str) -> bool {
+    let executable = |path: &Path| {
+        fs::metadata(path)
+            .is_ok_and(|metadata| metadata.is_file() && metadata.permissions().mode() & 0o111 != 0)
+    };
+
+    if program.contains('/') {
+        return executable(Path::new(program));
+    }
+    env::var_os("PATH")
+        .is_some_and(|paths| env::split_paths(&paths).any(|dir| executable(&dir.join(program))))
+}
+
+impl Visibility {
+    /// The keys of the `[Desktop Entry]` section
+    pub fn from_map(map: &HashMap<&str, &str>) -> Self {
+        Self {
+            no_display: map.get("NoDisplay") == Some(&"true"),
+            hidden: map.get("Hidden") == Some(&"true"),
+            only_show_in: list(map.get("OnlyShowIn")),
+            not_show_in: list(map.get("NotShowIn")),
+            try_exec: map.get("TryExec").map(|try_exec| try_exec.to_string()),
+        }
+    }
+
+    /// Whether one of the keys hiding the application is set, whatever the config says about them
+    pub fn hides(&self) -> bool {
+        self.no_display || self.hidden
+    }
+
+    pub fn shown(&self, config: &Config) -> bool {
+        if (self.no_display && config.honor_no_display) || (self.hidden && config.honor_hidden) {
+            return false;
+        }
+
+        if config.honor_show_in {
+            let current = env::var("XDG_CURRENT_DESKTOP").unwrap_or_default();
+            let current = current.split(':').filter(|desktop| !desktop.is_empty());
+            let is_current = |desktops: &[String]| {
+                current
+                    .clone()
+                    .any(|desktop| desktops.iter().any(|listed| listed == desktop))
+            };
+
+            if !self.only_show_in.is_empty() && !is_current(&self.only_show_in) {
+                return false;
+            }
+            if is_current(&self.not_show_in) {
+                return false;
+            }
+        }
+
+        match &self.try_exec {
+            Some(try_exec) if config.honor_try_exec => installed(try_exec),
+            _ => true,
+        }
+    }
+}
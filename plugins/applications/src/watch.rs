@@ -0,0 +1,75 @@
+//! Keeping the entries current while the plugin keeps running, like in `anyrun-bridge`. The
+//! application directories are scanned again whenever desktop files or AppImages are added,
+//! changed or removed in them.
+
+use std::{
+    io,
+    path::PathBuf,
+    sync::{Arc, RwLock},
+    thread,
+    time::Duration,
+};
+
+use anyrun_plugin::log;
+use inotify::{Inotify, WatchMask};
+
+use crate::{appimage, scrubber, scrubber::DesktopEntry, Config};
+
+/// How long to wait for more changes after one, as installing a package changes many files at once
+const SETTLE_TIME: Duration = Duration::from_millis(500);
+
+pub fn start(config: Config, entries: Arc<RwLock<Vec<(DesktopEntry, u64)>>>) {
+    let mut inotify = match watch(&config) {
+        Ok(inotify) => inotify,
+        Err(why) => {
+            log::warn!("Failed to watch the application directories: {}", why);
+            return;
+        }
+    };
+
+    thread::spawn(move || {
+        let mut buffer = [0; 4096];
+        loop {
+            if let Err(why) = inotify.read_events_blocking(&mut buffer) {
+                log::warn!("Failed to watch the application directories: {}", why);
+                return;
+            }
+            thread::sleep(SETTLE_TIME);
+            // The changes that came in meanwhile are covered by the same scan
+            while inotify
+                .read_events(&mut buffer)
+                .is_ok_and(|mut events| events.next().is_some())
+            {}
+
+            match scrubber::scrubber(&config) {
+                Ok(scanned) => *entries.write().unwrap() = scanned,
+                Err(why) => log::error!("Failed to load desktop entries: {}", why),
+            }
+        }
+    });
+}
+
+fn watch(config: &Config) -> io::Result<Inotify> {
+    let inotify = Inotify::init()?;
+    let mask = WatchMask::CREATE
+        | WatchMask::DELETE
+        | WatchMask::CLOSE_WRITE
+        | WatchMask::MOVED_FROM
+        | WatchMask::MOVED_TO;
+
+    let mut dirs = scrubber::dirs(config)
+        .into_iter()
+        .map(PathBuf::from)
+        .collect::<Vec<_>>();
+    if config.appimages {
+        dirs.extend(appimage::dirs(config));
+    }
+
+    // The directories that don't exist yet aren't watched
+    for dir in dirs {
+        if dir.is_dir() {
+            inotify.watches().add(&dir, mask)?;
+        }
+    }
+    Ok(inotify)
+}
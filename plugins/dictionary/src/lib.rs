@@ -1,4 +1,4 @@
-use std::fs;
+use std::sync::Mutex;
 
 use abi_stable::std_types::{ROption, RString, RVec};
 use anyrun_plugin::*;
@@ -19,6 +19,12 @@ impl Default for Config {
     }
 }
 
+pub struct State {
+    config: Config,
+    /// The previews of the latest matches, by their ids
+    previews: Mutex<Vec<String>>,
+}
+
 #[derive(Deserialize)]
 struct ApiResponse {
     meanings: Vec<Meaning>,
@@ -34,23 +40,53 @@ struct Meaning {
 #[derive(Deserialize)]
 struct Definition {
     definition: String,
+    #[serde(default)]
+    example: Option<String>,
+    #[serde(default)]
+    synonyms: Vec<String>,
+}
+
+impl Definition {
+    /// The definition with an example and synonyms, if the dictionary has them
+    fn preview(&self, part_of_speech: &str) -> String {
+        let mut preview = format!("{}\n{}", part_of_speech, self.definition);
+        if let Some(example) = &self.example {
+            preview.push_str(&format!("\n\n“{}”", example));
+        }
+        if !self.synonyms.is_empty() {
+            preview.push_str(&format!("\n\nSynonyms: {}", self.synonyms.join(", ")));
+        }
+        preview
+    }
 }
 
 #[init]
-pub fn init(config_dir: RString) -> Config {
-    match fs::read_to_string(format!("{}/dictionary.ron", config_dir)) {
-        Ok(content) => ron::from_str(&content).unwrap_or_default(),
-        Err(_) => Config::default(),
+pub fn init(config_dir: RString) -> State {
+    let config: Config = plugin_config(&config_dir, "dictionary");
+
+    prefix::set(&config.prefix);
+    State {
+        config,
+        previews: Mutex::new(Vec::new()),
     }
 }
 
 #[handler]
 pub fn handler(_match: Match) -> HandleResult {
-    HandleResult::Copy(_match.title.into_bytes())
+    HandleResult::Copy(_match.title.into_bytes(), ROption::RNone)
+}
+
+#[preview]
+pub fn preview(selection: Match, state: &State) -> Option<Preview> {
+    let id = selection.id.into_option()?;
+    let previews = state.previews.lock().unwrap();
+    let preview = previews.get(id as usize)?;
+    Some(Preview::Text(preview.as_str().into(), false))
 }
 
 #[get_matches]
-pub fn get_matches(input: RString, config: &Config) -> RVec<Match> {
+pub fn get_matches(input: RString, state: &State) -> RVec<Match> {
+    let config = &state.config;
     let input = if let Some(input) = input.strip_prefix(&config.prefix) {
         input.trim()
     } else {
@@ -64,38 +100,58 @@ pub fn get_matches(input: RString, config: &Config) -> RVec<Match> {
         Ok(response) => match response.json() {
             Ok(response) => response,
             Err(why) => {
-                eprintln!("Error deserializing response: {}", why);
+                log::error!("Error deserializing response: {}", why);
                 return RVec::new();
             }
         },
         Err(why) => {
-            eprintln!("Error fetching dictionary result: {}", why);
+            // Without the word in the URL, so that it is shown once and not for every query
+            report::error!("Error fetching dictionary result: {}", why.without_url());
             return RVec::new();
         }
     };
 
-    responses
+    // The previews are of the newer input by now
+    if cancel::is_cancelled() {
+        return RVec::new();
+    }
+
+    let definitions = responses
         .into_iter()
-        .flat_map(|response| {
-            response
-                .meanings
+        .flat_map(|response| response.meanings)
+        .flat_map(|meaning| {
+            let part_of_speech = meaning.part_of_speech;
+            meaning
+                .definitions
                 .into_iter()
-                .flat_map(|meaning| {
-                    meaning
-                        .definitions
-                        .into_iter()
-                        .map(|definition| Match {
-                            title: definition.definition.into(),
-                            description: ROption::RSome(meaning.part_of_speech.clone().into()),
-                            use_pango: false,
-                            icon: ROption::RSome("accessories-dictionary".into()),
-                            id: ROption::RNone,
-                        })
-                        .collect::<RVec<_>>()
-                })
-                .collect::<RVec<_>>()
+                .map(move |definition| (part_of_speech.clone(), definition))
         })
         .take(config.max_entries)
+        .collect::<Vec<_>>();
+
+    // The matches are identified by the index of their preview
+    *state.previews.lock().unwrap() = definitions
+        .iter()
+        .map(|(part_of_speech, definition)| definition.preview(part_of_speech))
+        .collect();
+
+    definitions
+        .into_iter()
+        .enumerate()
+        .map(|(id, (part_of_speech, definition))| Match {
+            title: definition.definition.into(),
+            description: ROption::RSome(part_of_speech.into()),
+            title_pango: false,
+            description_pango: false,
+            icon: ROption::RSome("accessories-dictionary".into()),
+            id: ROption::RSome(id as u64),
+            actions: RVec::new(),
+            action: ROption::RNone,
+            image: ROption::RNone,
+            widgets: RVec::new(),
+            priority: 0,
+            pinned: false,
+        })
         .collect()
 }
 
@@ -0,0 +1,320 @@
+use std::{
+    io::{self, BufRead, BufReader, Write},
+    process::{Child, ChildStdin, Command, Stdio},
+    sync::{
+        mpsc::{self, Receiver, RecvTimeoutError},
+        Mutex,
+    },
+    thread,
+    time::Duration,
+};
+
+use abi_stable::std_types::{ROption, RString, RVec};
+use anyrun_plugin::*;
+use serde::{Deserialize, Serialize};
+
+#[derive(Deserialize, Default)]
+struct Config {
+    programs: Vec<Program>,
+}
+
+#[derive(Deserialize)]
+struct Program {
+    /// The executable and its arguments
+    command: Vec<String>,
+    /// Only the queries starting with this are sent to the program, without it
+    #[serde(default)]
+    prefix: Option<String>,
+    /// How many milliseconds the program may take to answer before it is restarted
+    #[serde(default = "Program::default_timeout")]
+    timeout: u64,
+}
+
+impl Program {
+    fn default_timeout() -> u64 {
+        3000
+    }
+}
+
+pub struct State {
+    programs: Vec<(Program, Mutex<Option<Process>>)>,
+    /// The latest matches with the index of the program they came from, by the ids of the matches
+    matches: Mutex<Vec<(usize, ExternalMatch)>>,
+}
+
+/// A running program, asked one line at a time
+struct Process {
+    child: Child,
+    stdin: ChildStdin,
+    /// The lines of its stdout, read on a thread of their own so that waiting for them can time
+    /// out. Disconnected once the program exits.
+    lines: Receiver<io::Result<String>>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "snake_case")]
+enum Request<'a> {
+    Query {
+        input: &'a str,
+    },
+    Select {
+        selection: &'a ExternalMatch,
+        action: Option<u64>,
+        input: &'a str,
+    },
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum Response {
+    Matches(Vec<ExternalMatch>),
+    Result(ExternalResult),
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+struct ExternalMatch {
+    title: String,
+    #[serde(default)]
+    description: Option<String>,
+    #[serde(default)]
+    icon: Option<String>,
+    #[serde(default)]
+    id: Option<u64>,
+    #[serde(default)]
+    actions: Vec<ExternalAction>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+struct ExternalAction {
+    title: String,
+    #[serde(default)]
+    icon: Option<String>,
+    id: u64,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum ExternalResult {
+    Close,
+    Refresh,
+    Copy(String),
+    Stdout(String),
+    SetInput(String),
+    Type(String),
+}
+
+impl Process {
+    fn spawn(command: &[String]) -> io::Result<Self> {
+        let (program, args) = command
+            .split_first()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "The command is empty"))?;
+        let mut child = Command::new(program)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()?;
+
+        let stdout = BufReader::new(child.stdout.take().unwrap());
+        let (sender, lines) = mpsc::channel();
+        thread::spawn(move || {
+            for line in stdout.lines() {
+                if sender.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+
+        Ok(Self {
+            stdin: child.stdin.take().unwrap(),
+            lines,
+            child,
+        })
+    }
+
+    /// Send the request and read the line the program answers with, failing if it takes longer
+    /// than the timeout
+    fn request(&mut self, request: &Request, timeout: Duration) -> io::Result<Response> {
+        writeln!(self.stdin, "{}", serde_json::to_string(request)?)?;
+        self.stdin.flush()?;
+
+        let line = match self.lines.recv_timeout(timeout) {
+            Ok(line) => line?,
+            Err(RecvTimeoutError::Timeout) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    "The program took too long to answer",
+                ))
+            }
+            Err(RecvTimeoutError::Disconnected) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    "The program exited",
+                ))
+            }
+        };
+        Ok(serde_json::from_str(&line)?)
+    }
+}
+
+impl State {
+    /// Ask the program, starting it first if it isn't running. A program that exited, or took
+    /// too long to answer, is started again on the next request.
+    fn request(&self, index: usize, request: &Request) -> Option<Response> {
+        let (program, process) = &self.programs[index];
+        let mut process = process.lock().unwrap();
+
+        if process.is_none() {
+            match Process::spawn(&program.command) {
+                Ok(spawned) => *process = Some(spawned),
+                Err(why) => {
+                    report::error!("Failed to start {}: {}", program.command.join(" "), why);
+                    return None;
+                }
+            }
+        }
+
+        let timeout = Duration::from_millis(program.timeout);
+        match process.as_mut().unwrap().request(request, timeout) {
+            Ok(response) => Some(response),
+            Err(why) => {
+                report::error!("{} failed: {}", program.command.join(" "), why);
+                if let Some(mut process) = process.take() {
+                    let _ = process.child.kill();
+                    let _ = process.child.wait();
+                }
+                None
+            }
+        }
+    }
+}
+
+#[init]
+fn init(config_dir: RString) -> State {
+    let config: Config = plugin_config(&config_dir, "external");
+
+    prefix::set_single(
+        config
+            .programs
+            .iter()
+            .map(|program| program.prefix.as_deref()),
+    );
+
+    State {
+        programs: config
+            .programs
+            .into_iter()
+            .map(|program| (program, Mutex::new(None)))
+            .collect(),
+        matches: Mutex::new(Vec::new()),
+    }
+}
+
+#[info]
+fn info() -> PluginInfo {
+    PluginInfo {
+        name: "External".into(),
+        icon: "application-x-executable".into(),
+    }
+}
+
+#[get_matches]
+fn get_matches(input: RString, state: &State) -> RVec<Match> {
+    let mut matches = Vec::new();
+
+    for (index, (program, _)) in state.programs.iter().enumerate() {
+        let input = match prefix::strip(&input, program.prefix.as_deref()) {
+            Some(input) => input,
+            None => continue,
+        };
+
+        match state.request(index, &Request::Query { input }) {
+            Some(Response::Matches(found)) => {
+                matches.extend(found.into_iter().map(|_match| (index, _match)))
+            }
+            Some(Response::Result(_)) => {
+                report::error!(
+                    "{} answered a query with a result",
+                    program.command.join(" ")
+                )
+            }
+            None => (),
+        }
+
+        if cancel::is_cancelled() {
+            return RVec::new();
+        }
+    }
+
+    let result = matches
+        .iter()
+        .enumerate()
+        .map(|(id, (_, _match))| Match {
+            title: _match.title.clone().into(),
+            description: _match.description.clone().map(RString::from).into(),
+            title_pango: false,
+            description_pango: false,
+            icon: _match.icon.clone().map(RString::from).into(),
+            // The matches are found again by their index, as the programs may reuse their ids
+            id: ROption::RSome(id as u64),
+            actions: _match
+                .actions
+                .iter()
+                .map(|action| Action {
+                    title: action.title.clone().into(),
+                    icon: action.icon.clone().map(RString::from).into(),
+                    id: action.id,
+                })
+                .collect(),
+            action: ROption::RNone,
+            image: ROption::RNone,
+            widgets: RVec::new(),
+            priority: 0,
+            pinned: false,
+        })
+        .collect();
+
+    *state.matches.lock().unwrap() = matches;
+    result
+}
+
+#[handler]
+fn handler(selection: Match, input: RString, state: &State) -> HandleResult {
+    let found = selection
+        .id
+        .into_option()
+        .and_then(|id| state.matches.lock().unwrap().get(id as usize).cloned());
+    let (index, _match) = match found {
+        Some(found) => found,
+        None => return HandleResult::Close,
+    };
+
+    let request = Request::Select {
+        selection: &_match,
+        action: selection.action.into_option(),
+        input: &input,
+    };
+    match state.request(index, &request) {
+        Some(Response::Result(result)) => match result {
+            ExternalResult::Close => HandleResult::Close,
+            ExternalResult::Refresh => HandleResult::Refresh(false),
+            ExternalResult::Copy(text) => {
+                HandleResult::Copy(text.into_bytes().into(), ROption::RNone)
+            }
+            ExternalResult::Stdout(text) => HandleResult::Stdout(text.into_bytes().into()),
+            ExternalResult::SetInput(input) => HandleResult::SetInput(input.into()),
+            ExternalResult::Type(text) => HandleResult::Type(text.into()),
+        },
+        // Staying open for the error `request` reported about the program
+        _ => HandleResult::Refresh(false),
+    }
+}
+
+#[deinit]
+fn deinit(state: &mut State) {
+    for (_, process) in &state.programs {
+        if let Some(mut process) = process.lock().unwrap().take() {
+            let _ = process.child.kill();
+            let _ = process.child.wait();
+        }
+    }
+}
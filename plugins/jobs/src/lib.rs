@@ -0,0 +1,104 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use abi_stable::std_types::{ROption, RString, RVec};
+use anyrun_plugin::*;
+use serde::Deserialize;
+
+#[derive(Deserialize)]
+struct Config {
+    prefix: String,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            prefix: ":jobs".to_string(),
+        }
+    }
+}
+
+#[init]
+fn init(config_dir: RString) -> Config {
+    let config: Config = plugin_config(&config_dir, "jobs");
+
+    prefix::set(&config.prefix);
+    config
+}
+
+#[info]
+fn info() -> PluginInfo {
+    PluginInfo {
+        name: "Jobs".into(),
+        icon: "system-run".into(),
+    }
+}
+
+/// How long the job has been running, like `3m 20s`
+fn elapsed(started: u64) -> String {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |time| time.as_secs());
+    let seconds = now.saturating_sub(started);
+
+    match seconds {
+        0..=59 => format!("{}s", seconds),
+        60..=3599 => format!("{}m {}s", seconds / 60, seconds % 60),
+        _ => format!("{}h {}m", seconds / 3600, seconds / 60 % 60),
+    }
+}
+
+#[get_matches]
+fn get_matches(input: RString, config: &Config) -> RVec<Match> {
+    let filter = match input.strip_prefix(&config.prefix) {
+        Some(filter) => filter.trim().to_lowercase(),
+        None => return RVec::new(),
+    };
+
+    let jobs = jobs::list();
+    // Keep the progress and the running times current
+    if !jobs.is_empty() {
+        refresh::after(Duration::from_secs(1));
+    }
+
+    jobs.into_iter()
+        .filter(|(_, job)| job.title.to_lowercase().contains(&filter))
+        .map(|(group, job)| Match {
+            title: job.title.into(),
+            description: ROption::RSome(
+                format!(
+                    "Running for {}, select to cancel: {}",
+                    elapsed(job.started),
+                    job.command.join(" ")
+                )
+                .into(),
+            ),
+            title_pango: false,
+            description_pango: false,
+            icon: ROption::RSome("process-stop".into()),
+            id: ROption::RSome(group as u64),
+            actions: RVec::new(),
+            action: ROption::RNone,
+            image: ROption::RNone,
+            widgets: match job.progress {
+                Some(progress) => vec![Widget::Progress(
+                    f64::from(progress) / 100.0,
+                    ROption::RSome(format!("{}%", progress).into()),
+                )]
+                .into(),
+                None => RVec::new(),
+            },
+            priority: 0,
+            pinned: false,
+        })
+        .collect()
+}
+
+#[handler]
+fn handler(selection: Match) -> HandleResult {
+    if let Err(why) = jobs::cancel(selection.id.unwrap() as i32) {
+        report::error!("Failed to cancel {}: {}", selection.title, why);
+    }
+
+    // Show the jobs that are left
+    HandleResult::RefreshPlugin
+}
@@ -3,7 +3,7 @@ use anyrun_plugin::{anyrun_interface::HandleResult, *};
 use fuzzy_matcher::FuzzyMatcher;
 use kidex_common::IndexEntry;
 use serde::Deserialize;
-use std::{fs, os::unix::prelude::OsStrExt, process::Command};
+use std::{os::unix::prelude::OsStrExt, process::Command};
 
 #[derive(Deserialize)]
 struct Config {
@@ -39,32 +39,45 @@ impl From<u64> for IndexAction {
     }
 }
 
+fn run_action(action: IndexAction, index_entry: &IndexEntry, state: &mut State) -> HandleResult {
+    match action {
+        IndexAction::Open => {
+            // For resuming it later
+            recent::record(&index_entry.path.to_string_lossy());
+            if let Err(why) = Command::new("xdg-open").arg(&index_entry.path).spawn() {
+                log::error!("Error running xdg-open: {}", why);
+            }
+            HandleResult::Close
+        }
+        IndexAction::CopyPath => HandleResult::Copy(
+            index_entry.path.clone().into_os_string().as_bytes().into(),
+            ROption::RNone,
+        ),
+        IndexAction::Back => {
+            state.selection = None;
+            HandleResult::Refresh(false)
+        }
+    }
+}
+
 #[handler]
 pub fn handler(selection: Match, state: &mut State) -> HandleResult {
-    match &state.selection {
-        Some(index_entry) => match selection.id.unwrap().into() {
-            IndexAction::Open => {
-                if let Err(why) = Command::new("xdg-open").arg(&index_entry.path).spawn() {
-                    println!("Error running xdg-open: {}", why);
-                }
-                HandleResult::Close
-            }
-            IndexAction::CopyPath => {
-                HandleResult::Copy(index_entry.path.clone().into_os_string().as_bytes().into())
-            }
-            IndexAction::Back => {
-                state.selection = None;
-                HandleResult::Refresh(false)
-            }
-        },
+    match state.selection.clone() {
+        Some(index_entry) => run_action(selection.id.unwrap().into(), &index_entry, state),
         None => {
             let (_, index_entry) = state
                 .index
                 .iter()
                 .find(|(id, _)| selection.id == ROption::RSome(*id as u64))
-                .unwrap();
+                .unwrap()
+                .clone();
 
-            state.selection = Some(index_entry.clone());
+            // The actions from the menu skip choosing them from the matches
+            if let ROption::RSome(action) = selection.action {
+                return run_action(action.into(), &index_entry, state);
+            }
+
+            state.selection = Some(index_entry);
             HandleResult::Refresh(true)
         }
     }
@@ -72,14 +85,11 @@ pub fn handler(selection: Match, state: &mut State) -> HandleResult {
 
 #[init]
 pub fn init(config_dir: RString) -> State {
-    let config = match fs::read_to_string(format!("{}/kidex.ron", config_dir)) {
-        Ok(content) => ron::from_str(&content).unwrap_or_default(),
-        Err(_) => Config::default(),
-    };
+    let config: Config = plugin_config(&config_dir, "kidex");
     let index = match kidex_common::util::get_index(None) {
         Ok(index) => index.into_iter().enumerate().collect(),
         Err(why) => {
-            println!("Failed to get kidex index: {}", why);
+            report::error!("Failed to get kidex index: {}", why);
             Vec::new()
         }
     };
@@ -99,23 +109,44 @@ pub fn get_matches(input: RString, state: &State) -> RVec<Match> {
                 Match {
                     title: "Open File".into(),
                     description: ROption::RSome(path.clone().into()),
-                    use_pango: false,
+                    title_pango: false,
+                    description_pango: false,
                     id: ROption::RSome(IndexAction::Open as u64),
                     icon: ROption::RSome("document-open".into()),
+                    actions: RVec::new(),
+                    action: ROption::RNone,
+                    image: ROption::RNone,
+                    widgets: RVec::new(),
+                    priority: 0,
+                    pinned: false,
                 },
                 Match {
                     title: "Copy Path".into(),
                     description: ROption::RSome(path.into()),
-                    use_pango: false,
+                    title_pango: false,
+                    description_pango: false,
                     id: ROption::RSome(IndexAction::CopyPath as u64),
                     icon: ROption::RSome("edit-copy".into()),
+                    actions: RVec::new(),
+                    action: ROption::RNone,
+                    image: ROption::RNone,
+                    widgets: RVec::new(),
+                    priority: 0,
+                    pinned: false,
                 },
                 Match {
                     title: "Back".into(),
                     description: ROption::RNone,
-                    use_pango: false,
+                    title_pango: false,
+                    description_pango: false,
                     id: ROption::RSome(IndexAction::Back as u64),
                     icon: ROption::RSome("edit-undo".into()),
+                    actions: RVec::new(),
+                    action: ROption::RNone,
+                    image: ROption::RNone,
+                    widgets: RVec::new(),
+                    priority: 0,
+                    pinned: false,
                 },
             ]
             .into()
@@ -133,7 +164,12 @@ pub fn get_matches(input: RString, state: &State) -> RVec<Match> {
                 })
                 .collect::<Vec<_>>();
 
-            index.sort_by(|a, b| b.2.cmp(&a.2));
+            // Equally good matches are in alphabetical order
+            index.sort_by(|a, b| {
+                b.2.cmp(&a.2).then_with(|| {
+                    collate::compare(&a.0.path.to_string_lossy(), &b.0.path.to_string_lossy())
+                })
+            });
 
             index.truncate(state.config.max_entries);
             index
@@ -149,13 +185,32 @@ pub fn get_matches(input: RString, state: &State) -> RVec<Match> {
                         .parent()
                         .map(|path| path.display().to_string().into())
                         .into(),
-                    use_pango: false,
+                    title_pango: false,
+                    description_pango: false,
                     icon: ROption::RSome(if entry_index.directory {
                         "folder".into()
                     } else {
                         "text-x-generic".into()
                     }),
                     id: ROption::RSome(id as u64),
+                    actions: vec![
+                        Action {
+                            title: "Open File".into(),
+                            icon: ROption::RSome("document-open".into()),
+                            id: IndexAction::Open as u64,
+                        },
+                        Action {
+                            title: "Copy Path".into(),
+                            icon: ROption::RSome("edit-copy".into()),
+                            id: IndexAction::CopyPath as u64,
+                        },
+                    ]
+                    .into(),
+                    action: ROption::RNone,
+                    image: ROption::RNone,
+                    widgets: RVec::new(),
+                    priority: 0,
+                    pinned: false,
                 })
                 .collect()
         }
@@ -0,0 +1,290 @@
+use std::{
+    fs,
+    path::Path,
+    process::{Command, Stdio},
+    sync::Mutex,
+};
+
+use abi_stable::std_types::{ROption, RString, RVec};
+use anyrun_plugin::*;
+use mlua::{Function, Lua, RegistryKey, Table, Value};
+
+/// A loaded script, in a Lua state of its own so the globals of the scripts don't mix
+struct Script {
+    name: String,
+    lua: Lua,
+    /// The table the script returned
+    module: RegistryKey,
+    /// The `prefix` of the table, without which the queries don't reach `get_matches`
+    prefix: Option<String>,
+}
+
+pub struct State {
+    scripts: Vec<Mutex<Script>>,
+    /// The tables of the latest matches with the index of the script they came from, by the ids of
+    /// the matches
+    matches: Mutex<Vec<(usize, RegistryKey)>>,
+}
+
+impl Script {
+    fn load(path: &Path) -> Result<Self, String> {
+        let source = fs::read_to_string(path).map_err(|why| why.to_string())?;
+        let name = path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default();
+
+        let lua = Lua::new();
+        let (module, prefix) = module(&lua, &source, &name).map_err(|why| why.to_string())?;
+
+        Ok(Self {
+            name,
+            lua,
+            module,
+            prefix,
+        })
+    }
+
+    /// The tables of the matches the script has for the input
+    fn get_matches(&self, input: &str) -> mlua::Result<Vec<Table<'_>>> {
+        let module: Table = self.lua.registry_value(&self.module)?;
+        let get_matches: Function = module.get("get_matches")?;
+        get_matches.call(input)
+    }
+
+    /// Call the handler of the script with the table of the match, closing anyrun if there is none
+    fn handle(
+        &self,
+        selection: &RegistryKey,
+        action: Option<u64>,
+        input: &str,
+    ) -> mlua::Result<HandleResult> {
+        let module: Table = self.lua.registry_value(&self.module)?;
+        let handler = match module.get::<_, Option<Function>>("handler")? {
+            Some(handler) => handler,
+            None => return Ok(HandleResult::Close),
+        };
+        let selection: Table = self.lua.registry_value(selection)?;
+        to_result(handler.call((selection, action, input))?)
+    }
+}
+
+/// Run the script with the `anyrun` table set, keeping the table it returns
+fn module(lua: &Lua, source: &str, name: &str) -> mlua::Result<(RegistryKey, Option<String>)> {
+    lua.globals().set("anyrun", stdlib(lua)?)?;
+    let module: Table = lua.load(source).set_name(name).eval()?;
+    let prefix = module.get("prefix")?;
+    Ok((lua.create_registry_value(module)?, prefix))
+}
+
+/// The functions the scripts get in the `anyrun` table
+fn stdlib(lua: &Lua) -> mlua::Result<Table<'_>> {
+    let anyrun = lua.create_table()?;
+
+    // Start the command in the shell without waiting for it
+    anyrun.set(
+        "spawn",
+        lua.create_function(|_, command: String| {
+            Command::new("sh")
+                .arg("-c")
+                .arg(command)
+                .stdout(Stdio::null())
+                .spawn()
+                .map(|_| ())
+                .map_err(mlua::Error::external)
+        })?,
+    )?;
+    // Run the command in the shell and give back what it printed
+    anyrun.set(
+        "run",
+        lua.create_function(|_, command: String| {
+            output(Command::new("sh").arg("-c").arg(command))
+        })?,
+    )?;
+    anyrun.set(
+        "clipboard",
+        lua.create_function(|_, ()| output(Command::new("wl-paste").arg("--no-newline")))?,
+    )?;
+
+    Ok(anyrun)
+}
+
+fn output(command: &mut Command) -> mlua::Result<String> {
+    let output = command
+        .stdin(Stdio::null())
+        .output()
+        .map_err(mlua::Error::external)?;
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+fn to_match(table: &Table, id: u64) -> mlua::Result<Match> {
+    let actions = table
+        .get::<_, Option<Vec<Table>>>("actions")?
+        .unwrap_or_default()
+        .into_iter()
+        .map(|action| {
+            Ok(Action {
+                title: action.get::<_, String>("title")?.into(),
+                icon: action
+                    .get::<_, Option<String>>("icon")?
+                    .map(RString::from)
+                    .into(),
+                id: action.get("id")?,
+            })
+        })
+        .collect::<mlua::Result<_>>()?;
+
+    Ok(Match {
+        title: table.get::<_, String>("title")?.into(),
+        description: table
+            .get::<_, Option<String>>("description")?
+            .map(RString::from)
+            .into(),
+        title_pango: false,
+        description_pango: false,
+        icon: table
+            .get::<_, Option<String>>("icon")?
+            .map(RString::from)
+            .into(),
+        id: ROption::RSome(id),
+        actions,
+        action: ROption::RNone,
+        image: ROption::RNone,
+        widgets: RVec::new(),
+        priority: 0,
+        pinned: false,
+    })
+}
+
+/// What the handler returned, `nil`, `"close"`, `"refresh"` or a table like `{ copy = "text" }`
+fn to_result(value: Value) -> mlua::Result<HandleResult> {
+    let unknown =
+        || mlua::Error::RuntimeError("The handler returned an unknown result".to_string());
+
+    match value {
+        Value::Nil => Ok(HandleResult::Close),
+        Value::String(result) => match result.to_str()? {
+            "close" => Ok(HandleResult::Close),
+            "refresh" => Ok(HandleResult::Refresh(false)),
+            _ => Err(unknown()),
+        },
+        Value::Table(result) => {
+            if let Some(text) = result.get::<_, Option<String>>("copy")? {
+                Ok(HandleResult::Copy(text.into_bytes().into(), ROption::RNone))
+            } else if let Some(text) = result.get::<_, Option<String>>("stdout")? {
+                Ok(HandleResult::Stdout(text.into_bytes().into()))
+            } else if let Some(input) = result.get::<_, Option<String>>("set_input")? {
+                Ok(HandleResult::SetInput(input.into()))
+            } else if let Some(text) = result.get::<_, Option<String>>("type")? {
+                Ok(HandleResult::Type(text.into()))
+            } else {
+                Err(unknown())
+            }
+        }
+        _ => Err(unknown()),
+    }
+}
+
+#[init]
+fn init(config_dir: RString) -> State {
+    let mut paths = fs::read_dir(Path::new(config_dir.as_str()).join("lua"))
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| Some(entry.ok()?.path()))
+        .filter(|path| path.extension().is_some_and(|extension| extension == "lua"))
+        .collect::<Vec<_>>();
+    paths.sort();
+
+    let scripts = paths
+        .iter()
+        .filter_map(|path| match Script::load(path) {
+            Ok(script) => Some(script),
+            Err(why) => {
+                report::error!("Failed to load {}: {}", path.display(), why);
+                None
+            }
+        })
+        .collect::<Vec<_>>();
+
+    prefix::set_single(scripts.iter().map(|script| script.prefix.as_deref()));
+
+    State {
+        scripts: scripts.into_iter().map(Mutex::new).collect(),
+        matches: Mutex::new(Vec::new()),
+    }
+}
+
+#[info]
+fn info() -> PluginInfo {
+    PluginInfo {
+        name: "Lua".into(),
+        icon: "text-x-script".into(),
+    }
+}
+
+#[get_matches]
+fn get_matches(input: RString, state: &State) -> RVec<Match> {
+    let mut matches = RVec::new();
+    let mut tables = Vec::new();
+
+    for (index, script) in state.scripts.iter().enumerate() {
+        let script = script.lock().unwrap();
+        // Free the tables of the matches replaced by the last query
+        script.lua.expire_registry_values();
+
+        let input = match prefix::strip(&input, script.prefix.as_deref()) {
+            Some(input) => input,
+            None => continue,
+        };
+
+        let found = match script.get_matches(input) {
+            Ok(found) => found,
+            Err(why) => {
+                report::error!("{} failed: {}", script.name, why);
+                continue;
+            }
+        };
+        for table in found {
+            // The matches are found again by their index, the tables can hold anything
+            match to_match(&table, tables.len() as u64)
+                .and_then(|_match| Ok((_match, script.lua.create_registry_value(table)?)))
+            {
+                Ok((_match, key)) => {
+                    matches.push(_match);
+                    tables.push((index, key));
+                }
+                Err(why) => report::error!("{} gave an invalid match: {}", script.name, why),
+            }
+        }
+
+        if cancel::is_cancelled() {
+            return RVec::new();
+        }
+    }
+
+    *state.matches.lock().unwrap() = tables;
+    matches
+}
+
+#[handler]
+fn handler(selection: Match, input: RString, state: &State) -> HandleResult {
+    let matches = state.matches.lock().unwrap();
+    let (index, key) = match selection
+        .id
+        .into_option()
+        .and_then(|id| matches.get(id as usize))
+    {
+        Some(found) => found,
+        None => return HandleResult::Close,
+    };
+
+    let script = state.scripts[*index].lock().unwrap();
+    match script.handle(key, selection.action.into_option(), &input) {
+        Ok(result) => result,
+        Err(why) => {
+            report::error!("{} failed: {}", script.name, why);
+            // The Lua error is only readable with anyrun still open
+            HandleResult::Refresh(false)
+        }
+    }
+}
@@ -0,0 +1,288 @@
+use std::{fs, path::Path, process::Command};
+
+use abi_stable::std_types::{ROption, RString, RVec};
+use anyrun_plugin::*;
+use nix::sys::statvfs::statvfs;
+use serde::Deserialize;
+
+#[derive(Deserialize)]
+struct Config {
+    prefix: String,
+    bar_width: usize,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            prefix: ":disk".to_string(),
+            bar_width: 20,
+        }
+    }
+}
+
+struct State {
+    config: Config,
+    /// The mounts of the latest matches, which the ids refer to
+    mounts: Vec<Mount>,
+}
+
+#[derive(Clone, Copy)]
+enum Action {
+    Open,
+    Unmount,
+    Eject,
+}
+
+impl Action {
+    const ALL: [Action; 3] = [Action::Open, Action::Unmount, Action::Eject];
+}
+
+struct Mount {
+    device: String,
+    mount_point: String,
+    size: u64,
+    used: u64,
+    /// The whole drive the filesystem is on, if it can be removed
+    removable_drive: Option<String>,
+}
+
+impl Mount {
+    fn usage_bar(&self, width: usize) -> String {
+        let fraction = if self.size > 0 {
+            self.used as f64 / self.size as f64
+        } else {
+            0.0
+        };
+        let filled = (fraction * width as f64).round() as usize;
+
+        format!(
+            "{}{} {:.0}% ({} of {} used)",
+            "█".repeat(filled),
+            "░".repeat(width.saturating_sub(filled)),
+            fraction * 100.0,
+            format_size(self.used),
+            format_size(self.size)
+        )
+    }
+}
+
+fn format_size(bytes: u64) -> String {
+    const UNITS: &[&str] = &["B", "KiB", "MiB", "GiB", "TiB"];
+
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+
+    format!("{:.1} {}", size, UNITS[unit])
+}
+
+/// The mounted filesystems backed by a block device, skipping the virtual ones
+fn mounts() -> Vec<Mount> {
+    let content = fs::read_to_string("/proc/self/mounts").unwrap_or_default();
+    let mut mounts: Vec<Mount> = Vec::new();
+
+    for line in content.lines() {
+        let mut fields = line.split(' ');
+        let (device, mount_point) = match (fields.next(), fields.next()) {
+            (Some(device), Some(mount_point)) if device.starts_with("/dev/") => {
+                (device, unescape(mount_point))
+            }
+            _ => continue,
+        };
+
+        // Bind mounts and subvolumes show up once per mount point, only list the device once
+        if mounts.iter().any(|mount| mount.device == device) {
+            continue;
+        }
+
+        let stat = match statvfs(mount_point.as_str()) {
+            Ok(stat) => stat,
+            Err(_) => continue,
+        };
+        let block_size = stat.fragment_size() as u64;
+        let size = stat.blocks() as u64 * block_size;
+
+        mounts.push(Mount {
+            device: device.to_string(),
+            mount_point,
+            size,
+            used: size - stat.blocks_free() as u64 * block_size,
+            removable_drive: removable_drive(device),
+        });
+    }
+
+    mounts
+}
+
+/// `/proc/self/mounts` escapes spaces and such as octal
+fn unescape(field: &str) -> String {
+    let mut unescaped = String::new();
+    let mut chars = field.chars();
+
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            let code = chars.by_ref().take(3).collect::<String>();
+            match u8::from_str_radix(&code, 8) {
+                Ok(byte) => unescaped.push(byte as char),
+                Err(_) => unescaped.push_str(&code),
+            }
+        } else {
+            unescaped.push(c);
+        }
+    }
+
+    unescaped
+}
+
+/// The drive of a partition like `/dev/sdb1`, if the drive is removable or connected over USB
+fn removable_drive(device: &str) -> Option<String> {
+    let name = Path::new(device).file_name()?.to_str()?;
+    let sys_path = fs::canonicalize(format!("/sys/class/block/{}", name)).ok()?;
+
+    // Partitions are in the directory of their drive
+    let drive_path = if sys_path.join("partition").exists() {
+        sys_path.parent()?.to_path_buf()
+    } else {
+        sys_path
+    };
+
+    let removable = fs::read_to_string(drive_path.join("removable"))
+        .is_ok_and(|removable| removable.trim() == "1");
+    let usb = drive_path.components().any(|component| {
+        component
+            .as_os_str()
+            .to_str()
+            .is_some_and(|component| component.starts_with("usb"))
+    });
+
+    if !removable && !usb {
+        return None;
+    }
+
+    Some(format!("/dev/{}", drive_path.file_name()?.to_str()?))
+}
+
+#[init]
+fn init(config_dir: RString) -> State {
+    let config: Config = plugin_config(&config_dir, "mounts");
+    prefix::set(&config.prefix);
+
+    State {
+        config,
+        mounts: Vec::new(),
+    }
+}
+
+#[info]
+fn info() -> PluginInfo {
+    PluginInfo {
+        name: "Mounts".into(),
+        icon: "drive-harddisk".into(),
+    }
+}
+
+#[get_matches]
+fn get_matches(input: RString, state: &mut State) -> RVec<Match> {
+    let filter = match input.strip_prefix(&state.config.prefix) {
+        Some(filter) => filter.trim(),
+        None => return RVec::new(),
+    };
+
+    state.mounts = mounts()
+        .into_iter()
+        .filter(|mount| mount.mount_point.contains(filter) || mount.device.contains(filter))
+        .collect();
+
+    let mut matches = Vec::new();
+
+    for (index, mount) in state.mounts.iter().enumerate() {
+        for (action_index, action) in Action::ALL.iter().enumerate() {
+            let (title, description, icon) = match (action, &mount.removable_drive) {
+                (Action::Open, removable_drive) => (
+                    mount.mount_point.clone(),
+                    mount.usage_bar(state.config.bar_width),
+                    if removable_drive.is_some() {
+                        "drive-removable-media"
+                    } else {
+                        "drive-harddisk"
+                    },
+                ),
+                (Action::Unmount, Some(_)) => (
+                    format!("Unmount {}", mount.mount_point),
+                    mount.device.clone(),
+                    "media-eject",
+                ),
+                (Action::Eject, Some(drive)) => (
+                    format!("Eject {}", drive),
+                    format!("Unmount {} and power off the drive", mount.mount_point),
+                    "media-eject",
+                ),
+                _ => continue,
+            };
+
+            matches.push(Match {
+                title: title.into(),
+                description: ROption::RSome(description.into()),
+                title_pango: false,
+                description_pango: false,
+                icon: ROption::RSome(icon.into()),
+                id: ROption::RSome((index * Action::ALL.len() + action_index) as u64),
+                actions: RVec::new(),
+                action: ROption::RNone,
+                image: ROption::RNone,
+                widgets: RVec::new(),
+                priority: 0,
+                pinned: false,
+            });
+        }
+    }
+
+    matches.into()
+}
+
+#[handler]
+fn handler(selection: Match, state: &State) -> HandleResult {
+    // The matches may be from before the latest query, like the ones pinned or in the history
+    let (mount, action) = match selection.id.into_option().and_then(|id| {
+        let id = id as usize;
+        let mount = state.mounts.get(id / Action::ALL.len())?;
+        Some((mount, Action::ALL[id % Action::ALL.len()]))
+    }) {
+        Some(selected) => selected,
+        None => return HandleResult::Refresh(false),
+    };
+
+    let commands = match action {
+        Action::Open => vec![vec!["xdg-open", mount.mount_point.as_str()]],
+        Action::Unmount => vec![vec!["udisksctl", "unmount", "-b", mount.device.as_str()]],
+        // The drive can only be powered off once it is unmounted
+        Action::Eject => vec![
+            vec!["udisksctl", "unmount", "-b", mount.device.as_str()],
+            vec![
+                "udisksctl",
+                "power-off",
+                "-b",
+                mount.removable_drive.as_deref().unwrap_or_default(),
+            ],
+        ],
+    };
+
+    for command in commands {
+        match Command::new(command[0]).args(&command[1..]).status() {
+            Ok(status) if status.success() => (),
+            Ok(_) => {
+                log::error!("`{}` failed", command.join(" "));
+                break;
+            }
+            Err(why) => {
+                log::error!("Failed to run {}: {}", command[0], why);
+                break;
+            }
+        }
+    }
+
+    HandleResult::Close
+}
@@ -0,0 +1,234 @@
+use std::{fs, path::Path, process::Command};
+
+use abi_stable::std_types::{ROption, RString, RVec};
+use anyrun_plugin::*;
+use serde::Deserialize;
+
+/// The power profiles of power-profiles-daemon, with the names `powerprofilesctl` uses
+const PROFILES: &[(&str, &str)] = &[
+    ("performance", "Performance"),
+    ("balanced", "Balanced"),
+    ("power-saver", "Power Saver"),
+];
+
+#[derive(Deserialize)]
+struct Config {
+    prefix: String,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            prefix: ":power".to_string(),
+        }
+    }
+}
+
+struct Battery {
+    name: String,
+    capacity: u32,
+    status: String,
+    /// Until empty while discharging, until full while charging
+    hours_left: Option<f64>,
+}
+
+impl Battery {
+    fn read(path: &Path) -> Option<Self> {
+        let read = |file: &str| {
+            fs::read_to_string(path.join(file))
+                .ok()
+                .map(|content| content.trim().to_string())
+        };
+        let read_num = |file: &str| read(file).and_then(|content| content.parse::<f64>().ok());
+
+        if read("type")? != "Battery" {
+            return None;
+        }
+
+        let status = read("status").unwrap_or_else(|| "Unknown".to_string());
+
+        // Batteries report either energy and power, or charge and current
+        let (now, full, rate) = match (read_num("energy_now"), read_num("power_now")) {
+            (Some(now), Some(rate)) => (now, read_num("energy_full"), rate),
+            _ => (
+                read_num("charge_now")?,
+                read_num("charge_full"),
+                read_num("current_now")?,
+            ),
+        };
+        let hours_left = match status.as_str() {
+            _ if rate <= 0.0 => None,
+            "Discharging" => Some(now / rate),
+            "Charging" => full.map(|full| (full - now) / rate),
+            _ => None,
+        };
+
+        Some(Self {
+            name: path.file_name()?.to_string_lossy().to_string(),
+            capacity: read_num("capacity")? as u32,
+            status,
+            hours_left,
+        })
+    }
+
+    fn icon(&self) -> String {
+        let level = match self.capacity {
+            90.. => "full",
+            50..=89 => "good",
+            20..=49 => "low",
+            5..=19 => "caution",
+            _ => "empty",
+        };
+
+        if self.status == "Charging" {
+            format!("battery-{}-charging", level)
+        } else {
+            format!("battery-{}", level)
+        }
+    }
+
+    fn description(&self) -> String {
+        match self.hours_left {
+            Some(hours) => {
+                let minutes = (hours * 60.0).round() as u32;
+                format!(
+                    "{}, {}h {}m until {}",
+                    self.status,
+                    minutes / 60,
+                    minutes % 60,
+                    if self.status == "Charging" {
+                        "full"
+                    } else {
+                        "empty"
+                    }
+                )
+            }
+            None => self.status.clone(),
+        }
+    }
+}
+
+fn batteries() -> Vec<Battery> {
+    let mut batteries = fs::read_dir("/sys/class/power_supply")
+        .map(|entries| {
+            entries
+                .filter_map(|entry| Battery::read(&entry.ok()?.path()))
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+
+    batteries.sort_by(|a, b| a.name.cmp(&b.name));
+    batteries
+}
+
+/// The active power profile, if power-profiles-daemon is available
+fn active_profile() -> Option<String> {
+    let output = Command::new("powerprofilesctl").arg("get").output().ok()?;
+
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+#[init]
+fn init(config_dir: RString) -> Config {
+    let config: Config = plugin_config(&config_dir, "power");
+
+    prefix::set(&config.prefix);
+    config
+}
+
+#[info]
+fn info() -> PluginInfo {
+    PluginInfo {
+        name: "Power".into(),
+        icon: "battery".into(),
+    }
+}
+
+#[get_matches]
+fn get_matches(input: RString, config: &Config) -> RVec<Match> {
+    let filter = match input.strip_prefix(&config.prefix) {
+        Some(filter) => filter.trim().to_lowercase(),
+        None => return RVec::new(),
+    };
+
+    let mut matches = Vec::new();
+
+    if filter.is_empty() {
+        matches.extend(batteries().into_iter().map(|battery| {
+            Match {
+                title: format!("{}: {}%", battery.name, battery.capacity).into(),
+                description: ROption::RSome(battery.description().into()),
+                title_pango: false,
+                description_pango: false,
+                icon: ROption::RSome(battery.icon().into()),
+                id: ROption::RNone,
+                actions: RVec::new(),
+                action: ROption::RNone,
+                image: ROption::RNone,
+                widgets: vec![Widget::Progress(
+                    f64::from(battery.capacity) / 100.0,
+                    ROption::RNone,
+                )]
+                .into(),
+                priority: 0,
+                pinned: false,
+            }
+        }));
+    }
+
+    if let Some(active) = active_profile() {
+        matches.extend(
+            PROFILES
+                .iter()
+                .enumerate()
+                .filter(|(_, (name, title))| {
+                    name.contains(&filter) || title.to_lowercase().contains(&filter)
+                })
+                .map(|(index, (name, title))| Match {
+                    title: (*title).into(),
+                    description: ROption::RSome(
+                        if *name == active {
+                            "The active power profile"
+                        } else {
+                            "Switch to this power profile"
+                        }
+                        .into(),
+                    ),
+                    title_pango: false,
+                    description_pango: false,
+                    icon: ROption::RSome(format!("power-profile-{}-symbolic", name).into()),
+                    id: ROption::RSome(index as u64),
+                    actions: RVec::new(),
+                    action: ROption::RNone,
+                    image: ROption::RNone,
+                    widgets: RVec::new(),
+                    priority: 0,
+                    pinned: false,
+                }),
+        );
+    }
+
+    matches.into()
+}
+
+#[handler]
+fn handler(selection: Match) -> HandleResult {
+    // Only the profiles have an id, the batteries are just for showing
+    if let ROption::RSome(index) = selection.id {
+        let (name, _) = PROFILES[index as usize];
+
+        match Command::new("powerprofilesctl")
+            .args(["set", name])
+            .status()
+        {
+            Ok(status) if status.success() => (),
+            Ok(_) => log::error!("powerprofilesctl failed to switch to {}", name),
+            Err(why) => log::error!("Failed to run powerprofilesctl: {}", why),
+        }
+    }
+
+    HandleResult::Close
+}
@@ -0,0 +1,201 @@
+use std::process::Command;
+
+use abi_stable::std_types::{ROption, RString, RVec};
+use anyrun_plugin::*;
+use serde::Deserialize;
+
+#[derive(Deserialize)]
+struct Config {
+    prefix: String,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            prefix: ":print".to_string(),
+        }
+    }
+}
+
+struct State {
+    config: Config,
+    /// The items of the latest matches, which the ids refer to
+    items: Vec<Item>,
+}
+
+enum Item {
+    Printer { name: String, enabled: bool },
+    Job { id: String },
+}
+
+struct Job {
+    id: String,
+    printer: String,
+    user: String,
+    size: String,
+}
+
+/// Run one of the CUPS tools with the untranslated output, so that it can be parsed
+fn lpstat(arg: &str) -> String {
+    Command::new("lpstat")
+        .arg(arg)
+        .env("LC_ALL", "C")
+        .output()
+        .map(|output| String::from_utf8_lossy(&output.stdout).to_string())
+        .unwrap_or_default()
+}
+
+/// The printers with whether they are accepting jobs
+fn printers() -> Vec<(String, bool)> {
+    lpstat("-p")
+        .lines()
+        .filter_map(|line| {
+            // The indented lines are the reasons for the state of the printer above
+            let rest = line.strip_prefix("printer ")?;
+            let name = rest.split_whitespace().next()?;
+            Some((name.to_string(), !rest.contains(" disabled")))
+        })
+        .collect()
+}
+
+fn jobs() -> Vec<Job> {
+    lpstat("-o")
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let id = fields.next()?;
+            let user = fields.next()?;
+            let size = fields.next()?;
+
+            // The job ids are the name of the printer and a number
+            let (printer, _) = id.rsplit_once('-')?;
+
+            Some(Job {
+                id: id.to_string(),
+                printer: printer.to_string(),
+                user: user.to_string(),
+                size: size.to_string(),
+            })
+        })
+        .collect()
+}
+
+fn run(command: &str, arg: &str) {
+    match Command::new(command).arg(arg).status() {
+        Ok(status) if status.success() => (),
+        Ok(_) => log::error!("`{} {}` failed", command, arg),
+        Err(why) => log::error!("Failed to run {}: {}", command, why),
+    }
+}
+
+#[init]
+fn init(config_dir: RString) -> State {
+    let config: Config = plugin_config(&config_dir, "printers");
+    prefix::set(&config.prefix);
+
+    State {
+        config,
+        items: Vec::new(),
+    }
+}
+
+#[info]
+fn info() -> PluginInfo {
+    PluginInfo {
+        name: "Printers".into(),
+        icon: "printer".into(),
+    }
+}
+
+#[get_matches]
+fn get_matches(input: RString, state: &mut State) -> RVec<Match> {
+    let filter = match input.strip_prefix(&state.config.prefix) {
+        Some(filter) => filter.trim().to_lowercase(),
+        None => return RVec::new(),
+    };
+    let matches_filter = |printer: &str| printer.to_lowercase().contains(&filter);
+
+    let mut matches = Vec::new();
+    state.items.clear();
+
+    for (name, enabled) in printers()
+        .into_iter()
+        .filter(|(name, _)| matches_filter(name))
+    {
+        matches.push(Match {
+            title: name.clone().into(),
+            description: ROption::RSome(
+                if enabled {
+                    "Ready, select to pause"
+                } else {
+                    "Paused, select to resume"
+                }
+                .into(),
+            ),
+            title_pango: false,
+            description_pango: false,
+            icon: ROption::RSome(if enabled { "printer" } else { "printer-paused" }.into()),
+            id: ROption::RSome(state.items.len() as u64),
+            actions: RVec::new(),
+            action: ROption::RNone,
+            image: ROption::RNone,
+            widgets: RVec::new(),
+            priority: 0,
+            pinned: false,
+        });
+        state.items.push(Item::Printer { name, enabled });
+    }
+
+    for job in jobs()
+        .into_iter()
+        .filter(|job| matches_filter(&job.printer))
+    {
+        matches.push(Match {
+            title: format!("Cancel {}", job.id).into(),
+            description: ROption::RSome(
+                format!("{} bytes from {} on {}", job.size, job.user, job.printer).into(),
+            ),
+            title_pango: false,
+            description_pango: false,
+            icon: ROption::RSome("process-stop".into()),
+            id: ROption::RSome(state.items.len() as u64),
+            actions: RVec::new(),
+            action: ROption::RNone,
+            image: ROption::RNone,
+            widgets: RVec::new(),
+            priority: 0,
+            pinned: false,
+        });
+        state.items.push(Item::Job { id: job.id });
+    }
+
+    matches.into()
+}
+
+#[handler]
+fn handler(selection: Match, state: &State) -> HandleResult {
+    // The matches may be from before the latest query, like the ones pinned or in the history
+    let item = match selection
+        .id
+        .into_option()
+        .and_then(|id| state.items.get(id as usize))
+    {
+        Some(item) => item,
+        None => return HandleResult::Refresh(false),
+    };
+
+    match item {
+        Item::Printer {
+            name,
+            enabled: true,
+        } => run("cupsdisable", name),
+        Item::Printer {
+            name,
+            enabled: false,
+        } => run("cupsenable", name),
+        Item::Job { id } => run("cancel", id),
+    }
+
+    // Show the new state of the printers and the jobs
+    HandleResult::RefreshPlugin
+}
@@ -1,4 +1,4 @@
-use std::{env, fs};
+use std::{cmp::Reverse, env};
 
 use abi_stable::std_types::{ROption, RString, RVec};
 use anyrun_plugin::*;
@@ -43,15 +43,12 @@ pub fn init(config_dir: RString) -> State {
         Box::new(Dummy)
     };
 
+    let config: Config = plugin_config(&config_dir, "randr");
+    prefix::set(&config.prefix);
+
     State {
         randr,
-        config: match fs::read_to_string(format!("{}/randr.ron", config_dir)) {
-            Ok(content) => ron::from_str(&content).unwrap_or_default(),
-            Err(why) => {
-                eprintln!("Error reading Randr config file: {}", why);
-                Config::default()
-            }
-        },
+        config,
         inner: InnerState::None,
     }
 }
@@ -122,9 +119,16 @@ pub fn get_matches(input: RString, state: &State) -> RVec<Match> {
                 description: ROption::RSome(
                     format!("{}x{} at {}x{}", mon.width, mon.height, mon.x, mon.y).into(),
                 ),
-                use_pango: false,
+                title_pango: false,
+                description_pango: false,
                 icon: ROption::RSome("object-flip-horizontal".into()),
                 id: ROption::RSome(mon.id),
+                actions: RVec::new(),
+                action: ROption::RNone,
+                image: ROption::RNone,
+                widgets: RVec::new(),
+                priority: 0,
+                pinned: false,
             })
             .collect::<RVec<_>>(),
         InnerState::Position(mon) => {
@@ -146,12 +150,19 @@ pub fn get_matches(input: RString, state: &State) -> RVec<Match> {
                             ]
                             .iter()
                             .map(|configure| Match {
-                                title: format!("{} {}", configure.to_string(), _mon.name).into(),
+                                title: format!("{} {}", configure, _mon.name).into(),
                                 description: ROption::RNone,
-                                use_pango: false,
+                                title_pango: false,
+                                description_pango: false,
                                 icon: ROption::RSome(configure.icon().into()),
                                 // Store 2 32 bit IDs in the single 64 bit integer, a bit of a hack
                                 id: ROption::RSome(_mon.id << 32 | Into::<u64>::into(configure)),
+                                actions: RVec::new(),
+                                action: ROption::RNone,
+                                image: ROption::RNone,
+                                widgets: RVec::new(),
+                                priority: 0,
+                                pinned: false,
                             })
                             .collect::<Vec<_>>(),
                         )
@@ -163,17 +174,31 @@ pub fn get_matches(input: RString, state: &State) -> RVec<Match> {
             vec.push(Match {
                 title: "Reset position".into(),
                 description: ROption::RNone,
-                use_pango: false,
+                title_pango: false,
+                description_pango: false,
                 icon: ROption::RSome(Configure::Zero.icon().into()),
                 id: ROption::RSome((&Configure::Zero).into()),
+                actions: RVec::new(),
+                action: ROption::RNone,
+                image: ROption::RNone,
+                widgets: RVec::new(),
+                priority: 0,
+                pinned: false,
             });
 
             vec.push(Match {
                 title: "Back".into(),
                 description: ROption::RSome("Return to the previous menu".into()),
-                use_pango: false,
+                title_pango: false,
+                description_pango: false,
                 icon: ROption::RSome("edit-undo".into()),
                 id: ROption::RSome(u64::MAX),
+                actions: RVec::new(),
+                action: ROption::RNone,
+                image: ROption::RNone,
+                widgets: RVec::new(),
+                priority: 0,
+                pinned: false,
             });
 
             vec
@@ -187,7 +212,7 @@ pub fn get_matches(input: RString, state: &State) -> RVec<Match> {
     })
     .collect::<Vec<_>>();
 
-    vec.sort_by(|a, b| b.1.cmp(&a.1));
+    vec.sort_by_key(|(_, score)| Reverse(*score));
 
     vec.truncate(state.config.max_entries);
 
@@ -0,0 +1,202 @@
+use std::future::Future;
+
+use abi_stable::std_types::{ROption, RString, RVec};
+use anyrun_plugin::*;
+use gio::{glib, prelude::*};
+use serde::Deserialize;
+
+#[derive(Deserialize)]
+struct Config {
+    prefix: String,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            prefix: ":usb".to_string(),
+        }
+    }
+}
+
+struct State {
+    config: Config,
+    /// The keys of the volumes in the latest matches, which the ids refer to. The volumes themselves
+    /// can't be kept, as they are bound to the thread that got the matches.
+    volumes: Vec<String>,
+}
+
+/// Identifies the volume between the threads of `get_matches` and the handler
+fn volume_key(volume: &gio::Volume) -> String {
+    volume
+        .uuid()
+        .or_else(|| volume.identifier("unix-device"))
+        .unwrap_or_else(|| volume.name())
+        .to_string()
+}
+
+/// The volumes on removable drives and the ones without a drive, like MTP devices
+fn removable_volumes() -> Vec<gio::Volume> {
+    gio::VolumeMonitor::get()
+        .volumes()
+        .into_iter()
+        .filter(|volume| volume.drive().is_none_or(|drive| drive.is_removable()))
+        .collect()
+}
+
+fn icon_name(icon: gio::Icon) -> Option<String> {
+    icon.downcast::<gio::ThemedIcon>()
+        .ok()?
+        .names()
+        .first()
+        .map(|name| name.to_string())
+}
+
+/// Run an operation of GIO to completion. It gets a context of its own, as the main context is
+/// busy dispatching the events of anyrun while the handler runs.
+fn block_on<T>(future: impl Future<Output = T>) -> T {
+    let context = glib::MainContext::new();
+    context
+        .with_thread_default(|| context.block_on(future))
+        .expect("Failed to acquire a main context")
+}
+
+#[init]
+fn init(config_dir: RString) -> State {
+    let config: Config = plugin_config(&config_dir, "removable");
+    prefix::set(&config.prefix);
+
+    State {
+        config,
+        volumes: Vec::new(),
+    }
+}
+
+#[info]
+fn info() -> PluginInfo {
+    PluginInfo {
+        name: "Removable".into(),
+        icon: "drive-removable-media".into(),
+    }
+}
+
+#[get_matches]
+fn get_matches(input: RString, state: &mut State) -> RVec<Match> {
+    let filter = match input.strip_prefix(&state.config.prefix) {
+        Some(filter) => filter.trim().to_lowercase(),
+        None => return RVec::new(),
+    };
+
+    let volumes = removable_volumes()
+        .into_iter()
+        .filter(|volume| volume.name().to_lowercase().contains(&filter))
+        .collect::<Vec<_>>();
+    state.volumes = volumes.iter().map(volume_key).collect();
+
+    let mut matches = Vec::new();
+
+    for (index, volume) in volumes.iter().enumerate() {
+        let mount = volume.get_mount();
+        let icon = icon_name(volume.icon()).unwrap_or_else(|| "drive-removable-media".to_string());
+
+        matches.push(Match {
+            title: volume.name().to_string().into(),
+            description: ROption::RSome(
+                match mount.as_ref().and_then(|mount| mount.root().path()) {
+                    Some(path) => format!("Mounted at {}", path.display()),
+                    None if mount.is_some() => "Mounted".to_string(),
+                    None => "Mount and open".to_string(),
+                }
+                .into(),
+            ),
+            title_pango: false,
+            description_pango: false,
+            icon: ROption::RSome(icon.into()),
+            id: ROption::RSome(index as u64 * 2),
+            actions: RVec::new(),
+            action: ROption::RNone,
+            image: ROption::RNone,
+            widgets: RVec::new(),
+            priority: 0,
+            pinned: false,
+        });
+
+        if volume.can_eject() || mount.as_ref().is_some_and(|mount| mount.can_unmount()) {
+            matches.push(Match {
+                title: format!("Eject {}", volume.name()).into(),
+                description: ROption::RSome("Unmount and safely remove the device".into()),
+                title_pango: false,
+                description_pango: false,
+                icon: ROption::RSome("media-eject".into()),
+                id: ROption::RSome(index as u64 * 2 + 1),
+                actions: RVec::new(),
+                action: ROption::RNone,
+                image: ROption::RNone,
+                widgets: RVec::new(),
+                priority: 0,
+                pinned: false,
+            });
+        }
+    }
+
+    matches.into()
+}
+
+#[handler]
+fn handler(selection: Match, state: &State) -> HandleResult {
+    let id = selection.id.unwrap();
+    let key = &state.volumes[id as usize / 2];
+
+    let volume = match removable_volumes()
+        .into_iter()
+        .find(|volume| volume_key(volume) == *key)
+    {
+        Some(volume) => volume,
+        None => {
+            log::error!("The device {} is gone", selection.title);
+            return HandleResult::Close;
+        }
+    };
+
+    if id % 2 == 1 {
+        let result = if volume.can_eject() {
+            block_on(volume.eject_with_operation_future(
+                gio::MountUnmountFlags::NONE,
+                None::<&gio::MountOperation>,
+            ))
+        } else if let Some(mount) = volume.get_mount() {
+            block_on(mount.unmount_with_operation_future(
+                gio::MountUnmountFlags::NONE,
+                None::<&gio::MountOperation>,
+            ))
+        } else {
+            Ok(())
+        };
+
+        if let Err(why) = result {
+            log::error!("Failed to eject {}: {}", volume.name(), why);
+        }
+
+        return HandleResult::Close;
+    }
+
+    if volume.get_mount().is_none() {
+        if let Err(why) =
+            block_on(volume.mount_future(gio::MountMountFlags::NONE, None::<&gio::MountOperation>))
+        {
+            log::error!("Failed to mount {}: {}", volume.name(), why);
+            return HandleResult::Close;
+        }
+    }
+
+    // MTP devices only have a path if the FUSE daemon of GVfs is running, so open the URI instead
+    if let Some(mount) = volume.get_mount() {
+        if let Err(why) = gio::AppInfo::launch_default_for_uri(
+            &mount.root().uri(),
+            None::<&gio::AppLaunchContext>,
+        ) {
+            log::error!("Failed to open {}: {}", volume.name(), why);
+        }
+    }
+
+    HandleResult::Close
+}
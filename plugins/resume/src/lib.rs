@@ -0,0 +1,250 @@
+use std::{
+    collections::HashMap,
+    env, fs,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use abi_stable::std_types::{ROption, RString, RVec};
+use anyrun_plugin::*;
+use serde::Deserialize;
+
+#[derive(Deserialize)]
+struct Config {
+    #[serde(default = "Config::default_max_entries")]
+    max_entries: usize,
+    #[serde(default = "Config::default_plugins")]
+    plugins: Vec<String>,
+    #[serde(default)]
+    apps: HashMap<String, App>,
+}
+
+impl Config {
+    fn default_max_entries() -> usize {
+        5
+    }
+
+    fn default_plugins() -> Vec<String> {
+        vec!["Kidex".to_string()]
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            max_entries: Self::default_max_entries(),
+            plugins: Self::default_plugins(),
+            apps: HashMap::new(),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct App {
+    #[serde(default = "App::default_resume")]
+    resume: bool,
+    /// Opens the file instead of the default app, with `{file}` replaced by the path
+    #[serde(default)]
+    command: Option<String>,
+}
+
+impl App {
+    fn default_resume() -> bool {
+        true
+    }
+}
+
+struct Item {
+    path: PathBuf,
+    /// The desktop file of the app opening the file
+    app: Option<String>,
+    /// The name and the icon of the app
+    name: Option<String>,
+    icon: Option<String>,
+}
+
+struct State {
+    config: Config,
+    items: Vec<Item>,
+}
+
+fn xdg_mime(args: &[&str]) -> Option<String> {
+    let output = Command::new("xdg-mime").args(args).output().ok()?;
+    let output = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    (!output.is_empty()).then_some(output)
+}
+
+/// The desktop file of the default app for the type of the file
+fn default_app(path: &Path) -> Option<String> {
+    let mime_type = xdg_mime(&["query", "filetype", path.to_str()?])?;
+    xdg_mime(&["query", "default", &mime_type])
+}
+
+/// The name and the icon of the app from its desktop file
+fn describe_app(desktop_file: &str) -> (Option<String>, Option<String>) {
+    let data_home = env::var("XDG_DATA_HOME")
+        .unwrap_or_else(|_| format!("{}/.local/share", env::var("HOME").unwrap_or_default()));
+    let data_dirs =
+        env::var("XDG_DATA_DIRS").unwrap_or_else(|_| "/usr/local/share:/usr/share".to_string());
+
+    let content = match std::iter::once(data_home.as_str())
+        .chain(data_dirs.split(':'))
+        .find_map(|dir| fs::read_to_string(format!("{}/applications/{}", dir, desktop_file)).ok())
+    {
+        Some(content) => content,
+        None => return (None, None),
+    };
+
+    // Only the main section, the actions have names and icons of their own
+    let entry = content
+        .lines()
+        .skip_while(|line| line.trim() != "[Desktop Entry]")
+        .skip(1)
+        .take_while(|line| !line.starts_with('['));
+    let (mut name, mut icon) = (None, None);
+    for line in entry {
+        if let Some(value) = line.strip_prefix("Name=") {
+            name = Some(value.to_string());
+        } else if let Some(value) = line.strip_prefix("Icon=") {
+            icon = Some(value.to_string());
+        }
+    }
+
+    (name, icon)
+}
+
+#[init]
+fn init(config_dir: RString) -> State {
+    let config: Config = plugin_config(&config_dir, "resume");
+
+    // Take turns between the plugins, as there is nothing telling which file was opened last
+    let recent = config
+        .plugins
+        .iter()
+        .map(|plugin| recent::recent_of(plugin))
+        .collect::<Vec<_>>();
+    let longest = recent.iter().map(Vec::len).max().unwrap_or(0);
+    let mut paths = Vec::new();
+    for index in 0..longest {
+        for path in recent.iter().filter_map(|paths| paths.get(index)) {
+            if !paths.contains(path) {
+                paths.push(path.clone());
+            }
+        }
+    }
+
+    let items = paths
+        .into_iter()
+        .map(PathBuf::from)
+        .filter(|path| path.exists())
+        .map(|path| {
+            let app = default_app(&path);
+            (path, app)
+        })
+        .filter(|(_, app)| {
+            app.as_ref()
+                .and_then(|app| config.apps.get(app))
+                .is_none_or(|app| app.resume)
+        })
+        .take(config.max_entries)
+        .map(|(path, app)| {
+            let (name, icon) = app.as_deref().map_or((None, None), describe_app);
+            Item {
+                path,
+                app,
+                name,
+                icon,
+            }
+        })
+        .collect();
+
+    State { config, items }
+}
+
+#[info]
+fn info() -> PluginInfo {
+    PluginInfo {
+        name: "Resume".into(),
+        icon: "document-open-recent".into(),
+    }
+}
+
+#[get_matches]
+fn get_matches(input: RString, state: &State) -> RVec<Match> {
+    if !input.is_empty() {
+        return RVec::new();
+    }
+
+    state
+        .items
+        .iter()
+        .enumerate()
+        .map(|(index, item)| Match {
+            title: item
+                .path
+                .file_name()
+                .map_or_else(
+                    || item.path.display().to_string(),
+                    |name| name.to_string_lossy().to_string(),
+                )
+                .into(),
+            description: ROption::RSome(
+                match &item.name {
+                    Some(name) => format!("Resume in {}: {}", name, item.path.display()),
+                    None => format!("Resume: {}", item.path.display()),
+                }
+                .into(),
+            ),
+            title_pango: false,
+            description_pango: false,
+            icon: ROption::RSome(
+                item.icon
+                    .as_deref()
+                    .unwrap_or("document-open-recent")
+                    .into(),
+            ),
+            id: ROption::RSome(index as u64),
+            actions: RVec::new(),
+            action: ROption::RNone,
+            image: ROption::RNone,
+            widgets: RVec::new(),
+            priority: 0,
+            pinned: false,
+        })
+        .collect()
+}
+
+#[handler]
+fn handler(selection: Match, state: &State) -> HandleResult {
+    // The matches may be from before the latest query, like the ones pinned or in the history
+    let item = match selection
+        .id
+        .into_option()
+        .and_then(|id| state.items.get(id as usize))
+    {
+        Some(item) => item,
+        None => return HandleResult::Refresh(false),
+    };
+    let command = item
+        .app
+        .as_ref()
+        .and_then(|app| state.config.apps.get(app)?.command.as_ref());
+
+    let result = match command {
+        Some(command) => {
+            // Quoted for the shell, the path may contain anything
+            let path = item.path.to_string_lossy().replace('\'', "'\\''");
+            Command::new("sh")
+                .arg("-c")
+                .arg(command.replace("{file}", &format!("'{}'", path)))
+                .spawn()
+        }
+        None => Command::new("xdg-open").arg(&item.path).spawn(),
+    };
+
+    if let Err(why) = result {
+        log::error!("Failed to open {}: {}", item.path.display(), why);
+    }
+
+    HandleResult::Close
+}
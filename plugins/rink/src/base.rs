@@ -0,0 +1,113 @@
+//! Showing an integer in the common bases at once, for queries like `0xff`, `0xff to dec` or
+//! `255 to hex`. Every base gets a match of its own so that each form can be copied.
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Base {
+    Hex,
+    Decimal,
+    Binary,
+    Octal,
+}
+
+const BASES: [Base; 4] = [Base::Hex, Base::Decimal, Base::Binary, Base::Octal];
+
+impl Base {
+    fn from_name(name: &str) -> Option<Self> {
+        Some(match name.to_lowercase().as_str() {
+            "hex" | "hexadecimal" | "base16" | "base 16" => Self::Hex,
+            "dec" | "decimal" | "base10" | "base 10" => Self::Decimal,
+            "bin" | "binary" | "base2" | "base 2" => Self::Binary,
+            "oct" | "octal" | "base8" | "base 8" => Self::Octal,
+            _ => return None,
+        })
+    }
+
+    fn radix(self) -> u32 {
+        match self {
+            Self::Hex => 16,
+            Self::Decimal => 10,
+            Self::Binary => 2,
+            Self::Octal => 8,
+        }
+    }
+
+    fn prefix(self) -> &'static str {
+        match self {
+            Self::Hex => "0x",
+            Self::Decimal => "",
+            Self::Binary => "0b",
+            Self::Octal => "0o",
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Hex => "Hexadecimal",
+            Self::Decimal => "Decimal",
+            Self::Binary => "Binary",
+            Self::Octal => "Octal",
+        }
+    }
+
+    pub fn format(self, value: i128) -> String {
+        let magnitude = value.unsigned_abs();
+        let digits = match self {
+            Self::Hex => format!("{:x}", magnitude),
+            Self::Decimal => magnitude.to_string(),
+            Self::Binary => format!("{:b}", magnitude),
+            Self::Octal => format!("{:o}", magnitude),
+        };
+        let sign = if value < 0 { "-" } else { "" };
+        format!("{}{}{}", sign, self.prefix(), digits)
+    }
+}
+
+/// The integer, with the base it was written in when it had a prefix like `0x`
+fn literal(text: &str) -> Option<(i128, Option<Base>)> {
+    let (negative, text) = match text.strip_prefix('-') {
+        Some(text) => (true, text),
+        None => (false, text),
+    };
+    let text = text.replace('_', "");
+    let lower = text.to_lowercase();
+
+    let (base, digits) = BASES
+        .into_iter()
+        .filter(|base| *base != Base::Decimal)
+        .find_map(|base| {
+            lower
+                .strip_prefix(base.prefix())
+                .map(|digits| (Some(base), digits))
+        })
+        .unwrap_or((None, lower.as_str()));
+    if digits.is_empty() {
+        return None;
+    }
+
+    let radix = base.map_or(10, Base::radix);
+    let value = i128::from_str_radix(digits, radix).ok()?;
+    Some((if negative { -value } else { value }, base))
+}
+
+/// The integer and the bases to show it in, the one asked for first. Only the queries that are
+/// clearly about bases are taken, so that rink still gets plain numbers.
+pub fn parse(input: &str) -> Option<(i128, Vec<Base>)> {
+    let input = input.trim();
+    let (number, target) = [" to ", " in ", " -> "]
+        .into_iter()
+        .find_map(|separator| input.split_once(separator))
+        .map_or((input, None), |(number, target)| {
+            (number.trim(), Some(target.trim()))
+        });
+
+    let (value, written) = literal(number)?;
+    let first = match target {
+        Some(target) => Base::from_name(target)?,
+        // A lone number has to have a prefix to be meant for this
+        None => written.map(|_| Base::Decimal)?,
+    };
+
+    let mut bases = vec![first];
+    bases.extend(BASES.into_iter().filter(|base| *base != first));
+    Some((value, bases))
+}
@@ -0,0 +1,173 @@
+//! The current exchange rates, fetched on a thread of their own so that startup never waits for the
+//! network. The last ones fetched are cached, and used until newer ones are fetched, or for as
+//! long as there is no network. For as long as anyrun keeps running, they are fetched again every
+//! once in a while. The rates can also be read from a file.
+//!
+//! The rates are read in the JSON of rinkcalc.app, the XML of the European Central Bank, or as
+//! definitions in the units format of Rink.
+
+use std::{
+    env, fs,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    thread,
+    time::{Duration, SystemTime},
+};
+
+use anyrun_plugin::{log, report};
+use rink_core::{ast, gnu_units};
+use serde::{Deserialize, Serialize};
+
+use crate::Config;
+
+/// The rates as they were fetched, with where from so that other ones are fetched when the source
+/// changes
+#[derive(Serialize, Deserialize)]
+struct Cached {
+    url: String,
+    content: String,
+}
+
+fn path() -> PathBuf {
+    match env::var("XDG_CACHE_HOME") {
+        Ok(cache_home) => PathBuf::from(cache_home),
+        Err(_) => PathBuf::from(format!("{}/.cache", env::var("HOME").unwrap_or_default())),
+    }
+    .join("anyrun/rink-currency.json")
+}
+
+/// The path of the file the rates are read from instead of fetching them
+fn local(url: &str) -> Option<PathBuf> {
+    if let Some(path) = url.strip_prefix("file://") {
+        Some(PathBuf::from(path))
+    } else if let Some(path) = url.strip_prefix("~/") {
+        Some(PathBuf::from(env::var("HOME").unwrap_or_default()).join(path))
+    } else {
+        url.starts_with('/').then(|| PathBuf::from(url))
+    }
+}
+
+pub fn parse(content: &str) -> Result<ast::Defs, String> {
+    match content.trim_start().chars().next() {
+        Some('{') => serde_json::from_str(content).map_err(|why| why.to_string()),
+        Some('<') => Ok(gnu_units::parse_str(&ecb(content))),
+        _ => Ok(gnu_units::parse_str(content)),
+    }
+}
+
+/// The value of the attribute in the tag, in either kind of quotes
+fn attribute<'a>(tag: &'a str, name: &str) -> Option<&'a str> {
+    let start = tag.find(&format!("{}=", name))? + name.len() + 1;
+    let quote = tag[start..].chars().next()?;
+    let value = &tag[start + 1..];
+    value.find(quote).map(|end| &value[..end])
+}
+
+/// The rates of the European Central Bank, which are how much of each currency one euro buys, as
+/// definitions like `USD EUR / 1.0876`
+fn ecb(xml: &str) -> String {
+    xml.split("<Cube")
+        .filter_map(|tag| {
+            let currency = attribute(tag, "currency")?;
+            let rate = attribute(tag, "rate")?;
+            Some(format!("{} EUR / {}\n", currency, rate))
+        })
+        .collect()
+}
+
+/// The rates read from the file, or the ones fetched before however old they are. None when the
+/// currencies are disabled, which leaves the bundled rates.
+pub fn load(config: &Config) -> Option<ast::Defs> {
+    if config.disable_currency {
+        return None;
+    }
+
+    let result = match local(&config.currency_url) {
+        Some(path) => fs::read_to_string(&path)
+            .map_err(|why| format!("Failed to read {}: {}", path.display(), why))
+            .and_then(|content| parse(&content)),
+        None => {
+            let cached = fs::read_to_string(path())
+                .ok()
+                .and_then(|content| serde_json::from_str::<Cached>(&content).ok())
+                .filter(|cached| cached.url == config.currency_url)?;
+            parse(&cached.content)
+        }
+    };
+
+    result
+        .map_err(|why| report::error!("Error parsing the exchange rates: {}", why))
+        .ok()
+}
+
+/// Whether the cached rates are from the url and younger than `max_age`
+fn fresh(url: &str, max_age: Duration) -> bool {
+    let cached = fs::read_to_string(path())
+        .ok()
+        .and_then(|content| serde_json::from_str::<Cached>(&content).ok());
+    let age = fs::metadata(path())
+        .and_then(|metadata| metadata.modified())
+        .ok()
+        .and_then(|modified| SystemTime::now().duration_since(modified).ok());
+
+    cached.is_some_and(|cached| cached.url == url) && age.is_some_and(|age| age < max_age)
+}
+
+/// The rates as they were fetched, once they are known to parse
+fn fetch(url: &str, timeout: Duration) -> Result<String, String> {
+    let content = reqwest::blocking::Client::builder()
+        .timeout(timeout)
+        .build()
+        .and_then(|client| client.get(url).send())
+        .and_then(|response| response.error_for_status())
+        .and_then(|response| response.text())
+        .map_err(|why| why.to_string())?;
+    parse(&content)?;
+
+    let cached = Cached {
+        url: url.to_string(),
+        content: content.clone(),
+    };
+    let path = path();
+    if let Err(why) = path
+        .parent()
+        .map_or(Ok(()), fs::create_dir_all)
+        .and_then(|_| fs::write(&path, serde_json::to_string(&cached).unwrap_or_default()))
+    {
+        log::warn!("Failed to cache the exchange rates: {}", why);
+    }
+    Ok(content)
+}
+
+/// Fetch the rates whenever the cached ones are older than the refresh interval, leaving them in
+/// `fetched` for the context to be made again with. They are left unparsed, as the definitions of
+/// Rink can't be sent between threads.
+pub fn update(config: &Config, fetched: Arc<Mutex<Option<String>>>) {
+    if config.disable_currency || local(&config.currency_url).is_some() {
+        return;
+    }
+
+    let url = config.currency_url.clone();
+    let timeout = Duration::from_secs(config.timeout);
+    // Not fetching them over and over
+    let interval = Duration::from_secs(config.refresh_interval.max(1) * 60);
+    thread::spawn(move || {
+        let mut first = true;
+        loop {
+            if !fresh(&url, interval) {
+                match fetch(&url, timeout) {
+                    Ok(content) => *fetched.lock().unwrap() = Some(content),
+                    // Only told once, the cached rates do until the network is back
+                    Err(why) if first && !path().exists() => {
+                        report::error!("Error fetching up-to-date currency conversions: {}", why)
+                    }
+                    Err(why) => {
+                        log::warn!("Error fetching up-to-date currency conversions: {}", why)
+                    }
+                }
+            }
+            first = false;
+            thread::sleep(interval);
+        }
+    });
+}
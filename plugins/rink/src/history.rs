@@ -0,0 +1,56 @@
+//! The results that were copied before, kept in the store of the plugin so that they can be copied
+//! again from the matches shown for just the prefix. Only the copied ones are kept, as every
+//! keystroke evaluates a query and most of them are only halfway typed.
+
+use anyrun_plugin::{log, store};
+use serde::{Deserialize, Serialize};
+
+const KEY: &str = "history";
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Entry {
+    /// What was typed, without the prefix
+    pub query: String,
+    pub result: String,
+}
+
+#[derive(Default)]
+pub struct History {
+    /// The most recent first
+    pub entries: Vec<Entry>,
+}
+
+impl History {
+    pub fn load() -> Self {
+        Self {
+            entries: store::get(KEY).unwrap_or_default(),
+        }
+    }
+
+    /// Put the result first, keeping at most `limit` of them. A result that is already there is
+    /// moved up, with the query it was copied with the first time when this one is empty.
+    pub fn record(&mut self, query: &str, result: &str, limit: usize) {
+        let previous = self
+            .entries
+            .iter()
+            .position(|entry| entry.result == result)
+            .map(|index| self.entries.remove(index));
+        let query = match previous {
+            Some(previous) if query.is_empty() => previous.query,
+            _ => query.to_string(),
+        };
+
+        self.entries.insert(
+            0,
+            Entry {
+                query,
+                result: result.to_string(),
+            },
+        );
+        self.entries.truncate(limit);
+
+        if let Err(why) = store::set(KEY, &self.entries) {
+            log::warn!("Failed to save the history: {}", why);
+        }
+    }
+}
@@ -0,0 +1,37 @@
+//! The other ways an ambiguous query could be meant, like `10 pounds` being money as well as a mass.
+//! Rink only ever takes one meaning, so the query is also evaluated with the words that have other
+//! meanings replaced by the units of those.
+
+/// The words that mean more than one unit, with the units of their other meanings, in the
+/// singular and the plural
+const AMBIGUOUS: &[(&[&str], &[&str])] = &[
+    (&["pound", "pounds"], &["GBP"]),
+    (&["dollar", "dollars"], &["USD", "CAD", "AUD"]),
+    (&["ounce", "ounces", "oz"], &["floz"]),
+    (&["ton", "tons"], &["tonne", "longton"]),
+    (&["gallon", "gallons"], &["brgallon"]),
+    (&["pint", "pints"], &["brpint"]),
+    (&["calorie", "calories"], &["kcal"]),
+];
+
+/// The query as typed, followed by the rewritten ones. Only one word is replaced in each of them,
+/// so that there are never more than a few.
+pub fn queries(input: &str) -> Vec<String> {
+    let mut queries = vec![input.to_string()];
+    let words = input.split(' ').collect::<Vec<_>>();
+
+    for (index, word) in words.iter().enumerate() {
+        let lower = word.to_lowercase();
+        let meanings = AMBIGUOUS
+            .iter()
+            .find(|(names, _)| names.contains(&lower.as_str()))
+            .map_or(&[][..], |(_, meanings)| *meanings);
+
+        for meaning in meanings {
+            let mut rewritten = words.clone();
+            rewritten[index] = meaning;
+            queries.push(rewritten.join(" "));
+        }
+    }
+    queries
+}
@@ -1,19 +1,92 @@
-use std::fs;
+use std::{
+    fs, io,
+    path::Path,
+    sync::{Arc, Mutex},
+};
 
 use abi_stable::std_types::{ROption, RString, RVec};
 use anyrun_plugin::*;
 use rink_core::{ast, date, gnu_units, CURRENCY_FILE};
 use serde::Deserialize;
 
+use history::History;
+
+mod base;
+#[cfg(feature = "live-currency")]
+mod currency;
+mod history;
+mod interpret;
+
+/// What of the result is copied, or typed
+#[derive(Deserialize, Clone, Copy, Default)]
+enum CopyTarget {
+    /// Like `1.609344 kilometer`
+    #[default]
+    Full,
+    /// Like `1.609344`
+    Number,
+    /// What is in the brackets after the result, like `length`
+    Description,
+}
+
 #[derive(Deserialize)]
 struct Config {
     prefix: String,
+    /// Where the exchange rates are fetched from, or the path of a file to read them from
+    #[serde(default = "Config::default_currency_url")]
+    currency_url: String,
+    /// How long fetching the exchange rates may take, in seconds
+    #[serde(default = "Config::default_timeout")]
+    timeout: u64,
+    /// How many minutes the exchange rates are used for before they are fetched again
+    #[serde(default = "Config::default_refresh_interval")]
+    refresh_interval: u64,
+    /// Only use the exchange rates bundled with Rink, without fetching any
+    #[serde(default)]
+    disable_currency: bool,
+    /// How many of the copied results to keep, shown when only the prefix is typed
+    #[serde(default = "Config::default_history")]
+    history: usize,
+    #[serde(default)]
+    copy: CopyTarget,
+    /// How many of the meanings of an ambiguous query to show, 1 for only the one Rink takes
+    #[serde(default = "Config::default_max_entries")]
+    max_entries: usize,
+}
+
+impl Config {
+    fn default_currency_url() -> String {
+        "https://rinkcalc.app/data/currency.json".to_string()
+    }
+
+    fn default_timeout() -> u64 {
+        10
+    }
+
+    fn default_refresh_interval() -> u64 {
+        24 * 60
+    }
+
+    fn default_history() -> usize {
+        20
+    }
+
+    fn default_max_entries() -> usize {
+        3
+    }
 }
 
 impl Default for Config {
     fn default() -> Self {
         Self {
             prefix: "".to_string(),
+            currency_url: Self::default_currency_url(),
+            timeout: Self::default_timeout(),
+            refresh_interval: Self::default_refresh_interval(),
+            disable_currency: false,
+            history: Self::default_history(),
+            copy: CopyTarget::Full,
+            max_entries: Self::default_max_entries(),
         }
     }
 }
@@ -21,41 +94,79 @@ impl Default for Config {
 struct State {
     config: Config,
     ctx: rink_core::Context,
+    /// The exchange rates fetched in the background, until the context is made again with them
+    #[cfg(feature = "live-currency")]
+    fetched: Arc<Mutex<Option<String>>>,
+    history: History,
+    /// The units of the user, kept for when the context is made again
+    units: Option<String>,
 }
 
-#[init]
-fn init(config_dir: RString) -> State {
+/// The names of the file the user can define units in, in the config directory
+const UNITS_FILES: &[&str] = &["units.txt", "definitions.units"];
+
+/// The definitions in the units file of the user, in the units format of Rink
+fn user_units(config_dir: &str) -> Option<String> {
+    UNITS_FILES.iter().find_map(|name| {
+        let path = Path::new(config_dir).join(name);
+        match fs::read_to_string(&path) {
+            Ok(content) => Some(content),
+            Err(why) if why.kind() == io::ErrorKind::NotFound => None,
+            Err(why) => {
+                report::error!("Failed to read {}: {}", path.display(), why);
+                None
+            }
+        }
+    })
+}
+
+/// The context with the units, the dates and the exchange rates, the bundled rates being used for
+/// the currencies the live ones don't have. The units of the user come last, so that they can
+/// refer to all of the others.
+fn context(live: Option<ast::Defs>, user: Option<&str>) -> rink_core::Context {
     let mut ctx = rink_core::Context::new();
 
     let units = gnu_units::parse_str(rink_core::DEFAULT_FILE.unwrap());
     let dates = date::parse_datefile(rink_core::DATES_FILE);
 
-    let mut currency_defs = Vec::new();
-
-    match reqwest::blocking::get("https://rinkcalc.app/data/currency.json") {
-        Ok(response) => match response.json::<ast::Defs>() {
-            Ok(mut live_defs) => {
-                currency_defs.append(&mut live_defs.defs);
-            }
-            Err(why) => println!("Error parsing currency json: {}", why),
-        },
-        Err(why) => println!("Error fetching up-to-date currency conversions: {}", why),
-    }
-
+    let mut currency_defs = live.map(|live| live.defs).unwrap_or_default();
     currency_defs.append(&mut gnu_units::parse_str(CURRENCY_FILE).defs);
 
     ctx.load(units);
     ctx.load(ast::Defs {
         defs: currency_defs,
     });
+    if let Some(user) = user {
+        ctx.load(gnu_units::parse_str(user));
+    }
     ctx.load_dates(dates);
+    ctx
+}
+
+#[init]
+fn init(config_dir: RString) -> State {
+    let config: Config = plugin_config(&config_dir, "rink");
+    prefix::set(&config.prefix);
+
+    let units = user_units(&config_dir);
+
+    #[cfg(feature = "live-currency")]
+    let fetched = Arc::new(Mutex::new(None));
+    #[cfg(feature = "live-currency")]
+    let ctx = {
+        currency::update(&config, fetched.clone());
+        context(currency::load(&config), units.as_deref())
+    };
+    #[cfg(not(feature = "live-currency"))]
+    let ctx = context(None, units.as_deref());
 
     State {
-        config: match fs::read_to_string(format!("{}/rink.ron", config_dir)) {
-            Ok(content) => ron::from_str(&content).unwrap_or_default(),
-            Err(_) => Config::default(),
-        },
-        ctx: ctx,
+        config,
+        ctx,
+        #[cfg(feature = "live-currency")]
+        fetched,
+        history: History::load(),
+        units,
     }
 }
 
@@ -75,25 +186,126 @@ fn get_matches(input: RString, state: &mut State) -> RVec<Match> {
         return RVec::new();
     };
 
-    match rink_core::one_line(&mut state.ctx, &input) {
-        Ok(result) => {
+    // Without a prefix, this would be whenever nothing is typed
+    if input.is_empty() {
+        if state.config.prefix.is_empty() {
+            return RVec::new();
+        }
+        return state
+            .history
+            .entries
+            .iter()
+            .take(state.config.history)
+            .map(|entry| Match {
+                // Telling them apart from the results, as their descriptions are the queries
+                id: ROption::RSome(HISTORY),
+                ..row(entry.result.clone(), Some(entry.query.clone()))
+            })
+            .collect();
+    }
+
+    #[cfg(feature = "live-currency")]
+    if let Some(fetched) = state.fetched.lock().unwrap().take() {
+        state.ctx = context(currency::parse(&fetched).ok(), state.units.as_deref());
+    }
+
+    if let Some((value, bases)) = base::parse(input) {
+        return bases
+            .into_iter()
+            .map(|base| Match {
+                id: ROption::RSome(BASE),
+                ..row(base.format(value), Some(base.name().to_string()))
+            })
+            .collect();
+    }
+
+    let mut matches: Vec<Match> = Vec::new();
+    for query in interpret::queries(input) {
+        if matches.len() >= state.config.max_entries {
+            break;
+        }
+        // The rewritten queries that don't make sense fail, like `1 GBP to kg`
+        if let Ok(result) = rink_core::one_line(&mut state.ctx, &query) {
             let (title, desc) = parse_result(result);
-            vec![Match {
-                title: title.into(),
-                description: desc.map(RString::from).into(),
-                use_pango: false,
-                icon: ROption::RNone,
-                id: ROption::RNone,
-            }]
-            .into()
+            if !matches.iter().any(|other| other.title.as_str() == title) {
+                matches.push(row(title, desc));
+            }
         }
-        Err(_) => RVec::new(),
     }
+    matches.into()
 }
 
+fn row(title: String, description: Option<String>) -> Match {
+    Match {
+        title: title.into(),
+        description: description.map(RString::from).into(),
+        title_pango: false,
+        description_pango: false,
+        icon: ROption::RNone,
+        id: ROption::RNone,
+        actions: vec![Action {
+            title: "Type".into(),
+            icon: ROption::RSome("input-keyboard".into()),
+            id: TYPE,
+        }]
+        .into(),
+        action: ROption::RNone,
+        image: ROption::RNone,
+        widgets: RVec::new(),
+        priority: 0,
+        pinned: false,
+    }
+}
+
+/// The id of the matches from the history
+const HISTORY: u64 = 0;
+/// The id of the number written in the other bases, described by the name of the base
+const BASE: u64 = 1;
+
+/// The action typing the result into the focused window instead of copying it
+const TYPE: u64 = 0;
+
 #[handler]
-fn handler(selection: Match) -> HandleResult {
-    HandleResult::Copy(selection.title.into_bytes())
+fn handler(selection: Match, input: RString, state: &mut State) -> HandleResult {
+    if state.config.history > 0 {
+        let query = input
+            .strip_prefix(&state.config.prefix)
+            .unwrap_or(&input)
+            .trim();
+        state
+            .history
+            .record(query, &selection.title, state.config.history);
+    }
+
+    let text = match state.config.copy {
+        CopyTarget::Full => selection.title.to_string(),
+        CopyTarget::Number => number(&selection.title).to_string(),
+        // The rows whose descriptions aren't what the result is, like the history and the bases,
+        // or the results without one are copied whole
+        CopyTarget::Description => match (&selection.description, selection.id) {
+            (ROption::RSome(description), ROption::RNone) => description.to_string(),
+            _ => selection.title.to_string(),
+        },
+    };
+    match selection.action {
+        ROption::RSome(TYPE) => HandleResult::Type(text.into()),
+        _ => HandleResult::Copy(text.into_bytes().into(), ROption::RNone),
+    }
+}
+
+/// The number the result starts with, without its units, like `0.3333` in `approx. 0.3333 meter`.
+/// The whole result when it doesn't start with one.
+fn number(result: &str) -> &str {
+    result
+        .split_whitespace()
+        .find(|word| *word != "approx.")
+        .map(|word| word.trim_end_matches(','))
+        .filter(|word| {
+            word.chars()
+                .next()
+                .is_some_and(|c| c.is_ascii_digit() || matches!(c, '-' | '.'))
+        })
+        .unwrap_or(result)
 }
 
 /// Extracts the title and description from `rink` result.
@@ -0,0 +1,39 @@
+use std::{env, process};
+
+use abi_stable::std_types::ROption;
+use anyrun_plugin_test::{titles, HandleResult, Harness};
+
+#[test]
+fn rink() {
+    // The history of the copied results is kept in the store, which mustn't be the one of the user
+    env::set_var(
+        "XDG_STATE_HOME",
+        env::temp_dir().join(format!("anyrun-rink-test-{}", process::id())),
+    );
+    let plugin = Harness::new(rink::anyrun_internal_init_root_module)
+        .config("rink.ron", r#"Config(prefix: "=", disable_currency: true)"#)
+        .init();
+    assert!(plugin.query("1 mile to km").is_empty());
+
+    assert_eq!(
+        titles(&plugin.query("=0xff")),
+        ["255", "0xff", "0b11111111", "0o377"]
+    );
+    assert_eq!(titles(&plugin.query("=255 to hex"))[0], "0xff");
+
+    let matches = plugin.query("=1 mile to km");
+    assert!(matches[0].title.starts_with("1.609344"));
+
+    // Nothing is in the history until a result is copied
+    assert!(plugin.query("=").is_empty());
+    assert!(matches!(
+        plugin.select(&matches[0], "=1 mile to km"),
+        HandleResult::Copy(..)
+    ));
+    let history = plugin.query("=");
+    assert_eq!(titles(&history), [matches[0].title.as_str()]);
+    assert_eq!(
+        history[0].description,
+        ROption::RSome("1 mile to km".into())
+    );
+}
@@ -0,0 +1,297 @@
+use std::{cmp::Reverse, io, process::Command, sync::Mutex};
+
+use abi_stable::std_types::{ROption, RString, RVec};
+use anyrun_plugin::*;
+use fuzzy_matcher::FuzzyMatcher;
+use serde::Deserialize;
+
+#[derive(Deserialize)]
+struct Config {
+    scripts: Vec<Script>,
+    max_entries: usize,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            scripts: Vec::new(),
+            max_entries: 10,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct Script {
+    /// The script and the arguments to give it before the selected entry
+    command: Vec<String>,
+    /// Typed to get to the menu of the script
+    #[serde(default)]
+    prefix: Option<String>,
+}
+
+pub struct State {
+    config: Config,
+    /// The latest menu of every script, none until the script is first asked for one
+    menus: Vec<Mutex<Option<Menu>>>,
+    /// The latest selectable matches with the index of the script they came from, by the ids of
+    /// the matches
+    matches: Mutex<Vec<(usize, Selection)>>,
+}
+
+/// The entries and the options a script printed
+#[derive(Default)]
+struct Menu {
+    entries: Vec<Entry>,
+    message: Option<String>,
+    /// Given back to the script in `ROFI_DATA`
+    data: Option<String>,
+    markup: bool,
+    no_custom: bool,
+}
+
+#[derive(Default, Clone)]
+struct Entry {
+    text: String,
+    /// Shown instead of the text, which is still what the script gets back
+    display: Option<String>,
+    icon: Option<String>,
+    /// Given back to the script in `ROFI_INFO`
+    info: Option<String>,
+    /// More terms to match the entry by
+    meta: Option<String>,
+    nonselectable: bool,
+}
+
+#[derive(Clone)]
+enum Selection {
+    Entry(Entry),
+    /// The input itself, for scripts that take what was typed
+    Custom(String),
+}
+
+impl Menu {
+    /// Read the lines the script printed, an entry on each line with its options after a null
+    /// character and the options of the menu on the lines starting with one, all split by `\x1f`
+    fn parse(output: &str) -> Self {
+        let mut menu = Self::default();
+
+        for line in output.lines() {
+            if let Some(option) = line.strip_prefix('\0') {
+                let (key, value) = option.split_once('\x1f').unwrap_or((option, ""));
+                match key {
+                    "message" => menu.message = Some(value.to_string()),
+                    "data" => menu.data = Some(value.to_string()),
+                    "markup-rows" => menu.markup = value == "true",
+                    "no-custom" => menu.no_custom = value == "true",
+                    // Like the prompt and the hot keys, which anyrun has nothing for
+                    _ => (),
+                }
+                continue;
+            }
+
+            let (text, options) = line.split_once('\0').unwrap_or((line, ""));
+            let mut entry = Entry {
+                text: text.to_string(),
+                ..Default::default()
+            };
+            let mut options = options.split('\x1f');
+            while let Some(key) = options.next() {
+                let value = options.next().unwrap_or_default().to_string();
+                match key {
+                    "display" => entry.display = Some(value),
+                    "icon" => entry.icon = Some(value),
+                    "info" => entry.info = Some(value),
+                    "meta" => entry.meta = Some(value),
+                    "nonselectable" => entry.nonselectable = value == "true",
+                    _ => (),
+                }
+            }
+            menu.entries.push(entry);
+        }
+
+        menu
+    }
+}
+
+/// Run the script like rofi does, with the selection as its argument and what became of it in
+/// `ROFI_RETV`: 0 for the first menu, 1 for a selected entry and 2 for custom input
+fn run(script: &Script, selection: Option<&Selection>, data: Option<&str>) -> io::Result<Menu> {
+    let (program, args) = script
+        .command
+        .split_first()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "The command is empty"))?;
+
+    let mut command = Command::new(program);
+    command.args(args);
+    match selection {
+        None => command.env("ROFI_RETV", "0"),
+        Some(Selection::Entry(entry)) => {
+            if let Some(info) = &entry.info {
+                command.env("ROFI_INFO", info);
+            }
+            command.arg(&entry.text).env("ROFI_RETV", "1")
+        }
+        Some(Selection::Custom(input)) => command.arg(input).env("ROFI_RETV", "2"),
+    };
+    if let Some(data) = data {
+        command.env("ROFI_DATA", data);
+    }
+
+    let output = command.output()?;
+    if !output.status.success() {
+        return Err(io::Error::other(format!(
+            "The script exited with {}",
+            output.status
+        )));
+    }
+    Ok(Menu::parse(&String::from_utf8_lossy(&output.stdout)))
+}
+
+#[init]
+fn init(config_dir: RString) -> State {
+    let config: Config = plugin_config(&config_dir, "rofi");
+
+    prefix::set_single(config.scripts.iter().map(|script| script.prefix.as_deref()));
+
+    State {
+        menus: config.scripts.iter().map(|_| Mutex::new(None)).collect(),
+        config,
+        matches: Mutex::new(Vec::new()),
+    }
+}
+
+#[info]
+fn info() -> PluginInfo {
+    PluginInfo {
+        name: "Rofi scripts".into(),
+        icon: "utilities-terminal".into(),
+    }
+}
+
+#[get_matches]
+fn get_matches(input: RString, state: &State) -> RVec<Match> {
+    let matcher = fuzzy_matcher::skim::SkimMatcherV2::default().ignore_case();
+    let mut matches = RVec::new();
+    let mut selections = Vec::new();
+
+    for (index, script) in state.config.scripts.iter().enumerate() {
+        // What is typed after the prefix filters the entries of the menu
+        let input = match prefix::strip(&input, script.prefix.as_deref()) {
+            Some(input) => input,
+            None => continue,
+        };
+
+        let mut menu = state.menus[index].lock().unwrap();
+        if menu.is_none() {
+            match run(script, None, None) {
+                Ok(first) => *menu = Some(first),
+                Err(why) => {
+                    report::error!("Failed to run {}: {}", script.command.join(" "), why);
+                    continue;
+                }
+            }
+        }
+        let menu = menu.as_ref().unwrap();
+
+        if let Some(message) = &menu.message {
+            matches.push(row(message, None, menu.markup, ROption::RNone));
+        }
+
+        // The entries are shown in the order of the script until there is something to match
+        let mut entries = menu
+            .entries
+            .iter()
+            .filter_map(|entry| {
+                let terms = format!(
+                    "{} {}",
+                    entry.display.as_ref().unwrap_or(&entry.text),
+                    entry.meta.as_deref().unwrap_or_default()
+                );
+                matcher
+                    .fuzzy_match(&terms, input)
+                    .map(|score| (entry, score))
+            })
+            .collect::<Vec<_>>();
+        if !input.is_empty() {
+            entries.sort_by_key(|(_, score)| Reverse(*score));
+        }
+        entries.truncate(state.config.max_entries);
+
+        let exact = entries.iter().any(|(entry, _)| entry.text == input);
+        for (entry, _) in entries {
+            let id = if entry.nonselectable {
+                ROption::RNone
+            } else {
+                selections.push((index, Selection::Entry(entry.clone())));
+                ROption::RSome(selections.len() as u64 - 1)
+            };
+            matches.push(row(
+                entry.display.as_ref().unwrap_or(&entry.text),
+                entry.icon.as_deref(),
+                menu.markup,
+                id,
+            ));
+        }
+
+        if !menu.no_custom && !input.is_empty() && !exact {
+            selections.push((index, Selection::Custom(input.to_string())));
+            matches.push(row(
+                input,
+                None,
+                false,
+                ROption::RSome(selections.len() as u64 - 1),
+            ));
+        }
+    }
+
+    *state.matches.lock().unwrap() = selections;
+    matches
+}
+
+fn row(title: &str, icon: Option<&str>, markup: bool, id: ROption<u64>) -> Match {
+    Match {
+        title: title.into(),
+        description: ROption::RNone,
+        title_pango: markup,
+        description_pango: false,
+        icon: icon.map(RString::from).into(),
+        id,
+        actions: RVec::new(),
+        action: ROption::RNone,
+        image: ROption::RNone,
+        widgets: RVec::new(),
+        priority: 0,
+        pinned: false,
+    }
+}
+
+#[handler]
+fn handler(selection: Match, state: &State) -> HandleResult {
+    let found = selection
+        .id
+        .into_option()
+        .and_then(|id| state.matches.lock().unwrap().get(id as usize).cloned());
+    // The message and the entries that can't be selected
+    let (index, selection) = match found {
+        Some(found) => found,
+        None => return HandleResult::Refresh(false),
+    };
+
+    let script = &state.config.scripts[index];
+    let mut menu = state.menus[index].lock().unwrap();
+    let data = menu.as_ref().and_then(|menu| menu.data.clone());
+
+    match run(script, Some(&selection), data.as_deref()) {
+        // A script that prints nothing is done, like rofi closes then
+        Ok(next) if next.entries.is_empty() && next.message.is_none() => HandleResult::Close,
+        // Otherwise its next menu is shown, with what was typed cleared
+        Ok(next) => {
+            *menu = Some(next);
+            HandleResult::SetInput(script.prefix.clone().unwrap_or_default().into())
+        }
+        Err(why) => {
+            report::error!("Failed to run {}: {}", script.command.join(" "), why);
+            HandleResult::Refresh(false)
+        }
+    }
+}
@@ -0,0 +1,217 @@
+use std::{
+    env, fs,
+    os::unix::process::CommandExt,
+    path::PathBuf,
+    process::{Command, Stdio},
+};
+
+use abi_stable::std_types::{ROption, RString, RVec};
+use anyrun_plugin::*;
+use nix::{
+    sys::signal::{self, Signal},
+    unistd::Pid,
+};
+use serde::Deserialize;
+
+#[derive(Deserialize)]
+struct Config {
+    prefix: String,
+    helper: String,
+    sources: Vec<Source>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            prefix: ":cast".to_string(),
+            helper: "anyrun-screencast".to_string(),
+            sources: vec![Source {
+                name: "Screen".to_string(),
+                kind: Kind::Monitor,
+                cursor: Cursor::Embedded,
+                command: "gst-launch-1.0 pipewiresrc fd={fd} path={node} ! videoconvert ! v4l2sink device=/dev/video10".to_string(),
+            }],
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct Source {
+    name: String,
+    #[serde(default = "Source::default_kind")]
+    kind: Kind,
+    #[serde(default = "Source::default_cursor")]
+    cursor: Cursor,
+    command: String,
+}
+
+impl Source {
+    fn default_kind() -> Kind {
+        Kind::Monitor
+    }
+
+    fn default_cursor() -> Cursor {
+        Cursor::Embedded
+    }
+}
+
+/// The source types of the portal, the values are the bits of them
+#[derive(Deserialize, Clone, Copy)]
+enum Kind {
+    Monitor = 1,
+    Window = 2,
+    Virtual = 4,
+}
+
+/// The cursor modes of the portal, the values are the bits of them
+#[derive(Deserialize, Clone, Copy)]
+enum Cursor {
+    Hidden = 1,
+    Embedded = 2,
+    Metadata = 4,
+}
+
+struct State {
+    config: Config,
+    /// Where the helpers of the running sessions keep their pids
+    runtime_dir: PathBuf,
+    /// Where the portal tokens for restoring the chosen monitors and windows are kept
+    token_dir: PathBuf,
+}
+
+impl State {
+    fn pid_file(&self, source: &Source) -> PathBuf {
+        self.runtime_dir.join(format!("{}.pid", source.name))
+    }
+
+    /// The process group of the helper sharing the source, if it is running
+    fn running(&self, source: &Source) -> Option<Pid> {
+        let pid = fs::read_to_string(self.pid_file(source))
+            .ok()?
+            .trim()
+            .parse()
+            .ok()
+            .map(Pid::from_raw)?;
+
+        // The helper may have been killed without cleaning up
+        signal::kill(pid, None).ok().map(|_| pid)
+    }
+}
+
+#[init]
+fn init(config_dir: RString) -> State {
+    let config: Config = plugin_config(&config_dir, "screencast");
+    prefix::set(&config.prefix);
+
+    let runtime_dir = env::var("XDG_RUNTIME_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(|_| env::temp_dir())
+        .join("anyrun-screencast");
+    let token_dir = match env::var("XDG_STATE_HOME") {
+        Ok(state_home) => PathBuf::from(state_home),
+        Err(_) => PathBuf::from(format!(
+            "{}/.local/state",
+            env::var("HOME").unwrap_or_default()
+        )),
+    }
+    .join("anyrun/screencast");
+
+    State {
+        config,
+        runtime_dir,
+        token_dir,
+    }
+}
+
+#[info]
+fn info() -> PluginInfo {
+    PluginInfo {
+        name: "Screencast".into(),
+        icon: "video-display".into(),
+    }
+}
+
+#[get_matches]
+fn get_matches(input: RString, state: &State) -> RVec<Match> {
+    let filter = match input.strip_prefix(&state.config.prefix) {
+        Some(filter) => filter.trim().to_lowercase(),
+        None => return RVec::new(),
+    };
+
+    state
+        .config
+        .sources
+        .iter()
+        .enumerate()
+        .filter(|(_, source)| source.name.to_lowercase().contains(&filter))
+        .map(|(index, source)| {
+            let running = state.running(source).is_some();
+            Match {
+                title: if running {
+                    format!("Stop sharing {}", source.name)
+                } else {
+                    format!("Share {}", source.name)
+                }
+                .into(),
+                description: ROption::RSome(
+                    match source.kind {
+                        Kind::Monitor => "A monitor",
+                        Kind::Window => "A window",
+                        Kind::Virtual => "A virtual monitor",
+                    }
+                    .into(),
+                ),
+                title_pango: false,
+                description_pango: false,
+                icon: ROption::RSome(
+                    if running {
+                        "media-playback-stop"
+                    } else {
+                        "video-display"
+                    }
+                    .into(),
+                ),
+                id: ROption::RSome(index as u64),
+                actions: RVec::new(),
+                action: ROption::RNone,
+                image: ROption::RNone,
+                widgets: RVec::new(),
+                priority: 0,
+                pinned: false,
+            }
+        })
+        .collect()
+}
+
+#[handler]
+fn handler(selection: Match, state: &State) -> HandleResult {
+    let source = &state.config.sources[selection.id.unwrap() as usize];
+
+    match state.running(source) {
+        // The command reading the stream is in the process group of the helper, so it stops too
+        Some(pid) => {
+            if let Err(why) = signal::killpg(pid, Signal::SIGTERM) {
+                log::error!("Failed to stop sharing {}: {}", source.name, why);
+            }
+            let _ = fs::remove_file(state.pid_file(source));
+        }
+        None => {
+            let _ = fs::create_dir_all(&state.runtime_dir);
+            let result = Command::new(&state.config.helper)
+                .arg(state.pid_file(source))
+                .arg(state.token_dir.join(format!("{}.token", source.name)))
+                .arg((source.kind as u32).to_string())
+                .arg((source.cursor as u32).to_string())
+                .arg(&source.command)
+                .stdin(Stdio::null())
+                .process_group(0)
+                .spawn();
+
+            if let Err(why) = result {
+                log::error!("Failed to run {}: {}", state.config.helper, why);
+            }
+        }
+    }
+
+    HandleResult::Close
+}
@@ -0,0 +1,197 @@
+//! Keeps a session of the ScreenCast portal open for the plugin, as the portal closes the session
+//! once the connection that created it goes away. Runs the command receiving the stream, and
+//! closes the session when it exits.
+//!
+//! `anyrun-screencast <pid file> <token file> <source types> <cursor mode> <command>`
+
+use std::{
+    collections::HashMap,
+    env,
+    error::Error,
+    fs,
+    os::fd::IntoRawFd,
+    path::Path,
+    process::{self, Command},
+};
+
+use nix::fcntl::{self, FcntlArg, FdFlag};
+use zbus::{
+    blocking::{Connection, Proxy},
+    zvariant::{ObjectPath, OwnedFd, OwnedValue, Value},
+};
+
+type Results = HashMap<String, OwnedValue>;
+
+struct Portal {
+    connection: Connection,
+    screencast: Proxy<'static>,
+    /// The unique name of the connection the way it appears in the paths of the requests
+    sender: String,
+    next_token: u32,
+}
+
+impl Portal {
+    fn new() -> zbus::Result<Self> {
+        let connection = Connection::session()?;
+        let screencast = Proxy::new(
+            &connection,
+            "org.freedesktop.portal.Desktop",
+            "/org/freedesktop/portal/desktop",
+            "org.freedesktop.portal.ScreenCast",
+        )?;
+        let sender = connection
+            .unique_name()
+            .map(|name| name.trim_start_matches(':').replace('.', "_"))
+            .unwrap_or_default();
+
+        Ok(Self {
+            connection,
+            screencast,
+            sender,
+            next_token: 0,
+        })
+    }
+
+    fn token(&mut self) -> String {
+        self.next_token += 1;
+        format!("anyrun{}", self.next_token)
+    }
+
+    /// Call a method that answers with the Response signal of a request object. The path of the
+    /// object is known from the token beforehand, so that the response can't be missed.
+    fn request<B>(&self, method: &str, body: &B, token: &str) -> Result<Results, Box<dyn Error>>
+    where
+        B: serde::Serialize + zbus::zvariant::DynamicType,
+    {
+        let request = Proxy::new(
+            &self.connection,
+            "org.freedesktop.portal.Desktop",
+            format!(
+                "/org/freedesktop/portal/desktop/request/{}/{}",
+                self.sender, token
+            ),
+            "org.freedesktop.portal.Request",
+        )?;
+        let mut responses = request.receive_signal("Response")?;
+
+        self.screencast.call_method(method, body)?;
+
+        let response = responses.next().ok_or("The portal went away")?;
+        match response.body::<(u32, Results)>()? {
+            (0, results) => Ok(results),
+            (1, _) => Err("Cancelled".into()),
+            _ => Err(format!("{} failed", method).into()),
+        }
+    }
+}
+
+fn main() {
+    let args = env::args().skip(1).collect::<Vec<_>>();
+    let [pid_file, token_file, types, cursor_mode, command] = match <[String; 5]>::try_from(args) {
+        Ok(args) => args,
+        Err(_) => {
+            eprintln!(
+                "Usage: anyrun-screencast <pid file> <token file> <source types> <cursor mode> <command>"
+            );
+            process::exit(2);
+        }
+    };
+
+    if let Err(why) = fs::write(&pid_file, process::id().to_string()) {
+        eprintln!("Failed to write {}: {}", pid_file, why);
+    }
+
+    let result = share(
+        Path::new(&token_file),
+        types.parse().unwrap_or(1),
+        cursor_mode.parse().unwrap_or(2),
+        &command,
+    );
+    let _ = fs::remove_file(&pid_file);
+
+    if let Err(why) = result {
+        eprintln!("Failed to share the screen: {}", why);
+        process::exit(1);
+    }
+}
+
+fn share(
+    token_file: &Path,
+    types: u32,
+    cursor_mode: u32,
+    command: &str,
+) -> Result<(), Box<dyn Error>> {
+    let mut portal = Portal::new()?;
+
+    let token = portal.token();
+    let session_token = portal.token();
+    let options = HashMap::from([
+        ("handle_token", Value::from(token.as_str())),
+        ("session_handle_token", Value::from(session_token.as_str())),
+    ]);
+    let results = portal.request("CreateSession", &(options,), &token)?;
+    let session = String::try_from(
+        results
+            .get("session_handle")
+            .ok_or("No session was created")?
+            .clone(),
+    )?;
+    let session = ObjectPath::try_from(session.as_str())?;
+
+    // Share what was chosen the last time, without asking again
+    let restore_token = fs::read_to_string(token_file).ok();
+    let token = portal.token();
+    let mut options = HashMap::from([
+        ("handle_token", Value::from(token.as_str())),
+        ("types", Value::U32(types)),
+        ("multiple", Value::Bool(false)),
+        ("cursor_mode", Value::U32(cursor_mode)),
+        // Until the permission is revoked
+        ("persist_mode", Value::U32(2)),
+    ]);
+    if let Some(restore_token) = &restore_token {
+        options.insert("restore_token", Value::from(restore_token.trim()));
+    }
+    portal.request("SelectSources", &(&session, options), &token)?;
+
+    let token = portal.token();
+    let options = HashMap::from([("handle_token", Value::from(token.as_str()))]);
+    let results = portal.request("Start", &(&session, "", options), &token)?;
+
+    if let Some(restore_token) = results.get("restore_token") {
+        let restore_token = String::try_from(restore_token.clone())?;
+        if let Some(parent) = token_file.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(token_file, restore_token)?;
+    }
+
+    let streams = Vec::<(u32, Results)>::try_from(
+        results
+            .get("streams")
+            .ok_or("Nothing is being shared")?
+            .clone(),
+    )?;
+    let node = streams.first().ok_or("Nothing is being shared")?.0;
+
+    let fd = portal
+        .screencast
+        .call_method(
+            "OpenPipeWireRemote",
+            &(&session, HashMap::<&str, Value>::new()),
+        )?
+        .body::<OwnedFd>()?
+        .into_raw_fd();
+    // Let the command inherit the remote
+    fcntl::fcntl(fd, FcntlArg::F_SETFD(FdFlag::empty()))?;
+
+    let command = command
+        .replace("{fd}", &fd.to_string())
+        .replace("{node}", &node.to_string());
+    let status = Command::new("sh").arg("-c").arg(&command).status()?;
+    if !status.success() {
+        return Err(format!("`{}` failed", command).into());
+    }
+
+    Ok(())
+}
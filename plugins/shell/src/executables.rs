@@ -0,0 +1,74 @@
+//! The programs in the directories of `$PATH`, for completing the command being typed. The names
+//! in each directory are kept in the store of the plugin with its modification time, so only the
+//! directories that changed since are read again.
+
+use std::{
+    collections::{BTreeSet, HashMap},
+    env, fs,
+    os::unix::fs::PermissionsExt,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use anyrun_plugin::{log, store};
+use serde::{Deserialize, Serialize};
+
+const KEY: &str = "executables";
+
+#[derive(Serialize, Deserialize)]
+struct Dir {
+    modified: SystemTime,
+    names: Vec<String>,
+}
+
+fn read(dir: &Path) -> Vec<String> {
+    fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| {
+            // Following the links, which many of the programs are
+            fs::metadata(entry.path()).is_ok_and(|metadata| {
+                metadata.is_file() && metadata.permissions().mode() & 0o111 != 0
+            })
+        })
+        .map(|entry| entry.file_name().to_string_lossy().to_string())
+        .collect()
+}
+
+/// The names of the programs, sorted and each of them once
+pub fn load() -> Vec<String> {
+    let mut cached: HashMap<PathBuf, Dir> = store::get(KEY).unwrap_or_default();
+    let dirs = env::var_os("PATH")
+        .map(|paths| env::split_paths(&paths).collect::<Vec<_>>())
+        .unwrap_or_default();
+
+    let mut changed = false;
+    let mut names = BTreeSet::new();
+    for dir in &dirs {
+        let modified = match fs::metadata(dir).and_then(|metadata| metadata.modified()) {
+            Ok(modified) => modified,
+            Err(_) => continue,
+        };
+        match cached.get(dir) {
+            Some(cached) if cached.modified == modified => (),
+            _ => {
+                let names = read(dir);
+                cached.insert(dir.clone(), Dir { modified, names });
+                changed = true;
+            }
+        }
+        names.extend(cached[dir].names.iter().cloned());
+    }
+
+    // The directories no longer in `$PATH` are left out
+    let count = cached.len();
+    cached.retain(|dir, _| dirs.contains(dir));
+    if changed || cached.len() != count {
+        if let Err(why) = store::set(KEY, &cached) {
+            log::warn!("Failed to save the executables: {}", why);
+        }
+    }
+
+    names.into_iter().collect()
+}
@@ -0,0 +1,117 @@
+//! The commands run before in the shell, read from its history file. bash, zsh and fish are known,
+//! each with a file format of its own. The commands spanning several lines are left out, as those
+//! aren't run from anyrun either.
+
+use std::{collections::HashSet, env, fs, path::PathBuf};
+
+use crate::Config;
+
+fn home() -> String {
+    env::var("HOME").unwrap_or_default()
+}
+
+/// Where the shell keeps its history unless told otherwise
+fn default_path(shell: &str) -> Option<PathBuf> {
+    match shell {
+        "bash" => Some(PathBuf::from(format!("{}/.bash_history", home()))),
+        "zsh" => {
+            let dir = env::var("ZDOTDIR").unwrap_or_else(|_| home());
+            Some(PathBuf::from(format!("{}/.zsh_history", dir)))
+        }
+        "fish" => {
+            let data_home =
+                env::var("XDG_DATA_HOME").unwrap_or_else(|_| format!("{}/.local/share", home()));
+            Some(PathBuf::from(format!("{}/fish/fish_history", data_home)))
+        }
+        _ => None,
+    }
+}
+
+/// The lines of `.bash_history`, with the timestamps written by `HISTTIMEFORMAT` left out
+fn bash(content: &str) -> Vec<String> {
+    content
+        .lines()
+        .filter(|line| !(line.starts_with('#') && line[1..].chars().all(|c| c.is_ascii_digit())))
+        .map(str::to_string)
+        .collect()
+}
+
+/// zsh writes the bytes it uses as tokens, which are in many characters that aren't ASCII, as 0x83
+/// followed by the byte xor 0x20
+fn unmetafy(bytes: &[u8]) -> Vec<u8> {
+    let mut unmetafied = Vec::with_capacity(bytes.len());
+    let mut bytes = bytes.iter();
+    while let Some(byte) = bytes.next() {
+        match byte {
+            0x83 => unmetafied.extend(bytes.next().map(|byte| byte ^ 0x20)),
+            _ => unmetafied.push(*byte),
+        }
+    }
+    unmetafied
+}
+
+/// The lines of `.zsh_history`, which start with `: <time>:<duration>;` when `EXTENDED_HISTORY` is
+/// set. A line ending in a backslash goes on in the next one.
+fn zsh(content: &str) -> Vec<String> {
+    let mut commands = Vec::new();
+    let mut multiline = false;
+    for line in content.lines() {
+        let continued = multiline;
+        multiline = line.ends_with('\\');
+        if continued || multiline {
+            continue;
+        }
+
+        let command = match line.strip_prefix(": ") {
+            Some(extended) => extended
+                .split_once(';')
+                .map_or(line, |(_, command)| command),
+            None => line,
+        };
+        commands.push(command.to_string());
+    }
+    commands
+}
+
+/// The `- cmd:` lines of the YAML-like `fish_history`
+fn fish(content: &str) -> Vec<String> {
+    content
+        .lines()
+        .filter_map(|line| line.strip_prefix("- cmd: "))
+        .filter(|command| !command.contains("\\n"))
+        .map(|command| command.replace("\\\\", "\\"))
+        .collect()
+}
+
+/// The commands in the history of the shell, the most recent first and each of them once
+pub fn load(config: &Config, shell: &str) -> Vec<String> {
+    let name = shell.rsplit('/').next().unwrap_or(shell);
+    let path = match config.history_files.get(name) {
+        Some(path) => match path.strip_prefix("~/") {
+            Some(path) => PathBuf::from(home()).join(path),
+            None => PathBuf::from(path),
+        },
+        None => match default_path(name) {
+            Some(path) => path,
+            None => return Vec::new(),
+        },
+    };
+    let bytes = match fs::read(&path) {
+        Ok(bytes) => bytes,
+        Err(_) => return Vec::new(),
+    };
+
+    let commands = match name {
+        "zsh" => zsh(&String::from_utf8_lossy(&unmetafy(&bytes))),
+        "fish" => fish(&String::from_utf8_lossy(&bytes)),
+        _ => bash(&String::from_utf8_lossy(&bytes)),
+    };
+
+    let mut seen = HashSet::new();
+    commands
+        .into_iter()
+        .rev()
+        .map(|command| command.trim().to_string())
+        .filter(|command| !command.is_empty() && seen.insert(command.clone()))
+        .collect()
+}
@@ -1,13 +1,94 @@
-use std::{env, fs, process::Command};
+use std::{cmp::Reverse, collections::HashMap, env, process::Command};
+
+use fuzzy_matcher::FuzzyMatcher;
 
 use abi_stable::std_types::{ROption, RString, RVec};
 use anyrun_plugin::*;
 use serde::Deserialize;
 
+use preview::Preview;
+
+mod executables;
+mod history;
+mod preview;
+mod terminal;
+
+/// The action running the command as a job, with its progress in notifications
+const BACKGROUND: u64 = 0;
+/// The action running the command in a terminal, like starting it with `!`
+const IN_TERMINAL: u64 = 1;
+
+/// The action running a previewed command instead of copying its output
+const RUN: u64 = 2;
+
+/// The id of the matches from the history of the shell
+const HISTORY: u64 = 0;
+/// The id of the match showing the output of the command
+const PREVIEW: u64 = 1;
+/// The id of the matches completing the name of a program
+const EXECUTABLE: u64 = 2;
+
 #[derive(Deserialize)]
 struct Config {
     prefix: String,
     shell: Option<String>,
+    /// The command line of the terminal, with `{}` where the command goes, like `foot -e {}`
+    #[serde(default)]
+    terminal: Option<String>,
+    /// Wait for Enter after the commands run in a terminal, so that their output stays visible
+    #[serde(default)]
+    hold: bool,
+    /// Suggest the commands in the history of the shell
+    #[serde(default = "Config::default_history")]
+    history: bool,
+    /// How many of the commands in the history to suggest
+    #[serde(default = "Config::default_max_entries")]
+    max_entries: usize,
+    /// The history files of the shells by their names, for the ones not where the shells keep
+    /// them by default
+    #[serde(default)]
+    history_files: HashMap<String, String>,
+    /// Complete the names of the programs in `$PATH`
+    #[serde(default = "Config::default_executables")]
+    executables: bool,
+    /// The commands that are run while they are typed to show their output, as regexes the whole
+    /// command has to match
+    #[serde(default = "Config::default_preview")]
+    preview: Vec<String>,
+    /// How many milliseconds a previewed command may take
+    #[serde(default = "Config::default_preview_timeout")]
+    preview_timeout: u64,
+    /// How many lines of the output to show
+    #[serde(default = "Config::default_preview_lines")]
+    preview_lines: usize,
+}
+
+impl Config {
+    fn default_history() -> bool {
+        true
+    }
+
+    fn default_max_entries() -> usize {
+        5
+    }
+
+    fn default_executables() -> bool {
+        true
+    }
+
+    fn default_preview() -> Vec<String> {
+        ["date", "whoami", "hostname", "uptime", "uname( -[a-z]+)?"]
+            .map(str::to_string)
+            .to_vec()
+    }
+
+    fn default_preview_timeout() -> u64 {
+        200
+    }
+
+    fn default_preview_lines() -> usize {
+        3
+    }
 }
 
 impl Default for Config {
@@ -15,15 +96,53 @@ impl Default for Config {
         Config {
             prefix: ":sh".to_string(),
             shell: None,
+            terminal: None,
+            hold: false,
+            history: Self::default_history(),
+            max_entries: Self::default_max_entries(),
+            history_files: HashMap::new(),
+            executables: Self::default_executables(),
+            preview: Self::default_preview(),
+            preview_timeout: Self::default_preview_timeout(),
+            preview_lines: Self::default_preview_lines(),
         }
     }
 }
 
+struct State {
+    config: Config,
+    /// The most recent first
+    history: Vec<String>,
+    /// The names of the programs in `$PATH`
+    executables: Vec<String>,
+    preview: Preview,
+}
+
+/// The configured shell, otherwise the one of the user
+fn shell(config: &Config) -> Option<String> {
+    config.shell.clone().or_else(|| env::var("SHELL").ok())
+}
+
 #[init]
-fn init(config_dir: RString) -> Config {
-    match fs::read_to_string(format!("{}/shell.ron", config_dir)) {
-        Ok(content) => ron::from_str(&content).unwrap_or_default(),
-        Err(_) => Config::default(),
+fn init(config_dir: RString) -> State {
+    let config: Config = plugin_config(&config_dir, "shell");
+
+    prefix::set(&config.prefix);
+    let history = match shell(&config) {
+        Some(shell) if config.history => history::load(&config, &shell),
+        _ => Vec::new(),
+    };
+    let executables = if config.executables {
+        executables::load()
+    } else {
+        Vec::new()
+    };
+    let preview = Preview::new(&config);
+    State {
+        config,
+        history,
+        executables,
+        preview,
     }
 }
 
@@ -35,45 +154,201 @@ fn info() -> PluginInfo {
     }
 }
 
-#[get_matches]
-fn get_matches(input: RString, config: &Config) -> RVec<Match> {
-    if input.starts_with(&config.prefix) {
-        let (_, command) = input.split_once(&config.prefix).unwrap();
-        if !command.is_empty() {
-            vec![Match {
-                title: command.trim().into(),
-                description: ROption::RSome(
-                    config
-                        .shell
-                        .clone()
-                        .unwrap_or_else(|| {
-                            env::var("SHELL").unwrap_or_else(|_| {
-                                "The shell could not be determined!".to_string()
-                            })
-                        })
-                        .into(),
-                ),
-                use_pango: false,
-                icon: ROption::RNone,
-                id: ROption::RNone,
-            }]
-            .into()
-        } else {
-            RVec::new()
-        }
+#[validate_input]
+fn validate_input(input: RString) -> ValidateResult {
+    // Multiline commands are most likely pasted by accident, refuse to run them
+    if input.contains('\n') {
+        ValidateResult::Reject
     } else {
-        RVec::new()
+        ValidateResult::Accept
+    }
+}
+
+#[get_matches]
+fn get_matches(input: RString, state: &State) -> RVec<Match> {
+    let config = &state.config;
+    let command = match input.strip_prefix(&config.prefix) {
+        Some(command) => command.trim(),
+        None => return RVec::new(),
+    };
+    let shell = shell(config);
+
+    let mut matches = Vec::new();
+    let output = shell
+        .as_ref()
+        .filter(|_| !command.is_empty())
+        .and_then(|shell| state.preview.output(shell, command));
+    if let Some(output) = output {
+        let mut row = row(command);
+        row.actions.insert(
+            0,
+            Action {
+                title: "Run".into(),
+                icon: ROption::RSome("system-run".into()),
+                id: RUN,
+            },
+        );
+        matches.push(Match {
+            description: ROption::RSome(
+                output
+                    .lines()
+                    .take(config.preview_lines)
+                    .collect::<Vec<_>>()
+                    .join("\n")
+                    .into(),
+            ),
+            id: ROption::RSome(PREVIEW),
+            ..row
+        });
+    } else if !command.is_empty() {
+        matches.push(Match {
+            description: ROption::RSome(
+                shell
+                    .clone()
+                    .unwrap_or_else(|| "The shell could not be determined!".to_string())
+                    .into(),
+            ),
+            ..row(command)
+        });
+    }
+
+    // The most recent ones for just the prefix, otherwise the ones most like the typed command
+    let matcher = fuzzy_matcher::skim::SkimMatcherV2::default().smart_case();
+    let mut suggestions = state
+        .history
+        .iter()
+        .enumerate()
+        .filter(|(_, past)| past.as_str() != command)
+        .filter_map(|(index, past)| {
+            if command.is_empty() {
+                Some((past, -(index as i64)))
+            } else {
+                matcher
+                    .fuzzy_match(past, command)
+                    .map(|score| (past, score))
+            }
+        })
+        .collect::<Vec<_>>();
+    suggestions.sort_by_key(|(_, score)| Reverse(*score));
+    suggestions.truncate(config.max_entries);
+
+    let description = shell.map_or("History".to_string(), |shell| {
+        format!(
+            "From the history of {}",
+            shell.rsplit('/').next().unwrap_or(&shell)
+        )
+    });
+    matches.extend(suggestions.into_iter().map(|(past, _)| Match {
+        description: ROption::RSome(description.clone().into()),
+        id: ROption::RSome(HISTORY),
+        ..row(past)
+    }));
+
+    // The programs named like the first word, with the rest of the command after them
+    if !command.is_empty() {
+        let (program, args) = command.split_once(' ').unwrap_or((command, ""));
+        let mut completions = state
+            .executables
+            .iter()
+            .filter(|name| name.as_str() != program)
+            .filter_map(|name| {
+                matcher
+                    .fuzzy_match(name, program)
+                    .map(|score| (name, score))
+            })
+            .collect::<Vec<_>>();
+        completions.sort_by_key(|(_, score)| Reverse(*score));
+        completions.truncate(config.max_entries);
+
+        matches.extend(completions.into_iter().map(|(name, _)| {
+            let completed = match args {
+                "" => name.clone(),
+                args => format!("{} {}", name, args),
+            };
+            Match {
+                description: ROption::RSome("Program in $PATH".into()),
+                id: ROption::RSome(EXECUTABLE),
+                ..row(&completed)
+            }
+        }));
+    }
+
+    matches.into()
+}
+
+fn row(command: &str) -> Match {
+    Match {
+        title: command.into(),
+        description: ROption::RNone,
+        title_pango: false,
+        description_pango: false,
+        icon: ROption::RNone,
+        id: ROption::RNone,
+        actions: vec![
+            Action {
+                title: "Run in the background".into(),
+                icon: ROption::RSome("system-run".into()),
+                id: BACKGROUND,
+            },
+            Action {
+                title: "Run in a terminal".into(),
+                icon: ROption::RSome("utilities-terminal".into()),
+                id: IN_TERMINAL,
+            },
+        ]
+        .into(),
+        action: ROption::RNone,
+        image: ROption::RNone,
+        widgets: RVec::new(),
+        priority: 0,
+        pinned: false,
     }
 }
 
 #[handler]
-fn handler(selection: Match) -> HandleResult {
-    if let Err(why) = Command::new(selection.description.unwrap().as_str())
-        .arg("-c")
-        .arg(selection.title.as_str())
-        .spawn()
-    {
-        println!("Failed to run command: {}", why);
+fn handler(selection: Match, input: RString, state: &State) -> HandleResult {
+    let config = &state.config;
+    // The commands from the history and the completed ones are run as they are, and a past selection picked from the
+    // recently used ones comes with the input that searched for it instead
+    let command = match selection.id {
+        ROption::RSome(HISTORY | EXECUTABLE) => selection.title.as_str(),
+        _ => input
+            .strip_prefix(&config.prefix)
+            .map_or(selection.title.as_str(), str::trim),
+    };
+
+    let shell = match shell(config) {
+        Some(shell) => shell,
+        None => {
+            log::error!("The shell could not be determined");
+            return HandleResult::Close;
+        }
+    };
+
+    // The output was shown, so it is what is wanted
+    if selection.id == ROption::RSome(PREVIEW) && selection.action.is_none() {
+        if let Some(output) = state.preview.output(&shell, command) {
+            return HandleResult::Copy(output.into_bytes().into(), ROption::RNone);
+        }
+    }
+
+    let (command, in_terminal) = match command.strip_prefix('!') {
+        Some(command) => (command.trim(), true),
+        None => (command, selection.action == ROption::RSome(IN_TERMINAL)),
+    };
+
+    if in_terminal {
+        if let Err(why) = terminal::run(config, &shell, command, config.hold) {
+            log::error!("Failed to run the command in a terminal: {}", why);
+        }
+    } else if let ROption::RSome(BACKGROUND) = selection.action {
+        if let Err(why) = jobs::start(command, &shell, &["-c", command]) {
+            report::error!("Failed to start the job: {}", why);
+            // Staying open for the error to be seen
+            return HandleResult::Refresh(false);
+        }
+    } else if let Err(why) = Command::new(shell.as_str()).arg("-c").arg(command).spawn() {
+        log::error!("Failed to run command: {}", why);
     }
 
     HandleResult::Close
@@ -0,0 +1,110 @@
+//! Running the commands that are quick and change nothing while they are typed, to show what they
+//! print. Only the commands matching one of the configured patterns are run, and only for as long
+//! as the time budget allows.
+
+use std::{
+    io::Read,
+    os::unix::process::CommandExt,
+    process::{Command, Stdio},
+    sync::{mpsc, Mutex},
+    thread,
+    time::Duration,
+};
+
+use anyrun_plugin::report;
+use nix::{
+    sys::signal::{self, Signal},
+    unistd::Pid,
+};
+use regex::Regex;
+
+use crate::Config;
+
+pub struct Preview {
+    patterns: Vec<Regex>,
+    timeout: Duration,
+    /// The output of the last command, which is asked for again as long as the input stays the
+    /// same, and run again once it was changed
+    last: Mutex<Option<(String, String)>>,
+}
+
+impl Preview {
+    pub fn new(config: &Config) -> Self {
+        let patterns = config
+            .preview
+            .iter()
+            // The whole command has to match, not just a part of it
+            .filter_map(|pattern| match Regex::new(&format!("^(?:{})$", pattern)) {
+                Ok(regex) => Some(regex),
+                Err(why) => {
+                    report::error!("Invalid preview pattern {}: {}", pattern, why);
+                    None
+                }
+            })
+            .collect();
+
+        Self {
+            patterns,
+            timeout: Duration::from_millis(config.preview_timeout),
+            last: Mutex::new(None),
+        }
+    }
+
+    /// What the command prints, if it may be previewed and is done in time
+    pub fn output(&self, shell: &str, command: &str) -> Option<String> {
+        let mut last = self.last.lock().unwrap();
+        match &*last {
+            Some((previous, output)) if previous == command => return Some(output.clone()),
+            // Typing the command again runs it again
+            Some(_) => *last = None,
+            None => (),
+        }
+
+        if !self.patterns.iter().any(|regex| regex.is_match(command)) {
+            return None;
+        }
+
+        let output = run(shell, command, self.timeout)?;
+        *last = Some((command.to_string(), output.clone()));
+        Some(output)
+    }
+}
+
+/// The stdout of the command, None when it fails or takes longer than the timeout, in which case
+/// it is killed with the programs it started
+fn run(shell: &str, command: &str, timeout: Duration) -> Option<String> {
+    let mut child = Command::new(shell)
+        .arg("-c")
+        .arg(command)
+        // A group of its own, as the reader would otherwise wait for the programs of a pipeline
+        // still holding stdout after the shell was killed
+        .process_group(0)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .ok()?;
+
+    // Read on a thread of its own, as reading blocks until the command is done
+    let mut stdout = child.stdout.take()?;
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        let mut output = Vec::new();
+        let _ = stdout.read_to_end(&mut output);
+        let _ = sender.send(output);
+    });
+
+    match receiver.recv_timeout(timeout) {
+        Ok(output) => {
+            let status = child.wait().ok()?;
+            status
+                .success()
+                .then(|| String::from_utf8_lossy(&output).trim_end().to_string())
+        }
+        Err(_) => {
+            let _ = signal::killpg(Pid::from_raw(child.id() as i32), Signal::SIGKILL);
+            let _ = child.wait();
+            None
+        }
+    }
+}
@@ -0,0 +1,103 @@
+//! Running the commands in a terminal, for the ones that are interactive or whose output is to be
+//! read. The command line of the terminal is a template with `{}` where the command goes, as the
+//! terminals don't agree on how they take it.
+
+use std::{
+    env, fs, io,
+    os::unix::fs::PermissionsExt,
+    path::Path,
+    process::{Command, Stdio},
+};
+
+use crate::Config;
+
+/// The terminals looked for when none is configured, in this order
+const SENSIBLE_TERMINALS: &[&str] = &[
+    "alacritty -e {}",
+    "foot {}",
+    "kitty {}",
+    "wezterm start -- {}",
+    "gnome-terminal -- {}",
+    "konsole -e {}",
+    "wterm -e {}",
+    "xterm -e {}",
+];
+
+fn installed(program: &str) -> bool {
+    let executable = |path: &Path| {
+        fs::metadata(path)
+            .is_ok_and(|metadata| metadata.is_file() && metadata.permissions().mode() & 0o111 != 0)
+    };
+
+    if program.contains('/') {
+        return executable(Path::new(program));
+    }
+    env::var_os("PATH")
+        .is_some_and(|paths| env::split_paths(&paths).any(|dir| executable(&dir.join(program))))
+}
+
+/// The configured terminal, the one in `$TERMINAL`, or the first of the known ones installed
+fn template(config: &Config) -> Option<String> {
+    if let Some(terminal) = &config.terminal {
+        return Some(terminal.clone());
+    }
+    if let Ok(terminal) = env::var("TERMINAL") {
+        if !terminal.is_empty() {
+            return Some(format!("{} -e {{}}", terminal));
+        }
+    }
+    SENSIBLE_TERMINALS
+        .iter()
+        .find(|template| installed(template.split(' ').next().unwrap()))
+        .map(|template| template.to_string())
+}
+
+/// Run the command with the shell in a terminal, which waits for Enter after the command when
+/// `hold` is set so that the output can still be read
+pub fn run(config: &Config, shell: &str, command: &str, hold: bool) -> io::Result<()> {
+    let template = template(config).ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::NotFound,
+            "None of the known terminals is installed, set terminal in the config",
+        )
+    })?;
+
+    let command = if hold {
+        format!("{}; printf '\\n[Press Enter to close]'; read _", command)
+    } else {
+        command.to_string()
+    };
+    let wrapped = [shell, "-c", &command];
+
+    // Quoted like in a shell, for the arguments with spaces in them
+    let template = shlex::split(&template).ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "The quotes of the terminal aren't closed",
+        )
+    })?;
+    let mut args = Vec::new();
+    let mut placed = false;
+    for arg in template {
+        if arg == "{}" {
+            args.extend(wrapped.iter().map(|arg| arg.to_string()));
+            placed = true;
+        } else {
+            args.push(arg);
+        }
+    }
+    if !placed {
+        args.extend(wrapped.iter().map(|arg| arg.to_string()));
+    }
+
+    let (program, args) = args
+        .split_first()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "The terminal is empty"))?;
+    Command::new(program)
+        .args(args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .map(|_| ())
+}
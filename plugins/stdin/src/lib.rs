@@ -1,4 +1,4 @@
-use std::{fs, io::stdin};
+use std::{cmp::Reverse, env, io::stdin};
 
 use abi_stable::std_types::{ROption, RString, RVec};
 use anyrun_plugin::*;
@@ -22,20 +22,27 @@ impl Default for Config {
 
 struct State {
     config: Config,
+    /// Set with `-i` in the dmenu mode of anyrun
+    ignore_case: bool,
     lines: Vec<String>,
 }
 
 #[init]
 fn init(config_dir: RString) -> State {
-    let config = if let Ok(content) = fs::read_to_string(format!("{}/stdin.ron", config_dir)) {
-        ron::from_str(&content).unwrap_or_default()
-    } else {
-        Config::default()
-    };
+    let mut config: Config = plugin_config(&config_dir, "stdin");
+
+    // The dmenu mode of anyrun passes on the options of its flags
+    if let Some(max_entries) = env::var("ANYRUN_STDIN_MAX_ENTRIES")
+        .ok()
+        .and_then(|max_entries| max_entries.parse().ok())
+    {
+        config.max_entries = max_entries;
+    }
 
     State {
         config,
-        lines: stdin().lines().filter_map(|line| line.ok()).collect(),
+        ignore_case: env::var_os("ANYRUN_STDIN_IGNORE_CASE").is_some(),
+        lines: stdin().lines().map_while(Result::ok).collect(),
     }
 }
 
@@ -46,7 +53,11 @@ fn handler(_match: Match) -> HandleResult {
 
 #[get_matches]
 fn get_matches(input: RString, state: &State) -> RVec<Match> {
-    let matcher = fuzzy_matcher::skim::SkimMatcherV2::default().smart_case();
+    let matcher = if state.ignore_case {
+        fuzzy_matcher::skim::SkimMatcherV2::default().ignore_case()
+    } else {
+        fuzzy_matcher::skim::SkimMatcherV2::default().smart_case()
+    };
 
     let mut lines = state
         .lines
@@ -60,7 +71,7 @@ fn get_matches(input: RString, state: &State) -> RVec<Match> {
         .collect::<Vec<_>>();
 
     if !lines.is_empty() {
-        lines.sort_by(|a, b| b.1.cmp(&a.1));
+        lines.sort_by_key(|(_, score)| Reverse(*score));
         lines.truncate(state.config.max_entries);
     } else if state.config.allow_invalid {
         lines.push((input.into(), 0));
@@ -71,9 +82,16 @@ fn get_matches(input: RString, state: &State) -> RVec<Match> {
         .map(|(line, _)| Match {
             title: line.into(),
             description: ROption::RNone,
-            use_pango: false,
+            title_pango: false,
+            description_pango: false,
             icon: ROption::RNone,
             id: ROption::RNone,
+            actions: RVec::new(),
+            action: ROption::RNone,
+            image: ROption::RNone,
+            widgets: RVec::new(),
+            priority: 0,
+            pinned: false,
         })
         .collect::<Vec<_>>()
         .into()
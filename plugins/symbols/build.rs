@@ -9,7 +9,7 @@ fn main() {
     let mut file = File::create(format!("{}/unicode.rs", env::var("OUT_DIR").unwrap()))
         .expect("Unable to create unicode output file!");
 
-    file.write_all(b"#[allow(text_direction_codepoint_in_literal)]\nconst UNICODE_CHARS: &[(&str, &str)] = &[\n")
+    file.write_all(b"const UNICODE_CHARS: &[(&str, &str)] = &[\n")
         .unwrap();
     string.lines().for_each(|line| {
         let fields = line.split(';').collect::<Vec<_>>();
@@ -18,8 +18,10 @@ fn main() {
             None => return,
         };
 
+        // Escaped where needed, as rustc rejects the characters changing the direction of the text
+        // in literals
         if fields[1] != "<control>" {
-            file.write_all(format!("(r#\"{}\"#, r#\"{}\"#),\n", fields[1], chr).as_bytes())
+            file.write_all(format!("(r#\"{}\"#, {:?}),\n", fields[1], chr.to_string()).as_bytes())
                 .unwrap();
         }
     });
@@ -1,4 +1,4 @@
-use std::{collections::HashMap, fs};
+use std::collections::HashMap;
 
 use abi_stable::std_types::{ROption, RString, RVec};
 use anyrun_plugin::*;
@@ -11,6 +11,8 @@ include!(concat!(env!("OUT_DIR"), "/unicode.rs"));
 struct Symbol {
     chr: String,
     name: String,
+    /// From the config, which come before the unicode ones that score the same
+    user: bool,
 }
 
 #[derive(Deserialize, Debug)]
@@ -38,17 +40,24 @@ struct State {
 #[init]
 fn init(config_dir: RString) -> State {
     // Try to load the config file, if it does not exist only use the static unicode characters
-    let config = if let Ok(content) = fs::read_to_string(format!("{}/symbols.ron", config_dir)) {
-        ron::from_str(&content).unwrap_or_default()
-    } else {
-        Config::default()
-    };
+    let config: Config = plugin_config(&config_dir, "symbols");
+    prefix::set(&config.prefix);
 
-    let symbols = UNICODE_CHARS
-        .iter()
-        .map(|(name, chr)| (name.to_string(), chr.to_string()))
-        .chain(config.symbols.clone().into_iter())
-        .map(|(name, chr)| Symbol { chr, name })
+    let user = config
+        .symbols
+        .clone()
+        .into_iter()
+        .map(|(name, chr)| Symbol {
+            chr,
+            name,
+            user: true,
+        });
+    let symbols = user
+        .chain(UNICODE_CHARS.iter().map(|(name, chr)| Symbol {
+            chr: chr.to_string(),
+            name: name.to_string(),
+            user: false,
+        }))
         .collect();
 
     State { config, symbols }
@@ -80,8 +89,13 @@ fn get_matches(input: RString, state: &State) -> RVec<Match> {
         })
         .collect::<Vec<_>>();
 
-    // Sort the symbol list according to the score
-    symbols.sort_by(|a, b| b.1.cmp(&a.1));
+    // Sort the symbol list according to the score, or show the recently used ones first when there
+    // is nothing to score them by
+    if input.is_empty() {
+        recent::sort(&mut symbols, |(symbol, _)| &symbol.chr);
+    } else {
+        symbols.sort_by(|a, b| b.1.cmp(&a.1).then(b.0.user.cmp(&a.0.user)));
+    }
 
     symbols.truncate(state.config.max_entries);
 
@@ -90,14 +104,22 @@ fn get_matches(input: RString, state: &State) -> RVec<Match> {
         .map(|(symbol, _)| Match {
             title: symbol.chr.clone().into(),
             description: ROption::RSome(symbol.name.clone().into()),
-            use_pango: false,
+            title_pango: false,
+            description_pango: false,
             icon: ROption::RNone,
             id: ROption::RNone,
+            actions: RVec::new(),
+            action: ROption::RNone,
+            image: ROption::RNone,
+            widgets: RVec::new(),
+            priority: 0,
+            pinned: false,
         })
         .collect()
 }
 
 #[handler]
 fn handler(selection: Match) -> HandleResult {
-    HandleResult::Copy(selection.title.into_bytes())
+    recent::record(&selection.title);
+    HandleResult::Copy(selection.title.into_bytes(), ROption::RNone)
 }
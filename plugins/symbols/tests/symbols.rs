@@ -0,0 +1,27 @@
+use anyrun_plugin_test::{titles, HandleResult, Harness};
+
+const SHRUG: &str = "¯\\_(ツ)_/¯";
+
+#[test]
+fn symbols() {
+    let plugin = Harness::new(symbols::anyrun_internal_init_root_module)
+        .config(
+            "symbols.ron",
+            r#"Config(prefix: ":sy", symbols: { "shrug": "¯\\_(ツ)_/¯" }, max_entries: 3)"#,
+        )
+        .init();
+    assert_eq!(plugin.prefix().as_deref(), Some(":sy"));
+    assert!(plugin.query("shrug").is_empty());
+
+    // The symbols of the config come before the unicode ones that match as well, like SHRUG
+    let matches = plugin.query(":sy shrug");
+    assert!(matches.len() <= 3);
+    assert_eq!(titles(&matches)[0], SHRUG);
+    match plugin.select(&matches[0], ":sy shrug") {
+        HandleResult::Copy(bytes, _) => assert_eq!(bytes.as_slice(), SHRUG.as_bytes()),
+        _ => panic!("The symbol wasn't copied"),
+    }
+
+    // The recently used ones first when only the prefix is typed
+    assert_eq!(titles(&plugin.query(":sy"))[0], SHRUG);
+}
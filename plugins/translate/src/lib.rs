@@ -1,7 +1,8 @@
-use std::fs;
+use std::cmp::Reverse;
 
 use abi_stable::std_types::{ROption, RString, RVec};
 use anyrun_plugin::*;
+use futures::stream::{FuturesOrdered, StreamExt};
 use fuzzy_matcher::FuzzyMatcher;
 use reqwest::Client;
 use serde::Deserialize;
@@ -33,11 +34,11 @@ struct State {
 
 #[init]
 fn init(config_dir: RString) -> State {
+    let config: Config = plugin_config(&config_dir, "translate");
+    prefix::set(&config.prefix);
+
     State {
-        config: match fs::read_to_string(format!("{}/translate.ron", config_dir)) {
-            Ok(content) => ron::from_str(&content).unwrap_or_default(),
-            Err(_) => Config::default(),
-        },
+        config,
         client: Client::new(),
         runtime: Runtime::new().expect("Failed to create tokio runtime"),
         langs: vec![
@@ -214,7 +215,7 @@ fn get_matches(input: RString, state: &State) -> RVec<Match> {
                 .flat_map(|src| dest_matches.clone().into_iter().map(move |dest| (Some(src), dest)))
                 .collect::<Vec<_>>();
 
-            matches.sort_by(|a, b| (b.1 .2 + b.0.unwrap().2).cmp(&(a.1 .2 + a.0.unwrap().2)));
+            matches.sort_by_key(|(src, dest)| Reverse(dest.2 + src.unwrap().2));
             matches
         }
         None => {
@@ -223,7 +224,7 @@ fn get_matches(input: RString, state: &State) -> RVec<Match> {
                 .map(|dest| (None, dest))
                 .collect::<Vec<_>>();
 
-            matches.sort_by(|a, b| b.1 .2.cmp(&a.1 .2));
+            matches.sort_by_key(|(_, dest)| Reverse(dest.2));
             matches
         }
     };
@@ -233,7 +234,7 @@ fn get_matches(input: RString, state: &State) -> RVec<Match> {
 
     state.runtime.block_on(async move {
         // Create the futures for fetching the translation results
-        let futures = matches
+        let mut responses = matches
             .into_iter()
             .map(|(src, dest)| async move {
                 match src {
@@ -241,50 +242,72 @@ fn get_matches(input: RString, state: &State) -> RVec<Match> {
                 (dest.1, state.client.get(format!("https://translate.googleapis.com/translate_a/single?client=gtx&sl={}&tl={}&dt=t&q={}", src.0, dest.0, text)).send().await),
                     None => (dest.1, state.client.get(format!("https://translate.googleapis.com/translate_a/single?client=gtx&sl=auto&tl={}&dt=t&q={}", dest.0, text)).send().await)
                 }
-            });
-       
-        let res = futures::future::join_all(futures) // Wait for all futures to complete
-            .await;
+            })
+            .collect::<FuturesOrdered<_>>();
 
-        res
-            .into_iter()
-            .filter_map(|(name, res)| res
-                .ok()
-                .map(|response| futures::executor::block_on(response.json())
-                    .ok()
-                    .map(|json: serde_json::Value|
-                        Match {
-                            title: json[0]
-                                .as_array()
-                                .expect("Malformed JSON!")
-                                .iter()
-                                .map(|val| val.as_array().expect("Malformed JSON!")[0].as_str()
-                                    .expect("Malformed JSON!")
-                                ).collect::<Vec<_>>()
-                                .join(" ")
-                                .into(),
-                            description: ROption::RSome(
-                                format!(
-                                    "{} -> {}",
-                                    state.langs.iter()
-                                    .find_map(|(code, name)| if *code == json[2].as_str().expect("Malformed JSON!") {
-                                            Some(*name)
-                                        } else {
-                                            None
-                                    }).unwrap_or_else(|| json[2].as_str().expect("Malformed JSON!")),
-                                    name)
-                                .into()),
-                            use_pango: false,
-                            icon: ROption::RNone,
-                            id: ROption::RNone
-                        }
+        // Each translation is shown once it and the ones before it are in, and the rest aren't
+        // waited for once the input changed
+        while let Some((name, res)) = responses.next().await {
+            if cancel::is_cancelled() {
+                break;
+            }
+
+            let json: serde_json::Value = match res {
+                Ok(response) => match response.json().await {
+                    Ok(json) => json,
+                    Err(_) => continue,
+                },
+                Err(_) => continue,
+            };
+            stream::send([Match {
+                title: json[0]
+                    .as_array()
+                    .expect("Malformed JSON!")
+                    .iter()
+                    .map(|val| {
+                        val.as_array().expect("Malformed JSON!")[0]
+                            .as_str()
+                            .expect("Malformed JSON!")
+                    })
+                    .collect::<Vec<_>>()
+                    .join(" ")
+                    .into(),
+                description: ROption::RSome(
+                    format!(
+                        "{} -> {}",
+                        state
+                            .langs
+                            .iter()
+                            .find_map(|(code, name)| {
+                                if *code == json[2].as_str().expect("Malformed JSON!") {
+                                    Some(*name)
+                                } else {
+                                    None
+                                }
+                            })
+                            .unwrap_or_else(|| json[2].as_str().expect("Malformed JSON!")),
+                        name
                     )
-                )
-            ).flatten().collect::<RVec<_>>()
+                    .into(),
+                ),
+                title_pango: false,
+                description_pango: false,
+                icon: ROption::RNone,
+                id: ROption::RNone,
+                actions: RVec::new(),
+                action: ROption::RNone,
+                image: ROption::RNone,
+                widgets: RVec::new(),
+                priority: 0,
+                pinned: false,
+            }]);
+        }
+
+        RVec::new()
     })
 }
 
 #[handler]
 fn handler(selection: Match) -> HandleResult {
-    HandleResult::Copy(selection.title.into_bytes())
+    HandleResult::Copy(selection.title.into_bytes(), ROption::RNone)
 }
@@ -1,7 +1,7 @@
 use abi_stable::std_types::{ROption, RString, RVec};
 use anyrun_plugin::*;
 use serde::{Deserialize, Serialize};
-use std::{fmt, fs, process::Command};
+use std::{fmt, process::Command};
 use urlencoding::encode;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -54,10 +54,10 @@ impl Default for Config {
 
 #[init]
 fn init(config_dir: RString) -> Config {
-    match fs::read_to_string(format!("{}/websearch.ron", config_dir)) {
-        Ok(content) => ron::from_str(&content).unwrap_or_default(),
-        Err(_) => Config::default(),
-    }
+    let config: Config = plugin_config(&config_dir, "websearch");
+
+    prefix::set(&config.prefix);
+    config
 }
 
 #[info]
@@ -80,29 +80,38 @@ fn get_matches(input: RString, config: &Config) -> RVec<Match> {
             .map(|(i, engine)| Match {
                 title: input.trim_start_matches(&config.prefix).into(),
                 description: ROption::RSome(format!("Search with {}", engine).into()),
-                use_pango: false,
+                title_pango: false,
+                description_pango: false,
                 icon: ROption::RNone,
                 id: ROption::RSome(i as u64),
+                actions: RVec::new(),
+                action: ROption::RNone,
+                image: ROption::RNone,
+                widgets: RVec::new(),
+                priority: 0,
+                pinned: false,
             })
             .collect()
     }
 }
 
 #[handler]
-fn handler(selection: Match, config: &Config) -> HandleResult {
+fn handler(selection: Match, input: RString, config: &Config) -> HandleResult {
     let engine = &config.engines[selection.id.unwrap() as usize];
+    // A past selection picked from the history comes with the input that searched for it instead
+    let query = input
+        .strip_prefix(&config.prefix)
+        .unwrap_or(selection.title.as_str());
 
     if let Err(why) = Command::new("sh")
         .arg("-c")
         .arg(format!(
             "xdg-open https://{}",
-            engine
-                .value()
-                .replace("{}", &encode(&selection.title.to_string()))
+            engine.value().replace("{}", &encode(query))
         ))
         .spawn()
     {
-        println!("Failed to perform websearch: {}", why);
+        log::error!("Failed to perform websearch: {}", why);
     }
 
     HandleResult::Close
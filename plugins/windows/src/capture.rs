@@ -0,0 +1,210 @@
+//! Taking pictures of the windows with the toplevel export protocol of Hyprland. The compositor
+//! draws the window on its own for it, so the picture is of the window even while it is covered,
+//! like by anyrun, or on a workspace that isn't shown.
+
+use std::{
+    fs::File,
+    io::{Read, Seek, SeekFrom},
+    os::fd::{AsFd, FromRawFd},
+};
+
+use nix::sys::memfd::{memfd_create, MemFdCreateFlag};
+use wayland_client::{
+    delegate_noop,
+    globals::{registry_queue_init, GlobalListContents},
+    protocol::{wl_buffer::WlBuffer, wl_registry, wl_shm, wl_shm_pool::WlShmPool},
+    Connection, Dispatch, QueueHandle, WEnum,
+};
+use wayland_protocols_hyprland::toplevel_export::v1::client::{
+    hyprland_toplevel_export_frame_v1::{self, HyprlandToplevelExportFrameV1},
+    hyprland_toplevel_export_manager_v1::HyprlandToplevelExportManagerV1,
+};
+
+/// A picture with 4 bytes for each pixel, in RGBA order
+pub struct Picture {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>,
+}
+
+impl Picture {
+    /// The picture made smaller so that its longer side is at most `size`, each pixel being the
+    /// average of the ones it covers
+    pub fn shrink(self, size: u32) -> Self {
+        let longer = self.width.max(self.height);
+        if longer <= size {
+            return self;
+        }
+
+        let width = (self.width * size / longer).max(1);
+        let height = (self.height * size / longer).max(1);
+        let mut pixels = Vec::with_capacity((width * height * 4) as usize);
+        for y in 0..height {
+            let (top, bottom) = (y * self.height / height, (y + 1) * self.height / height);
+            for x in 0..width {
+                let (left, right) = (x * self.width / width, (x + 1) * self.width / width);
+                let mut sum = [0u32; 4];
+                for source_y in top..bottom.max(top + 1) {
+                    for source_x in left..right.max(left + 1) {
+                        let index = ((source_y * self.width + source_x) * 4) as usize;
+                        for (channel, sum) in sum.iter_mut().enumerate() {
+                            *sum += u32::from(self.pixels[index + channel]);
+                        }
+                    }
+                }
+                let count = (bottom.max(top + 1) - top) * (right.max(left + 1) - left);
+                pixels.extend(sum.iter().map(|sum| (sum / count) as u8));
+            }
+        }
+
+        Self {
+            width,
+            height,
+            pixels,
+        }
+    }
+}
+
+#[derive(Default)]
+struct State {
+    /// The format, width, height and stride of the buffer the compositor copies the window into
+    buffer: Option<(wl_shm::Format, u32, u32, u32)>,
+    buffer_done: bool,
+    y_invert: bool,
+    ready: bool,
+    failed: bool,
+}
+
+impl Dispatch<wl_registry::WlRegistry, GlobalListContents> for State {
+    fn event(
+        _: &mut Self,
+        _: &wl_registry::WlRegistry,
+        _: wl_registry::Event,
+        _: &GlobalListContents,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+impl Dispatch<HyprlandToplevelExportFrameV1, ()> for State {
+    fn event(
+        state: &mut Self,
+        _: &HyprlandToplevelExportFrameV1,
+        event: hyprland_toplevel_export_frame_v1::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        use hyprland_toplevel_export_frame_v1::{Event, Flags};
+
+        match event {
+            Event::Buffer {
+                format: WEnum::Value(format),
+                width,
+                height,
+                stride,
+            } => state.buffer = Some((format, width, height, stride)),
+            Event::BufferDone => state.buffer_done = true,
+            Event::Flags {
+                flags: WEnum::Value(flags),
+            } => state.y_invert = flags.contains(Flags::YInvert),
+            Event::Ready { .. } => state.ready = true,
+            Event::Failed => state.failed = true,
+            _ => (),
+        }
+    }
+}
+
+delegate_noop!(State: ignore wl_shm::WlShm);
+delegate_noop!(State: WlShmPool);
+delegate_noop!(State: ignore WlBuffer);
+delegate_noop!(State: HyprlandToplevelExportManagerV1);
+
+/// The window with the address from `hyprctl clients`, None if the compositor has no toplevel
+/// export or the window can't be exported
+pub fn capture(address: &str) -> Result<Option<Picture>, Box<dyn std::error::Error>> {
+    // The protocol takes the lower 32 bits of the address
+    let handle = u64::from_str_radix(address.trim_start_matches("0x"), 16)? as u32;
+
+    let connection = Connection::connect_to_env()?;
+    let (globals, mut queue) = registry_queue_init::<State>(&connection)?;
+    let qh = queue.handle();
+
+    let shm = globals.bind::<wl_shm::WlShm, _, _>(&qh, 1..=1, ())?;
+    let manager = globals.bind::<HyprlandToplevelExportManagerV1, _, _>(&qh, 1..=1, ())?;
+
+    let mut state = State::default();
+    let frame = manager.capture_toplevel(0, handle, &qh, ());
+    while !state.buffer_done && !state.failed {
+        queue.blocking_dispatch(&mut state)?;
+    }
+    let (format, width, height, stride) = match state.buffer {
+        Some(buffer) if !state.failed => buffer,
+        _ => {
+            frame.destroy();
+            return Ok(None);
+        }
+    };
+
+    // Byte offsets of red, green, blue and alpha, the formats being little endian. None for the
+    // formats without alpha, which are opaque.
+    let (channels, alpha) = match format {
+        wl_shm::Format::Argb8888 => ([2, 1, 0], Some(3)),
+        wl_shm::Format::Xrgb8888 => ([2, 1, 0], None),
+        wl_shm::Format::Abgr8888 => ([0, 1, 2], Some(3)),
+        wl_shm::Format::Xbgr8888 => ([0, 1, 2], None),
+        _ => {
+            frame.destroy();
+            return Ok(None);
+        }
+    };
+
+    // The buffer is a file only in memory, read once the compositor has copied the window into it
+    let size = stride * height;
+    let fd = memfd_create(c"anyrun-windows", MemFdCreateFlag::MFD_CLOEXEC)?;
+    // The file takes ownership of the descriptor, closing it when dropped
+    let mut file = unsafe { File::from_raw_fd(fd) };
+    file.set_len(u64::from(size))?;
+    let pool = shm.create_pool(file.as_fd(), size as i32, &qh, ());
+    let buffer = pool.create_buffer(
+        0,
+        width as i32,
+        height as i32,
+        stride as i32,
+        format,
+        &qh,
+        (),
+    );
+
+    frame.copy(&buffer, 1);
+    while !state.ready && !state.failed {
+        queue.blocking_dispatch(&mut state)?;
+    }
+    frame.destroy();
+    buffer.destroy();
+    pool.destroy();
+    if state.failed {
+        return Ok(None);
+    }
+
+    let mut data = Vec::with_capacity(size as usize);
+    file.seek(SeekFrom::Start(0))?;
+    file.read_to_end(&mut data)?;
+
+    let mut pixels = Vec::with_capacity((width * height * 4) as usize);
+    for y in 0..height {
+        let row = if state.y_invert { height - 1 - y } else { y };
+        for x in 0..width {
+            let pixel = &data[(row * stride + x * 4) as usize..][..4];
+            pixels.extend(channels.iter().map(|channel| pixel[*channel]));
+            pixels.push(alpha.map_or(u8::MAX, |alpha| pixel[alpha]));
+        }
+    }
+
+    Ok(Some(Picture {
+        width,
+        height,
+        pixels,
+    }))
+}
@@ -0,0 +1,237 @@
+use std::{
+    cmp::Reverse,
+    collections::HashMap,
+    env,
+    io::{self, Read, Write},
+    os::unix::net::UnixStream,
+    path::PathBuf,
+    sync::Mutex,
+};
+
+use abi_stable::std_types::{ROption, RString, RVec};
+use anyrun_plugin::*;
+use fuzzy_matcher::FuzzyMatcher;
+use serde::Deserialize;
+
+mod capture;
+
+/// The longer side of the thumbnails in pixels, as large as the preview pane shows them
+const THUMBNAIL_SIZE: u32 = 256;
+
+#[derive(Deserialize)]
+#[serde(default)]
+struct Config {
+    prefix: String,
+    max_entries: usize,
+    thumbnails: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            prefix: ":win".to_string(),
+            max_entries: 10,
+            thumbnails: true,
+        }
+    }
+}
+
+struct State {
+    config: Config,
+    /// The windows of the latest matches, which the ids refer to
+    windows: Vec<Window>,
+    /// The thumbnails taken so far by the addresses of their windows, None for the windows that
+    /// couldn't be taken. The preview only gets to read the state, hence the lock.
+    thumbnails: Mutex<HashMap<String, Option<Image>>>,
+}
+
+/// A client as Hyprland describes it in `hyprctl clients -j`
+#[derive(Deserialize)]
+struct Window {
+    address: String,
+    class: String,
+    title: String,
+    workspace: Workspace,
+    #[serde(default = "Window::default_mapped")]
+    mapped: bool,
+    /// Set for the windows in a group other than the one shown
+    #[serde(default)]
+    hidden: bool,
+    /// 0 for the focused window, counting up for the ones focused before it
+    #[serde(rename = "focusHistoryID", default)]
+    focus_history: i32,
+}
+
+impl Window {
+    fn default_mapped() -> bool {
+        true
+    }
+}
+
+#[derive(Deserialize)]
+struct Workspace {
+    name: String,
+}
+
+/// Send a command to the socket of Hyprland, the same way `hyprctl` does
+fn request(command: &str) -> io::Result<String> {
+    let signature = env::var("HYPRLAND_INSTANCE_SIGNATURE")
+        .map_err(|_| io::Error::new(io::ErrorKind::NotFound, "Hyprland is not running"))?;
+
+    // Newer versions of Hyprland keep the socket in the runtime directory instead of /tmp
+    let path = env::var_os("XDG_RUNTIME_DIR")
+        .map(|runtime_dir| PathBuf::from(runtime_dir).join("hypr"))
+        .filter(|dir| dir.join(&signature).exists())
+        .unwrap_or_else(|| PathBuf::from("/tmp/hypr"))
+        .join(&signature)
+        .join(".socket.sock");
+
+    let mut stream = UnixStream::connect(path)?;
+    stream.write_all(command.as_bytes())?;
+
+    let mut response = String::new();
+    stream.read_to_string(&mut response)?;
+    Ok(response)
+}
+
+/// The windows that can be focused, the most recently focused first
+fn windows() -> Vec<Window> {
+    let response = match request("j/clients") {
+        Ok(response) => response,
+        Err(why) => {
+            log::error!("Failed to get the windows from Hyprland: {}", why);
+            return Vec::new();
+        }
+    };
+    let mut windows: Vec<Window> = match serde_json::from_str(&response) {
+        Ok(windows) => windows,
+        Err(why) => {
+            log::error!("Error deserializing the windows from Hyprland: {}", why);
+            return Vec::new();
+        }
+    };
+
+    windows.retain(|window| window.mapped && !window.hidden && window.class != "anyrun");
+    windows.sort_by_key(|window| window.focus_history);
+    windows
+}
+
+/// The thumbnail of the window, taken the first time its preview is asked for. They are taken
+/// while anyrun is shown, which the toplevel export doesn't mind.
+fn thumbnail(address: &str) -> Option<Image> {
+    match capture::capture(address) {
+        Ok(picture) => picture.map(|picture| {
+            let picture = picture.shrink(THUMBNAIL_SIZE);
+            Image {
+                width: picture.width,
+                height: picture.height,
+                pixels: picture.pixels.into(),
+            }
+        }),
+        Err(why) => {
+            log::warn!("Failed to take the thumbnail of a window: {}", why);
+            None
+        }
+    }
+}
+
+#[init]
+fn init(config_dir: RString) -> State {
+    let config: Config = plugin_config(&config_dir, "windows");
+    prefix::set(&config.prefix);
+
+    State {
+        config,
+        windows: Vec::new(),
+        thumbnails: Mutex::new(HashMap::new()),
+    }
+}
+
+#[info]
+fn info() -> PluginInfo {
+    PluginInfo {
+        name: "Windows".into(),
+        icon: "preferences-system-windows".into(),
+    }
+}
+
+#[get_matches]
+fn get_matches(input: RString, state: &mut State) -> RVec<Match> {
+    let filter = match input.strip_prefix(&state.config.prefix) {
+        Some(filter) => filter.trim(),
+        None => return RVec::new(),
+    };
+
+    // The scores are all the same without a filter, keeping the order of the focus history
+    let matcher = fuzzy_matcher::skim::SkimMatcherV2::default().smart_case();
+    let mut windows = windows()
+        .into_iter()
+        .filter_map(|window| {
+            let score =
+                matcher.fuzzy_match(&format!("{} {}", window.title, window.class), filter)?;
+            Some((window, score))
+        })
+        .collect::<Vec<_>>();
+    windows.sort_by_key(|(_, score)| Reverse(*score));
+    windows.truncate(state.config.max_entries);
+    state.windows = windows.into_iter().map(|(window, _)| window).collect();
+
+    state
+        .windows
+        .iter()
+        .enumerate()
+        .map(|(id, window)| Match {
+            title: window.title.clone().into(),
+            description: ROption::RSome(
+                format!("{} on workspace {}", window.class, window.workspace.name).into(),
+            ),
+            title_pango: false,
+            description_pango: false,
+            // The classes are mostly the names of the icons of the applications
+            icon: ROption::RSome(window.class.to_lowercase().into()),
+            id: ROption::RSome(id as u64),
+            actions: RVec::new(),
+            action: ROption::RNone,
+            image: ROption::RNone,
+            widgets: RVec::new(),
+            priority: 0,
+            pinned: false,
+        })
+        .collect()
+}
+
+#[preview]
+fn preview(selection: Match, state: &State) -> Option<Preview> {
+    if !state.config.thumbnails {
+        return None;
+    }
+    let window = state.windows.get(selection.id.into_option()? as usize)?;
+
+    let mut thumbnails = state.thumbnails.lock().ok()?;
+    thumbnails
+        .entry(window.address.clone())
+        .or_insert_with(|| thumbnail(&window.address))
+        .clone()
+        .map(Preview::Image)
+}
+
+#[handler]
+fn handler(selection: Match, state: &State) -> HandleResult {
+    // The matches may be from before the latest query, like the ones pinned or in the history
+    let window = match selection
+        .id
+        .into_option()
+        .and_then(|id| state.windows.get(id as usize))
+    {
+        Some(window) => window,
+        None => return HandleResult::Refresh(false),
+    };
+    let command = format!("dispatch focuswindow address:{}", window.address);
+
+    match request(&command) {
+        Ok(response) if response.trim() == "ok" => (),
+        Ok(response) => log::error!("Hyprland refused to focus the window: {}", response.trim()),
+        Err(why) => log::error!("Failed to focus the window: {}", why),
+    }
+    HandleResult::Close
+}